@@ -1448,7 +1448,18 @@ fun MusicPlayerPage(
             ?: emptyList()
         else -> emptyList()
     }
-    val currentLyricIndex = resolveLyricIndex(currentDuration, displayLyrics)
+    // Plain text lyrics have no timestamps, so no line is highlighted.
+    val displayLyricsSynced = when {
+        localLyricState == LyricLoadState.LOADED -> currentMusic?.lyric?.data?.synced ?: true
+        activeRemoteLyricsState?.state == LyricLoadState.LOADED -> activeRemoteLyricsState.lyrics?.synced
+            ?: true
+        else -> true
+    }
+    val currentLyricIndex = if (displayLyricsSynced) {
+        resolveLyricIndex(currentDuration, displayLyrics)
+    } else {
+        -1
+    }
     var showLyric by rememberSaveable { mutableStateOf(false) }
     var queueSheetOpen by rememberSaveable { mutableStateOf(false) }
 
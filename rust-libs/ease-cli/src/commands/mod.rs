@@ -0,0 +1,9 @@
+mod music;
+mod playlist;
+mod scan;
+mod storage;
+
+pub use music::*;
+pub use playlist::*;
+pub use scan::*;
+pub use storage::*;
@@ -0,0 +1,178 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, bail};
+use clap::Subcommand;
+use ease_client_backend::{
+    ArgSearchLibrary, Backend, LyricLoadState, Music,
+    controllers::{music::ct_get_music, search::ct_search_library},
+};
+use ease_client_schema::MusicId;
+use serde_json::json;
+
+use crate::{
+    commands::playlist::{music_abstract_json, music_rows},
+    output::{Output, duration_ms, fmt_duration, table},
+};
+
+#[derive(Debug, Subcommand)]
+pub enum MusicCommand {
+    /// Show the details of a music.
+    Show { id: i64 },
+    /// Print the lyric of a music.
+    Lyric { id: i64 },
+    /// Search the musics of the library.
+    Search {
+        keywords: String,
+        #[arg(long, default_value_t = 1)]
+        page: u32,
+        #[arg(long, default_value_t = 50)]
+        per_page: u32,
+    },
+}
+
+async fn get_music(backend: Arc<Backend>, id: i64) -> anyhow::Result<Music> {
+    ct_get_music(backend, MusicId::wrap(id))
+        .await?
+        .with_context(|| format!("music {id} not found"))
+}
+
+/// Formats a lyric time like LRC files do, `mm:ss.xx`.
+fn fmt_lyric_time(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    format!(
+        "{:02}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+impl MusicCommand {
+    pub async fn run(self, backend: Arc<Backend>) -> anyhow::Result<Output> {
+        match self {
+            MusicCommand::Show { id } => {
+                let music = get_music(backend, id).await?;
+                let lyric = music.lyric.as_ref().map(|v| {
+                    json!({
+                        "storage_id": v.loc.storage_id,
+                        "path": v.loc.path,
+                        "source": format!("{:?}", v.source),
+                        "state": format!("{:?}", v.loaded_state),
+                        "line_count": v.data.lines.len(),
+                    })
+                });
+                let json = json!({
+                    "id": music.meta.id,
+                    "title": music.meta.title,
+                    "duration_ms": duration_ms(music.meta.duration),
+                    "file_state": music.meta.file_state,
+                    "storage_id": music.loc.storage_id,
+                    "path": music.loc.path,
+                    "stream_info": music.stream_info,
+                    "track_gain": music.meta.replay_gain.as_ref().map(|v| v.track_gain),
+                    "lyric": lyric,
+                });
+
+                let mut text = format!(
+                    "{}\nid:       {}\nduration: {}\nstorage:  {}\npath:     {}\nstate:    {:?}\n",
+                    music.meta.title,
+                    music.meta.id.as_ref(),
+                    fmt_duration(music.meta.duration),
+                    music.loc.storage_id.as_ref(),
+                    music.loc.path,
+                    music.meta.file_state,
+                );
+                if let Some(info) = music.stream_info.as_ref() {
+                    text.push_str(&format!(
+                        "stream:   {} Hz, {} channels",
+                        info.sample_rate, info.channels
+                    ));
+                    if let Some(bitrate) = info.bitrate {
+                        text.push_str(&format!(", {bitrate} kbps"));
+                    }
+                    text.push('\n');
+                }
+                match music.lyric.as_ref() {
+                    Some(lyric) => text.push_str(&format!(
+                        "lyric:    {} ({:?}, {:?})\n",
+                        lyric.loc.path, lyric.source, lyric.loaded_state
+                    )),
+                    None => text.push_str("lyric:    -\n"),
+                }
+                Ok(Output::new(json, text))
+            }
+            MusicCommand::Lyric { id } => {
+                let music = get_music(backend, id).await?;
+                let Some(lyric) = music.lyric else {
+                    bail!("music {id} has no lyric");
+                };
+                if lyric.loaded_state != LyricLoadState::Loaded {
+                    bail!("lyric of music {id} is {:?}", lyric.loaded_state);
+                }
+                let mut text = String::new();
+                for line in lyric.data.lines.iter() {
+                    if lyric.data.synced {
+                        text.push_str(&format!("[{}] ", fmt_lyric_time(line.duration)));
+                    }
+                    text.push_str(&line.text);
+                    text.push('\n');
+                }
+                let metadata = &lyric.data.metdata;
+                Ok(Output::new(
+                    json!({
+                        "storage_id": lyric.loc.storage_id,
+                        "path": lyric.loc.path,
+                        "source": format!("{:?}", lyric.source),
+                        "synced": lyric.data.synced,
+                        "metadata": {
+                            "artist": metadata.artist,
+                            "album": metadata.album,
+                            "title": metadata.title,
+                            "lyricist": metadata.lyricist,
+                            "author": metadata.author,
+                            "length": metadata.length,
+                            "offset": metadata.offset,
+                        },
+                        "lines": lyric
+                            .data
+                            .lines
+                            .iter()
+                            .map(|v| json!({ "time_ms": v.duration.as_millis() as u64, "text": v.text }))
+                            .collect::<Vec<_>>(),
+                    }),
+                    text,
+                ))
+            }
+            MusicCommand::Search {
+                keywords,
+                page,
+                per_page,
+            } => {
+                let result = ct_search_library(
+                    backend,
+                    ArgSearchLibrary {
+                        keywords,
+                        page,
+                        per_page,
+                    },
+                )
+                .await?;
+                let text = format!(
+                    "{}page {} of {} results\n",
+                    table(&["ID", "DURATION", "TITLE"], music_rows(&result.musics)),
+                    result.page,
+                    result.total
+                );
+                Ok(Output::new(
+                    json!({
+                        "musics": result.musics.iter().map(music_abstract_json).collect::<Vec<_>>(),
+                        "total": result.total,
+                        "page": result.page,
+                        "per_page": result.per_page,
+                    }),
+                    text,
+                ))
+            }
+        }
+    }
+}
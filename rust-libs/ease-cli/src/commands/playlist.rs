@@ -0,0 +1,385 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::{Context, bail};
+use clap::{Subcommand, ValueEnum};
+use ease_client_backend::{
+    Backend, MusicAbstract, PlaylistAbstract, StorageEntry,
+    controllers::{
+        playlist::{
+            ArgReorderMusic, ArgReorderPlaylist, ct_create_playlist, ct_get_playlist,
+            ct_import_playlist, ct_list_playlist, ct_remove_playlist, cts_export_playlist,
+            cts_reorder_music_in_playlist, cts_reorder_playlist,
+        },
+        storage::ct_list_storage,
+    },
+    services::{
+        ArgCreatePlaylist, ArgExportPlaylist, ArgImportPlaylist, PlaylistExportFormat,
+        PlaylistExportPaths, ToAddMusicEntry,
+    },
+};
+use ease_client_schema::{MusicId, PlaylistId, StorageEntryLoc, StorageId, StorageType};
+use serde_json::{Value, json};
+
+use crate::output::{Output, duration_ms, fmt_duration, table};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ExportFormatArg {
+    M3u8,
+    Xspf,
+    Json,
+}
+
+impl From<ExportFormatArg> for PlaylistExportFormat {
+    fn from(value: ExportFormatArg) -> Self {
+        match value {
+            ExportFormatArg::M3u8 => PlaylistExportFormat::M3u8,
+            ExportFormatArg::Xspf => PlaylistExportFormat::Xspf,
+            ExportFormatArg::Json => PlaylistExportFormat::Json,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ExportPathsArg {
+    /// `file://` and HTTP URLs.
+    Absolute,
+    /// Paths from the root of each storage.
+    Storage,
+    /// Paths relative to the output file, which must be given.
+    Relative,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PlaylistCommand {
+    /// List the playlists in order.
+    List,
+    /// Show a playlist and its musics.
+    Show { id: i64 },
+    /// Create a playlist from files of a storage.
+    Create {
+        title: String,
+        /// Storage of the files.
+        #[arg(short, long)]
+        storage: Option<i64>,
+        /// Paths of the files on the storage.
+        #[arg(requires = "storage")]
+        paths: Vec<String>,
+    },
+    /// Import an M3U, M3U8, PLS or XSPF file of a storage.
+    Import {
+        storage: i64,
+        path: String,
+        /// Defaults to the title in the file, then to the file name.
+        #[arg(long)]
+        title: Option<String>,
+    },
+    /// Export a playlist, to stdout unless an output file is given.
+    Export {
+        id: i64,
+        #[arg(long, value_enum, default_value = "m3u8")]
+        format: ExportFormatArg,
+        #[arg(long, value_enum, default_value = "absolute")]
+        paths: ExportPathsArg,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Move a playlist, or a music of it, to a position counted from 0.
+    Reorder {
+        id: i64,
+        position: usize,
+        /// Move this music within the playlist instead.
+        #[arg(long)]
+        music: Option<i64>,
+    },
+    /// Remove a playlist.
+    Remove { id: i64 },
+}
+
+fn playlist_json(playlist: &PlaylistAbstract) -> Value {
+    json!({
+        "id": playlist.meta.id,
+        "title": playlist.meta.title,
+        "music_count": playlist.music_count,
+        "duration_ms": duration_ms(playlist.duration),
+        "created_time_ms": playlist.meta.created_time.as_millis() as u64,
+        "is_smart": playlist.meta.is_smart,
+    })
+}
+
+pub(crate) fn music_abstract_json(music: &MusicAbstract) -> Value {
+    json!({
+        "id": music.meta.id,
+        "title": music.meta.title,
+        "duration_ms": duration_ms(music.meta.duration),
+        "file_state": music.meta.file_state,
+    })
+}
+
+pub(crate) fn music_rows(musics: &[MusicAbstract]) -> Vec<Vec<String>> {
+    musics
+        .iter()
+        .map(|v| {
+            vec![
+                v.meta.id.as_ref().to_string(),
+                fmt_duration(v.meta.duration),
+                v.meta.title.clone(),
+            ]
+        })
+        .collect()
+}
+
+/// Neighbours to put an item between so it ends at `position` among the
+/// `others`.
+fn neighbours<T: Copy>(others: &[T], position: usize) -> (Option<T>, Option<T>) {
+    let position = position.min(others.len());
+    let a = position.checked_sub(1).map(|i| others[i]);
+    let b = others.get(position).copied();
+    (a, b)
+}
+
+async fn local_storage_id(backend: &Arc<Backend>) -> anyhow::Result<StorageId> {
+    ct_list_storage(backend.clone())
+        .await?
+        .into_iter()
+        .find(|v| v.typ == StorageType::Local)
+        .map(|v| v.id)
+        .context("local storage not found")
+}
+
+impl PlaylistCommand {
+    pub async fn run(self, backend: Arc<Backend>) -> anyhow::Result<Output> {
+        match self {
+            PlaylistCommand::List => {
+                let playlists = ct_list_playlist(backend).await?;
+                let rows = playlists
+                    .iter()
+                    .map(|v| {
+                        vec![
+                            v.meta.id.as_ref().to_string(),
+                            v.music_count.to_string(),
+                            fmt_duration(v.duration),
+                            v.meta.title.clone(),
+                        ]
+                    })
+                    .collect();
+                Ok(Output::new(
+                    playlists.iter().map(playlist_json).collect(),
+                    table(&["ID", "MUSICS", "DURATION", "TITLE"], rows),
+                ))
+            }
+            PlaylistCommand::Show { id } => {
+                let playlist = ct_get_playlist(backend, PlaylistId::wrap(id))
+                    .await?
+                    .with_context(|| format!("playlist {id} not found"))?;
+                let mut json = playlist_json(&playlist.abstr);
+                json["musics"] = playlist.musics.iter().map(music_abstract_json).collect();
+                let text = format!(
+                    "{}\n{} musics, {}\n\n{}",
+                    playlist.abstr.meta.title,
+                    playlist.abstr.music_count,
+                    fmt_duration(playlist.abstr.duration),
+                    table(&["ID", "DURATION", "TITLE"], music_rows(&playlist.musics))
+                );
+                Ok(Output::new(json, text))
+            }
+            PlaylistCommand::Create {
+                title,
+                storage,
+                paths,
+            } => {
+                let entries = paths
+                    .into_iter()
+                    .map(|path| {
+                        let name = path.rsplit('/').next().unwrap_or_default().to_string();
+                        ToAddMusicEntry {
+                            entry: StorageEntry {
+                                storage_id: StorageId::wrap(storage.unwrap_or_default()),
+                                name: name.clone(),
+                                path,
+                                size: None,
+                                is_dir: false,
+                            },
+                            name,
+                        }
+                    })
+                    .collect();
+                let created = ct_create_playlist(
+                    backend,
+                    ArgCreatePlaylist {
+                        title,
+                        cover: None,
+                        entries,
+                    },
+                )
+                .await?;
+                Ok(Output::new(
+                    json!({
+                        "id": created.id,
+                        "music_ids": created.music_ids.iter().map(|v| v.id).collect::<Vec<_>>(),
+                    }),
+                    format!(
+                        "created playlist {} with {} musics",
+                        created.id.as_ref(),
+                        created.music_ids.len()
+                    ),
+                ))
+            }
+            PlaylistCommand::Import {
+                storage,
+                path,
+                title,
+            } => {
+                let imported = ct_import_playlist(
+                    backend,
+                    ArgImportPlaylist {
+                        loc: StorageEntryLoc {
+                            storage_id: StorageId::wrap(storage),
+                            path,
+                        },
+                        title,
+                    },
+                )
+                .await?;
+                let mut text = format!(
+                    "imported playlist {} with {} musics\n",
+                    imported.id.as_ref(),
+                    imported.music_ids.len()
+                );
+                for entry in imported.unresolved.iter() {
+                    text.push_str(&format!(
+                        "unresolved {}: {:?}\n",
+                        entry.location, entry.reason
+                    ));
+                }
+                Ok(Output::new(
+                    json!({
+                        "id": imported.id,
+                        "music_ids": imported.music_ids,
+                        "unresolved": imported
+                            .unresolved
+                            .iter()
+                            .map(|v| {
+                                json!({
+                                    "location": v.location,
+                                    "title": v.title,
+                                    "reason": format!("{:?}", v.reason),
+                                })
+                            })
+                            .collect::<Vec<_>>(),
+                    }),
+                    text,
+                ))
+            }
+            PlaylistCommand::Export {
+                id,
+                format,
+                paths,
+                output,
+            } => {
+                let paths = match paths {
+                    ExportPathsArg::Absolute => PlaylistExportPaths::AbsoluteUrl,
+                    ExportPathsArg::Storage => PlaylistExportPaths::StorageRelative,
+                    ExportPathsArg::Relative => {
+                        let Some(output) = output.as_ref() else {
+                            bail!("relative paths need an output file");
+                        };
+                        let dir = std::path::absolute(output)?
+                            .parent()
+                            .context("output file has no directory")?
+                            .display()
+                            .to_string();
+                        PlaylistExportPaths::RelativeTo {
+                            dir: StorageEntryLoc {
+                                storage_id: local_storage_id(&backend).await?,
+                                path: dir,
+                            },
+                        }
+                    }
+                };
+                let exported = cts_export_playlist(
+                    backend,
+                    ArgExportPlaylist {
+                        id: PlaylistId::wrap(id),
+                        format: format.into(),
+                        paths,
+                    },
+                )?;
+                match output {
+                    Some(output) => {
+                        std::fs::write(&output, &exported.bytes)
+                            .with_context(|| format!("failed to write {}", output.display()))?;
+                        Ok(Output::new(
+                            json!({
+                                "file_name": exported.file_name,
+                                "path": output.display().to_string(),
+                            }),
+                            format!("exported to {}", output.display()),
+                        ))
+                    }
+                    None => {
+                        let content = String::from_utf8_lossy(&exported.bytes).into_owned();
+                        Ok(Output::new(
+                            json!({
+                                "file_name": exported.file_name,
+                                "content": content,
+                            }),
+                            content,
+                        ))
+                    }
+                }
+            }
+            PlaylistCommand::Reorder {
+                id,
+                position,
+                music,
+            } => {
+                let id = PlaylistId::wrap(id);
+                match music {
+                    Some(music) => {
+                        let music = MusicId::wrap(music);
+                        let playlist = ct_get_playlist(backend.clone(), id)
+                            .await?
+                            .with_context(|| format!("playlist {} not found", id.as_ref()))?;
+                        let others: Vec<MusicId> = playlist
+                            .musics
+                            .iter()
+                            .map(|v| v.meta.id)
+                            .filter(|v| *v != music)
+                            .collect();
+                        let (a, b) = neighbours(&others, position);
+                        cts_reorder_music_in_playlist(
+                            backend,
+                            ArgReorderMusic {
+                                playlist_id: id,
+                                id: music,
+                                a,
+                                b,
+                            },
+                        )?;
+                    }
+                    None => {
+                        let others: Vec<PlaylistId> = ct_list_playlist(backend.clone())
+                            .await?
+                            .iter()
+                            .map(|v| v.meta.id)
+                            .filter(|v| *v != id)
+                            .collect();
+                        let (a, b) = neighbours(&others, position);
+                        cts_reorder_playlist(backend, ArgReorderPlaylist { id, a, b })?;
+                    }
+                }
+                Ok(Output::new(
+                    json!({ "position": position }),
+                    format!("moved to position {position}"),
+                ))
+            }
+            PlaylistCommand::Remove { id } => {
+                ct_remove_playlist(backend, PlaylistId::wrap(id)).await?;
+                Ok(Output::new(
+                    json!({ "removed": id }),
+                    format!("removed playlist {id}"),
+                ))
+            }
+        }
+    }
+}
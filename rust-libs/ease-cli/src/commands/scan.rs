@@ -0,0 +1,139 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, bail};
+use clap::Subcommand;
+use ease_client_backend::{
+    Backend,
+    controllers::scan::{
+        ct_rescan_library, cts_cancel_library_scan, cts_get_library_scan_progress,
+        cts_start_library_scan,
+    },
+    services::{ArgRescanLibrary, ArgStartLibraryScan, LibraryScanProgress, LibraryScanStatus},
+};
+use ease_client_schema::{StorageEntryLoc, StorageId};
+use serde_json::json;
+
+use crate::output::Output;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Subcommand)]
+pub enum ScanCommand {
+    /// Import the musics of a storage directory and its subdirectories.
+    Run {
+        storage: i64,
+        #[arg(default_value = "/")]
+        dir: String,
+        /// Directories listed at the same time, 0 for the default.
+        #[arg(long, default_value_t = 0)]
+        concurrency: u32,
+    },
+    /// Check the imported musics for changed, missing or restored files.
+    Rescan {
+        /// Only rescan this storage.
+        #[arg(long)]
+        storage: Option<i64>,
+    },
+}
+
+fn progress_json(progress: &LibraryScanProgress) -> serde_json::Value {
+    json!({
+        "id": progress.id,
+        "status": format!("{:?}", progress.status),
+        "dirs_scanned": progress.dirs_scanned,
+        "musics_found": progress.musics_found,
+        "musics_added": progress.musics_added,
+        "lyrics_paired": progress.lyrics_paired,
+        "covers_paired": progress.covers_paired,
+        "failed_dirs": progress.failed_dirs,
+        "error": progress.error,
+    })
+}
+
+impl ScanCommand {
+    pub async fn run(self, backend: Arc<Backend>) -> anyhow::Result<Output> {
+        match self {
+            ScanCommand::Run {
+                storage,
+                dir,
+                concurrency,
+            } => {
+                let id = cts_start_library_scan(
+                    backend.clone(),
+                    ArgStartLibraryScan {
+                        dir: StorageEntryLoc {
+                            storage_id: StorageId::wrap(storage),
+                            path: dir,
+                        },
+                        max_concurrency: concurrency,
+                    },
+                );
+                let progress = loop {
+                    tokio::select! {
+                        _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                        _ = tokio::signal::ctrl_c() => {
+                            // Musics imported so far are kept.
+                            cts_cancel_library_scan(backend.clone(), id);
+                        }
+                    }
+                    let progress = cts_get_library_scan_progress(backend.clone(), id)
+                        .context("scan job not found")?;
+                    if progress.status != LibraryScanStatus::Running {
+                        break progress;
+                    }
+                    eprint!(
+                        "\rscanned {} directories, {} pending, {} musics found",
+                        progress.dirs_scanned, progress.dirs_pending, progress.musics_found
+                    );
+                };
+                eprintln!();
+                if progress.status == LibraryScanStatus::Failed {
+                    bail!(
+                        "scan failed: {}",
+                        progress.error.as_deref().unwrap_or("unknown error")
+                    );
+                }
+                let text = format!(
+                    "scan {:?}: {} directories, {} musics found, {} added, {} lyrics and {} covers paired, {} directories failed",
+                    progress.status,
+                    progress.dirs_scanned,
+                    progress.musics_found,
+                    progress.musics_added,
+                    progress.lyrics_paired,
+                    progress.covers_paired,
+                    progress.failed_dirs,
+                );
+                Ok(Output::new(progress_json(&progress), text))
+            }
+            ScanCommand::Rescan { storage } => {
+                let result = ct_rescan_library(
+                    backend,
+                    ArgRescanLibrary {
+                        storage_id: storage.map(StorageId::wrap),
+                    },
+                )
+                .await?;
+                let text = format!(
+                    "{} unchanged, {} stamped, {} changed, {} missing, {} restored, {} directories failed",
+                    result.unchanged,
+                    result.stamped,
+                    result.changed.len(),
+                    result.missing.len(),
+                    result.restored.len(),
+                    result.failed_dirs.len(),
+                );
+                Ok(Output::new(
+                    json!({
+                        "unchanged": result.unchanged,
+                        "stamped": result.stamped,
+                        "changed": result.changed,
+                        "missing": result.missing,
+                        "restored": result.restored,
+                        "failed_dirs": result.failed_dirs,
+                    }),
+                    text,
+                ))
+            }
+        }
+    }
+}
@@ -0,0 +1,371 @@
+use std::sync::Arc;
+
+use anyhow::{Context, bail};
+use clap::{Args, Subcommand, ValueEnum};
+use ease_client_backend::{
+    ArgSearchStorageEntries, ArgUpsertStorage, Backend, ListStorageEntryChildrenResp,
+    SearchStorageEntriesResp, Storage, StorageConnectionTestResult, StorageSearchScope,
+    controllers::storage::{
+        ct_list_storage, ct_list_storage_entry_children, ct_remove_storage,
+        ct_search_storage_entries, ct_test_storage, ct_upsert_storage,
+    },
+};
+use ease_client_schema::{StorageEntryLoc, StorageId, StorageType};
+use serde_json::{Value, json};
+
+use crate::output::{Output, table};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum StorageKind {
+    Local,
+    Webdav,
+    Onedrive,
+    Openlist,
+}
+
+impl From<StorageKind> for StorageType {
+    fn from(value: StorageKind) -> Self {
+        match value {
+            StorageKind::Local => StorageType::Local,
+            StorageKind::Webdav => StorageType::Webdav,
+            StorageKind::Onedrive => StorageType::OneDrive,
+            StorageKind::Openlist => StorageType::OpenList,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum SearchScopeArg {
+    All,
+    Dir,
+    File,
+}
+
+impl From<SearchScopeArg> for StorageSearchScope {
+    fn from(value: SearchScopeArg) -> Self {
+        match value {
+            SearchScopeArg::All => StorageSearchScope::All,
+            SearchScopeArg::Dir => StorageSearchScope::Directory,
+            SearchScopeArg::File => StorageSearchScope::File,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ConnectionArgs {
+    /// Address, like `https://dav.example.com` for WebDAV.
+    #[arg(long, default_value = "")]
+    addr: String,
+    #[arg(long, default_value = "")]
+    alias: String,
+    #[arg(long, default_value = "")]
+    username: String,
+    /// Password, or the refresh token for OneDrive.
+    #[arg(
+        long,
+        default_value = "",
+        env = "EASE_STORAGE_PASSWORD",
+        hide_env_values = true
+    )]
+    password: String,
+    #[arg(long)]
+    anonymous: bool,
+    /// Directory browsed by default.
+    #[arg(long, default_value = "/")]
+    default_path: String,
+}
+
+impl ConnectionArgs {
+    fn into_arg(self, id: Option<StorageId>, typ: StorageType) -> ArgUpsertStorage {
+        ArgUpsertStorage {
+            id,
+            addr: self.addr,
+            alias: self.alias,
+            username: self.username,
+            password: self.password,
+            is_anonymous: self.anonymous,
+            typ,
+            default_path: self.default_path,
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum StorageCommand {
+    /// List the storages.
+    List,
+    /// Add a storage after testing the connection to it.
+    Add {
+        #[arg(long = "type", value_enum)]
+        typ: StorageKind,
+        #[command(flatten)]
+        conn: ConnectionArgs,
+        /// Add the storage even if it cannot be connected to.
+        #[arg(long)]
+        skip_test: bool,
+    },
+    /// Test the connection to an added storage, or to the given one.
+    Test {
+        #[arg(required_unless_present = "typ")]
+        id: Option<i64>,
+        #[arg(long = "type", value_enum, conflicts_with = "id")]
+        typ: Option<StorageKind>,
+        #[command(flatten)]
+        conn: ConnectionArgs,
+    },
+    /// Remove a storage and its musics.
+    Remove { id: i64 },
+    /// List a directory of a storage, by default the default directory.
+    Browse { id: i64, path: Option<String> },
+    /// Search the entries of a storage by name.
+    Search {
+        id: i64,
+        keywords: String,
+        /// Directory to search in.
+        #[arg(long, default_value = "/")]
+        parent: String,
+        #[arg(long, value_enum, default_value = "all")]
+        scope: SearchScopeArg,
+        #[arg(long, default_value_t = 1)]
+        page: u32,
+        #[arg(long, default_value_t = 50)]
+        per_page: u32,
+    },
+}
+
+fn storage_json(storage: &Storage) -> Value {
+    // The password is left out on purpose.
+    json!({
+        "id": storage.id,
+        "type": storage.typ,
+        "alias": storage.alias,
+        "addr": storage.addr,
+        "username": storage.username,
+        "is_anonymous": storage.is_anonymous,
+        "default_path": storage.default_path,
+        "music_count": storage.music_count,
+    })
+}
+
+async fn find_storage(backend: &Arc<Backend>, id: i64) -> anyhow::Result<Storage> {
+    ct_list_storage(backend.clone())
+        .await?
+        .into_iter()
+        .find(|v| *v.id.as_ref() == id)
+        .with_context(|| format!("storage {id} not found"))
+}
+
+fn storage_to_arg(storage: Storage) -> ArgUpsertStorage {
+    ArgUpsertStorage {
+        id: Some(storage.id),
+        addr: storage.addr,
+        alias: storage.alias,
+        username: storage.username,
+        password: storage.password,
+        is_anonymous: storage.is_anonymous,
+        typ: storage.typ,
+        default_path: storage.default_path,
+    }
+}
+
+fn test_result_name(result: StorageConnectionTestResult) -> &'static str {
+    match result {
+        StorageConnectionTestResult::None => "none",
+        StorageConnectionTestResult::Testing => "testing",
+        StorageConnectionTestResult::Success => "success",
+        StorageConnectionTestResult::Unauthorized => "unauthorized",
+        StorageConnectionTestResult::Timeout => "timeout",
+        StorageConnectionTestResult::OtherError => "error",
+    }
+}
+
+/// Turns the failures a storage reports as values into errors.
+fn storage_failure(what: &str) -> anyhow::Error {
+    anyhow::anyhow!("storage {what}")
+}
+
+impl StorageCommand {
+    pub async fn run(self, backend: Arc<Backend>) -> anyhow::Result<Output> {
+        match self {
+            StorageCommand::List => {
+                let storages = ct_list_storage(backend).await?;
+                let rows = storages
+                    .iter()
+                    .map(|v| {
+                        vec![
+                            v.id.as_ref().to_string(),
+                            format!("{:?}", v.typ),
+                            v.alias.clone(),
+                            v.addr.clone(),
+                            v.music_count.to_string(),
+                        ]
+                    })
+                    .collect();
+                Ok(Output::new(
+                    storages.iter().map(storage_json).collect(),
+                    table(&["ID", "TYPE", "ALIAS", "ADDRESS", "MUSICS"], rows),
+                ))
+            }
+            StorageCommand::Add {
+                typ,
+                conn,
+                skip_test,
+            } => {
+                let arg = conn.into_arg(None, typ.into());
+                if !skip_test {
+                    let result = ct_test_storage(backend.clone(), arg.clone()).await?;
+                    if result != StorageConnectionTestResult::Success {
+                        bail!(
+                            "connection test failed: {}, pass --skip-test to add it anyway",
+                            test_result_name(result)
+                        );
+                    }
+                }
+                let before = ct_list_storage(backend.clone()).await?;
+                ct_upsert_storage(backend.clone(), arg).await?;
+                let added = ct_list_storage(backend)
+                    .await?
+                    .into_iter()
+                    .find(|v| before.iter().all(|b| b.id != v.id))
+                    .context("added storage not found")?;
+                Ok(Output::new(
+                    storage_json(&added),
+                    format!("added storage {}", added.id.as_ref()),
+                ))
+            }
+            StorageCommand::Test { id, typ, conn } => {
+                let arg = match (id, typ) {
+                    (Some(id), _) => storage_to_arg(find_storage(&backend, id).await?),
+                    (None, Some(typ)) => conn.into_arg(None, typ.into()),
+                    (None, None) => bail!("pass a storage id or --type"),
+                };
+                let result = test_result_name(ct_test_storage(backend, arg).await?);
+                Ok(Output::new(json!({ "result": result }), result))
+            }
+            StorageCommand::Remove { id } => {
+                let storage = find_storage(&backend, id).await?;
+                ct_remove_storage(backend, storage.id).await?;
+                Ok(Output::new(
+                    json!({ "removed": storage.id }),
+                    format!("removed storage {id}"),
+                ))
+            }
+            StorageCommand::Browse { id, path } => {
+                let storage = find_storage(&backend, id).await?;
+                let path = path.unwrap_or(storage.default_path);
+                let resp = ct_list_storage_entry_children(
+                    backend,
+                    StorageEntryLoc {
+                        storage_id: storage.id,
+                        path,
+                    },
+                )
+                .await?;
+                let entries = match resp {
+                    ListStorageEntryChildrenResp::Ok(entries) => entries,
+                    ListStorageEntryChildrenResp::AuthenticationFailed => {
+                        return Err(storage_failure("authentication failed"));
+                    }
+                    ListStorageEntryChildrenResp::Timeout => {
+                        return Err(storage_failure("timed out"));
+                    }
+                    ListStorageEntryChildrenResp::Unavailable => {
+                        return Err(storage_failure("unavailable"));
+                    }
+                    ListStorageEntryChildrenResp::BlockedBySite => {
+                        return Err(storage_failure("blocked by the site"));
+                    }
+                    ListStorageEntryChildrenResp::Unknown => {
+                        return Err(storage_failure("failed to list the directory"));
+                    }
+                };
+                let mut text = String::new();
+                for entry in entries.iter() {
+                    let suffix = if entry.is_dir { "/" } else { "" };
+                    text.push_str(&format!("{}{}\n", entry.name, suffix));
+                }
+                Ok(Output::new(
+                    entries
+                        .iter()
+                        .map(|v| {
+                            json!({
+                                "name": v.name,
+                                "path": v.path,
+                                "size": v.size,
+                                "is_dir": v.is_dir,
+                            })
+                        })
+                        .collect(),
+                    text,
+                ))
+            }
+            StorageCommand::Search {
+                id,
+                keywords,
+                parent,
+                scope,
+                page,
+                per_page,
+            } => {
+                let storage = find_storage(&backend, id).await?;
+                let resp = ct_search_storage_entries(
+                    backend,
+                    ArgSearchStorageEntries {
+                        storage_id: storage.id,
+                        parent,
+                        keywords,
+                        scope: scope.into(),
+                        page,
+                        per_page,
+                    },
+                )
+                .await?;
+                let page = match resp {
+                    SearchStorageEntriesResp::Ok(page) => page,
+                    SearchStorageEntriesResp::AuthenticationFailed => {
+                        return Err(storage_failure("authentication failed"));
+                    }
+                    SearchStorageEntriesResp::Timeout => {
+                        return Err(storage_failure("timed out"));
+                    }
+                    SearchStorageEntriesResp::Unavailable => {
+                        return Err(storage_failure("does not support search"));
+                    }
+                    SearchStorageEntriesResp::BlockedBySite => {
+                        return Err(storage_failure("blocked by the site"));
+                    }
+                    SearchStorageEntriesResp::Unknown => {
+                        return Err(storage_failure("failed to search"));
+                    }
+                };
+                let mut text = String::new();
+                for entry in page.entries.iter() {
+                    let suffix = if entry.is_dir { "/" } else { "" };
+                    text.push_str(&format!("{}{}\n", entry.path, suffix));
+                }
+                text.push_str(&format!("page {} of {} results\n", page.page, page.total));
+                Ok(Output::new(
+                    json!({
+                        "entries": page
+                            .entries
+                            .iter()
+                            .map(|v| {
+                                json!({
+                                    "name": v.name,
+                                    "path": v.path,
+                                    "parent_path": v.parent_path,
+                                    "size": v.size,
+                                    "is_dir": v.is_dir,
+                                })
+                            })
+                            .collect::<Vec<_>>(),
+                        "total": page.total,
+                        "page": page.page,
+                        "per_page": page.per_page,
+                    }),
+                    text,
+                ))
+            }
+        }
+    }
+}
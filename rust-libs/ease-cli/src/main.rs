@@ -0,0 +1,201 @@
+//! Command-line client that drives the backend directly on a data directory,
+//! without the app. Every command prints human-readable text, or JSON with
+//! `--json`.
+
+mod commands;
+mod output;
+
+use std::{path::PathBuf, process::ExitCode, sync::Arc};
+
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use ease_client_backend::{ArgInitializeApp, Backend, create_backend};
+
+use crate::{
+    commands::{MusicCommand, PlaylistCommand, ScanCommand, StorageCommand},
+    output::Output,
+};
+
+#[derive(Debug, Parser)]
+#[command(name = "ease-cli", version, about = "Manage the Ease Music library")]
+struct Cli {
+    /// Directory of the database, logs and caches. Defaults to
+    /// `~/.local/share/ease-music`.
+    #[arg(long, global = true, env = "EASE_DATA_DIR")]
+    data_dir: Option<PathBuf>,
+    /// Print JSON instead of text.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Add, test, browse and search storages.
+    #[command(subcommand)]
+    Storage(StorageCommand),
+    /// Create, import, export and reorder playlists.
+    #[command(subcommand)]
+    Playlist(PlaylistCommand),
+    /// Inspect and search musics.
+    #[command(subcommand)]
+    Music(MusicCommand),
+    /// Scan storages for musics.
+    #[command(subcommand)]
+    Scan(ScanCommand),
+}
+
+fn default_data_dir() -> anyhow::Result<PathBuf> {
+    let home = std::env::var_os("HOME").context("HOME is not set, pass --data-dir")?;
+    Ok(PathBuf::from(home).join(".local/share/ease-music"))
+}
+
+fn open_backend(data_dir: Option<PathBuf>) -> anyhow::Result<Arc<Backend>> {
+    let data_dir = match data_dir {
+        Some(dir) => dir,
+        None => default_data_dir()?,
+    };
+    let documents_dir = data_dir.join("documents");
+    let cache_dir = data_dir.join("cache");
+    for dir in [&documents_dir, &cache_dir] {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+
+    let backend = create_backend(ArgInitializeApp {
+        app_document_dir: format!("{}/", documents_dir.display()),
+        app_cache_dir: format!("{}/", cache_dir.display()),
+        storage_path: "/".to_string(),
+    });
+    backend.init()?;
+    Ok(backend)
+}
+
+async fn run(backend: Arc<Backend>, command: Command) -> anyhow::Result<Output> {
+    match command {
+        Command::Storage(command) => command.run(backend).await,
+        Command::Playlist(command) => command.run(backend).await,
+        Command::Music(command) => command.run(backend).await,
+        Command::Scan(command) => command.run(backend).await,
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let ret = open_backend(cli.data_dir).and_then(|backend| {
+        let ret = ease_client_tokio::tokio_runtime().block_on(run(backend.clone(), cli.command));
+        backend.deinit()?;
+        ret
+    });
+    match ret {
+        Ok(output) => {
+            output.print(cli.json);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            Output::error(&e).print(cli.json);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn setup_backend() -> (TempDir, Arc<Backend>) {
+        let tempdir = tempfile::tempdir().expect("create tempdir");
+        let backend = open_backend(Some(tempdir.path().to_path_buf())).expect("open backend");
+        (tempdir, backend)
+    }
+
+    fn exec(backend: &Arc<Backend>, args: &[&str]) -> Value {
+        let args = std::iter::once("ease-cli").chain(args.iter().copied());
+        let cli = Cli::try_parse_from(args).expect("parse args");
+        ease_client_tokio::tokio_runtime()
+            .block_on(run(backend.clone(), cli.command))
+            .expect("run command")
+            .json
+    }
+
+    fn titles(playlists: &Value) -> Vec<&str> {
+        playlists
+            .as_array()
+            .expect("playlists")
+            .iter()
+            .map(|v| v["title"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn manages_playlists_of_local_storage() {
+        let (tempdir, backend) = setup_backend();
+        let music_dir = tempdir.path().join("music");
+        std::fs::create_dir_all(&music_dir).expect("create music dir");
+        for name in ["a.mp3", "b.mp3", "cover.jpg"] {
+            std::fs::write(music_dir.join(name), b"").expect("write file");
+        }
+        let music_dir = music_dir.display().to_string();
+
+        let storages = exec(&backend, &["storage", "list"]);
+        let local = storages
+            .as_array()
+            .expect("storages")
+            .iter()
+            .find(|v| v["type"] == "Local")
+            .expect("local storage")["id"]
+            .to_string();
+
+        let children = exec(&backend, &["storage", "browse", &local, &music_dir]);
+        let mut names: Vec<&str> = children
+            .as_array()
+            .expect("entries")
+            .iter()
+            .map(|v| v["name"].as_str().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(vec!["a.mp3", "b.mp3", "cover.jpg"], names);
+
+        let a = format!("{music_dir}/a.mp3");
+        let b = format!("{music_dir}/b.mp3");
+        let first = exec(
+            &backend,
+            &["playlist", "create", "first", "-s", &local, &a, &b],
+        );
+        assert_eq!(2, first["music_ids"].as_array().unwrap().len());
+        exec(&backend, &["playlist", "create", "second"]);
+        let playlists = exec(&backend, &["playlist", "list"]);
+        assert_eq!(vec!["first", "second"], titles(&playlists));
+
+        let id = first["id"].to_string();
+        exec(&backend, &["playlist", "reorder", &id, "1"]);
+        let playlists = exec(&backend, &["playlist", "list"]);
+        assert_eq!(vec!["second", "first"], titles(&playlists));
+
+        let b_id = first["music_ids"][1].to_string();
+        exec(
+            &backend,
+            &["playlist", "reorder", &id, "0", "--music", &b_id],
+        );
+        let shown = exec(&backend, &["playlist", "show", &id]);
+        let musics: Vec<&str> = shown["musics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v["title"].as_str().unwrap())
+            .collect();
+        assert_eq!(vec!["b.mp3", "a.mp3"], musics);
+
+        let exported = exec(&backend, &["playlist", "export", &id, "--format", "m3u8"]);
+        let content = exported["content"].as_str().expect("content");
+        assert!(content.starts_with("#EXTM3U"));
+        assert!(content.find("b.mp3").unwrap() < content.find("a.mp3").unwrap());
+
+        let music = exec(&backend, &["music", "show", &b_id]);
+        assert_eq!(b, music["path"].as_str().unwrap());
+    }
+}
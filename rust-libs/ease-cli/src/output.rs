@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+/// Result of a command, kept both as JSON and as text so `--json` only
+/// decides which one is printed.
+#[derive(Debug)]
+pub struct Output {
+    pub json: Value,
+    pub text: String,
+}
+
+impl Output {
+    pub fn new(json: Value, text: impl Into<String>) -> Self {
+        Self {
+            json,
+            text: text.into(),
+        }
+    }
+
+    pub fn error(e: &anyhow::Error) -> Self {
+        Self::new(
+            json!({ "error": format!("{e:#}") }),
+            format!("error: {e:#}"),
+        )
+    }
+
+    pub fn print(&self, json: bool) {
+        let is_error = self.json.get("error").is_some();
+        let s = if json {
+            serde_json::to_string_pretty(&self.json).unwrap()
+        } else {
+            self.text.trim_end().to_string()
+        };
+        if s.is_empty() {
+            return;
+        }
+        if is_error {
+            eprintln!("{s}");
+        } else {
+            println!("{s}");
+        }
+    }
+}
+
+/// Formats a duration as `m:ss`, or `-` when it is unknown.
+pub fn fmt_duration(duration: Option<Duration>) -> String {
+    match duration {
+        Some(duration) => {
+            let secs = duration.as_secs();
+            format!("{}:{:02}", secs / 60, secs % 60)
+        }
+        None => "-".to_string(),
+    }
+}
+
+pub fn duration_ms(duration: Option<Duration>) -> Value {
+    match duration {
+        Some(duration) => json!(duration.as_millis() as u64),
+        None => Value::Null,
+    }
+}
+
+/// Lays out rows in columns padded to the widest cell.
+pub fn table(header: &[&str], rows: Vec<Vec<String>>) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|v| v.chars().count()).collect();
+    for row in rows.iter() {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let mut ret = String::new();
+        for (i, cell) in cells.iter().enumerate() {
+            if i + 1 == cells.len() {
+                ret.push_str(cell);
+            } else {
+                ret.push_str(&format!("{:<width$}  ", cell, width = widths[i]));
+            }
+        }
+        ret.push('\n');
+        ret
+    };
+    let mut ret = line(header.to_vec());
+    for row in rows.iter() {
+        ret.push_str(&line(row.iter().map(|v| v.as_str()).collect()));
+    }
+    ret
+}
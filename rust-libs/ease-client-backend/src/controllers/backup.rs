@@ -0,0 +1,207 @@
+use std::sync::Arc;
+
+use crate::{
+    error::BResult,
+    services::{
+        export_backup, import_backup, ArgExportBackup, ArgImportBackup, BackupImportResult,
+    },
+    Backend,
+};
+
+#[uniffi::export]
+pub async fn ct_export_backup(cx: Arc<Backend>, arg: ArgExportBackup) -> BResult<Vec<u8>> {
+    let cx = cx.get_context();
+    export_backup(cx, arg)
+}
+
+#[uniffi::export]
+pub async fn ct_import_backup(
+    cx: Arc<Backend>,
+    arg: ArgImportBackup,
+) -> BResult<BackupImportResult> {
+    let cx = cx.get_context();
+    import_backup(cx, arg)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ease_client_schema::StorageType;
+
+    use crate::{
+        controllers::{
+            playlist::{ct_create_playlist, ct_list_playlist},
+            storage::{ct_list_storage, ct_upsert_storage},
+        },
+        error::BError,
+        objects::ArgUpsertStorage,
+        services::{
+            ArgCreatePlaylist, ArgExportBackup, ArgImportBackup, BackupCredentials, ToAddMusicEntry,
+        },
+        test_utils::{local_storage, setup_backend},
+        StorageEntry,
+    };
+
+    use super::{ct_export_backup, ct_import_backup};
+
+    async fn webdav_password(backend: &Arc<crate::Backend>) -> Option<String> {
+        ct_list_storage(backend.clone())
+            .await
+            .expect("list storages")
+            .into_iter()
+            .find(|storage| storage.typ == StorageType::Webdav)
+            .map(|storage| storage.password)
+    }
+
+    async fn playlist_titles(backend: &Arc<crate::Backend>) -> Vec<String> {
+        ct_list_playlist(backend.clone())
+            .await
+            .expect("list playlists")
+            .into_iter()
+            .map(|v| v.meta.title)
+            .collect()
+    }
+
+    #[test]
+    fn restores_library_with_covers_and_encrypted_credentials() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (_tempdir, backend) = setup_backend();
+            ct_upsert_storage(
+                backend.clone(),
+                ArgUpsertStorage {
+                    id: None,
+                    addr: "https://dav.example.com".to_string(),
+                    alias: "nas".to_string(),
+                    username: "me".to_string(),
+                    password: "secret".to_string(),
+                    is_anonymous: false,
+                    typ: StorageType::Webdav,
+                    default_path: "/".to_string(),
+                },
+            )
+            .await
+            .expect("add storage");
+            let local = local_storage(&backend).await;
+            let created = ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "kept".to_string(),
+                    cover: None,
+                    entries: vec![ToAddMusicEntry {
+                        entry: StorageEntry {
+                            storage_id: local.id,
+                            name: "a.mp3".to_string(),
+                            path: "/music/a.mp3".to_string(),
+                            size: None,
+                            is_dir: false,
+                        },
+                        name: "a.mp3".to_string(),
+                    }],
+                },
+            )
+            .await
+            .expect("create playlist");
+            let music_id = created.music_ids[0].id;
+            let db = backend.get_context().database_server();
+            db.update_music_cover(music_id, b"cover".to_vec())
+                .expect("update cover");
+
+            let encrypted = ct_export_backup(
+                backend.clone(),
+                ArgExportBackup {
+                    credentials: BackupCredentials::Encrypt {
+                        passphrase: "pass phrase".to_string(),
+                    },
+                },
+            )
+            .await
+            .expect("export backup");
+            let redacted = ct_export_backup(
+                backend.clone(),
+                ArgExportBackup {
+                    credentials: BackupCredentials::Redact,
+                },
+            )
+            .await
+            .expect("export backup");
+            assert!(!encrypted.windows(b"secret".len()).any(|v| v == b"secret"));
+
+            // A fresh install, as after reinstalling the app.
+            let (_tempdir, restored) = setup_backend();
+            ct_create_playlist(
+                restored.clone(),
+                ArgCreatePlaylist {
+                    title: "replaced".to_string(),
+                    cover: None,
+                    entries: vec![],
+                },
+            )
+            .await
+            .expect("create playlist");
+
+            let wrong = ct_import_backup(
+                restored.clone(),
+                ArgImportBackup {
+                    data: encrypted.clone(),
+                    passphrase: Some("wrong".to_string()),
+                },
+            )
+            .await;
+            assert!(matches!(wrong, Err(BError::BackupPassphraseMismatch)));
+            let mut corrupted = encrypted.clone();
+            *corrupted.last_mut().unwrap() ^= 1;
+            let corrupted = ct_import_backup(
+                restored.clone(),
+                ArgImportBackup {
+                    data: corrupted,
+                    passphrase: Some("pass phrase".to_string()),
+                },
+            )
+            .await;
+            assert!(matches!(corrupted, Err(BError::InvalidBackup(_))));
+            assert_eq!(vec!["replaced"], playlist_titles(&restored).await);
+
+            let result = ct_import_backup(
+                restored.clone(),
+                ArgImportBackup {
+                    data: encrypted,
+                    passphrase: Some("pass phrase".to_string()),
+                },
+            )
+            .await
+            .expect("import backup");
+            assert_eq!(6, result.schema_version);
+            assert_eq!(2, result.storages);
+            assert_eq!(1, result.playlists);
+            assert_eq!(1, result.musics);
+            assert_eq!(1, result.blobs);
+            assert!(!result.credentials_redacted);
+            assert_eq!(vec!["kept"], playlist_titles(&restored).await);
+            assert_eq!(Some("secret".to_string()), webdav_password(&restored).await);
+            let db = restored.get_context().database_server();
+            let cover = db
+                .load_music(music_id)
+                .expect("load music")
+                .expect("music")
+                .cover
+                .expect("cover");
+            assert_eq!(
+                b"cover".to_vec(),
+                db.blob().read(cover).expect("read cover")
+            );
+
+            let result = ct_import_backup(
+                restored.clone(),
+                ArgImportBackup {
+                    data: redacted,
+                    passphrase: None,
+                },
+            )
+            .await
+            .expect("import backup");
+            assert!(result.credentials_redacted);
+            assert_eq!(Some(String::new()), webdav_password(&restored).await);
+        })
+    }
+}
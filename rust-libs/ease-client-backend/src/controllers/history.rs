@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use ease_client_schema::{MusicId, PlaylistId};
+
+use crate::{
+    error::BResult,
+    services::{
+        get_most_played, get_music_play_stats, get_playlist_play_stats, get_recently_played,
+        record_play_event, ArgMostPlayed, ArgRecordPlayEvent, MostPlayedMusic, PlayStats,
+        RecentlyPlayedMusic,
+    },
+    Backend,
+};
+
+#[uniffi::export]
+pub fn cts_record_play_event(cx: Arc<Backend>, arg: ArgRecordPlayEvent) -> BResult<()> {
+    let cx = cx.get_context();
+    record_play_event(cx, arg)
+}
+
+#[uniffi::export]
+pub fn cts_get_music_play_stats(cx: Arc<Backend>, id: MusicId) -> BResult<PlayStats> {
+    let cx = cx.get_context();
+    get_music_play_stats(cx, id)
+}
+
+#[uniffi::export]
+pub fn cts_get_playlist_play_stats(cx: Arc<Backend>, id: PlaylistId) -> BResult<PlayStats> {
+    let cx = cx.get_context();
+    get_playlist_play_stats(cx, id)
+}
+
+#[uniffi::export]
+pub fn cts_get_recently_played(cx: Arc<Backend>, limit: u32) -> BResult<Vec<RecentlyPlayedMusic>> {
+    let cx = cx.get_context();
+    get_recently_played(cx, limit)
+}
+
+#[uniffi::export]
+pub fn cts_get_most_played(cx: Arc<Backend>, arg: ArgMostPlayed) -> BResult<Vec<MostPlayedMusic>> {
+    let cx = cx.get_context();
+    get_most_played(cx, arg)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ease_client_schema::{
+        PlayEventKind, SmartCondition, SmartConditionGroup, SmartPlaylistModel,
+        SmartRuleCombinator, SmartSort, SmartSortField,
+    };
+
+    use crate::{
+        controllers::playlist::{
+            ct_create_playlist, ct_create_smart_playlist, ct_get_playlist, ct_remove_playlist,
+        },
+        error::BError,
+        objects::ArgCreateSmartPlaylist,
+        services::ArgCreatePlaylist,
+        test_utils::{local_storage_id, music_entry, setup_backend},
+    };
+
+    use super::*;
+
+    fn event(
+        music: MusicId,
+        playlist: Option<PlaylistId>,
+        kind: PlayEventKind,
+        position_secs: u64,
+        time_secs: u64,
+    ) -> ArgRecordPlayEvent {
+        ArgRecordPlayEvent {
+            music,
+            playlist,
+            kind,
+            position: Duration::from_secs(position_secs),
+            time: Some(Duration::from_secs(time_secs)),
+        }
+    }
+
+    #[test]
+    fn records_play_events_into_stats() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (_tempdir, backend) = setup_backend();
+            let storage_id = local_storage_id(&backend).await;
+            let created = ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "road".to_string(),
+                    cover: None,
+                    entries: vec![
+                        music_entry(storage_id, "/music/a.mp3"),
+                        music_entry(storage_id, "/music/b.mp3"),
+                    ],
+                },
+            )
+            .await
+            .expect("create playlist");
+            let playlist = Some(created.id);
+            let a = created.music_ids[0].id;
+            let b = created.music_ids[1].id;
+
+            let record = |arg| cts_record_play_event(backend.clone(), arg);
+            record(event(a, playlist, PlayEventKind::Started, 0, 100)).expect("record");
+            record(event(a, playlist, PlayEventKind::Completed, 180, 280)).expect("record");
+            record(event(b, playlist, PlayEventKind::Started, 30, 300)).expect("record");
+            record(event(b, playlist, PlayEventKind::Skipped, 40, 310)).expect("record");
+            record(event(a, None, PlayEventKind::Started, 0, 1000)).expect("record");
+            record(event(a, None, PlayEventKind::Completed, 180, 1180)).expect("record");
+            record(event(b, None, PlayEventKind::Started, 0, 2000)).expect("record");
+            record(event(b, None, PlayEventKind::Completed, 200, 2200)).expect("record");
+            assert!(matches!(
+                record(event(
+                    MusicId::wrap(999),
+                    None,
+                    PlayEventKind::Started,
+                    0,
+                    0
+                )),
+                Err(BError::MusicNotFound(_))
+            ));
+
+            let stats = cts_get_music_play_stats(backend.clone(), a).expect("stats");
+            assert_eq!(2, stats.play_count);
+            assert_eq!(0, stats.skip_count);
+            assert_eq!(Some(Duration::from_secs(1180)), stats.last_played);
+            assert_eq!(Duration::from_secs(360), stats.listened);
+            let stats = cts_get_music_play_stats(backend.clone(), b).expect("stats");
+            assert_eq!(1, stats.play_count);
+            assert_eq!(1, stats.skip_count);
+            assert_eq!(Duration::from_secs(210), stats.listened);
+            let stats = cts_get_playlist_play_stats(backend.clone(), created.id).expect("stats");
+            assert_eq!(1, stats.play_count);
+            assert_eq!(1, stats.skip_count);
+            assert_eq!(Some(Duration::from_secs(310)), stats.last_played);
+            assert_eq!(Duration::from_secs(190), stats.listened);
+
+            let recent = cts_get_recently_played(backend.clone(), 10).expect("recent");
+            let recent: Vec<(MusicId, Duration)> = recent
+                .into_iter()
+                .map(|v| (v.music.meta.id, v.played_time))
+                .collect();
+            assert_eq!(
+                vec![
+                    (b, Duration::from_secs(2000)),
+                    (a, Duration::from_secs(1000))
+                ],
+                recent
+            );
+
+            // Only the plays of `b` end in the window.
+            let most = cts_get_most_played(
+                backend.clone(),
+                ArgMostPlayed {
+                    since: Duration::from_secs(1500),
+                    until: None,
+                    limit: 10,
+                },
+            )
+            .expect("most played");
+            assert_eq!(1, most.len());
+            assert_eq!(b, most[0].music.meta.id);
+            assert_eq!(Duration::from_secs(200), most[0].listened);
+            let most = cts_get_most_played(
+                backend.clone(),
+                ArgMostPlayed {
+                    since: Duration::ZERO,
+                    until: Some(Duration::from_secs(3000)),
+                    limit: 1,
+                },
+            )
+            .expect("most played");
+            assert_eq!(1, most.len());
+            assert_eq!(a, most[0].music.meta.id);
+            assert_eq!(2, most[0].play_count);
+            let most = cts_get_most_played(
+                backend.clone(),
+                ArgMostPlayed {
+                    since: Duration::from_secs(3000),
+                    until: Some(Duration::from_secs(1500)),
+                    limit: 10,
+                },
+            )
+            .expect("most played in an inverted window");
+            assert!(most.is_empty());
+
+            // Smart playlists match the recorded play counts.
+            let smart = ct_create_smart_playlist(
+                backend.clone(),
+                ArgCreateSmartPlaylist {
+                    title: "played twice".to_string(),
+                    cover: None,
+                    rule: SmartPlaylistModel {
+                        combinator: SmartRuleCombinator::All,
+                        groups: vec![SmartConditionGroup {
+                            combinator: SmartRuleCombinator::All,
+                            conditions: vec![SmartCondition::PlayCountRange {
+                                min: Some(2),
+                                max: None,
+                            }],
+                        }],
+                        sort: SmartSort {
+                            field: SmartSortField::PlayCount,
+                            descending: true,
+                        },
+                        limit: None,
+                    },
+                },
+            )
+            .await
+            .expect("create smart playlist");
+            let smart = ct_get_playlist(backend.clone(), smart)
+                .await
+                .expect("get playlist")
+                .expect("playlist");
+            let ids: Vec<MusicId> = smart.musics.iter().map(|v| v.meta.id).collect();
+            assert_eq!(vec![a], ids);
+
+            // Statistics go with the playlist and the musics removed.
+            ct_remove_playlist(backend.clone(), created.id)
+                .await
+                .expect("remove playlist");
+            let stats = cts_get_playlist_play_stats(backend.clone(), created.id).expect("stats");
+            assert_eq!(0, stats.play_count);
+            assert_eq!(None, stats.last_played);
+            let stats = cts_get_music_play_stats(backend.clone(), a).expect("stats");
+            assert_eq!(0, stats.play_count);
+            assert!(cts_get_recently_played(backend.clone(), 10)
+                .expect("recent")
+                .is_empty());
+        });
+    }
+}
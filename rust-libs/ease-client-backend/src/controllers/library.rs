@@ -0,0 +1,197 @@
+use std::sync::Arc;
+
+use ease_client_schema::{AlbumId, ArtistId, GenreId};
+
+use crate::{
+    error::BResult,
+    objects::{Album, AlbumAbstract, Artist, ArtistAbstract, Genre, GenreAbstract},
+    services::{
+        get_album, get_artist, get_genre, list_albums, list_artists, list_genres,
+        update_music_tags, ArgUpdateMusicTags,
+    },
+    Backend,
+};
+
+#[uniffi::export]
+pub async fn ct_list_artists(cx: Arc<Backend>) -> BResult<Vec<ArtistAbstract>> {
+    let cx = cx.get_context();
+    list_artists(cx)
+}
+
+#[uniffi::export]
+pub async fn ct_get_artist(cx: Arc<Backend>, id: ArtistId) -> BResult<Option<Artist>> {
+    let cx = cx.get_context();
+    get_artist(cx, id)
+}
+
+#[uniffi::export]
+pub async fn ct_list_albums(cx: Arc<Backend>) -> BResult<Vec<AlbumAbstract>> {
+    let cx = cx.get_context();
+    list_albums(cx)
+}
+
+#[uniffi::export]
+pub async fn ct_get_album(cx: Arc<Backend>, id: AlbumId) -> BResult<Option<Album>> {
+    let cx = cx.get_context();
+    get_album(cx, id)
+}
+
+#[uniffi::export]
+pub async fn ct_list_genres(cx: Arc<Backend>) -> BResult<Vec<GenreAbstract>> {
+    let cx = cx.get_context();
+    list_genres(cx)
+}
+
+#[uniffi::export]
+pub async fn ct_get_genre(cx: Arc<Backend>, id: GenreId) -> BResult<Option<Genre>> {
+    let cx = cx.get_context();
+    get_genre(cx, id)
+}
+
+#[uniffi::export]
+pub fn cts_update_music_tags(cx: Arc<Backend>, arg: ArgUpdateMusicTags) -> BResult<()> {
+    let cx = cx.get_context();
+    update_music_tags(cx, arg)
+}
+
+#[cfg(test)]
+mod tests {
+    use ease_client_schema::MusicId;
+
+    use crate::{
+        controllers::playlist::{ct_create_playlist, ct_remove_playlist},
+        services::{ArgCreatePlaylist, ArgUpdateMusicTags, ToAddMusicEntry},
+        test_utils::{local_storage, setup_backend},
+        StorageEntry,
+    };
+
+    use super::{
+        ct_get_album, ct_get_artist, ct_list_albums, ct_list_artists, ct_list_genres,
+        cts_update_music_tags,
+    };
+
+    fn tags(id: MusicId, artist: &str, album: &str, track_number: u32) -> ArgUpdateMusicTags {
+        ArgUpdateMusicTags {
+            id,
+            artists: vec![artist.to_string()],
+            album: Some(album.to_string()),
+            album_artist: None,
+            year: Some(2003),
+            genres: vec!["Mandopop".to_string()],
+            track_number: Some(track_number),
+            disc_number: Some(1),
+        }
+    }
+
+    #[test]
+    fn lists_albums_and_artists_with_their_tracks() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (_tempdir, backend) = setup_backend();
+            let local_storage = local_storage(&backend).await;
+
+            let make_entry = |path: &str, name: &str| ToAddMusicEntry {
+                entry: StorageEntry {
+                    storage_id: local_storage.id,
+                    name: name.to_string(),
+                    path: path.to_string(),
+                    size: None,
+                    is_dir: false,
+                },
+                name: name.to_string(),
+            };
+
+            let created = ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "ye-hui-mei".to_string(),
+                    cover: None,
+                    entries: vec![
+                        make_entry("/album/02.flac", "晴天"),
+                        make_entry("/album/01.flac", "以父之名"),
+                        make_entry("/other/01.flac", "Other"),
+                    ],
+                },
+            )
+            .await
+            .expect("create playlist");
+            let ids: Vec<MusicId> = created.music_ids.iter().map(|m| m.id).collect();
+
+            cts_update_music_tags(backend.clone(), tags(ids[0], "周杰伦", "叶惠美", 2))
+                .expect("tag first");
+            cts_update_music_tags(backend.clone(), tags(ids[1], " 周杰伦 ", "叶惠美", 1))
+                .expect("tag second");
+            cts_update_music_tags(backend.clone(), tags(ids[2], "Someone", "Else", 1))
+                .expect("tag third");
+
+            let artists = ct_list_artists(backend.clone())
+                .await
+                .expect("list artists");
+            assert_eq!(2, artists.len());
+            let jay = artists
+                .iter()
+                .find(|artist| artist.name == "周杰伦")
+                .expect("artist exists");
+            assert_eq!(2, jay.music_count);
+
+            let albums = ct_list_albums(backend.clone()).await.expect("list albums");
+            assert_eq!(2, albums.len());
+            let album = albums
+                .iter()
+                .find(|album| album.title == "叶惠美")
+                .expect("album exists");
+            assert_eq!(Some(2003), album.year);
+            assert_eq!(
+                Some("周杰伦"),
+                album.artist.as_ref().map(|artist| artist.name.as_str())
+            );
+
+            let album = ct_get_album(backend.clone(), album.id)
+                .await
+                .expect("get album")
+                .expect("album");
+            assert_eq!(
+                vec!["以父之名", "晴天"],
+                album
+                    .musics
+                    .iter()
+                    .map(|m| m.meta.title.as_str())
+                    .collect::<Vec<_>>()
+            );
+
+            let artist = ct_get_artist(backend.clone(), jay.id)
+                .await
+                .expect("get artist")
+                .expect("artist");
+            assert_eq!(1, artist.albums.len());
+            assert_eq!(2, artist.musics.len());
+
+            let genres = ct_list_genres(backend.clone()).await.expect("list genres");
+            assert_eq!(1, genres.len());
+            assert_eq!(3, genres[0].music_count);
+
+            cts_update_music_tags(
+                backend.clone(),
+                ArgUpdateMusicTags {
+                    id: ids[2],
+                    artists: vec![],
+                    album: None,
+                    album_artist: None,
+                    year: None,
+                    genres: vec![],
+                    track_number: None,
+                    disc_number: None,
+                },
+            )
+            .expect("clear tags");
+            assert_eq!(1, ct_list_albums(backend.clone()).await.unwrap().len());
+            assert_eq!(1, ct_list_artists(backend.clone()).await.unwrap().len());
+
+            ct_remove_playlist(backend.clone(), created.id)
+                .await
+                .expect("remove playlist");
+            assert!(ct_list_albums(backend.clone()).await.unwrap().is_empty());
+            assert!(ct_list_artists(backend.clone()).await.unwrap().is_empty());
+            assert!(ct_list_genres(backend).await.unwrap().is_empty());
+        })
+    }
+}
@@ -1,7 +1,16 @@
-mod asset;
-mod debug;
-mod lrcapi;
-mod music;
-mod playlist;
-mod preference;
-mod storage;
+pub mod asset;
+pub mod backup;
+pub mod debug;
+pub mod history;
+pub mod library;
+pub mod lrcapi;
+pub mod music;
+pub mod playlist;
+pub mod preference;
+pub mod queue;
+pub mod scan;
+pub mod scrobble;
+pub mod search;
+pub mod storage;
+pub mod sync;
+pub mod user_data;
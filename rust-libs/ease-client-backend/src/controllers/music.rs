@@ -1,14 +1,18 @@
 use std::sync::Arc;
 
-use ease_client_schema::MusicId;
+use ease_client_schema::{MusicId, PlaylistId};
 
 use crate::{
     error::BResult,
     objects::Music,
     repositories::music::ArgDBAddMusic,
     services::{
-        get_music, get_music_abstract, update_music_cover, update_music_duration, ArgEnsureMusics,
-        ArgUpdateMusicCover, ArgUpdateMusicDuration, ArgUpdateMusicLyric,
+        analyze_musics_loudness, extract_music_cover, find_cross_storage_duplicates,
+        find_playlist_duplicates, fingerprint_musics, get_music, get_music_abstract, new_music_ids,
+        note_sync_changes, probe_musics, spawn_probe_musics, update_music_cover,
+        update_music_duration, ArgAnalyzeLoudness, ArgEnsureMusics, ArgExtractMusicCover,
+        ArgFingerprintMusics, ArgProbeMusics, ArgUpdateMusicCover, ArgUpdateMusicDuration,
+        ArgUpdateMusicLyric, DuplicateMusicGroup, SyncChange,
     },
     Backend, MusicAbstract,
 };
@@ -30,6 +34,7 @@ pub async fn ct_update_music_lyric(cx: Arc<Backend>, arg: ArgUpdateMusicLyric) -
     let cx = cx.get_context();
     cx.database_server()
         .update_music_lyric(arg.id, arg.lyric_loc)?;
+    note_sync_changes(cx, &[SyncChange::Music(arg.id)]);
 
     Ok(())
 }
@@ -46,6 +51,12 @@ pub fn cts_update_music_cover(cx: Arc<Backend>, arg: ArgUpdateMusicCover) -> BRe
     update_music_cover(cx, arg)
 }
 
+#[uniffi::export]
+pub async fn ct_extract_music_cover(cx: Arc<Backend>, arg: ArgExtractMusicCover) -> BResult<bool> {
+    let cx = cx.get_context();
+    extract_music_cover(cx, arg).await
+}
+
 #[uniffi::export]
 pub async fn ct_ensure_musics(
     cx: Arc<Backend>,
@@ -63,5 +74,40 @@ pub async fn ct_ensure_musics(
             title: entry.name,
         })
         .collect();
-    cx.database_server().upsert_musics(musics)
+    let ret = cx.database_server().upsert_musics(musics)?;
+    spawn_probe_musics(cx, new_music_ids(&ret));
+    Ok(ret)
+}
+
+#[uniffi::export]
+pub async fn ct_analyze_loudness(cx: Arc<Backend>, arg: ArgAnalyzeLoudness) -> BResult<()> {
+    let cx = cx.get_context();
+    analyze_musics_loudness(cx, arg).await
+}
+
+#[uniffi::export]
+pub async fn ct_probe_musics(cx: Arc<Backend>, arg: ArgProbeMusics) -> BResult<()> {
+    let cx = cx.get_context();
+    probe_musics(cx, arg).await
+}
+
+#[uniffi::export]
+pub async fn ct_fingerprint_musics(cx: Arc<Backend>, arg: ArgFingerprintMusics) -> BResult<()> {
+    let cx = cx.get_context();
+    fingerprint_musics(cx, arg).await
+}
+
+#[uniffi::export]
+pub fn cts_find_cross_storage_duplicates(cx: Arc<Backend>) -> BResult<Vec<DuplicateMusicGroup>> {
+    let cx = cx.get_context();
+    find_cross_storage_duplicates(cx)
+}
+
+#[uniffi::export]
+pub fn cts_find_playlist_duplicates(
+    cx: Arc<Backend>,
+    id: PlaylistId,
+) -> BResult<Vec<DuplicateMusicGroup>> {
+    let cx = cx.get_context();
+    find_playlist_duplicates(cx, id)
 }
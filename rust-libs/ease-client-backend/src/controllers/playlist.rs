@@ -1,15 +1,24 @@
 use std::sync::Arc;
 
-use ease_client_schema::{MusicId, PlaylistId, StorageEntryLoc};
+use ease_client_schema::{
+    MusicId, PlaylistFolderModel, PlaylistId, SmartPlaylistModel, StorageEntryLoc,
+};
 use ease_order_key::{OrderKey, OrderKeyRef};
 
 use crate::{
     error::{BError, BResult},
-    objects::{Playlist, PlaylistAbstract},
+    objects::{
+        ArgBindPlaylistFolder, ArgCreateSmartPlaylist, ArgUpdateSmartPlaylist, Playlist,
+        PlaylistAbstract,
+    },
     repositories::music::{AddedMusic, ArgDBAddMusic},
     services::{
-        get_all_playlist_abstracts, get_playlist, ArgAddMusicsToPlaylist, ArgCreatePlaylist,
-        ArgRemoveMusicFromPlaylist, ArgUpdatePlaylist,
+        check_smart_playlist_rule, compile_folder_glob, export_playlist,
+        export_playlist_to_storage, get_all_playlist_abstracts, get_playlist, import_playlist,
+        new_music_ids, next_playlist_order, note_sync_changes, refresh_playlist_folder,
+        spawn_probe_musics, ArgAddMusicsToPlaylist, ArgCreatePlaylist, ArgExportPlaylist,
+        ArgExportPlaylistToStorage, ArgImportPlaylist, ArgRemoveMusicFromPlaylist,
+        ArgUpdatePlaylist, ExportedPlaylist, FolderRefreshResult, PlaylistImportResult, SyncChange,
     },
     Backend,
 };
@@ -25,6 +34,7 @@ pub async fn ct_update_playlist(cx: Arc<Backend>, arg: ArgUpdatePlaylist) -> BRe
     let cx = cx.get_context();
     cx.database_server()
         .update_playlist(arg.id, arg.title, arg.cover)?;
+    note_sync_changes(cx, &[SyncChange::Playlist(arg.id)]);
 
     Ok(())
 }
@@ -66,18 +76,15 @@ pub async fn ct_create_playlist(
         })
         .collect();
 
-    let last_order = get_all_playlist_abstracts(cx)?
-        .last()
-        .map(|v| OrderKey::wrap(v.meta.order.clone()))
-        .unwrap_or_default();
-
     let (playlist_id, music_ids) = cx.database_server().create_playlist(
         arg.title,
         arg.cover.clone(),
         musics,
         current_time_ms,
-        OrderKey::greater(&last_order),
+        next_playlist_order(cx)?,
     )?;
+    spawn_probe_musics(cx, new_music_ids(&music_ids));
+    note_sync_changes(cx, &[SyncChange::PlaylistMusics(playlist_id)]);
 
     Ok(RetCreatePlaylist {
         id: playlist_id,
@@ -85,6 +92,128 @@ pub async fn ct_create_playlist(
     })
 }
 
+#[uniffi::export]
+pub async fn ct_create_smart_playlist(
+    cx: Arc<Backend>,
+    arg: ArgCreateSmartPlaylist,
+) -> BResult<PlaylistId> {
+    let cx = cx.get_context();
+    check_smart_playlist_rule(&arg.rule)?;
+    let current_time_ms = cx.current_time().as_millis() as i64;
+
+    cx.database_server().create_smart_playlist(
+        arg.title,
+        arg.cover,
+        arg.rule,
+        current_time_ms,
+        next_playlist_order(cx)?,
+    )
+}
+
+#[uniffi::export]
+pub async fn ct_update_smart_playlist(
+    cx: Arc<Backend>,
+    arg: ArgUpdateSmartPlaylist,
+) -> BResult<()> {
+    let cx = cx.get_context();
+    check_smart_playlist_rule(&arg.rule)?;
+    if !cx
+        .database_server()
+        .update_smart_playlist(arg.id, arg.rule)?
+    {
+        return Err(BError::PlaylistNotFound(arg.id));
+    }
+    Ok(())
+}
+
+#[uniffi::export]
+pub fn cts_get_smart_playlist_rule(
+    cx: Arc<Backend>,
+    arg: PlaylistId,
+) -> BResult<Option<SmartPlaylistModel>> {
+    let cx = cx.get_context();
+    cx.database_server().load_smart_playlist(arg)
+}
+
+#[uniffi::export]
+pub async fn ct_bind_playlist_folder(cx: Arc<Backend>, arg: ArgBindPlaylistFolder) -> BResult<()> {
+    let cx = cx.get_context();
+    compile_folder_glob(arg.glob.as_deref())?;
+    if cx.database_server().load_playlist(arg.id)?.is_none() {
+        return Err(BError::PlaylistNotFound(arg.id));
+    }
+    if cx.database_server().load_smart_playlist(arg.id)?.is_some() {
+        return Err(BError::SmartPlaylistNotEditable(arg.id));
+    }
+    let missing = cx
+        .database_server()
+        .load_playlist_folder(arg.id)?
+        .map(|v| v.missing)
+        .unwrap_or_default();
+    cx.database_server().save_playlist_folder(
+        arg.id,
+        Some(PlaylistFolderModel {
+            dir: arg.dir,
+            recursive: arg.recursive,
+            glob: arg.glob,
+            on_removed: arg.on_removed,
+            refresh_interval: arg.refresh_interval,
+            last_refreshed: 0,
+            missing,
+        }),
+    )
+}
+
+#[uniffi::export]
+pub async fn ct_unbind_playlist_folder(cx: Arc<Backend>, arg: PlaylistId) -> BResult<()> {
+    let cx = cx.get_context();
+    cx.database_server().save_playlist_folder(arg, None)
+}
+
+#[uniffi::export]
+pub fn cts_get_playlist_folder(
+    cx: Arc<Backend>,
+    arg: PlaylistId,
+) -> BResult<Option<PlaylistFolderModel>> {
+    let cx = cx.get_context();
+    cx.database_server().load_playlist_folder(arg)
+}
+
+#[uniffi::export]
+pub async fn ct_refresh_playlist_folder(
+    cx: Arc<Backend>,
+    arg: PlaylistId,
+) -> BResult<FolderRefreshResult> {
+    let cx = cx.get_context();
+    refresh_playlist_folder(cx, arg).await
+}
+
+#[uniffi::export]
+pub async fn ct_import_playlist(
+    cx: Arc<Backend>,
+    arg: ArgImportPlaylist,
+) -> BResult<PlaylistImportResult> {
+    let cx = cx.get_context();
+    let ret = import_playlist(cx, arg).await?;
+    note_sync_changes(cx, &[SyncChange::PlaylistMusics(ret.id)]);
+    Ok(ret)
+}
+
+#[uniffi::export]
+pub fn cts_export_playlist(cx: Arc<Backend>, arg: ArgExportPlaylist) -> BResult<ExportedPlaylist> {
+    let cx = cx.get_context();
+    export_playlist(cx, arg)
+}
+
+#[uniffi::export]
+pub async fn ct_export_playlist_to_storage(
+    cx: Arc<Backend>,
+    arg: ArgExportPlaylistToStorage,
+) -> BResult<StorageEntryLoc> {
+    let cx = cx.get_context();
+    export_playlist_to_storage(cx, arg).await
+}
+
 #[uniffi::export]
 pub async fn ct_add_musics_to_playlist(
     cx: Arc<Backend>,
@@ -111,6 +240,9 @@ pub async fn ct_add_musics_to_playlist(
     let Some(playlist) = get_playlist(cx, arg.id)? else {
         return Err(BError::PlaylistNotFound(arg.id));
     };
+    if playlist.abstr.meta.is_smart {
+        return Err(BError::SmartPlaylistNotEditable(arg.id));
+    }
     let last_order = playlist
         .musics
         .last()
@@ -120,6 +252,8 @@ pub async fn ct_add_musics_to_playlist(
     let ret = cx
         .database_server()
         .add_musics_to_playlist(arg.id, musics, last_order)?;
+    spawn_probe_musics(cx, new_music_ids(&ret));
+    note_sync_changes(cx, &[SyncChange::PlaylistMusics(arg.id)]);
 
     Ok(ret)
 }
@@ -130,17 +264,25 @@ pub async fn ct_remove_music_from_playlist(
     arg: ArgRemoveMusicFromPlaylist,
 ) -> BResult<()> {
     let cx = cx.get_context();
+    if cx
+        .database_server()
+        .load_smart_playlist(arg.playlist_id)?
+        .is_some()
+    {
+        return Err(BError::SmartPlaylistNotEditable(arg.playlist_id));
+    }
     cx.database_server()
         .remove_music_from_playlist(arg.playlist_id, arg.music_id)?;
+    note_sync_changes(cx, &[SyncChange::PlaylistMusics(arg.playlist_id)]);
 
     Ok(())
 }
 
 #[derive(uniffi::Record)]
 pub struct ArgReorderPlaylist {
-    id: PlaylistId,
-    a: Option<PlaylistId>,
-    b: Option<PlaylistId>,
+    pub id: PlaylistId,
+    pub a: Option<PlaylistId>,
+    pub b: Option<PlaylistId>,
 }
 
 #[uniffi::export]
@@ -150,17 +292,17 @@ pub fn cts_reorder_playlist(cx: Arc<Backend>, arg: ArgReorderPlaylist) -> BResul
         return Ok(());
     }
 
-    let playlists = get_all_playlist_abstracts(cx)?;
+    let playlists = cx.database_server().load_playlists()?;
 
     let from = playlists
         .iter()
-        .find(|v| v.meta.id == arg.id)
+        .find(|v| v.id == arg.id)
         .ok_or(BError::PlaylistNotFound(arg.id))?;
     let a = match arg.a {
         Some(id) => Some(
             playlists
                 .iter()
-                .find(|v| v.meta.id == id)
+                .find(|v| v.id == id)
                 .ok_or(BError::PlaylistNotFound(id))?,
         ),
         None => None,
@@ -169,7 +311,7 @@ pub fn cts_reorder_playlist(cx: Arc<Backend>, arg: ArgReorderPlaylist) -> BResul
         Some(id) => Some(
             playlists
                 .iter()
-                .find(|v| v.meta.id == id)
+                .find(|v| v.id == id)
                 .ok_or(BError::PlaylistNotFound(id))?,
         ),
         None => None,
@@ -180,8 +322,8 @@ pub fn cts_reorder_playlist(cx: Arc<Backend>, arg: ArgReorderPlaylist) -> BResul
         return Ok(());
     }
 
-    let a_order = a.map(|v| OrderKeyRef::wrap(&v.meta.order));
-    let b_order = b.map(|v| OrderKeyRef::wrap(&v.meta.order));
+    let a_order = a.map(|v| OrderKeyRef::wrap(&v.order));
+    let b_order = b.map(|v| OrderKeyRef::wrap(&v.order));
     let order = {
         match (a_order, b_order) {
             (Some(a), Some(b)) => OrderKey::between(a, b)?,
@@ -191,8 +333,8 @@ pub fn cts_reorder_playlist(cx: Arc<Backend>, arg: ArgReorderPlaylist) -> BResul
         }
     };
 
-    cx.database_server()
-        .set_playlist_order(from.meta.id, order)?;
+    cx.database_server().set_playlist_order(from.id, order)?;
+    note_sync_changes(cx, &[SyncChange::Playlist(from.id)]);
     Ok(())
 }
 
@@ -200,16 +342,17 @@ pub fn cts_reorder_playlist(cx: Arc<Backend>, arg: ArgReorderPlaylist) -> BResul
 pub async fn ct_remove_playlist(cx: Arc<Backend>, arg: PlaylistId) -> BResult<()> {
     let cx = cx.get_context();
     cx.database_server().remove_playlist(arg)?;
+    note_sync_changes(cx, &[SyncChange::Playlist(arg)]);
 
     Ok(())
 }
 
 #[derive(uniffi::Record)]
 pub struct ArgReorderMusic {
-    playlist_id: PlaylistId,
-    id: MusicId,
-    a: Option<MusicId>,
-    b: Option<MusicId>,
+    pub playlist_id: PlaylistId,
+    pub id: MusicId,
+    pub a: Option<MusicId>,
+    pub b: Option<MusicId>,
 }
 
 #[uniffi::export]
@@ -221,6 +364,9 @@ pub fn cts_reorder_music_in_playlist(cx: Arc<Backend>, arg: ArgReorderMusic) ->
     let Some(playlist) = get_playlist(cx, arg.playlist_id)? else {
         return Err(BError::PlaylistNotFound(arg.playlist_id));
     };
+    if playlist.abstr.meta.is_smart {
+        return Err(BError::SmartPlaylistNotEditable(arg.playlist_id));
+    }
 
     let from = playlist
         .musics
@@ -266,54 +412,52 @@ pub fn cts_reorder_music_in_playlist(cx: Arc<Backend>, arg: ArgReorderMusic) ->
 
     cx.database_server()
         .set_playlist_music_order(arg.playlist_id, from.meta.id, order)?;
+    note_sync_changes(cx, &[SyncChange::PlaylistMusics(arg.playlist_id)]);
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
+    use std::time::Duration;
 
-    use ease_client_schema::StorageType;
-    use tempfile::TempDir;
+    use ease_client_schema::{
+        FolderRemovedPolicy, SmartCondition, SmartConditionGroup, SmartPlaylistModel,
+        SmartRuleCombinator, SmartSort, SmartSortField, SmartTagField, StorageEntryLoc,
+        StorageType,
+    };
 
     use crate::{
-        controllers::storage::ct_list_storage,
-        create_backend,
-        services::{ArgCreatePlaylist, ArgInitializeApp, ToAddMusicEntry},
+        controllers::{
+            library::cts_update_music_tags,
+            storage::{ct_list_storage, ct_remove_storage, ct_upsert_storage},
+        },
+        error::BError,
+        objects::{
+            ArgBindPlaylistFolder, ArgCreateSmartPlaylist, ArgUpdateSmartPlaylist, ArgUpsertStorage,
+        },
+        services::{
+            ArgAddMusicsToPlaylist, ArgCreatePlaylist, ArgExportPlaylist,
+            ArgExportPlaylistToStorage, ArgImportPlaylist, ArgUpdateMusicTags,
+            PlaylistExportFormat, PlaylistExportPaths, ToAddMusicEntry,
+            UnresolvedPlaylistEntryReason,
+        },
+        test_utils::{local_storage, setup_backend},
         StorageEntry,
     };
 
     use super::{
-        ct_create_playlist, ct_get_playlist, ct_list_playlist, cts_reorder_music_in_playlist,
+        ct_add_musics_to_playlist, ct_bind_playlist_folder, ct_create_playlist,
+        ct_create_smart_playlist, ct_export_playlist_to_storage, ct_get_playlist,
+        ct_import_playlist, ct_list_playlist, ct_refresh_playlist_folder, ct_update_smart_playlist,
+        cts_export_playlist, cts_get_playlist_folder, cts_reorder_music_in_playlist,
         ArgReorderMusic,
     };
 
-    fn setup_backend() -> (TempDir, Arc<crate::Backend>) {
-        let tempdir = tempfile::tempdir().expect("create tempdir");
-        let documents_dir = tempdir.path().join("documents");
-        let cache_dir = tempdir.path().join("cache");
-        std::fs::create_dir_all(&documents_dir).expect("create documents dir");
-        std::fs::create_dir_all(&cache_dir).expect("create cache dir");
-
-        let backend = create_backend(ArgInitializeApp {
-            app_document_dir: format!("{}/", documents_dir.display()),
-            app_cache_dir: format!("{}/", cache_dir.display()),
-            storage_path: "/".to_string(),
-        });
-        backend.init().expect("init backend");
-        (tempdir, backend)
-    }
-
     #[test]
     fn reorder_music_only_changes_membership_order_inside_target_playlist() {
         ease_client_tokio::tokio_runtime().block_on(async {
             let (_tempdir, backend) = setup_backend();
-            let local_storage = ct_list_storage(backend.clone())
-                .await
-                .expect("list storages")
-                .into_iter()
-                .find(|storage| storage.typ == StorageType::Local)
-                .expect("local storage");
+            let local_storage = local_storage(&backend).await;
 
             let make_entry = |path: &str, name: &str| ToAddMusicEntry {
                 entry: StorageEntry {
@@ -417,4 +561,551 @@ mod tests {
             assert!(playlist_titles.iter().any(|title| title == "playlist-b"));
         })
     }
+
+    #[test]
+    fn smart_playlist_evaluates_rule_on_read() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (_tempdir, backend) = setup_backend();
+            let local_storage = local_storage(&backend).await;
+
+            let make_entry = |path: &str, name: &str| ToAddMusicEntry {
+                entry: StorageEntry {
+                    storage_id: local_storage.id,
+                    name: name.to_string(),
+                    path: path.to_string(),
+                    size: None,
+                    is_dir: false,
+                },
+                name: name.to_string(),
+            };
+            let created = ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "all".to_string(),
+                    cover: None,
+                    entries: vec![
+                        make_entry("/rock/a.flac", "Alpha"),
+                        make_entry("/rock/live/b.mp3", "Beta"),
+                        make_entry("/pop/c.flac", "Gamma"),
+                        make_entry("/pop/d.flac", "Delta"),
+                    ],
+                },
+            )
+            .await
+            .expect("create playlist");
+            let ids: Vec<_> = created.music_ids.iter().map(|m| m.id).collect();
+            let cx = backend.get_context();
+            cx.database_server()
+                .update_music_total_duration(ids[0], Duration::from_secs(200))
+                .expect("update duration");
+            cx.database_server()
+                .update_music_total_duration(ids[1], Duration::from_secs(400))
+                .expect("update duration");
+            cts_update_music_tags(
+                backend.clone(),
+                ArgUpdateMusicTags {
+                    id: ids[3],
+                    artists: vec!["Rock Band".to_string()],
+                    album: None,
+                    album_artist: None,
+                    year: None,
+                    genres: vec![],
+                    track_number: None,
+                    disc_number: None,
+                },
+            )
+            .expect("update tags");
+
+            // Short flac files under /rock, or musics by an artist named
+            // like "rock".
+            let mut rule = SmartPlaylistModel {
+                combinator: SmartRuleCombinator::Any,
+                groups: vec![
+                    SmartConditionGroup {
+                        combinator: SmartRuleCombinator::All,
+                        conditions: vec![
+                            SmartCondition::PathPrefix {
+                                prefix: "/rock/".to_string(),
+                            },
+                            SmartCondition::PathGlob {
+                                pattern: "**/*.flac".to_string(),
+                            },
+                            SmartCondition::DurationRange {
+                                min: None,
+                                max: Some(Duration::from_secs(300)),
+                            },
+                        ],
+                    },
+                    SmartConditionGroup {
+                        combinator: SmartRuleCombinator::All,
+                        conditions: vec![SmartCondition::TagContains {
+                            field: Some(SmartTagField::Artist),
+                            text: "ROCK".to_string(),
+                        }],
+                    },
+                ],
+                sort: SmartSort {
+                    field: SmartSortField::Title,
+                    descending: true,
+                },
+                limit: None,
+            };
+            let smart_id = ct_create_smart_playlist(
+                backend.clone(),
+                ArgCreateSmartPlaylist {
+                    title: "smart".to_string(),
+                    cover: None,
+                    rule: rule.clone(),
+                },
+            )
+            .await
+            .expect("create smart playlist");
+
+            let titles = |playlist: crate::objects::Playlist| {
+                playlist
+                    .musics
+                    .into_iter()
+                    .map(|m| m.meta.title)
+                    .collect::<Vec<_>>()
+            };
+            let smart = ct_get_playlist(backend.clone(), smart_id)
+                .await
+                .expect("get smart playlist")
+                .expect("smart playlist");
+            assert!(smart.abstr.meta.is_smart);
+            assert_eq!(vec!["Delta", "Alpha"], titles(smart));
+
+            let listed = ct_list_playlist(backend.clone())
+                .await
+                .expect("list playlists");
+            assert_eq!(
+                vec![(false, 4), (true, 2)],
+                listed
+                    .iter()
+                    .map(|p| (p.meta.is_smart, p.music_count))
+                    .collect::<Vec<_>>()
+            );
+
+            rule.combinator = SmartRuleCombinator::All;
+            rule.groups[0].combinator = SmartRuleCombinator::Any;
+            rule.groups[1].conditions = vec![SmartCondition::AddedTimeRange {
+                after: Some(Duration::from_secs(1)),
+                before: None,
+            }];
+            rule.sort.field = SmartSortField::Path;
+            rule.limit = Some(3);
+            ct_update_smart_playlist(
+                backend.clone(),
+                ArgUpdateSmartPlaylist { id: smart_id, rule },
+            )
+            .await
+            .expect("update smart playlist");
+            let smart = ct_get_playlist(backend.clone(), smart_id)
+                .await
+                .expect("get smart playlist")
+                .expect("smart playlist");
+            assert_eq!(vec!["Beta", "Alpha", "Delta"], titles(smart));
+
+            let added = ct_add_musics_to_playlist(
+                backend.clone(),
+                ArgAddMusicsToPlaylist {
+                    id: smart_id,
+                    entries: vec![make_entry("/pop/e.flac", "Epsilon")],
+                },
+            )
+            .await;
+            assert!(matches!(added, Err(BError::SmartPlaylistNotEditable(_))));
+        })
+    }
+
+    #[test]
+    fn folder_playlist_syncs_with_directory() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let media_dir = tempdir.path().join("media");
+            std::fs::create_dir_all(media_dir.join("disc2")).expect("create media dir");
+            for name in ["a.mp3", "b.flac", "disc2/c.ogg", "notes.txt"] {
+                std::fs::write(media_dir.join(name), b"data").expect("write file");
+            }
+            let media_dir = media_dir.canonicalize().expect("canonicalize media dir");
+            let local_storage = local_storage(&backend).await;
+
+            let id = ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "folder".to_string(),
+                    cover: None,
+                    entries: vec![],
+                },
+            )
+            .await
+            .expect("create playlist")
+            .id;
+            let bind = |on_removed| ArgBindPlaylistFolder {
+                id,
+                dir: StorageEntryLoc {
+                    storage_id: local_storage.id,
+                    path: media_dir.to_string_lossy().to_string(),
+                },
+                recursive: true,
+                glob: None,
+                on_removed,
+                refresh_interval: None,
+            };
+            ct_bind_playlist_folder(backend.clone(), bind(FolderRemovedPolicy::Mark))
+                .await
+                .expect("bind folder");
+
+            let names = |playlist: crate::objects::Playlist| {
+                playlist
+                    .musics
+                    .into_iter()
+                    .map(|m| m.meta.title)
+                    .collect::<Vec<_>>()
+            };
+            let result = ct_refresh_playlist_folder(backend.clone(), id)
+                .await
+                .expect("refresh folder");
+            assert_eq!(3, result.added.len());
+            let playlist = ct_get_playlist(backend.clone(), id)
+                .await
+                .expect("get playlist")
+                .expect("playlist");
+            let ids: Vec<_> = playlist.musics.iter().map(|m| m.meta.id).collect();
+            assert_eq!(vec!["a.mp3", "b.flac", "c.ogg"], names(playlist));
+
+            cts_reorder_music_in_playlist(
+                backend.clone(),
+                ArgReorderMusic {
+                    playlist_id: id,
+                    id: ids[2],
+                    a: None,
+                    b: Some(ids[0]),
+                },
+            )
+            .expect("reorder");
+            std::fs::remove_file(media_dir.join("a.mp3")).expect("remove file");
+            std::fs::write(media_dir.join("d.mp3"), b"data").expect("write file");
+
+            let result = ct_refresh_playlist_folder(backend.clone(), id)
+                .await
+                .expect("refresh folder");
+            assert_eq!(1, result.added.len());
+            assert_eq!(vec![ids[0]], result.missing);
+            assert_eq!(2, result.unchanged);
+            let playlist = ct_get_playlist(backend.clone(), id)
+                .await
+                .expect("get playlist")
+                .expect("playlist");
+            assert_eq!(vec!["c.ogg", "a.mp3", "b.flac", "d.mp3"], names(playlist));
+            let folder = cts_get_playlist_folder(backend.clone(), id)
+                .expect("get folder")
+                .expect("folder");
+            assert_eq!(vec![ids[0]], folder.missing);
+
+            ct_bind_playlist_folder(backend.clone(), bind(FolderRemovedPolicy::Remove))
+                .await
+                .expect("rebind folder");
+            let result = ct_refresh_playlist_folder(backend.clone(), id)
+                .await
+                .expect("refresh folder");
+            assert_eq!(vec![ids[0]], result.removed);
+            let playlist = ct_get_playlist(backend.clone(), id)
+                .await
+                .expect("get playlist")
+                .expect("playlist");
+            assert_eq!(vec!["c.ogg", "b.flac", "d.mp3"], names(playlist));
+        })
+    }
+
+    #[test]
+    fn folder_binding_is_dropped_with_its_storage() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let media_dir = tempdir.path().join("media");
+            std::fs::create_dir_all(&media_dir).expect("create media dir");
+            std::fs::write(media_dir.join("a.mp3"), b"data").expect("write file");
+            let media_dir = media_dir.canonicalize().expect("canonicalize media dir");
+            ct_upsert_storage(
+                backend.clone(),
+                ArgUpsertStorage {
+                    id: None,
+                    addr: String::new(),
+                    alias: "demo".to_string(),
+                    username: String::new(),
+                    password: String::new(),
+                    is_anonymous: true,
+                    typ: StorageType::Local,
+                    default_path: "/".to_string(),
+                },
+            )
+            .await
+            .expect("insert storage");
+            let storage_id = ct_list_storage(backend.clone())
+                .await
+                .expect("list storages")
+                .into_iter()
+                .find(|storage| storage.alias == "demo")
+                .expect("inserted storage")
+                .id;
+
+            let id = ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "folder".to_string(),
+                    cover: None,
+                    entries: vec![],
+                },
+            )
+            .await
+            .expect("create playlist")
+            .id;
+            ct_bind_playlist_folder(
+                backend.clone(),
+                ArgBindPlaylistFolder {
+                    id,
+                    dir: StorageEntryLoc {
+                        storage_id,
+                        path: media_dir.to_string_lossy().to_string(),
+                    },
+                    recursive: true,
+                    glob: None,
+                    on_removed: FolderRemovedPolicy::Remove,
+                    refresh_interval: Some(Duration::from_secs(60)),
+                },
+            )
+            .await
+            .expect("bind folder");
+            let db = backend.get_context().database_server();
+            let folder = db
+                .load_playlist_folder(id)
+                .expect("load folder")
+                .expect("folder");
+
+            ct_remove_storage(backend.clone(), storage_id)
+                .await
+                .expect("remove storage");
+            assert!(cts_get_playlist_folder(backend.clone(), id)
+                .expect("get folder")
+                .is_none());
+            assert!(matches!(
+                ct_refresh_playlist_folder(backend.clone(), id).await,
+                Err(BError::PlaylistNotFound(_))
+            ));
+
+            // A folder of a storage that is gone is not listed as empty.
+            db.save_playlist_folder(id, Some(folder))
+                .expect("save folder");
+            assert!(matches!(
+                ct_refresh_playlist_folder(backend.clone(), id).await,
+                Err(BError::AssetNotFound)
+            ));
+        })
+    }
+
+    #[test]
+    fn imports_m3u_resolving_paths_against_playlist_file() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let media_dir = tempdir.path().join("media");
+            std::fs::create_dir_all(media_dir.join("lists")).expect("create lists dir");
+            std::fs::create_dir_all(media_dir.join("songs")).expect("create songs dir");
+            for name in ["songs/a.mp3", "songs/b side.mp3", "songs/cover.jpg"] {
+                std::fs::write(media_dir.join(name), b"data").expect("write file");
+            }
+            let media_dir = media_dir.canonicalize().expect("canonicalize media dir");
+            let a_path = media_dir.join("songs/a.mp3");
+            let m3u = format!(
+                "#EXTM3U\n#EXTINF:125,Artist - A\n../songs/a.mp3\n..\\songs\\B%20Side.MP3\n\
+                 ../songs/missing.mp3\n../songs/cover.jpg\nhttp://radio.example/live\n\
+                 file://{}\n",
+                a_path.display()
+            );
+            let list_path = media_dir.join("lists/road trip.m3u8");
+            std::fs::write(&list_path, m3u).expect("write playlist");
+            let local_storage = local_storage(&backend).await;
+
+            let result = ct_import_playlist(
+                backend.clone(),
+                ArgImportPlaylist {
+                    loc: StorageEntryLoc {
+                        storage_id: local_storage.id,
+                        path: list_path.to_string_lossy().to_string(),
+                    },
+                    title: None,
+                },
+            )
+            .await
+            .expect("import playlist");
+            assert_eq!(2, result.music_ids.len());
+            let reasons: Vec<_> = result
+                .unresolved
+                .iter()
+                .map(|v| (v.location.as_str(), v.reason))
+                .collect();
+            assert_eq!(
+                vec![
+                    (
+                        "../songs/missing.mp3",
+                        UnresolvedPlaylistEntryReason::NotFound
+                    ),
+                    (
+                        "../songs/cover.jpg",
+                        UnresolvedPlaylistEntryReason::NotMusic
+                    ),
+                    (
+                        "http://radio.example/live",
+                        UnresolvedPlaylistEntryReason::Remote
+                    ),
+                ],
+                reasons
+            );
+
+            let playlist = ct_get_playlist(backend.clone(), result.id)
+                .await
+                .expect("get playlist")
+                .expect("playlist");
+            assert_eq!("road trip", playlist.abstr.meta.title);
+            let musics: Vec<_> = playlist
+                .musics
+                .iter()
+                .map(|m| (m.meta.title.as_str(), m.meta.duration))
+                .collect();
+            assert_eq!(
+                vec![
+                    ("Artist - A", Some(Duration::from_secs(125))),
+                    ("b side.mp3", None)
+                ],
+                musics
+            );
+        })
+    }
+
+    #[test]
+    fn exported_playlists_import_back_to_same_musics() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let media_dir = tempdir.path().join("media");
+            std::fs::create_dir_all(media_dir.join("lists")).expect("create lists dir");
+            std::fs::create_dir_all(media_dir.join("songs")).expect("create songs dir");
+            for name in ["songs/a.mp3", "songs/b & c.mp3"] {
+                std::fs::write(media_dir.join(name), b"data").expect("write file");
+            }
+            let media_dir = media_dir.canonicalize().expect("canonicalize media dir");
+            let local_storage = local_storage(&backend).await;
+            let loc = |path: &std::path::Path| StorageEntryLoc {
+                storage_id: local_storage.id,
+                path: path.to_string_lossy().to_string(),
+            };
+
+            let entries = ["songs/a.mp3", "songs/b & c.mp3"]
+                .into_iter()
+                .map(|name| ToAddMusicEntry {
+                    entry: StorageEntry {
+                        storage_id: local_storage.id,
+                        name: name.trim_start_matches("songs/").to_string(),
+                        path: media_dir.join(name).to_string_lossy().to_string(),
+                        size: None,
+                        is_dir: false,
+                    },
+                    name: name.trim_start_matches("songs/").to_string(),
+                })
+                .collect();
+            let created = ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "Mix: <1>".to_string(),
+                    cover: None,
+                    entries,
+                },
+            )
+            .await
+            .expect("create playlist");
+            backend
+                .get_context()
+                .database_server()
+                .update_music_total_duration(created.music_ids[0].id, Duration::from_secs(90))
+                .expect("update duration");
+
+            let lists_dir = media_dir.join("lists");
+            let cases = [
+                (
+                    PlaylistExportFormat::M3u8,
+                    PlaylistExportPaths::RelativeTo {
+                        dir: loc(&lists_dir),
+                    },
+                ),
+                (PlaylistExportFormat::Xspf, PlaylistExportPaths::AbsoluteUrl),
+            ];
+            for (format, paths) in cases {
+                let written = ct_export_playlist_to_storage(
+                    backend.clone(),
+                    ArgExportPlaylistToStorage {
+                        export: ArgExportPlaylist {
+                            id: created.id,
+                            format,
+                            paths,
+                        },
+                        dir: loc(&lists_dir),
+                    },
+                )
+                .await
+                .expect("export playlist");
+
+                let imported = ct_import_playlist(
+                    backend.clone(),
+                    ArgImportPlaylist {
+                        loc: written,
+                        title: None,
+                    },
+                )
+                .await
+                .expect("import playlist");
+                assert!(imported.unresolved.is_empty());
+                let ids: Vec<_> = created.music_ids.iter().map(|m| m.id).collect();
+                assert_eq!(ids, imported.music_ids);
+                let playlist = ct_get_playlist(backend.clone(), imported.id)
+                    .await
+                    .expect("get playlist")
+                    .expect("playlist");
+                assert_eq!("Mix: <1>", playlist.abstr.meta.title);
+            }
+
+            let text = String::from_utf8(
+                cts_export_playlist(
+                    backend.clone(),
+                    ArgExportPlaylist {
+                        id: created.id,
+                        format: PlaylistExportFormat::M3u8,
+                        paths: PlaylistExportPaths::RelativeTo {
+                            dir: loc(&lists_dir),
+                        },
+                    },
+                )
+                .expect("export playlist")
+                .bytes,
+            )
+            .expect("utf8");
+            assert!(text.contains("#EXTINF:90,a.mp3\n../songs/a.mp3\n"));
+
+            let exported = cts_export_playlist(
+                backend.clone(),
+                ArgExportPlaylist {
+                    id: created.id,
+                    format: PlaylistExportFormat::Json,
+                    paths: PlaylistExportPaths::StorageRelative,
+                },
+            )
+            .expect("export playlist");
+            assert_eq!("Mix_ _1_.json", exported.file_name);
+            let json: serde_json::Value =
+                serde_json::from_slice(&exported.bytes).expect("parse json");
+            assert_eq!(1, json["version"]);
+            assert_eq!(90000, json["musics"][0]["duration_ms"]);
+            assert_eq!(
+                media_dir.join("songs/b & c.mp3").to_string_lossy(),
+                json["musics"][1]["location"].as_str().expect("location")
+            );
+        })
+    }
 }
@@ -0,0 +1,330 @@
+use std::sync::Arc;
+
+use crate::{
+    error::BResult,
+    services::{
+        clear_play_queue, get_play_queue, play_queue_next, play_queue_previous, save_play_queue,
+        save_play_queue_position, set_play_queue_shuffle, ArgSavePlayQueuePosition,
+        ArgSetPlayQueueShuffle, PlayQueue, PlayQueueItem,
+    },
+    Backend,
+};
+
+#[uniffi::export]
+pub fn cts_save_play_queue(cx: Arc<Backend>, arg: PlayQueue) -> BResult<()> {
+    let cx = cx.get_context();
+    save_play_queue(cx, arg)
+}
+
+#[uniffi::export]
+pub fn cts_save_play_queue_position(
+    cx: Arc<Backend>,
+    arg: ArgSavePlayQueuePosition,
+) -> BResult<()> {
+    let cx = cx.get_context();
+    save_play_queue_position(cx, arg)
+}
+
+#[uniffi::export]
+pub fn cts_get_play_queue(cx: Arc<Backend>) -> BResult<Option<PlayQueue>> {
+    let cx = cx.get_context();
+    get_play_queue(cx)
+}
+
+#[uniffi::export]
+pub fn cts_clear_play_queue(cx: Arc<Backend>) -> BResult<()> {
+    let cx = cx.get_context();
+    clear_play_queue(cx)
+}
+
+#[uniffi::export]
+pub fn cts_set_play_queue_shuffle(cx: Arc<Backend>, arg: ArgSetPlayQueueShuffle) -> BResult<()> {
+    let cx = cx.get_context();
+    set_play_queue_shuffle(cx, arg)
+}
+
+/// Moves to the next music. `ended` is set when the current music finished
+/// playing rather than being skipped.
+#[uniffi::export]
+pub fn cts_play_queue_next(cx: Arc<Backend>, ended: bool) -> BResult<Option<PlayQueueItem>> {
+    let cx = cx.get_context();
+    play_queue_next(cx, ended)
+}
+
+#[uniffi::export]
+pub fn cts_play_queue_previous(cx: Arc<Backend>) -> BResult<Option<PlayQueueItem>> {
+    let cx = cx.get_context();
+    play_queue_previous(cx)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ease_client_schema::{MusicId, PlayMode, ShuffleWeight};
+
+    use crate::{
+        controllers::{
+            playlist::{ct_create_playlist, ct_remove_music_from_playlist, ct_remove_playlist},
+            preference::cts_save_preference_playmode,
+        },
+        error::BError,
+        services::{ArgCreatePlaylist, ArgRemoveMusicFromPlaylist},
+        test_utils::{local_storage_id, music_entry, setup_backend},
+    };
+
+    use super::*;
+
+    #[test]
+    fn restores_queue_without_removed_musics() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (_tempdir, backend) = setup_backend();
+            assert_eq!(None, cts_get_play_queue(backend.clone()).expect("queue"));
+            let storage_id = local_storage_id(&backend).await;
+            let created = ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "road".to_string(),
+                    cover: None,
+                    entries: vec![
+                        music_entry(storage_id, "/music/a.mp3"),
+                        music_entry(storage_id, "/music/b.mp3"),
+                        music_entry(storage_id, "/music/c.mp3"),
+                    ],
+                },
+            )
+            .await
+            .expect("create playlist");
+            let ids: Vec<_> = created.music_ids.iter().map(|v| v.id).collect();
+            let (a, b, c) = (ids[0], ids[1], ids[2]);
+
+            let queue = PlayQueue {
+                playlist: Some(created.id),
+                musics: vec![c, a, b],
+                index: 0,
+                position: Duration::ZERO,
+                shuffle_seed: Some(42),
+                shuffle_weight: ShuffleWeight::Uniform,
+            };
+            assert!(matches!(
+                cts_save_play_queue(
+                    backend.clone(),
+                    PlayQueue {
+                        index: 3,
+                        ..queue.clone()
+                    }
+                ),
+                Err(BError::InvalidQueueIndex(3))
+            ));
+            cts_save_play_queue(backend.clone(), queue.clone()).expect("save queue");
+            cts_save_play_queue_position(
+                backend.clone(),
+                ArgSavePlayQueuePosition {
+                    index: 1,
+                    position: Duration::from_millis(61_500),
+                },
+            )
+            .expect("save position");
+            let restored = cts_get_play_queue(backend.clone())
+                .expect("queue")
+                .expect("saved queue");
+            assert_eq!(
+                PlayQueue {
+                    index: 1,
+                    position: Duration::from_millis(61_500),
+                    ..queue.clone()
+                },
+                restored
+            );
+
+            // A music before the current one is removed, the current music
+            // and its position are kept.
+            ct_remove_music_from_playlist(
+                backend.clone(),
+                ArgRemoveMusicFromPlaylist {
+                    playlist_id: created.id,
+                    music_id: c,
+                },
+            )
+            .await
+            .expect("remove music");
+            let restored = cts_get_play_queue(backend.clone())
+                .expect("queue")
+                .expect("saved queue");
+            assert_eq!(vec![a, b], restored.musics);
+            assert_eq!(0, restored.index);
+            assert_eq!(Duration::from_millis(61_500), restored.position);
+
+            // The current music is removed, the queue goes on with the next
+            // one from the start.
+            ct_remove_music_from_playlist(
+                backend.clone(),
+                ArgRemoveMusicFromPlaylist {
+                    playlist_id: created.id,
+                    music_id: a,
+                },
+            )
+            .await
+            .expect("remove music");
+            let restored = cts_get_play_queue(backend.clone())
+                .expect("queue")
+                .expect("saved queue");
+            assert_eq!(vec![b], restored.musics);
+            assert_eq!(0, restored.index);
+            assert_eq!(Duration::ZERO, restored.position);
+            assert_eq!(Some(created.id), restored.playlist);
+            assert_eq!(Some(42), restored.shuffle_seed);
+
+            ct_remove_playlist(backend.clone(), created.id)
+                .await
+                .expect("remove playlist");
+            assert_eq!(None, cts_get_play_queue(backend.clone()).expect("queue"));
+            cts_save_play_queue_position(
+                backend.clone(),
+                ArgSavePlayQueuePosition {
+                    index: 0,
+                    position: Duration::from_secs(1),
+                },
+            )
+            .expect("save position");
+            assert_eq!(None, cts_get_play_queue(backend.clone()).expect("queue"));
+        });
+    }
+
+    #[test]
+    fn steps_through_queue_in_every_mode() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (_tempdir, backend) = setup_backend();
+            let storage_id = local_storage_id(&backend).await;
+            let names = ["a", "b", "c", "d", "e"];
+            let created = ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "road".to_string(),
+                    cover: None,
+                    entries: names
+                        .iter()
+                        .map(|v| music_entry(storage_id, &format!("/music/{v}.mp3")))
+                        .collect(),
+                },
+            )
+            .await
+            .expect("create playlist");
+            let musics: Vec<MusicId> = created.music_ids.iter().map(|v| v.id).collect();
+            cts_save_play_queue(
+                backend.clone(),
+                PlayQueue {
+                    playlist: Some(created.id),
+                    musics: musics.clone(),
+                    index: 0,
+                    position: Duration::from_secs(10),
+                    shuffle_seed: None,
+                    shuffle_weight: ShuffleWeight::Uniform,
+                },
+            )
+            .expect("save queue");
+
+            let mode = |mode| cts_save_preference_playmode(backend.clone(), mode).expect("mode");
+            let next = |ended| {
+                cts_play_queue_next(backend.clone(), ended)
+                    .expect("next")
+                    .map(|v| {
+                        assert_eq!(musics[v.index as usize], v.music);
+                        v.index
+                    })
+            };
+            let previous = || {
+                cts_play_queue_previous(backend.clone())
+                    .expect("previous")
+                    .map(|v| v.index)
+            };
+            let shuffle = |enabled, seed| {
+                cts_set_play_queue_shuffle(
+                    backend.clone(),
+                    ArgSetPlayQueueShuffle {
+                        enabled,
+                        seed,
+                        weight: ShuffleWeight::Uniform,
+                    },
+                )
+                .expect("shuffle")
+            };
+            let go_to = |index| {
+                cts_save_play_queue_position(
+                    backend.clone(),
+                    ArgSavePlayQueuePosition {
+                        index,
+                        position: Duration::ZERO,
+                    },
+                )
+                .expect("save position")
+            };
+
+            mode(PlayMode::List);
+            assert_eq!(vec![Some(1), Some(2), Some(3), Some(4), None], {
+                (0..5).map(|_| next(true)).collect::<Vec<_>>()
+            });
+            assert_eq!(Some(3), previous());
+            let queue = cts_get_play_queue(backend.clone())
+                .expect("queue")
+                .expect("saved queue");
+            assert_eq!(3, queue.index);
+            assert_eq!(Duration::ZERO, queue.position);
+            go_to(0);
+            assert_eq!(None, previous());
+
+            mode(PlayMode::ListLoop);
+            assert_eq!(Some(4), previous());
+            assert_eq!(Some(0), next(true));
+
+            mode(PlayMode::Single);
+            assert_eq!(None, next(true));
+            assert_eq!(Some(1), next(false));
+            mode(PlayMode::SingleLoop);
+            assert_eq!(Some(1), next(true));
+            go_to(4);
+            assert_eq!(Some(0), next(false));
+
+            // Shuffle plays every music once, then stops.
+            mode(PlayMode::List);
+            shuffle(true, Some(7));
+            let mut played = vec![0];
+            while let Some(index) = next(true) {
+                played.push(index);
+            }
+            let mut sorted = played.clone();
+            sorted.sort();
+            assert_eq!(vec![0, 1, 2, 3, 4], sorted);
+            assert_eq!(Some(played[3]), previous());
+
+            // The same seed gives the same order.
+            go_to(0);
+            shuffle(true, Some(7));
+            let mut again = vec![0];
+            while let Some(index) = next(true) {
+                again.push(index);
+            }
+            assert_eq!(played, again);
+
+            // Shuffle loop starts new cycles without repeating a music
+            // across them.
+            mode(PlayMode::ListLoop);
+            let mut cycles = vec![*played.last().unwrap()];
+            cycles.extend((0..10).map(|_| next(true).expect("next")));
+            for cycle in [&cycles[1..6], &cycles[6..11]] {
+                let mut sorted = cycle.to_vec();
+                sorted.sort();
+                assert_eq!(vec![0, 1, 2, 3, 4], sorted);
+            }
+            assert!(cycles.windows(2).all(|v| v[0] != v[1]));
+
+            shuffle(false, None);
+            go_to(2);
+            assert_eq!(Some(3), next(false));
+            let queue = cts_get_play_queue(backend.clone())
+                .expect("queue")
+                .expect("saved queue");
+            assert_eq!(None, queue.shuffle_seed);
+        });
+    }
+}
@@ -0,0 +1,460 @@
+use std::sync::Arc;
+
+use crate::{
+    error::BResult,
+    services::{
+        auto_relink_musics, cancel_library_scan, find_relink_candidates, get_library_scan_progress,
+        relink_music, rescan_library, start_library_scan, ArgAutoRelinkMusics,
+        ArgFindRelinkCandidates, ArgRelinkMusic, ArgRescanLibrary, ArgStartLibraryScan,
+        AutoRelinkResult, LibraryRescanResult, LibraryScanProgress, RelinkCandidate,
+    },
+    Backend,
+};
+
+#[uniffi::export]
+pub fn cts_start_library_scan(cx: Arc<Backend>, arg: ArgStartLibraryScan) -> u64 {
+    let cx = cx.get_context();
+    start_library_scan(cx, arg)
+}
+
+#[uniffi::export]
+pub fn cts_get_library_scan_progress(cx: Arc<Backend>, id: u64) -> Option<LibraryScanProgress> {
+    let cx = cx.get_context();
+    get_library_scan_progress(cx, id)
+}
+
+#[uniffi::export]
+pub fn cts_cancel_library_scan(cx: Arc<Backend>, id: u64) -> bool {
+    let cx = cx.get_context();
+    cancel_library_scan(cx, id)
+}
+
+#[uniffi::export]
+pub async fn ct_rescan_library(
+    cx: Arc<Backend>,
+    arg: ArgRescanLibrary,
+) -> BResult<LibraryRescanResult> {
+    let cx = cx.get_context();
+    rescan_library(cx, arg).await
+}
+
+#[uniffi::export]
+pub async fn ct_find_relink_candidates(
+    cx: Arc<Backend>,
+    arg: ArgFindRelinkCandidates,
+) -> BResult<Vec<RelinkCandidate>> {
+    let cx = cx.get_context();
+    find_relink_candidates(cx, arg).await
+}
+
+#[uniffi::export]
+pub async fn ct_relink_music(cx: Arc<Backend>, arg: ArgRelinkMusic) -> BResult<()> {
+    let cx = cx.get_context();
+    relink_music(cx, arg).await
+}
+
+#[uniffi::export]
+pub async fn ct_auto_relink_musics(
+    cx: Arc<Backend>,
+    arg: ArgAutoRelinkMusics,
+) -> BResult<AutoRelinkResult> {
+    let cx = cx.get_context();
+    auto_relink_musics(cx, arg).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use ease_client_schema::{MusicFileState, StorageEntryLoc};
+
+    use crate::{
+        controllers::playlist::{ct_create_playlist, ct_get_playlist},
+        error::BError,
+        objects::StorageEntry,
+        services::{
+            ArgAutoRelinkMusics, ArgCreatePlaylist, ArgFindRelinkCandidates, ArgRelinkMusic,
+            ArgRescanLibrary, ArgStartLibraryScan, LibraryScanProgress, LibraryScanStatus,
+            ToAddMusicEntry,
+        },
+        test_utils::{local_storage, setup_backend},
+    };
+
+    use super::{
+        ct_auto_relink_musics, ct_find_relink_candidates, ct_relink_music, ct_rescan_library,
+        cts_cancel_library_scan, cts_get_library_scan_progress, cts_start_library_scan,
+    };
+
+    async fn wait_scan(backend: &Arc<crate::Backend>, id: u64) -> LibraryScanProgress {
+        for _ in 0..500 {
+            let progress = cts_get_library_scan_progress(backend.clone(), id).expect("scan job");
+            if progress.status != LibraryScanStatus::Running {
+                return progress;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("scan did not finish");
+    }
+
+    #[test]
+    fn scans_directory_tree_and_pairs_lyrics_and_covers() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let media_dir = tempdir.path().join("media");
+            for dir in ["album", "album/cd2", "misc"] {
+                std::fs::create_dir_all(media_dir.join(dir)).expect("create dir");
+            }
+            let files: [(&str, &[u8]); 9] = [
+                ("album/01.mp3", b"ID3\x04\x00"),
+                ("album/02.mp3", b"ID3\x04\x00"),
+                ("album/01.LRC", b"[00:01.00]hello"),
+                ("album/Cover.jpg", b"\xFF\xD8\xFF\xE0cover"),
+                ("album/cd2/02.flac", b"fLaC\x00\x00"),
+                ("misc/track", b"OggS\x00\x02"),
+                ("misc/readme", b"plain text"),
+                ("misc/notes.txt", b"notes"),
+                ("misc/clip.bin", b"OggS\x00\x02"),
+            ];
+            for (name, bytes) in files {
+                std::fs::write(media_dir.join(name), bytes).expect("write file");
+            }
+            let media_dir = media_dir.canonicalize().expect("canonicalize media dir");
+            let local_storage = local_storage(&backend).await;
+
+            let id = cts_start_library_scan(
+                backend.clone(),
+                ArgStartLibraryScan {
+                    dir: StorageEntryLoc {
+                        storage_id: local_storage.id,
+                        path: media_dir.to_string_lossy().to_string(),
+                    },
+                    max_concurrency: 2,
+                },
+            );
+            let progress = wait_scan(&backend, id).await;
+            assert_eq!(LibraryScanStatus::Completed, progress.status);
+            assert_eq!(4, progress.dirs_scanned);
+            assert_eq!(4, progress.musics_found);
+            assert_eq!(4, progress.musics_added);
+            assert_eq!(1, progress.lyrics_paired);
+            assert_eq!(2, progress.covers_paired);
+            assert!(cts_get_library_scan_progress(backend.clone(), id).is_none());
+
+            let musics = backend
+                .get_context()
+                .database_server()
+                .load_all_musics()
+                .expect("load musics");
+            let mut titles: Vec<_> = musics.iter().map(|m| m.title.as_str()).collect();
+            titles.sort();
+            assert_eq!(vec!["01.mp3", "02.flac", "02.mp3", "track"], titles);
+            let first = musics.iter().find(|m| m.title == "01.mp3").expect("01.mp3");
+            assert!(first
+                .lyric
+                .as_ref()
+                .is_some_and(|loc| loc.path.ends_with("album/01.LRC")));
+
+            // The folder cover is stored once, and outlives the music that
+            // gets another cover.
+            let second = musics.iter().find(|m| m.title == "02.mp3").expect("02.mp3");
+            let cover = first.cover.expect("folder cover");
+            assert_eq!(Some(cover), second.cover);
+            let db = backend.get_context().database_server();
+            db.update_music_cover(second.id, b"\x89PNGother".to_vec())
+                .expect("update cover");
+            assert_eq!(
+                b"\xFF\xD8\xFF\xE0cover".to_vec(),
+                db.blob().read(cover).expect("read cover")
+            );
+            db.update_music_cover(first.id, b"\x89PNGother".to_vec())
+                .expect("update cover");
+            assert!(db.blob().read(cover).is_err());
+
+            let id = cts_start_library_scan(
+                backend.clone(),
+                ArgStartLibraryScan {
+                    dir: StorageEntryLoc {
+                        storage_id: local_storage.id,
+                        path: media_dir.to_string_lossy().to_string(),
+                    },
+                    max_concurrency: 0,
+                },
+            );
+            let progress = wait_scan(&backend, id).await;
+            assert_eq!(4, progress.musics_found);
+            assert_eq!(0, progress.musics_added);
+        });
+    }
+
+    #[test]
+    fn cancels_scan() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let mut dir = tempdir.path().join("media");
+            for i in 0..200 {
+                dir = dir.join(format!("d{i}"));
+            }
+            std::fs::create_dir_all(&dir).expect("create dirs");
+            let local_storage = local_storage(&backend).await;
+
+            let id = cts_start_library_scan(
+                backend.clone(),
+                ArgStartLibraryScan {
+                    dir: StorageEntryLoc {
+                        storage_id: local_storage.id,
+                        path: tempdir.path().join("media").to_string_lossy().to_string(),
+                    },
+                    max_concurrency: 1,
+                },
+            );
+            assert!(cts_cancel_library_scan(backend.clone(), id));
+            let progress = wait_scan(&backend, id).await;
+            assert_eq!(LibraryScanStatus::Cancelled, progress.status);
+            assert!(progress.dirs_scanned < 200);
+            assert!(!cts_cancel_library_scan(backend.clone(), id + 1));
+        });
+    }
+
+    #[test]
+    fn skips_directories_nested_too_deep() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let mut dir = tempdir.path().join("media");
+            for i in 0..40 {
+                dir = dir.join(format!("d{i}"));
+            }
+            std::fs::create_dir_all(&dir).expect("create dirs");
+            let local_storage = local_storage(&backend).await;
+
+            let id = cts_start_library_scan(
+                backend.clone(),
+                ArgStartLibraryScan {
+                    dir: StorageEntryLoc {
+                        storage_id: local_storage.id,
+                        path: tempdir.path().join("media").to_string_lossy().to_string(),
+                    },
+                    max_concurrency: 0,
+                },
+            );
+            let progress = wait_scan(&backend, id).await;
+            assert_eq!(LibraryScanStatus::Completed, progress.status);
+            assert_eq!(33, progress.dirs_scanned);
+            assert_eq!(1, progress.failed_dirs);
+        });
+    }
+
+    #[test]
+    fn rescan_marks_missing_and_changed_musics() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let media_dir = tempdir.path().join("media");
+            std::fs::create_dir_all(media_dir.join("sub")).expect("create dir");
+            for name in ["a.mp3", "b.mp3", "sub/c.mp3"] {
+                std::fs::write(media_dir.join(name), b"ID3\x04\x00").expect("write file");
+            }
+            let media_dir = media_dir.canonicalize().expect("canonicalize media dir");
+            let local_storage = local_storage(&backend).await;
+            let id = cts_start_library_scan(
+                backend.clone(),
+                ArgStartLibraryScan {
+                    dir: StorageEntryLoc {
+                        storage_id: local_storage.id,
+                        path: media_dir.to_string_lossy().to_string(),
+                    },
+                    max_concurrency: 0,
+                },
+            );
+            assert_eq!(3, wait_scan(&backend, id).await.musics_added);
+
+            let rescan = || {
+                ct_rescan_library(
+                    backend.clone(),
+                    ArgRescanLibrary {
+                        storage_id: Some(local_storage.id),
+                    },
+                )
+            };
+            let result = rescan().await.expect("rescan");
+            assert_eq!(3, result.unchanged);
+            assert!(result.changed.is_empty() && result.missing.is_empty());
+
+            std::fs::write(media_dir.join("a.mp3"), b"ID3\x04\x00 re-encoded").expect("write");
+            std::fs::remove_file(media_dir.join("b.mp3")).expect("remove file");
+            std::fs::remove_dir_all(media_dir.join("sub")).expect("remove dir");
+            let result = rescan().await.expect("rescan");
+            assert_eq!(0, result.unchanged);
+            assert_eq!(1, result.changed.len());
+            assert_eq!(2, result.missing.len());
+            assert!(result.failed_dirs.is_empty());
+
+            let db = backend.get_context().database_server();
+            let state_of = |name: &str| {
+                db.load_all_musics()
+                    .expect("load musics")
+                    .into_iter()
+                    .find(|m| m.title == name)
+                    .expect("music")
+                    .file_state
+            };
+            assert_eq!(MusicFileState::Changed, state_of("a.mp3"));
+            assert_eq!(MusicFileState::Missing, state_of("b.mp3"));
+            assert_eq!(MusicFileState::Missing, state_of("c.mp3"));
+
+            std::fs::write(media_dir.join("b.mp3"), b"ID3\x04\x00").expect("write file");
+            let result = rescan().await.expect("rescan");
+            assert_eq!(1, result.restored.len());
+            assert_eq!(1, result.missing.len());
+            assert_eq!(MusicFileState::Present, state_of("a.mp3"));
+            // The file is back but was written again since it was stamped.
+            assert_eq!(result.restored, result.changed);
+            assert_eq!(MusicFileState::Changed, state_of("b.mp3"));
+        });
+    }
+
+    fn id3_titled(title: &str, padding: usize) -> Vec<u8> {
+        let mut frame = b"TIT2".to_vec();
+        frame.extend_from_slice(&(title.len() as u32 + 1).to_be_bytes());
+        frame.extend_from_slice(&[0, 0, 3]);
+        frame.extend_from_slice(title.as_bytes());
+        let mut tag = b"ID3\x03\x00\x00\x00\x00\x00".to_vec();
+        tag.push(frame.len() as u8);
+        tag.extend(frame);
+        tag.resize(tag.len() + padding, 0);
+        tag
+    }
+
+    #[test]
+    fn relinks_moved_musics_keeping_playlists() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let media_dir = tempdir.path().join("media");
+            std::fs::create_dir_all(media_dir.join("old")).expect("create dir");
+            let media_dir = media_dir.canonicalize().expect("canonicalize media dir");
+            let files = [
+                ("01 Song.mp3", id3_titled("Song", 10)),
+                ("02 Other.mp3", id3_titled("Other", 20)),
+            ];
+            for (name, bytes) in files.iter() {
+                std::fs::write(media_dir.join("old").join(name), bytes).expect("write file");
+            }
+            let local_storage = local_storage(&backend).await;
+            let path_of = |path: &str| media_dir.join(path).to_string_lossy().to_string();
+            let make_entry = |path: &str, name: &str| ToAddMusicEntry {
+                entry: StorageEntry {
+                    storage_id: local_storage.id,
+                    name: name.to_string(),
+                    path: path_of(path),
+                    size: None,
+                    is_dir: false,
+                },
+                name: name.to_string(),
+            };
+            let playlist = ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "moved".to_string(),
+                    cover: None,
+                    entries: vec![
+                        make_entry("old/02 Other.mp3", "02 Other.mp3"),
+                        make_entry("old/01 Song.mp3", "01 Song.mp3"),
+                    ],
+                },
+            )
+            .await
+            .expect("create playlist")
+            .id;
+            let rescan = || {
+                ct_rescan_library(
+                    backend.clone(),
+                    ArgRescanLibrary {
+                        storage_id: Some(local_storage.id),
+                    },
+                )
+            };
+            assert_eq!(2, rescan().await.expect("rescan").stamped);
+
+            std::fs::create_dir_all(media_dir.join("new")).expect("create dir");
+            std::fs::rename(media_dir.join("old"), media_dir.join("new/sub")).expect("move dir");
+            std::fs::write(
+                media_dir.join("new/Song (live).mp3"),
+                id3_titled("Song", 99),
+            )
+            .expect("write file");
+            assert_eq!(2, rescan().await.expect("rescan").missing.len());
+
+            let before = ct_get_playlist(backend.clone(), playlist)
+                .await
+                .expect("load playlist")
+                .expect("playlist");
+            let song_id = before.musics[1].meta.id;
+            let root = StorageEntryLoc {
+                storage_id: local_storage.id,
+                path: path_of(""),
+            };
+            let candidates = ct_find_relink_candidates(
+                backend.clone(),
+                ArgFindRelinkCandidates {
+                    id: song_id,
+                    root: root.clone(),
+                    use_fingerprint: false,
+                    limit: 5,
+                },
+            )
+            .await
+            .expect("find candidates");
+            assert_eq!(2, candidates.len());
+            assert_eq!(path_of("new/sub/01 Song.mp3"), candidates[0].loc.path);
+            assert!(candidates[0].size_matched && candidates[0].tags_matched);
+            assert_eq!(path_of("new/Song (live).mp3"), candidates[1].loc.path);
+            assert!(!candidates[1].size_matched && candidates[1].tags_matched);
+
+            let result = ct_auto_relink_musics(
+                backend.clone(),
+                ArgAutoRelinkMusics {
+                    ids: vec![],
+                    root,
+                    use_fingerprint: false,
+                },
+            )
+            .await
+            .expect("auto relink");
+            assert_eq!(2, result.relinked.len());
+            assert!(result.unresolved.is_empty());
+
+            let after = ct_get_playlist(backend.clone(), playlist)
+                .await
+                .expect("reload playlist")
+                .expect("playlist");
+            let ids = |p: &crate::objects::Playlist| -> Vec<_> {
+                p.musics.iter().map(|m| m.meta.id).collect()
+            };
+            assert_eq!(ids(&before), ids(&after));
+            assert!(after
+                .musics
+                .iter()
+                .all(|m| m.meta.file_state == MusicFileState::Present));
+            let db = backend.get_context().database_server();
+            let song = db.load_music(song_id).expect("load").expect("song");
+            assert_eq!(path_of("new/sub/01 Song.mp3"), song.loc.path);
+            assert!(db
+                .load_music_by_loc(StorageEntryLoc {
+                    storage_id: local_storage.id,
+                    path: path_of("old/01 Song.mp3"),
+                })
+                .expect("load")
+                .is_none());
+
+            let other_id = before.musics[0].meta.id;
+            let err = ct_relink_music(
+                backend.clone(),
+                ArgRelinkMusic {
+                    id: other_id,
+                    loc: song.loc.clone(),
+                },
+            )
+            .await
+            .expect_err("entry is linked to the song");
+            assert!(matches!(err, BError::MusicLocInUse(id) if id == song_id));
+        });
+    }
+}
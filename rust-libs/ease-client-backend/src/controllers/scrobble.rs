@@ -0,0 +1,500 @@
+use std::sync::Arc;
+
+use ease_client_schema::ScrobbleConfigModel;
+
+use crate::{
+    error::BResult,
+    services::{
+        flush_scrobbles, get_scrobble_config, get_scrobble_status, set_scrobble_config,
+        ScrobbleStatus,
+    },
+    Backend,
+};
+
+#[uniffi::export]
+pub fn cts_get_scrobble_config(cx: Arc<Backend>) -> BResult<ScrobbleConfigModel> {
+    let cx = cx.get_context();
+    get_scrobble_config(cx)
+}
+
+#[uniffi::export]
+pub fn cts_set_scrobble_config(cx: Arc<Backend>, config: ScrobbleConfigModel) -> BResult<()> {
+    let cx = cx.get_context();
+    set_scrobble_config(cx, config)
+}
+
+#[uniffi::export]
+pub fn cts_get_scrobble_status(cx: Arc<Backend>) -> BResult<Vec<ScrobbleStatus>> {
+    let cx = cx.get_context();
+    get_scrobble_status(cx)
+}
+
+/// Submits the queued listens now, for example once the network is back.
+#[uniffi::export]
+pub async fn ct_flush_scrobbles(cx: Arc<Backend>) -> BResult<Vec<ScrobbleStatus>> {
+    let cx = cx.get_context();
+    flush_scrobbles(cx).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    use axum::{
+        extract::{Form, State},
+        http::{HeaderMap, StatusCode},
+        response::{IntoResponse, Response},
+        routing::post,
+        Json, Router,
+    };
+    use ease_client_schema::{
+        LastFmConfig, ListenBrainzConfig, MusicId, PlayEventKind, ScrobbleConfigModel, ScrobbleKey,
+        ScrobbleModel, ScrobbleService, StorageEntryLoc,
+    };
+    use md5::{Digest, Md5};
+    use serde_json::{json, Value};
+
+    use tokio::sync::oneshot;
+
+    use crate::{
+        controllers::{
+            history::cts_record_play_event, library::cts_update_music_tags,
+            playlist::ct_create_playlist,
+        },
+        repositories::music::ArgDBAddMusic,
+        services::{
+            ArgCreatePlaylist, ArgRecordPlayEvent, ArgUpdateMusicTags, MAX_SCROBBLE_ATTEMPTS,
+        },
+        test_utils::{local_storage_id, music_entry, setup_backend},
+    };
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MockServices {
+        offline: AtomicBool,
+        listenbrainz: Mutex<Vec<Value>>,
+        lastfm: Mutex<Vec<Vec<(String, String)>>>,
+    }
+
+    async fn listenbrainz(
+        State(mock): State<Arc<MockServices>>,
+        headers: HeaderMap,
+        Json(body): Json<Value>,
+    ) -> Response {
+        if mock.offline.load(Ordering::SeqCst) {
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+        let token = headers.get("authorization").and_then(|v| v.to_str().ok());
+        if token != Some("Token lb-token") {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        mock.listenbrainz.lock().unwrap().push(body);
+        Json(json!({ "status": "ok" })).into_response()
+    }
+
+    async fn lastfm(
+        State(mock): State<Arc<MockServices>>,
+        Form(params): Form<Vec<(String, String)>>,
+    ) -> Response {
+        if mock.offline.load(Ordering::SeqCst) {
+            return Json(json!({ "error": 16, "message": "unavailable" })).into_response();
+        }
+        let mut signed: Vec<&(String, String)> = params
+            .iter()
+            .filter(|(name, _)| name != "api_sig" && name != "format")
+            .collect();
+        signed.sort();
+        let mut hasher = Md5::new();
+        for (name, value) in signed {
+            hasher.update(format!("{name}{value}"));
+        }
+        hasher.update("lf-secret");
+        let signature = format!("{:x}", hasher.finalize());
+        let sent = params.iter().find(|(name, _)| name == "api_sig");
+        if sent.map(|(_, v)| v.as_str()) != Some(signature.as_str()) {
+            return Json(json!({ "error": 13, "message": "invalid signature" })).into_response();
+        }
+        mock.lastfm.lock().unwrap().push(params);
+        Json(json!({})).into_response()
+    }
+
+    fn spawn_mock_server(mock: Arc<MockServices>) -> (String, oneshot::Sender<()>) {
+        let app = Router::new()
+            .route("/1/submit-listens", post(listenbrainz))
+            .route("/2.0/", post(lastfm))
+            .with_state(mock);
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind test server");
+        let addr = listener.local_addr().expect("listener addr");
+        listener
+            .set_nonblocking(true)
+            .expect("set listener non-blocking");
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let server = axum::Server::from_tcp(listener)
+            .expect("build server")
+            .serve(app.into_make_service())
+            .with_graceful_shutdown(async move {
+                let _ = shutdown_rx.await;
+            });
+        tokio::spawn(server);
+        (format!("http://{}", addr), shutdown_tx)
+    }
+
+    fn tag(backend: &Arc<crate::Backend>, id: MusicId, duration_secs: u64) {
+        cts_update_music_tags(
+            backend.clone(),
+            ArgUpdateMusicTags {
+                id,
+                artists: vec!["Band".to_string()],
+                album: Some("Record".to_string()),
+                album_artist: None,
+                year: None,
+                genres: vec![],
+                track_number: None,
+                disc_number: None,
+            },
+        )
+        .expect("update tags");
+        backend
+            .get_context()
+            .database_server()
+            .update_music_total_duration(id, Duration::from_secs(duration_secs))
+            .expect("update duration");
+    }
+
+    fn text_frame(id: &[u8; 4], text: &str) -> Vec<u8> {
+        let mut body = vec![3u8];
+        body.extend_from_slice(text.as_bytes());
+
+        let mut frame = id.to_vec();
+        frame.extend_from_slice((body.len() as u32).to_be_bytes().as_slice());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(body.as_slice());
+        frame
+    }
+
+    fn id3_tagged_file(frames: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = frames.concat();
+        let size = body.len();
+        let mut file = b"ID3\x03\x00\x00".to_vec();
+        file.extend_from_slice(&[
+            ((size >> 21) & 0x7f) as u8,
+            ((size >> 14) & 0x7f) as u8,
+            ((size >> 7) & 0x7f) as u8,
+            (size & 0x7f) as u8,
+        ]);
+        file.extend_from_slice(body.as_slice());
+        file.extend_from_slice(&[0xff, 0xfb, 0x90, 0x00]);
+        file
+    }
+
+    fn play(backend: &Arc<crate::Backend>, music: MusicId, start: u64, end: (PlayEventKind, u64)) {
+        let record = |kind, position_secs, time_secs| {
+            cts_record_play_event(
+                backend.clone(),
+                ArgRecordPlayEvent {
+                    music,
+                    playlist: None,
+                    kind,
+                    position: Duration::from_secs(position_secs),
+                    time: Some(Duration::from_secs(time_secs)),
+                },
+            )
+            .expect("record play event")
+        };
+        record(PlayEventKind::Started, 0, start);
+        record(end.0, end.1, start + end.1);
+    }
+
+    #[test]
+    fn submits_listens_queued_while_offline() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let mock = Arc::new(MockServices::default());
+            mock.offline.store(true, Ordering::SeqCst);
+            let (base_url, shutdown_tx) = spawn_mock_server(mock.clone());
+            let (_tempdir, backend) = setup_backend();
+            let storage_id = local_storage_id(&backend).await;
+            let created = ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "road".to_string(),
+                    cover: None,
+                    entries: vec![
+                        music_entry(storage_id, "/music/a.mp3"),
+                        music_entry(storage_id, "/music/short.mp3"),
+                        music_entry(storage_id, "/music/untagged.mp3"),
+                    ],
+                },
+            )
+            .await
+            .expect("create playlist");
+            let a = created.music_ids[0].id;
+            let short = created.music_ids[1].id;
+            let untagged = created.music_ids[2].id;
+            tag(&backend, a, 200);
+            tag(&backend, short, 20);
+
+            cts_set_scrobble_config(
+                backend.clone(),
+                ScrobbleConfigModel {
+                    listenbrainz: Some(ListenBrainzConfig {
+                        base_url: base_url.clone(),
+                        token: "lb-token".to_string(),
+                    }),
+                    lastfm: Some(LastFmConfig {
+                        base_url: format!("{base_url}/2.0/"),
+                        api_key: "lf-key".to_string(),
+                        api_secret: "lf-secret".to_string(),
+                        session_key: "lf-session".to_string(),
+                    }),
+                },
+            )
+            .expect("set scrobble config");
+
+            // Only the first play of `a` is long enough, and it is recorded
+            // twice.
+            let now = backend.get_context().current_time().as_secs();
+            play(&backend, a, now - 1000, (PlayEventKind::Completed, 200));
+            play(&backend, a, now - 1000, (PlayEventKind::Completed, 200));
+            play(&backend, a, now - 500, (PlayEventKind::Skipped, 50));
+            play(&backend, short, now - 400, (PlayEventKind::Completed, 20));
+            play(
+                &backend,
+                untagged,
+                now - 300,
+                (PlayEventKind::Completed, 300),
+            );
+
+            let status = ct_flush_scrobbles(backend.clone()).await.expect("flush");
+            assert_eq!(2, status.len());
+            for status in status {
+                assert_eq!(1, status.pending);
+                assert!(status.last_error.is_some());
+            }
+
+            mock.offline.store(false, Ordering::SeqCst);
+            let status = ct_flush_scrobbles(backend.clone()).await.expect("flush");
+            for status in status {
+                assert_eq!(0, status.pending, "{:?}", status.service);
+                assert_eq!(None, status.last_error);
+            }
+            // Submitted listens are not sent again, even when recorded again.
+            play(&backend, a, now - 1000, (PlayEventKind::Completed, 200));
+            ct_flush_scrobbles(backend.clone()).await.expect("flush");
+
+            let listens: Vec<Value> = mock
+                .listenbrainz
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|v| v["listen_type"] != "playing_now")
+                .cloned()
+                .collect();
+            assert_eq!(1, listens.len());
+            assert_eq!("single", listens[0]["listen_type"]);
+            let listen = &listens[0]["payload"][0];
+            assert_eq!(json!(now - 1000), listen["listened_at"]);
+            assert_eq!("a.mp3", listen["track_metadata"]["track_name"]);
+            assert_eq!("Band", listen["track_metadata"]["artist_name"]);
+            assert_eq!("Record", listen["track_metadata"]["release_name"]);
+
+            let scrobbles: Vec<Vec<(String, String)>> = mock
+                .lastfm
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|v| v.contains(&("method".to_string(), "track.scrobble".to_string())))
+                .cloned()
+                .collect();
+            assert_eq!(1, scrobbles.len());
+            let param = |name: &str| {
+                scrobbles[0]
+                    .iter()
+                    .find(|(k, _)| k == name)
+                    .map(|(_, v)| v.clone())
+            };
+            assert_eq!(Some("a.mp3".to_string()), param("track[0]"));
+            assert_eq!(Some((now - 1000).to_string()), param("timestamp[0]"));
+            assert_eq!(Some("200".to_string()), param("duration[0]"));
+            assert_eq!(Some("lf-session".to_string()), param("sk"));
+
+            // "Now playing" is sent in the background when a music starts.
+            let mut now_playing = false;
+            for _ in 0..50 {
+                now_playing = mock
+                    .listenbrainz
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|v| v["listen_type"] == "playing_now")
+                    && mock.lastfm.lock().unwrap().iter().any(|v| {
+                        v.contains(&("method".to_string(), "track.updateNowPlaying".to_string()))
+                    });
+                if now_playing {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            assert!(now_playing);
+            assert_eq!(
+                vec![ScrobbleService::ListenBrainz, ScrobbleService::LastFm],
+                cts_get_scrobble_status(backend.clone())
+                    .expect("status")
+                    .into_iter()
+                    .map(|v| v.service)
+                    .collect::<Vec<_>>()
+            );
+            let _ = shutdown_tx.send(());
+        });
+    }
+
+    #[test]
+    fn scrobbles_artist_of_embedded_tags() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let mock = Arc::new(MockServices::default());
+            let (base_url, shutdown_tx) = spawn_mock_server(mock.clone());
+            let (tempdir, backend) = setup_backend();
+            let music_path = tempdir.path().join("tagged.mp3");
+            let file = id3_tagged_file(&[
+                text_frame(b"TPE1", "Some Artist"),
+                text_frame(b"TALB", "Some Album"),
+            ]);
+            std::fs::write(&music_path, file).expect("write music");
+
+            // Added without being probed, so the music isn't linked to the
+            // artist of its tags before it is played.
+            let storage_id = local_storage_id(&backend).await;
+            let music = backend
+                .get_context()
+                .database_server()
+                .upsert_musics(vec![ArgDBAddMusic {
+                    loc: StorageEntryLoc {
+                        storage_id,
+                        path: music_path.to_string_lossy().to_string(),
+                    },
+                    title: "tagged".to_string(),
+                }])
+                .expect("create music")[0]
+                .id;
+            backend
+                .get_context()
+                .database_server()
+                .update_music_total_duration(music, Duration::from_secs(200))
+                .expect("update duration");
+            cts_set_scrobble_config(
+                backend.clone(),
+                ScrobbleConfigModel {
+                    listenbrainz: Some(ListenBrainzConfig {
+                        base_url,
+                        token: "lb-token".to_string(),
+                    }),
+                    lastfm: None,
+                },
+            )
+            .expect("set scrobble config");
+
+            let now = backend.get_context().current_time().as_secs();
+            play(&backend, music, now - 1000, (PlayEventKind::Completed, 200));
+
+            let mut listens: Vec<Value> = Default::default();
+            for _ in 0..50 {
+                listens = mock
+                    .listenbrainz
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|v| v["listen_type"] == "single")
+                    .cloned()
+                    .collect();
+                if !listens.is_empty() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            assert_eq!(1, listens.len());
+            let metadata = &listens[0]["payload"][0]["track_metadata"];
+            assert_eq!("Some Artist", metadata["artist_name"]);
+            assert_eq!("Some Album", metadata["release_name"]);
+            let _ = shutdown_tx.send(());
+        });
+    }
+
+    #[test]
+    fn drops_listens_the_service_keeps_failing() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let mock = Arc::new(MockServices::default());
+            mock.offline.store(true, Ordering::SeqCst);
+            let (base_url, shutdown_tx) = spawn_mock_server(mock.clone());
+            // Nothing listens on the port of a dropped listener.
+            let unreachable = {
+                let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+                format!("http://{}/2.0/", listener.local_addr().expect("addr"))
+            };
+            let (_tempdir, backend) = setup_backend();
+            cts_set_scrobble_config(
+                backend.clone(),
+                ScrobbleConfigModel {
+                    listenbrainz: Some(ListenBrainzConfig {
+                        base_url,
+                        token: "lb-token".to_string(),
+                    }),
+                    lastfm: Some(LastFmConfig {
+                        base_url: unreachable,
+                        api_key: "lf-key".to_string(),
+                        api_secret: "lf-secret".to_string(),
+                        session_key: "lf-session".to_string(),
+                    }),
+                },
+            )
+            .expect("set scrobble config");
+            let listen = |service| {
+                (
+                    ScrobbleKey {
+                        service,
+                        listened_at: 1000,
+                        music: MusicId::wrap(1),
+                    },
+                    ScrobbleModel {
+                        title: "a".to_string(),
+                        artist: "Band".to_string(),
+                        album: None,
+                        duration: Some(200_000),
+                        attempts: 0,
+                        submitted: false,
+                    },
+                )
+            };
+            backend
+                .get_context()
+                .database_server()
+                .enqueue_scrobbles(vec![
+                    listen(ScrobbleService::ListenBrainz),
+                    listen(ScrobbleService::LastFm),
+                ])
+                .expect("enqueue listens");
+
+            let pending = |status: &[ScrobbleStatus], service| {
+                status
+                    .iter()
+                    .find(|v| v.service == service)
+                    .map(|v| v.pending)
+            };
+            for _ in 1..MAX_SCROBBLE_ATTEMPTS {
+                let status = ct_flush_scrobbles(backend.clone()).await.expect("flush");
+                assert_eq!(Some(1), pending(&status, ScrobbleService::ListenBrainz));
+            }
+            let status = ct_flush_scrobbles(backend.clone()).await.expect("flush");
+            assert_eq!(Some(0), pending(&status, ScrobbleService::ListenBrainz));
+            // The listens of a service that can't be reached are kept.
+            assert_eq!(Some(1), pending(&status, ScrobbleService::LastFm));
+            let _ = shutdown_tx.send(());
+        });
+    }
+}
@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use crate::{
+    error::BResult,
+    objects::{ArgSearchLibrary, LibrarySearchPage},
+    services::search_library,
+    Backend,
+};
+
+#[uniffi::export]
+pub async fn ct_search_library(
+    cx: Arc<Backend>,
+    arg: ArgSearchLibrary,
+) -> BResult<LibrarySearchPage> {
+    let cx = cx.get_context();
+    search_library(cx, arg)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        controllers::{
+            library::cts_update_music_tags,
+            music::{ct_get_music, ct_update_music_lyric},
+            playlist::{ct_create_playlist, ct_remove_playlist},
+        },
+        objects::ArgSearchLibrary,
+        services::{ArgCreatePlaylist, ArgUpdateMusicLyric, ArgUpdateMusicTags, ToAddMusicEntry},
+        test_utils::{local_storage, setup_backend},
+        StorageEntry,
+    };
+
+    use super::ct_search_library;
+
+    async fn search(backend: &Arc<crate::Backend>, keywords: &str, page: u32) -> Vec<String> {
+        ct_search_library(
+            backend.clone(),
+            ArgSearchLibrary {
+                keywords: keywords.to_string(),
+                page,
+                per_page: 2,
+            },
+        )
+        .await
+        .expect("search library")
+        .musics
+        .into_iter()
+        .map(|m| m.meta.title)
+        .collect()
+    }
+
+    #[test]
+    fn searches_library_by_prefix_typo_and_tags() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (_tempdir, backend) = setup_backend();
+            let local_storage = local_storage(&backend).await;
+
+            let make_entry = |path: &str, name: &str| ToAddMusicEntry {
+                entry: StorageEntry {
+                    storage_id: local_storage.id,
+                    name: name.to_string(),
+                    path: path.to_string(),
+                    size: None,
+                    is_dir: false,
+                },
+                name: name.to_string(),
+            };
+
+            let created = ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "playlist".to_string(),
+                    cover: None,
+                    entries: vec![
+                        make_entry("/day/01.flac", "Other"),
+                        make_entry("/album/sunny-day.flac", "Sunny Day"),
+                        make_entry("/album/secret.flac", "Secret"),
+                    ],
+                },
+            )
+            .await
+            .expect("create playlist");
+            let ids: Vec<_> = created.music_ids.iter().map(|m| m.id).collect();
+
+            assert_eq!(vec!["Sunny Day"], search(&backend, "sun", 1).await);
+            assert_eq!(vec!["Secret"], search(&backend, "secert", 1).await);
+            assert_eq!(vec!["Sunny Day", "Other"], search(&backend, "day", 1).await);
+            assert!(search(&backend, "day", 2).await.is_empty());
+            assert!(search(&backend, "sunny secret", 1).await.is_empty());
+
+            cts_update_music_tags(
+                backend.clone(),
+                ArgUpdateMusicTags {
+                    id: ids[2],
+                    artists: vec!["周杰伦".to_string()],
+                    album: Some("叶惠美".to_string()),
+                    album_artist: None,
+                    year: None,
+                    genres: vec![],
+                    track_number: None,
+                    disc_number: None,
+                },
+            )
+            .expect("update tags");
+            assert_eq!(vec!["Secret"], search(&backend, "周杰伦", 1).await);
+            assert_eq!(vec!["Secret"], search(&backend, "叶惠美 secret", 1).await);
+
+            ct_remove_playlist(backend.clone(), created.id)
+                .await
+                .expect("remove playlist");
+            assert!(search(&backend, "day", 1).await.is_empty());
+        });
+    }
+
+    #[test]
+    fn indexes_lyric_once_loaded() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let media_dir = tempdir.path().join("media");
+            std::fs::create_dir_all(&media_dir).expect("create media dir");
+            let music_path = media_dir.join("song.mp3");
+            std::fs::write(&music_path, b"ID3").expect("write fake music");
+            std::fs::write(media_dir.join("song.lrc"), "[00:01.00]Rainbow bridge\n")
+                .expect("write lyric");
+
+            let local_storage = local_storage(&backend).await;
+            let created = ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "playlist".to_string(),
+                    cover: None,
+                    entries: vec![ToAddMusicEntry {
+                        entry: StorageEntry {
+                            storage_id: local_storage.id,
+                            name: "song.mp3".to_string(),
+                            path: music_path.to_string_lossy().to_string(),
+                            size: None,
+                            is_dir: false,
+                        },
+                        name: "Song".to_string(),
+                    }],
+                },
+            )
+            .await
+            .expect("create playlist");
+            let id = created.music_ids[0].id;
+
+            assert!(search(&backend, "rainbow", 1).await.is_empty());
+            ct_get_music(backend.clone(), id)
+                .await
+                .expect("get music")
+                .expect("music");
+            assert_eq!(vec!["Song"], search(&backend, "rainbow", 1).await);
+
+            ct_update_music_lyric(
+                backend.clone(),
+                ArgUpdateMusicLyric {
+                    id,
+                    lyric_loc: None,
+                },
+            )
+            .await
+            .expect("update lyric");
+            assert!(search(&backend, "rainbow", 1).await.is_empty());
+        });
+    }
+
+    #[test]
+    fn searches_cjk_by_reading_and_variant() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (_tempdir, backend) = setup_backend();
+            let local_storage = local_storage(&backend).await;
+
+            let make_entry = |path: &str, name: &str| ToAddMusicEntry {
+                entry: StorageEntry {
+                    storage_id: local_storage.id,
+                    name: name.to_string(),
+                    path: path.to_string(),
+                    size: None,
+                    is_dir: false,
+                },
+                name: name.to_string(),
+            };
+            ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "playlist".to_string(),
+                    cover: None,
+                    entries: vec![
+                        make_entry("/jay/01.flac", "周杰伦 晴天"),
+                        make_entry("/jp/02.flac", "さくら"),
+                        make_entry("/en/03.flac", "Sunrise"),
+                    ],
+                },
+            )
+            .await
+            .expect("create playlist");
+
+            for keywords in ["zjl", "zhoujielun", "周杰倫", "jielun qingtian"] {
+                assert_eq!(
+                    vec!["周杰伦 晴天"],
+                    search(&backend, keywords, 1).await,
+                    "{keywords}"
+                );
+            }
+            assert_eq!(vec!["さくら"], search(&backend, "sakura", 1).await);
+            assert_eq!(vec!["さくら"], search(&backend, "サクラ", 1).await);
+            assert_eq!(vec!["Sunrise"], search(&backend, "ＳＵＮ", 1).await);
+        });
+    }
+}
@@ -13,7 +13,8 @@ use crate::{
     onedrive_oauth_url,
     services::{
         build_storage_backend_by_arg, evict_storage_backend_cache, get_storage_backend,
-        list_storage, normalize_storage_default_path_for_type,
+        list_storage, normalize_storage_default_path_for_type, remap_storage,
+        search_storage_entries, ArgRemapStorage, RemapStorageResult,
     },
     ArgUpsertStorage, Backend,
 };
@@ -61,6 +62,15 @@ pub async fn ct_remove_storage(cx: Arc<Backend>, id: StorageId) -> BResult<()> {
     Ok(())
 }
 
+#[uniffi::export]
+pub async fn ct_remap_storage(
+    cx: Arc<Backend>,
+    arg: ArgRemapStorage,
+) -> BResult<RemapStorageResult> {
+    let cx = cx.get_context();
+    remap_storage(cx, arg).await
+}
+
 #[uniffi::export]
 pub async fn ct_test_storage(
     cx: Arc<Backend>,
@@ -207,21 +217,23 @@ pub async fn ct_search_storage_entries(
         return Ok(SearchStorageEntriesResp::Unknown);
     };
 
-    let result = backend
-        .search(
-            arg.parent.clone(),
-            arg.keywords.clone(),
-            map_storage_search_scope(arg.scope),
-            arg.page.max(1) as usize,
-            arg.per_page.max(1) as usize,
-        )
-        .await;
+    let result = search_storage_entries(
+        backend,
+        &arg.parent,
+        &arg.keywords,
+        map_storage_search_scope(arg.scope),
+    )
+    .await;
 
+    let page = arg.page.max(1);
+    let per_page = arg.per_page.max(1);
     match result {
-        Ok(page) => Ok(SearchStorageEntriesResp::Ok(StorageSearchPage {
-            entries: page
-                .entries
+        Ok(entries) => Ok(SearchStorageEntriesResp::Ok(StorageSearchPage {
+            total: entries.len() as u64,
+            entries: entries
                 .into_iter()
+                .skip((page as usize - 1) * per_page as usize)
+                .take(per_page as usize)
                 .map(|entry| StorageSearchEntry {
                     parent_path: parent_storage_path(entry.path.as_str()),
                     storage_id: arg.storage_id,
@@ -231,9 +243,8 @@ pub async fn ct_search_storage_entries(
                     is_dir: entry.is_dir,
                 })
                 .collect(),
-            total: page.total as u64,
-            page: arg.page.max(1),
-            per_page: arg.per_page.max(1),
+            page,
+            per_page,
         })),
         Err(e) => {
             tracing::warn!("ct_search_storage_entries, {e:?}");
@@ -345,7 +356,10 @@ mod tests {
             let cached = crate::services::get_storage_backend(backend.get_context(), storage.id)
                 .expect("load backend");
             assert!(cached.is_some());
-            assert!(crate::services::storage_backend_cache_contains(backend.get_context(), storage.id));
+            assert!(crate::services::storage_backend_cache_contains(
+                backend.get_context(),
+                storage.id
+            ));
 
             let mut updated = sample_arg();
             updated.id = Some(storage.id);
@@ -354,7 +368,269 @@ mod tests {
                 .await
                 .expect("update storage");
 
-            assert!(!crate::services::storage_backend_cache_contains(backend.get_context(), storage.id));
+            assert!(!crate::services::storage_backend_cache_contains(
+                backend.get_context(),
+                storage.id
+            ));
+        });
+    }
+
+    #[test]
+    fn remap_storage_moves_musics_and_keeps_playlists() {
+        use crate::controllers::playlist::{ct_create_playlist, ct_get_playlist};
+        use crate::objects::StorageEntry;
+        use crate::services::{ArgCreatePlaylist, ArgRemapStorage, ToAddMusicEntry};
+        use ease_client_schema::StorageEntryLoc;
+
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let tempdir = tempfile::tempdir().expect("create tempdir");
+            let documents_dir = tempdir.path().join("documents");
+            let cache_dir = tempdir.path().join("cache");
+            std::fs::create_dir_all(&documents_dir).expect("create documents dir");
+            std::fs::create_dir_all(&cache_dir).expect("create cache dir");
+
+            let backend = crate::create_backend(crate::services::ArgInitializeApp {
+                app_document_dir: format!("{}/", documents_dir.display()),
+                app_cache_dir: format!("{}/", cache_dir.display()),
+                storage_path: "/".to_string(),
+            });
+            backend.init().expect("init backend");
+
+            let media_dir = tempdir.path().join("media");
+            std::fs::create_dir_all(media_dir.join("old")).expect("create old dir");
+            std::fs::create_dir_all(media_dir.join("new")).expect("create new dir");
+            let media_dir = media_dir.canonicalize().expect("canonicalize media dir");
+            for name in ["old/a.mp3", "old/b.mp3", "new/a.mp3"] {
+                std::fs::write(media_dir.join(name), b"ID3").expect("write music");
+            }
+            let path_of = |path: &str| media_dir.join(path).to_string_lossy().to_string();
+
+            let storages = super::ct_list_storage(backend.clone())
+                .await
+                .expect("list storages");
+            let local = storages
+                .iter()
+                .find(|storage| storage.typ == StorageType::Local)
+                .expect("local storage")
+                .id;
+            let mut mirror = sample_arg();
+            mirror.alias = "mirror".to_string();
+            mirror.typ = StorageType::Local;
+            super::ct_upsert_storage(backend.clone(), mirror)
+                .await
+                .expect("insert mirror storage");
+            let mirror = super::ct_list_storage(backend.clone())
+                .await
+                .expect("list storages")
+                .into_iter()
+                .find(|storage| storage.alias == "mirror")
+                .expect("mirror storage")
+                .id;
+
+            let make_entry = |path: &str| ToAddMusicEntry {
+                entry: StorageEntry {
+                    storage_id: local,
+                    name: path.to_string(),
+                    path: path_of(path),
+                    size: None,
+                    is_dir: false,
+                },
+                name: path.to_string(),
+            };
+            let playlist = ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "moving".to_string(),
+                    cover: None,
+                    entries: vec![make_entry("old/b.mp3"), make_entry("old/a.mp3")],
+                },
+            )
+            .await
+            .expect("create playlist")
+            .id;
+            let before = ct_get_playlist(backend.clone(), playlist)
+                .await
+                .expect("load playlist")
+                .expect("playlist");
+            let db = backend.get_context().database_server();
+            let a_id = before.musics[1].meta.id;
+            db.update_music_lyric(
+                a_id,
+                Some(StorageEntryLoc {
+                    storage_id: local,
+                    path: path_of("old/a.lrc"),
+                }),
+            )
+            .expect("bind lyric");
+            db.update_music_total_duration(a_id, std::time::Duration::from_secs(42))
+                .expect("update duration");
+
+            let arg = ArgRemapStorage {
+                from: StorageEntryLoc {
+                    storage_id: local,
+                    path: path_of("old/"),
+                },
+                to: StorageEntryLoc {
+                    storage_id: mirror,
+                    path: path_of("new"),
+                },
+                verify: true,
+            };
+            let refused = super::ct_remap_storage(backend.clone(), arg.clone())
+                .await
+                .expect("verify remap");
+            assert!(refused.remapped.is_empty());
+            assert_eq!(vec![before.musics[0].meta.id], refused.missing_targets);
+            assert_eq!(2, db.load_storage_music_count(local).expect("count"));
+
+            std::fs::write(media_dir.join("new/b.mp3"), b"ID3").expect("write music");
+            let result = super::ct_remap_storage(backend.clone(), arg)
+                .await
+                .expect("remap");
+            assert_eq!(2, result.remapped.len());
+            assert_eq!(0, db.load_storage_music_count(local).expect("count"));
+            assert_eq!(2, db.load_storage_music_count(mirror).expect("count"));
+
+            let after = ct_get_playlist(backend.clone(), playlist)
+                .await
+                .expect("reload playlist")
+                .expect("playlist");
+            let ids = |p: &crate::objects::Playlist| -> Vec<_> {
+                p.musics.iter().map(|m| m.meta.id).collect()
+            };
+            assert_eq!(ids(&before), ids(&after));
+            let a = db.load_music(a_id).expect("load").expect("music a");
+            assert_eq!(mirror, a.loc.storage_id);
+            assert_eq!(path_of("new/a.mp3"), a.loc.path);
+            assert_eq!(Some(path_of("new/a.lrc")), a.lyric.map(|v| v.path));
+            assert_eq!(Some(std::time::Duration::from_secs(42)), a.duration);
+            assert!(db
+                .load_music_by_loc(StorageEntryLoc {
+                    storage_id: local,
+                    path: path_of("old/a.mp3"),
+                })
+                .expect("load by old loc")
+                .is_none());
+        });
+    }
+
+    #[test]
+    fn remove_storage_forgets_its_musics() {
+        use crate::controllers::playlist::ct_create_playlist;
+        use crate::services::ArgCreatePlaylist;
+        use crate::test_utils::{music_entry, setup_backend};
+        use ease_client_schema::MusicFingerprintModel;
+
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (_tempdir, backend) = setup_backend();
+            let mut arg = sample_arg();
+            arg.typ = StorageType::Local;
+            super::ct_upsert_storage(backend.clone(), arg)
+                .await
+                .expect("insert storage");
+            let storage = super::ct_list_storage(backend.clone())
+                .await
+                .expect("list storages")
+                .into_iter()
+                .find(|storage| storage.alias == "demo")
+                .expect("inserted storage")
+                .id;
+
+            let playlist = ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "removed".to_string(),
+                    cover: None,
+                    entries: vec![music_entry(storage, "/music/a.mp3")],
+                },
+            )
+            .await
+            .expect("create playlist");
+            let music = playlist.music_ids[0].id;
+            let db = backend.get_context().database_server();
+            assert!(!db
+                .load_search_terms("")
+                .expect("load search terms")
+                .is_empty());
+            db.update_music_fingerprint(
+                music,
+                MusicFingerprintModel {
+                    fingerprint: vec![1, 2, 3],
+                    algorithm: 1,
+                },
+            )
+            .expect("store fingerprint");
+
+            super::ct_remove_storage(backend.clone(), storage)
+                .await
+                .expect("remove storage");
+
+            assert!(db.load_music(music).expect("load music").is_none());
+            assert!(db
+                .load_music_fingerprints()
+                .expect("load fingerprints")
+                .is_empty());
+            assert!(db
+                .load_search_terms("")
+                .expect("load search terms")
+                .is_empty());
+        });
+    }
+
+    #[test]
+    fn search_storage_entries_folds_scripts_and_matches_pinyin() {
+        use crate::objects::{
+            ArgSearchStorageEntries, SearchStorageEntriesResp, StorageSearchScope,
+        };
+        use crate::test_utils::{local_storage_id, setup_backend};
+
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let media_dir = tempdir.path().join("media");
+            std::fs::create_dir_all(media_dir.join("周杰倫")).expect("create artist dir");
+            std::fs::create_dir_all(media_dir.join("other")).expect("create other dir");
+            std::fs::write(media_dir.join("周杰倫/晴天.mp3"), b"ID3").expect("write music");
+            std::fs::write(media_dir.join("other/rain.mp3"), b"ID3").expect("write music");
+            let storage_id = local_storage_id(&backend).await;
+
+            let search = |keywords: &str, scope: StorageSearchScope| {
+                let arg = ArgSearchStorageEntries {
+                    storage_id,
+                    parent: media_dir.to_string_lossy().to_string(),
+                    keywords: keywords.to_string(),
+                    scope,
+                    page: 1,
+                    per_page: 10,
+                };
+                let backend = backend.clone();
+                async move {
+                    match super::ct_search_storage_entries(backend, arg)
+                        .await
+                        .expect("search")
+                    {
+                        SearchStorageEntriesResp::Ok(page) => page
+                            .entries
+                            .into_iter()
+                            .map(|entry| entry.name)
+                            .collect::<Vec<_>>(),
+                        _ => panic!("search failed"),
+                    }
+                }
+            };
+
+            assert_eq!(vec!["周杰倫"], search("zjl", StorageSearchScope::All).await);
+            assert_eq!(
+                vec!["周杰倫"],
+                search("周杰伦", StorageSearchScope::Directory).await
+            );
+            assert_eq!(
+                vec!["晴天.mp3"],
+                search("qingtian", StorageSearchScope::File).await
+            );
+            assert_eq!(
+                vec!["rain.mp3"],
+                search("ＲＡＩＮ", StorageSearchScope::All).await
+            );
         });
     }
 }
@@ -0,0 +1,296 @@
+use std::sync::Arc;
+
+use crate::{
+    error::BResult,
+    services::{
+        disable_sync, enable_sync, get_sync_status, sync_now, ArgEnableSync, SyncResult, SyncStatus,
+    },
+    Backend,
+};
+
+#[uniffi::export]
+pub async fn ct_enable_sync(cx: Arc<Backend>, arg: ArgEnableSync) -> BResult<SyncStatus> {
+    let cx = cx.get_context();
+    enable_sync(cx, arg).await
+}
+
+#[uniffi::export]
+pub async fn ct_disable_sync(cx: Arc<Backend>) -> BResult<()> {
+    let cx = cx.get_context();
+    disable_sync(cx)
+}
+
+#[uniffi::export]
+pub fn cts_get_sync_status(cx: Arc<Backend>) -> BResult<Option<SyncStatus>> {
+    let cx = cx.get_context();
+    get_sync_status(cx)
+}
+
+#[uniffi::export]
+pub async fn ct_sync_now(cx: Arc<Backend>) -> BResult<SyncResult> {
+    let cx = cx.get_context();
+    sync_now(cx).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use ease_client_schema::{PlaylistId, StorageEntryLoc, StorageType};
+    use ease_order_key::OrderKey;
+
+    use crate::{
+        controllers::{
+            playlist::{
+                ct_add_musics_to_playlist, ct_create_playlist, ct_get_playlist, ct_list_playlist,
+                ct_remove_music_from_playlist, ct_remove_playlist, ct_update_playlist,
+            },
+            storage::{ct_list_storage, ct_upsert_storage},
+            user_data::{
+                cts_get_music_user_data, cts_set_music_favorite, cts_set_music_rating,
+                cts_set_music_user_tags,
+            },
+        },
+        error::BError,
+        objects::ArgUpsertStorage,
+        services::{
+            ArgAddMusicsToPlaylist, ArgCreatePlaylist, ArgEnableSync, ArgRemoveMusicFromPlaylist,
+            ArgSetMusicFavorite, ArgSetMusicRating, ArgSetMusicUserTags, ArgUpdatePlaylist,
+        },
+        test_utils::{local_storage_id, music_entry, setup_backend},
+    };
+
+    use super::{ct_enable_sync, ct_sync_now, cts_get_sync_status};
+
+    async fn only_playlist(backend: &Arc<crate::Backend>) -> (PlaylistId, String, Vec<String>) {
+        let playlists = ct_list_playlist(backend.clone())
+            .await
+            .expect("list playlists");
+        assert_eq!(1, playlists.len());
+        let id = playlists[0].meta.id;
+        let playlist = ct_get_playlist(backend.clone(), id)
+            .await
+            .expect("get playlist")
+            .expect("playlist");
+        let titles = playlist
+            .musics
+            .iter()
+            .map(|v| v.meta.title.clone())
+            .collect();
+        (id, playlist.abstr.meta.title, titles)
+    }
+
+    #[test]
+    fn merges_playlist_changes_of_two_devices() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let shared = tempfile::tempdir().expect("create shared dir");
+            let shared_path = shared
+                .path()
+                .canonicalize()
+                .expect("canonicalize shared dir")
+                .to_string_lossy()
+                .replace('\\', "/");
+            let music = |name: &str| format!("{shared_path}/music/{name}");
+
+            let (_tempdir_a, a) = setup_backend();
+            let (_tempdir_b, b) = setup_backend();
+            let local_a = local_storage_id(&a).await;
+            let local_b = local_storage_id(&b).await;
+            assert!(matches!(
+                ct_sync_now(a.clone()).await,
+                Err(BError::SyncNotEnabled)
+            ));
+
+            // A playlist made before sync was turned on, with a music on a
+            // storage only device A has.
+            ct_upsert_storage(
+                a.clone(),
+                ArgUpsertStorage {
+                    id: None,
+                    addr: "http://127.0.0.1:9".to_string(),
+                    alias: "nas".to_string(),
+                    username: Default::default(),
+                    password: Default::default(),
+                    is_anonymous: true,
+                    typ: StorageType::Webdav,
+                    default_path: "/".to_string(),
+                },
+            )
+            .await
+            .expect("add storage");
+            let dav = ct_list_storage(a.clone())
+                .await
+                .expect("list storages")
+                .into_iter()
+                .find(|storage| storage.typ == StorageType::Webdav)
+                .expect("webdav storage")
+                .id;
+            let created = ct_create_playlist(
+                a.clone(),
+                ArgCreatePlaylist {
+                    title: "road".to_string(),
+                    cover: None,
+                    entries: vec![
+                        music_entry(local_a, &music("a.mp3")),
+                        music_entry(local_a, &music("b.mp3")),
+                        music_entry(dav, "/nas/d.mp3"),
+                    ],
+                },
+            )
+            .await
+            .expect("create playlist");
+
+            let dir = |storage_id| ArgEnableSync {
+                dir: StorageEntryLoc {
+                    storage_id,
+                    path: format!("{shared_path}/sync"),
+                },
+            };
+            let status = ct_enable_sync(a.clone(), dir(local_a))
+                .await
+                .expect("enable sync");
+            let first = ct_sync_now(a.clone()).await.expect("sync");
+            assert_eq!(0, first.devices);
+            ct_enable_sync(b.clone(), dir(local_b))
+                .await
+                .expect("enable sync");
+            let joined = ct_sync_now(b.clone()).await.expect("sync");
+            assert_eq!(1, joined.devices);
+            assert_eq!(1, joined.skipped);
+            let (id_b, title, musics) = only_playlist(&b).await;
+            assert_eq!("road", title);
+            assert_eq!(vec!["a.mp3", "b.mp3"], musics);
+            let synced = cts_get_sync_status(b.clone())
+                .expect("get status")
+                .expect("status");
+            assert_ne!(status.device_id, synced.device_id);
+            assert!(synced.last_synced.is_some());
+
+            // Both devices change the playlist while offline. The later
+            // title wins, additions and removals of both are kept.
+            ct_update_playlist(
+                a.clone(),
+                ArgUpdatePlaylist {
+                    id: created.id,
+                    title: "road trip".to_string(),
+                    cover: None,
+                },
+            )
+            .await
+            .expect("rename");
+            ct_remove_music_from_playlist(
+                a.clone(),
+                ArgRemoveMusicFromPlaylist {
+                    playlist_id: created.id,
+                    music_id: created.music_ids[0].id,
+                },
+            )
+            .await
+            .expect("remove music");
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            ct_update_playlist(
+                b.clone(),
+                ArgUpdatePlaylist {
+                    id: id_b,
+                    title: "summer".to_string(),
+                    cover: None,
+                },
+            )
+            .await
+            .expect("rename");
+            ct_add_musics_to_playlist(
+                b.clone(),
+                ArgAddMusicsToPlaylist {
+                    id: id_b,
+                    entries: vec![music_entry(local_b, &music("c.mp3"))],
+                },
+            )
+            .await
+            .expect("add music");
+
+            ct_sync_now(a.clone()).await.expect("sync");
+            ct_sync_now(b.clone()).await.expect("sync");
+            ct_sync_now(a.clone()).await.expect("sync");
+            for backend in [&a, &b] {
+                let (_, title, mut musics) = only_playlist(backend).await;
+                assert_eq!("summer", title);
+                // The music device B cannot reach stays on device A.
+                musics.retain(|v| v != "d.mp3");
+                assert_eq!(vec!["b.mp3", "c.mp3"], musics);
+            }
+            assert_eq!(3, only_playlist(&a).await.2.len());
+
+            // Changes made without going through the controllers are found
+            // when syncing.
+            let db = b.get_context().database_server();
+            let c = db
+                .load_music_by_loc(StorageEntryLoc {
+                    storage_id: local_b,
+                    path: music("c.mp3"),
+                })
+                .expect("load music")
+                .expect("music");
+            db.set_playlist_music_order(id_b, c.id, OrderKey::wrap(vec![0]))
+                .expect("reorder");
+            ct_sync_now(b.clone()).await.expect("sync");
+            let moved = ct_sync_now(a.clone()).await.expect("sync");
+            assert_eq!(1, moved.applied);
+            let (_, _, musics) = only_playlist(&b).await;
+            assert_eq!(vec!["c.mp3", "b.mp3"], musics);
+
+            // Favorites, ratings and tags of the same music merge too.
+            let b_on_a = created.music_ids[1].id;
+            let b_on_b = db
+                .load_music_by_loc(StorageEntryLoc {
+                    storage_id: local_b,
+                    path: music("b.mp3"),
+                })
+                .expect("load music")
+                .expect("music")
+                .id;
+            cts_set_music_rating(
+                a.clone(),
+                ArgSetMusicRating {
+                    id: b_on_a,
+                    rating: 4,
+                },
+            )
+            .expect("rate");
+            cts_set_music_user_tags(
+                a.clone(),
+                ArgSetMusicUserTags {
+                    id: b_on_a,
+                    tags: vec!["Chill".to_string()],
+                },
+            )
+            .expect("tag");
+            cts_set_music_favorite(
+                b.clone(),
+                ArgSetMusicFavorite {
+                    id: b_on_b,
+                    favorite: true,
+                },
+            )
+            .expect("favorite");
+            ct_sync_now(a.clone()).await.expect("sync");
+            ct_sync_now(b.clone()).await.expect("sync");
+            ct_sync_now(a.clone()).await.expect("sync");
+            for (backend, id) in [(&a, b_on_a), (&b, b_on_b)] {
+                let data = cts_get_music_user_data(backend.clone(), id).expect("user data");
+                assert!(data.favorite);
+                assert_eq!(4, data.rating);
+                assert_eq!(vec!["chill"], data.tags);
+            }
+
+            ct_remove_playlist(a.clone(), created.id)
+                .await
+                .expect("remove playlist");
+            ct_sync_now(a.clone()).await.expect("sync");
+            ct_sync_now(b.clone()).await.expect("sync");
+            assert!(ct_list_playlist(b.clone())
+                .await
+                .expect("list playlists")
+                .is_empty());
+        });
+    }
+}
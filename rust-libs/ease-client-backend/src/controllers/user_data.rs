@@ -0,0 +1,236 @@
+use std::sync::Arc;
+
+use ease_client_schema::MusicId;
+
+use crate::{
+    error::BResult,
+    services::{
+        get_favorite_musics, get_music_user_data, get_musics_by_rating, get_musics_by_user_tag,
+        list_user_tags, note_sync_changes, set_music_favorite, set_music_rating,
+        set_music_user_tags, ArgGetMusicsByRating, ArgSetMusicFavorite, ArgSetMusicRating,
+        ArgSetMusicUserTags, MusicUserData, SyncChange, UserTagCount,
+    },
+    Backend, MusicAbstract,
+};
+
+#[uniffi::export]
+pub fn cts_get_music_user_data(cx: Arc<Backend>, id: MusicId) -> BResult<MusicUserData> {
+    let cx = cx.get_context();
+    get_music_user_data(cx, id)
+}
+
+#[uniffi::export]
+pub fn cts_set_music_favorite(cx: Arc<Backend>, arg: ArgSetMusicFavorite) -> BResult<()> {
+    let cx = cx.get_context();
+    let id = arg.id;
+    set_music_favorite(cx, arg)?;
+    note_sync_changes(cx, &[SyncChange::Music(id)]);
+    Ok(())
+}
+
+#[uniffi::export]
+pub fn cts_set_music_rating(cx: Arc<Backend>, arg: ArgSetMusicRating) -> BResult<()> {
+    let cx = cx.get_context();
+    let id = arg.id;
+    set_music_rating(cx, arg)?;
+    note_sync_changes(cx, &[SyncChange::Music(id)]);
+    Ok(())
+}
+
+#[uniffi::export]
+pub fn cts_set_music_user_tags(cx: Arc<Backend>, arg: ArgSetMusicUserTags) -> BResult<()> {
+    let cx = cx.get_context();
+    let id = arg.id;
+    set_music_user_tags(cx, arg)?;
+    note_sync_changes(cx, &[SyncChange::Music(id)]);
+    Ok(())
+}
+
+#[uniffi::export]
+pub fn cts_get_favorite_musics(cx: Arc<Backend>) -> BResult<Vec<MusicAbstract>> {
+    let cx = cx.get_context();
+    get_favorite_musics(cx)
+}
+
+#[uniffi::export]
+pub fn cts_get_musics_by_rating(
+    cx: Arc<Backend>,
+    arg: ArgGetMusicsByRating,
+) -> BResult<Vec<MusicAbstract>> {
+    let cx = cx.get_context();
+    get_musics_by_rating(cx, arg)
+}
+
+#[uniffi::export]
+pub fn cts_get_musics_by_user_tag(cx: Arc<Backend>, tag: String) -> BResult<Vec<MusicAbstract>> {
+    let cx = cx.get_context();
+    get_musics_by_user_tag(cx, tag)
+}
+
+#[uniffi::export]
+pub fn cts_list_user_tags(cx: Arc<Backend>) -> BResult<Vec<UserTagCount>> {
+    let cx = cx.get_context();
+    list_user_tags(cx)
+}
+
+#[cfg(test)]
+mod tests {
+    use ease_client_schema::{
+        SmartCondition, SmartConditionGroup, SmartPlaylistModel, SmartRuleCombinator, SmartSort,
+        SmartSortField,
+    };
+
+    use crate::{
+        controllers::playlist::{
+            ct_create_playlist, ct_create_smart_playlist, ct_get_playlist, ct_remove_playlist,
+        },
+        error::BError,
+        objects::ArgCreateSmartPlaylist,
+        services::ArgCreatePlaylist,
+        test_utils::{local_storage_id, music_entry, setup_backend},
+    };
+
+    use super::*;
+
+    fn ids(musics: Vec<MusicAbstract>) -> Vec<MusicId> {
+        musics.into_iter().map(|v| v.meta.id).collect()
+    }
+
+    #[test]
+    fn keeps_favorites_ratings_and_tags() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (_tempdir, backend) = setup_backend();
+            let storage_id = local_storage_id(&backend).await;
+            let created = ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "road".to_string(),
+                    cover: None,
+                    entries: vec![
+                        music_entry(storage_id, "/music/a.mp3"),
+                        music_entry(storage_id, "/music/b.mp3"),
+                        music_entry(storage_id, "/music/c.mp3"),
+                    ],
+                },
+            )
+            .await
+            .expect("create playlist");
+            let a = created.music_ids[0].id;
+            let b = created.music_ids[1].id;
+            let c = created.music_ids[2].id;
+
+            let favorite = |id, favorite| {
+                cts_set_music_favorite(backend.clone(), ArgSetMusicFavorite { id, favorite })
+            };
+            favorite(b, true).expect("favorite");
+            favorite(a, true).expect("favorite");
+            favorite(c, true).expect("favorite");
+            favorite(c, false).expect("unfavorite");
+            let favorites = cts_get_favorite_musics(backend.clone()).expect("favorites");
+            assert_eq!(2, favorites.len());
+            assert!(!ids(favorites).contains(&c));
+            let data = cts_get_music_user_data(backend.clone(), a).expect("user data");
+            assert!(data.favorite);
+            assert!(data.favorite_time.is_some());
+
+            let rate = |id, rating| {
+                cts_set_music_rating(backend.clone(), ArgSetMusicRating { id, rating })
+            };
+            rate(a, 3).expect("rate");
+            rate(b, 5).expect("rate");
+            rate(c, 1).expect("rate");
+            rate(c, 0).expect("clear rating");
+            assert!(matches!(rate(a, 6), Err(BError::InvalidRating(6))));
+            assert!(matches!(
+                rate(MusicId::wrap(999), 1),
+                Err(BError::MusicNotFound(_))
+            ));
+            let rated =
+                cts_get_musics_by_rating(backend.clone(), ArgGetMusicsByRating { min: 0, max: 5 })
+                    .expect("by rating");
+            assert_eq!(vec![b, a], ids(rated));
+            let rated =
+                cts_get_musics_by_rating(backend.clone(), ArgGetMusicsByRating { min: 4, max: 5 })
+                    .expect("by rating");
+            assert_eq!(vec![b], ids(rated));
+
+            let tag = |id, tags: &[&str]| {
+                cts_set_music_user_tags(
+                    backend.clone(),
+                    ArgSetMusicUserTags {
+                        id,
+                        tags: tags.iter().map(|v| v.to_string()).collect(),
+                    },
+                )
+            };
+            tag(a, &["Chill", " night  drive ", "chill", ""]).expect("tag");
+            tag(b, &["chill"]).expect("tag");
+            tag(c, &["loud"]).expect("tag");
+            tag(c, &[]).expect("clear tags");
+            let data = cts_get_music_user_data(backend.clone(), a).expect("user data");
+            assert_eq!(vec!["chill", "night drive"], data.tags);
+            let mut tagged = ids(
+                cts_get_musics_by_user_tag(backend.clone(), "CHILL".to_string()).expect("by tag"),
+            );
+            tagged.sort();
+            let mut expected = vec![a, b];
+            expected.sort();
+            assert_eq!(expected, tagged);
+            let tags: Vec<(String, u64)> = cts_list_user_tags(backend.clone())
+                .expect("tags")
+                .into_iter()
+                .map(|v| (v.tag, v.count))
+                .collect();
+            assert_eq!(
+                vec![("chill".to_string(), 2), ("night drive".to_string(), 1)],
+                tags
+            );
+
+            // Smart playlists match the ratings.
+            let smart = ct_create_smart_playlist(
+                backend.clone(),
+                ArgCreateSmartPlaylist {
+                    title: "top rated".to_string(),
+                    cover: None,
+                    rule: SmartPlaylistModel {
+                        combinator: SmartRuleCombinator::All,
+                        groups: vec![SmartConditionGroup {
+                            combinator: SmartRuleCombinator::All,
+                            conditions: vec![SmartCondition::RatingRange {
+                                min: Some(3),
+                                max: None,
+                            }],
+                        }],
+                        sort: SmartSort {
+                            field: SmartSortField::Rating,
+                            descending: true,
+                        },
+                        limit: None,
+                    },
+                },
+            )
+            .await
+            .expect("create smart playlist");
+            let smart = ct_get_playlist(backend.clone(), smart)
+                .await
+                .expect("get playlist")
+                .expect("playlist");
+            let smart_ids: Vec<MusicId> = smart.musics.iter().map(|v| v.meta.id).collect();
+            assert_eq!(vec![b, a], smart_ids);
+
+            // User data goes with the musics removed.
+            ct_remove_playlist(backend.clone(), created.id)
+                .await
+                .expect("remove playlist");
+            assert!(cts_get_favorite_musics(backend.clone())
+                .expect("favorites")
+                .is_empty());
+            assert!(cts_list_user_tags(backend.clone())
+                .expect("tags")
+                .is_empty());
+            let data = cts_get_music_user_data(backend.clone(), a).expect("user data");
+            assert_eq!(0, data.rating);
+            assert!(!data.favorite);
+        });
+    }
+}
@@ -4,13 +4,20 @@ use std::{
     time::Duration,
 };
 
-use crate::{repositories::core::DatabaseServer, services::StorageState};
+use crate::{
+    repositories::core::DatabaseServer,
+    services::{MusicState, ScanState, ScrobbleState, StorageState, SyncState},
+};
 
 struct BackendContextInternal {
     storage_path: RwLock<String>,
     app_document_dir: RwLock<String>,
     schema_version: AtomicU32,
     storage_state: Arc<StorageState>,
+    music_state: Arc<MusicState>,
+    scan_state: Arc<ScanState>,
+    sync_state: Arc<SyncState>,
+    scrobble_state: Arc<ScrobbleState>,
     database_server: Arc<DatabaseServer>,
 }
 
@@ -61,6 +68,10 @@ impl BackendContext {
                 app_document_dir: RwLock::new(String::new()),
                 schema_version: AtomicU32::new(0),
                 storage_state: Default::default(),
+                music_state: Default::default(),
+                scan_state: Default::default(),
+                sync_state: Default::default(),
+                scrobble_state: Default::default(),
                 database_server: DatabaseServer::new(),
             }),
         }
@@ -89,6 +100,22 @@ impl BackendContext {
         &self.internal.storage_state
     }
 
+    pub(crate) fn music_state(&self) -> &Arc<MusicState> {
+        &self.internal.music_state
+    }
+
+    pub(crate) fn scan_state(&self) -> &Arc<ScanState> {
+        &self.internal.scan_state
+    }
+
+    pub(crate) fn sync_state(&self) -> &Arc<SyncState> {
+        &self.internal.sync_state
+    }
+
+    pub(crate) fn scrobble_state(&self) -> &Arc<ScrobbleState> {
+        &self.internal.scrobble_state
+    }
+
     pub(crate) fn database_server(&self) -> &Arc<DatabaseServer> {
         &self.internal.database_server
     }
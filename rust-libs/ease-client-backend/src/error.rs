@@ -12,8 +12,12 @@ pub enum BError {
     AssetNotFound,
     #[error("playlist not found")]
     PlaylistNotFound(PlaylistId),
+    #[error("smart playlist musics cannot be edited")]
+    SmartPlaylistNotEditable(PlaylistId),
     #[error("music not found")]
     MusicNotFound(MusicId),
+    #[error("storage entry is already linked to another music")]
+    MusicLocInUse(MusicId),
     #[error("redb error: {0:?}")]
     RedbError(#[from] redb::Error),
     #[error("redb transaction error: {0:?}")]
@@ -28,6 +32,16 @@ pub enum BError {
     IoError(#[from] std::io::Error),
     #[error(transparent)]
     OrderKeyError(#[from] OrderKeyError),
+    #[error("invalid backup: {0}")]
+    InvalidBackup(String),
+    #[error("backup passphrase is missing or wrong")]
+    BackupPassphraseMismatch,
+    #[error("rating must be from 0 to 5")]
+    InvalidRating(u8),
+    #[error("queue index {0} is out of range")]
+    InvalidQueueIndex(u32),
+    #[error("sync is not enabled")]
+    SyncNotEnabled,
     #[error("custom: {message}")]
     CustomError { message: String },
     #[error(transparent)]
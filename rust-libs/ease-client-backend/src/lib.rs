@@ -1,12 +1,14 @@
 use std::sync::Arc;
 
-pub(crate) mod controllers;
+pub mod controllers;
 pub(crate) mod ctx;
 pub mod error;
 mod infra;
 mod objects;
 pub(crate) mod repositories;
-pub(crate) mod services;
+pub mod services;
+#[cfg(test)]
+mod test_utils;
 pub(crate) mod utils;
 
 pub use objects::*;
@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use ease_client_schema::{AlbumId, ArtistId, DataSourceKey, GenreId};
+
+use super::music::MusicAbstract;
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArtistAbstract {
+    pub id: ArtistId,
+    pub name: String,
+    pub music_count: u64,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct AlbumAbstract {
+    pub id: AlbumId,
+    pub title: String,
+    pub artist: Option<ArtistAbstract>,
+    pub year: Option<u32>,
+    pub music_count: u64,
+    pub show_cover: Option<DataSourceKey>,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct GenreAbstract {
+    pub id: GenreId,
+    pub name: String,
+    pub music_count: u64,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct Album {
+    pub abstr: AlbumAbstract,
+    pub duration: Option<Duration>,
+    pub musics: Vec<MusicAbstract>,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct Artist {
+    pub abstr: ArtistAbstract,
+    pub albums: Vec<AlbumAbstract>,
+    pub musics: Vec<MusicAbstract>,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct Genre {
+    pub abstr: GenreAbstract,
+    pub musics: Vec<MusicAbstract>,
+}
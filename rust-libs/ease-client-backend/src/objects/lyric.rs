@@ -21,4 +21,7 @@ pub struct LyricLine {
 pub struct Lyrics {
     pub metdata: LrcMetadata,
     pub lines: Vec<LyricLine>,
+    /// Whether the lines are timed. Lines of plain text lyrics all start at
+    /// zero.
+    pub synced: bool,
 }
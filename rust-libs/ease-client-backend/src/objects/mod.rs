@@ -1,14 +1,18 @@
+mod library;
 mod lyric;
 mod music;
 mod player;
 mod playlist;
+mod search;
 mod storage;
 
 mod env;
 
 pub use env::*;
+pub use library::*;
 pub use lyric::*;
 pub use music::*;
 pub use player::*;
 pub use playlist::*;
+pub use search::*;
 pub use storage::*;
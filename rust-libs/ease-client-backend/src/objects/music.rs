@@ -1,15 +1,29 @@
 use std::time::Duration;
 
-use ease_client_schema::{DataSourceKey, MusicId, StorageEntryLoc};
+use ease_client_schema::{
+    AudioStreamInfo, DataSourceKey, MusicFileState, MusicId, StorageEntryLoc,
+};
 
 use super::lyric::Lyrics;
 
+/// ReplayGain values in dB relative to the -18 LUFS reference level, with
+/// linear peaks where 1.0 is full scale.
+#[derive(Debug, Clone, Copy, PartialEq, uniffi::Record)]
+pub struct ReplayGain {
+    pub track_gain: f64,
+    pub track_peak: Option<f64>,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
+}
+
 #[derive(Debug, Clone, uniffi::Record)]
 pub struct MusicMeta {
     pub id: MusicId,
     pub title: String,
     pub duration: Option<Duration>,
     pub order: Vec<u32>,
+    pub replay_gain: Option<ReplayGain>,
+    pub file_state: MusicFileState,
 }
 
 #[derive(Debug, Clone, uniffi::Record)]
@@ -27,11 +41,23 @@ pub enum LyricLoadState {
     Loaded,
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum LyricSource {
+    /// The lyric file bound to the music.
+    #[default]
+    File,
+    /// The `.lrc` file next to the music.
+    SiblingFile,
+    /// Lyrics embedded in the tags of the music file.
+    Embedded,
+}
+
 #[derive(Debug, Clone, uniffi::Record)]
 pub struct MusicLyric {
     pub loc: StorageEntryLoc,
     pub data: Lyrics,
     pub loaded_state: LyricLoadState,
+    pub source: LyricSource,
 }
 
 #[derive(Debug, Clone, uniffi::Record)]
@@ -40,6 +66,7 @@ pub struct Music {
     pub loc: StorageEntryLoc,
     pub cover: Option<DataSourceKey>,
     pub lyric: Option<MusicLyric>,
+    pub stream_info: Option<AudioStreamInfo>,
 }
 
 impl Music {
@@ -1,6 +1,8 @@
 use std::time::Duration;
 
-use ease_client_schema::{DataSourceKey, PlaylistId, StorageEntryLoc};
+use ease_client_schema::{
+    DataSourceKey, FolderRemovedPolicy, PlaylistId, SmartPlaylistModel, StorageEntryLoc,
+};
 
 use super::music::MusicAbstract;
 
@@ -12,6 +14,8 @@ pub struct PlaylistMeta {
     pub show_cover: Option<DataSourceKey>,
     pub created_time: Duration,
     pub order: Vec<u32>,
+    /// Whether the musics are evaluated from a rule rather than added.
+    pub is_smart: bool,
 }
 
 #[derive(Debug, Clone, uniffi::Record)]
@@ -34,6 +38,29 @@ pub enum CreatePlaylistMode {
     Empty,
 }
 
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgCreateSmartPlaylist {
+    pub title: String,
+    pub cover: Option<StorageEntryLoc>,
+    pub rule: SmartPlaylistModel,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgUpdateSmartPlaylist {
+    pub id: PlaylistId,
+    pub rule: SmartPlaylistModel,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgBindPlaylistFolder {
+    pub id: PlaylistId,
+    pub dir: StorageEntryLoc,
+    pub recursive: bool,
+    pub glob: Option<String>,
+    pub on_removed: FolderRemovedPolicy,
+    pub refresh_interval: Option<Duration>,
+}
+
 impl PlaylistAbstract {
     pub fn id(&self) -> PlaylistId {
         self.meta.id
@@ -0,0 +1,16 @@
+use super::music::MusicAbstract;
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgSearchLibrary {
+    pub keywords: String,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct LibrarySearchPage {
+    pub musics: Vec<MusicAbstract>,
+    pub total: u64,
+    pub page: u32,
+    pub per_page: u32,
+}
@@ -55,6 +55,35 @@ impl BlobManager {
         Ok(id)
     }
 
+    /// Ids of the blobs on disk.
+    pub fn ids(&self) -> BResult<Vec<BlobId>> {
+        let mut ret: Vec<BlobId> = Default::default();
+        for entry in std::fs::read_dir(blobs_path(&self.dir))? {
+            let name = entry?.file_name();
+            if let Some(id) = name.to_str().and_then(|v| v.parse::<i64>().ok()) {
+                ret.push(BlobId::wrap(id));
+            }
+        }
+        ret.sort();
+        Ok(ret)
+    }
+
+    /// Writes a blob under a given id, as when restoring a backup. Ids
+    /// allocated afterwards are greater.
+    pub fn write_at(&self, id: BlobId, buf: &[u8]) -> BResult<()> {
+        std::fs::write(blob_path(self.dir.as_str(), id), buf)?;
+        let txn = self.db.begin_write()?;
+        {
+            let mut table = txn.open_table(TABLE_BLOB)?;
+            let next_id = table.get(())?.map(|v| v.value()).unwrap_or(BlobId::wrap(0));
+            if *next_id.as_ref() <= *id.as_ref() {
+                table.insert((), BlobId::wrap(*id.as_ref() + 1))?;
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
     // pub fn clear(&self) -> BResult<()> {
     //     let paths = std::fs::read_dir(blobs_path(&self.dir)).expect("Failed to read directory");
     //     for path in paths {
@@ -6,9 +6,16 @@ use crate::error::BResult;
 
 use super::blob::BlobManager;
 use ease_client_schema::{
-    DbKeyAlloc, TABLE_ID_ALLOC, TABLE_MUSIC, TABLE_MUSIC_BY_LOC, TABLE_MUSIC_PLAYLIST,
-    TABLE_PLAYLIST, TABLE_PLAYLIST_MUSIC, TABLE_PREFERENCE, TABLE_SCHEMA_VERSION, TABLE_STORAGE,
-    TABLE_STORAGE_MUSIC,
+    DbKeyAlloc, TABLE_ALBUM, TABLE_ALBUM_BY_KEY, TABLE_ALBUM_MUSIC, TABLE_ARTIST,
+    TABLE_ARTIST_BY_NAME, TABLE_ARTIST_MUSIC, TABLE_BLOB_REF, TABLE_GENRE, TABLE_GENRE_BY_NAME,
+    TABLE_GENRE_MUSIC, TABLE_ID_ALLOC, TABLE_MUSIC, TABLE_MUSIC_BY_LOC, TABLE_MUSIC_FAVORITE,
+    TABLE_MUSIC_FINGERPRINT, TABLE_MUSIC_PLAYLIST, TABLE_MUSIC_PLAY_STATS, TABLE_MUSIC_RATING,
+    TABLE_MUSIC_USER_TAG, TABLE_PLAYLIST, TABLE_PLAYLIST_FOLDER, TABLE_PLAYLIST_MUSIC,
+    TABLE_PLAYLIST_PLAY_STATS, TABLE_PLAYLIST_SYNC_ID, TABLE_PLAY_EVENT, TABLE_PLAY_QUEUE,
+    TABLE_PLAY_QUEUE_CURSOR, TABLE_PREFERENCE, TABLE_RATING_MUSIC, TABLE_SCHEMA_VERSION,
+    TABLE_SCROBBLE_CONFIG, TABLE_SCROBBLE_QUEUE, TABLE_SEARCH_DOCUMENT, TABLE_SEARCH_TERM,
+    TABLE_SMART_PLAYLIST, TABLE_STORAGE, TABLE_STORAGE_MUSIC, TABLE_SYNC_CONFIG, TABLE_SYNC_FIELD,
+    TABLE_USER_TAG_MUSIC,
 };
 
 #[derive(Default)]
@@ -65,6 +72,36 @@ impl DatabaseServer {
         db.open_multimap_table(TABLE_STORAGE_MUSIC)?;
         db.open_table(TABLE_PREFERENCE)?;
         db.open_table(TABLE_SCHEMA_VERSION)?;
+        db.open_table(TABLE_ARTIST)?;
+        db.open_table(TABLE_ARTIST_BY_NAME)?;
+        db.open_multimap_table(TABLE_ARTIST_MUSIC)?;
+        db.open_table(TABLE_ALBUM)?;
+        db.open_table(TABLE_ALBUM_BY_KEY)?;
+        db.open_multimap_table(TABLE_ALBUM_MUSIC)?;
+        db.open_table(TABLE_GENRE)?;
+        db.open_table(TABLE_GENRE_BY_NAME)?;
+        db.open_multimap_table(TABLE_GENRE_MUSIC)?;
+        db.open_table(TABLE_MUSIC_FINGERPRINT)?;
+        db.open_multimap_table(TABLE_SEARCH_TERM)?;
+        db.open_table(TABLE_SEARCH_DOCUMENT)?;
+        db.open_table(TABLE_SMART_PLAYLIST)?;
+        db.open_table(TABLE_PLAYLIST_FOLDER)?;
+        db.open_table(TABLE_SYNC_CONFIG)?;
+        db.open_table(TABLE_SYNC_FIELD)?;
+        db.open_table(TABLE_PLAYLIST_SYNC_ID)?;
+        db.open_table(TABLE_PLAY_EVENT)?;
+        db.open_table(TABLE_MUSIC_PLAY_STATS)?;
+        db.open_table(TABLE_PLAYLIST_PLAY_STATS)?;
+        db.open_table(TABLE_SCROBBLE_CONFIG)?;
+        db.open_table(TABLE_SCROBBLE_QUEUE)?;
+        db.open_table(TABLE_MUSIC_FAVORITE)?;
+        db.open_table(TABLE_MUSIC_RATING)?;
+        db.open_multimap_table(TABLE_RATING_MUSIC)?;
+        db.open_multimap_table(TABLE_MUSIC_USER_TAG)?;
+        db.open_multimap_table(TABLE_USER_TAG_MUSIC)?;
+        db.open_table(TABLE_PLAY_QUEUE)?;
+        db.open_table(TABLE_PLAY_QUEUE_CURSOR)?;
+        db.open_table(TABLE_BLOB_REF)?;
         db.commit()?;
         Ok(())
     }
@@ -0,0 +1,155 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use ease_client_schema::{
+    DbKeyAlloc, MusicId, PlayEventKey, PlayEventKind, PlayEventModel, PlayStatsModel, PlaylistId,
+    TABLE_MUSIC, TABLE_MUSIC_PLAY_STATS, TABLE_PLAYLIST, TABLE_PLAYLIST_PLAY_STATS,
+    TABLE_PLAY_EVENT,
+};
+use redb::ReadableTable;
+
+use crate::error::{BError, BResult};
+
+use super::core::DatabaseServer;
+
+/// Returns the listening time of the play ended by the event, if any.
+fn apply_play_event(stats: &mut PlayStatsModel, time: i64, event: &PlayEventModel) -> Option<u64> {
+    stats.last_played = stats.last_played.max(time);
+    match event.kind {
+        PlayEventKind::Started => {
+            // A play left without an end event is not counted.
+            stats.started_at = Some(event.position);
+            return None;
+        }
+        PlayEventKind::Completed => stats.play_count += 1,
+        PlayEventKind::Skipped => stats.skip_count += 1,
+    }
+    let started_at = stats.started_at.take().unwrap_or_default();
+    let listened = event.position.saturating_sub(started_at);
+    stats.listened += listened;
+    Some(listened)
+}
+
+impl DatabaseServer {
+    /// Writes a play event and updates the statistics of its music and
+    /// playlist. Returns the listening time of the play ended by the event.
+    pub fn record_play_event(
+        self: &Arc<Self>,
+        time: i64,
+        event: PlayEventModel,
+    ) -> BResult<Option<u64>> {
+        let db = self.db().begin_write()?;
+        let listened = {
+            if db.open_table(TABLE_MUSIC)?.get(event.music)?.is_none() {
+                return Err(BError::MusicNotFound(event.music));
+            }
+            if let Some(playlist) = event.playlist {
+                if db.open_table(TABLE_PLAYLIST)?.get(playlist)?.is_none() {
+                    return Err(BError::PlaylistNotFound(playlist));
+                }
+                let mut table = db.open_table(TABLE_PLAYLIST_PLAY_STATS)?;
+                let mut stats = table.get(playlist)?.map(|v| v.value()).unwrap_or_default();
+                apply_play_event(&mut stats, time, &event);
+                table.insert(playlist, stats)?;
+            }
+
+            let mut table = db.open_table(TABLE_MUSIC_PLAY_STATS)?;
+            let mut stats = table
+                .get(event.music)?
+                .map(|v| v.value())
+                .unwrap_or_default();
+            let listened = apply_play_event(&mut stats, time, &event);
+            table.insert(event.music, stats)?;
+
+            let id = self.alloc_id(&db, DbKeyAlloc::PlayEvent)?;
+            db.open_table(TABLE_PLAY_EVENT)?
+                .insert(PlayEventKey { time, id }, event)?;
+            listened
+        };
+        db.commit()?;
+        Ok(listened)
+    }
+
+    pub fn load_music_play_stats(self: &Arc<Self>, id: MusicId) -> BResult<Option<PlayStatsModel>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_MUSIC_PLAY_STATS)?;
+        let v = table.get(id)?.map(|v| v.value());
+        Ok(v)
+    }
+
+    pub fn load_all_music_play_stats(
+        self: &Arc<Self>,
+    ) -> BResult<HashMap<MusicId, PlayStatsModel>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_MUSIC_PLAY_STATS)?;
+        let mut ret: HashMap<MusicId, PlayStatsModel> = Default::default();
+        for v in table.iter()? {
+            let (id, stats) = v?;
+            ret.insert(id.value(), stats.value());
+        }
+        Ok(ret)
+    }
+
+    pub fn load_playlist_play_stats(
+        self: &Arc<Self>,
+        id: PlaylistId,
+    ) -> BResult<Option<PlayStatsModel>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_PLAYLIST_PLAY_STATS)?;
+        let v = table.get(id)?.map(|v| v.value());
+        Ok(v)
+    }
+
+    /// Loads the play events from `since` to before `until`, oldest first.
+    /// Nothing is loaded when `until` is not after `since`.
+    pub fn load_play_events(
+        self: &Arc<Self>,
+        since: i64,
+        until: i64,
+    ) -> BResult<Vec<(i64, PlayEventModel)>> {
+        if since >= until {
+            return Ok(Default::default());
+        }
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_PLAY_EVENT)?;
+        let mut ret: Vec<(i64, PlayEventModel)> = Default::default();
+        let range = PlayEventKey {
+            time: since,
+            id: i64::MIN,
+        }..PlayEventKey {
+            time: until,
+            id: i64::MIN,
+        };
+        for v in table.range(range)? {
+            let (key, event) = v?;
+            ret.push((key.value().time, event.value()));
+        }
+        Ok(ret)
+    }
+
+    /// Latest musics started, at most `limit`, with the time they were last
+    /// started. Musics no longer in the library are left out.
+    pub fn load_recently_played(self: &Arc<Self>, limit: usize) -> BResult<Vec<(MusicId, i64)>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_PLAY_EVENT)?;
+        let table_music = db.open_table(TABLE_MUSIC)?;
+        let mut seen: HashSet<MusicId> = Default::default();
+        let mut ret: Vec<(MusicId, i64)> = Default::default();
+        for v in table.iter()?.rev() {
+            if ret.len() >= limit {
+                break;
+            }
+            let (key, event) = v?;
+            let event = event.value();
+            if event.kind != PlayEventKind::Started || !seen.insert(event.music) {
+                continue;
+            }
+            if table_music.get(event.music)?.is_some() {
+                ret.push((event.music, key.value().time));
+            }
+        }
+        Ok(ret)
+    }
+}
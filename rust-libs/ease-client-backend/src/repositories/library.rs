@@ -0,0 +1,466 @@
+use std::{collections::HashSet, sync::Arc};
+
+use redb::{ReadTransaction, ReadableMultimapTable, ReadableTable, WriteTransaction};
+
+use crate::error::BResult;
+
+use super::core::DatabaseServer;
+use ease_client_schema::{
+    AlbumId, AlbumKey, AlbumModel, ArtistId, ArtistModel, DbKeyAlloc, GenreId, GenreModel, MusicId,
+    MusicModel, TABLE_ALBUM, TABLE_ALBUM_BY_KEY, TABLE_ALBUM_MUSIC, TABLE_ARTIST,
+    TABLE_ARTIST_BY_NAME, TABLE_ARTIST_MUSIC, TABLE_GENRE, TABLE_GENRE_BY_NAME, TABLE_GENRE_MUSIC,
+    TABLE_MUSIC,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct ArgDBUpdateMusicTags {
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub year: Option<u32>,
+    pub genres: Vec<String>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+}
+
+fn library_name_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+fn sort_library_musics(musics: &mut [MusicModel]) {
+    musics.sort_by(|lhs, rhs| {
+        (lhs.disc_number, lhs.track_number, &lhs.title, lhs.id).cmp(&(
+            rhs.disc_number,
+            rhs.track_number,
+            &rhs.title,
+            rhs.id,
+        ))
+    });
+}
+
+impl DatabaseServer {
+    pub fn load_artists(self: &Arc<Self>) -> BResult<Vec<(ArtistModel, u64)>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_ARTIST)?;
+        let table_am = db.open_multimap_table(TABLE_ARTIST_MUSIC)?;
+
+        let mut ret: Vec<(ArtistModel, u64)> = Default::default();
+        for v in table.iter()? {
+            let v = v?.1.value();
+            let count = table_am.get(v.id)?.len();
+            ret.push((v, count));
+        }
+        ret.sort_by_key(|v| library_name_key(&v.0.name));
+        Ok(ret)
+    }
+
+    pub fn load_artist(self: &Arc<Self>, id: ArtistId) -> BResult<Option<ArtistModel>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_ARTIST)?;
+        let v = table.get(id)?.map(|v| v.value());
+        Ok(v)
+    }
+
+    pub fn load_albums(self: &Arc<Self>) -> BResult<Vec<(AlbumModel, u64)>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_ALBUM)?;
+        let table_am = db.open_multimap_table(TABLE_ALBUM_MUSIC)?;
+
+        let mut ret: Vec<(AlbumModel, u64)> = Default::default();
+        for v in table.iter()? {
+            let v = v?.1.value();
+            let count = table_am.get(v.id)?.len();
+            ret.push((v, count));
+        }
+        ret.sort_by_key(|v| library_name_key(&v.0.title));
+        Ok(ret)
+    }
+
+    pub fn load_album(self: &Arc<Self>, id: AlbumId) -> BResult<Option<AlbumModel>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_ALBUM)?;
+        let v = table.get(id)?.map(|v| v.value());
+        Ok(v)
+    }
+
+    pub fn load_albums_by_artist(self: &Arc<Self>, id: ArtistId) -> BResult<Vec<AlbumModel>> {
+        let mut ret: Vec<AlbumModel> = self
+            .load_albums()?
+            .into_iter()
+            .map(|(album, _)| album)
+            .filter(|album| album.artist == Some(id))
+            .collect();
+        ret.sort_by_key(|album| (album.year, library_name_key(&album.title)));
+        Ok(ret)
+    }
+
+    pub fn load_genres(self: &Arc<Self>) -> BResult<Vec<(GenreModel, u64)>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_GENRE)?;
+        let table_gm = db.open_multimap_table(TABLE_GENRE_MUSIC)?;
+
+        let mut ret: Vec<(GenreModel, u64)> = Default::default();
+        for v in table.iter()? {
+            let v = v?.1.value();
+            let count = table_gm.get(v.id)?.len();
+            ret.push((v, count));
+        }
+        ret.sort_by_key(|v| library_name_key(&v.0.name));
+        Ok(ret)
+    }
+
+    pub fn load_genre(self: &Arc<Self>, id: GenreId) -> BResult<Option<(GenreModel, u64)>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_GENRE)?;
+        let Some(model) = table.get(id)?.map(|v| v.value()) else {
+            return Ok(None);
+        };
+        let count = db.open_multimap_table(TABLE_GENRE_MUSIC)?.get(id)?.len();
+        Ok(Some((model, count)))
+    }
+
+    pub fn load_musics_by_artist(self: &Arc<Self>, id: ArtistId) -> BResult<Vec<MusicModel>> {
+        let db = self.db().begin_read()?;
+        let ids = {
+            let table = db.open_multimap_table(TABLE_ARTIST_MUSIC)?;
+            let mut ids: Vec<MusicId> = Default::default();
+            for v in table.get(id)? {
+                ids.push(v?.value());
+            }
+            ids
+        };
+        let mut ret = self.load_musics_by_ids_impl(&db, ids)?;
+        sort_library_musics(&mut ret);
+        Ok(ret)
+    }
+
+    pub fn load_musics_by_album(self: &Arc<Self>, id: AlbumId) -> BResult<Vec<MusicModel>> {
+        let db = self.db().begin_read()?;
+        let ids = {
+            let table = db.open_multimap_table(TABLE_ALBUM_MUSIC)?;
+            let mut ids: Vec<MusicId> = Default::default();
+            for v in table.get(id)? {
+                ids.push(v?.value());
+            }
+            ids
+        };
+        let mut ret = self.load_musics_by_ids_impl(&db, ids)?;
+        sort_library_musics(&mut ret);
+        Ok(ret)
+    }
+
+    pub fn load_musics_by_genre(self: &Arc<Self>, id: GenreId) -> BResult<Vec<MusicModel>> {
+        let db = self.db().begin_read()?;
+        let ids = {
+            let table = db.open_multimap_table(TABLE_GENRE_MUSIC)?;
+            let mut ids: Vec<MusicId> = Default::default();
+            for v in table.get(id)? {
+                ids.push(v?.value());
+            }
+            ids
+        };
+        let mut ret = self.load_musics_by_ids_impl(&db, ids)?;
+        sort_library_musics(&mut ret);
+        Ok(ret)
+    }
+
+    pub fn load_musics_by_ids_impl(
+        self: &Arc<Self>,
+        db: &ReadTransaction,
+        ids: Vec<MusicId>,
+    ) -> BResult<Vec<MusicModel>> {
+        let table = db.open_table(TABLE_MUSIC)?;
+        let mut ret: Vec<MusicModel> = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(m) = table.get(id)?.map(|v| v.value()) {
+                ret.push(m);
+            }
+        }
+        Ok(ret)
+    }
+
+    pub fn update_music_tags(
+        self: &Arc<Self>,
+        id: MusicId,
+        arg: ArgDBUpdateMusicTags,
+    ) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let m = db.open_table(TABLE_MUSIC)?.get(id)?.map(|v| v.value());
+            if let Some(m) = m {
+                self.update_music_tags_impl(&db, m, arg)?;
+            }
+        }
+        db.commit()?;
+        Ok(())
+    }
+
+    /// Links a music to the artists, album and genres of its tags, unless it
+    /// is already linked to some, as set by the user. Returns whether it was
+    /// linked.
+    pub fn link_unlinked_music_tags(
+        self: &Arc<Self>,
+        id: MusicId,
+        arg: ArgDBUpdateMusicTags,
+    ) -> BResult<bool> {
+        let db = self.db().begin_write()?;
+        let linked = {
+            let m = db.open_table(TABLE_MUSIC)?.get(id)?.map(|v| v.value());
+            match m {
+                Some(m) if m.artists.is_empty() && m.album.is_none() && m.genres.is_empty() => {
+                    self.update_music_tags_impl(&db, m, arg)?;
+                    true
+                }
+                _ => false,
+            }
+        };
+        db.commit()?;
+        Ok(linked)
+    }
+
+    fn update_music_tags_impl(
+        self: &Arc<Self>,
+        db: &WriteTransaction,
+        mut m: MusicModel,
+        arg: ArgDBUpdateMusicTags,
+    ) -> BResult<()> {
+        self.unlink_music_library_impl(db, &m)?;
+
+        let mut artists: Vec<ArtistId> = Default::default();
+        for name in arg.artists.iter() {
+            if let Some(artist) = self.ensure_artist_impl(db, name)? {
+                if !artists.contains(&artist) {
+                    artists.push(artist);
+                }
+            }
+        }
+
+        let album = match arg.album.as_deref() {
+            Some(title) => {
+                let album_artist = match arg.album_artist.as_deref() {
+                    Some(name) => self.ensure_artist_impl(db, name)?,
+                    None => artists.first().copied(),
+                };
+                self.ensure_album_impl(db, title, album_artist, arg.year)?
+            }
+            None => None,
+        };
+
+        let mut genres: Vec<GenreId> = Default::default();
+        for name in arg.genres.iter() {
+            if let Some(genre) = self.ensure_genre_impl(db, name)? {
+                if !genres.contains(&genre) {
+                    genres.push(genre);
+                }
+            }
+        }
+
+        m.artists = artists;
+        m.album = album;
+        m.genres = genres;
+        m.track_number = arg.track_number;
+        m.disc_number = arg.disc_number;
+        self.link_music_library_impl(db, &m)?;
+        self.index_music_impl(db, &m)?;
+
+        db.open_table(TABLE_MUSIC)?.insert(m.id, m)?;
+        Ok(())
+    }
+
+    fn ensure_artist_impl(
+        self: &Arc<Self>,
+        db: &WriteTransaction,
+        name: &str,
+    ) -> BResult<Option<ArtistId>> {
+        let key = library_name_key(name);
+        if key.is_empty() {
+            return Ok(None);
+        }
+
+        let existed = db
+            .open_table(TABLE_ARTIST_BY_NAME)?
+            .get(key.clone())?
+            .map(|v| v.value());
+        if let Some(id) = existed {
+            return Ok(Some(id));
+        }
+
+        let id = ArtistId::wrap(self.alloc_id(db, DbKeyAlloc::Artist)?);
+        db.open_table(TABLE_ARTIST)?.insert(
+            id,
+            ArtistModel {
+                id,
+                name: name.trim().to_string(),
+            },
+        )?;
+        db.open_table(TABLE_ARTIST_BY_NAME)?.insert(key, id)?;
+        Ok(Some(id))
+    }
+
+    fn ensure_album_impl(
+        self: &Arc<Self>,
+        db: &WriteTransaction,
+        title: &str,
+        artist: Option<ArtistId>,
+        year: Option<u32>,
+    ) -> BResult<Option<AlbumId>> {
+        let key = AlbumKey {
+            title: library_name_key(title),
+            artist,
+        };
+        if key.title.is_empty() {
+            return Ok(None);
+        }
+
+        let existed = db
+            .open_table(TABLE_ALBUM_BY_KEY)?
+            .get(key.clone())?
+            .map(|v| v.value());
+        if let Some(id) = existed {
+            if year.is_some() {
+                let mut table = db.open_table(TABLE_ALBUM)?;
+                let album = table.get(id)?.map(|v| v.value());
+                if let Some(mut album) = album {
+                    if album.year.is_none() {
+                        album.year = year;
+                        table.insert(id, album)?;
+                    }
+                }
+            }
+            return Ok(Some(id));
+        }
+
+        let id = AlbumId::wrap(self.alloc_id(db, DbKeyAlloc::Album)?);
+        db.open_table(TABLE_ALBUM)?.insert(
+            id,
+            AlbumModel {
+                id,
+                title: title.trim().to_string(),
+                artist,
+                year,
+            },
+        )?;
+        db.open_table(TABLE_ALBUM_BY_KEY)?.insert(key, id)?;
+        Ok(Some(id))
+    }
+
+    fn ensure_genre_impl(
+        self: &Arc<Self>,
+        db: &WriteTransaction,
+        name: &str,
+    ) -> BResult<Option<GenreId>> {
+        let key = library_name_key(name);
+        if key.is_empty() {
+            return Ok(None);
+        }
+
+        let existed = db
+            .open_table(TABLE_GENRE_BY_NAME)?
+            .get(key.clone())?
+            .map(|v| v.value());
+        if let Some(id) = existed {
+            return Ok(Some(id));
+        }
+
+        let id = GenreId::wrap(self.alloc_id(db, DbKeyAlloc::Genre)?);
+        db.open_table(TABLE_GENRE)?.insert(
+            id,
+            GenreModel {
+                id,
+                name: name.trim().to_string(),
+            },
+        )?;
+        db.open_table(TABLE_GENRE_BY_NAME)?.insert(key, id)?;
+        Ok(Some(id))
+    }
+
+    fn link_music_library_impl(
+        self: &Arc<Self>,
+        db: &WriteTransaction,
+        m: &MusicModel,
+    ) -> BResult<()> {
+        let mut table_artist_music = db.open_multimap_table(TABLE_ARTIST_MUSIC)?;
+        let mut table_album_music = db.open_multimap_table(TABLE_ALBUM_MUSIC)?;
+        let mut table_genre_music = db.open_multimap_table(TABLE_GENRE_MUSIC)?;
+        for artist in m.artists.iter() {
+            table_artist_music.insert(artist, m.id)?;
+        }
+        if let Some(album) = m.album {
+            table_album_music.insert(album, m.id)?;
+        }
+        for genre in m.genres.iter() {
+            table_genre_music.insert(genre, m.id)?;
+        }
+        Ok(())
+    }
+
+    /// Drops the music from the artist/album/genre indices, and removes the
+    /// entities that are no longer referenced by any music.
+    pub fn unlink_music_library_impl(
+        self: &Arc<Self>,
+        db: &WriteTransaction,
+        m: &MusicModel,
+    ) -> BResult<()> {
+        let mut table_artist_music = db.open_multimap_table(TABLE_ARTIST_MUSIC)?;
+        let mut table_album_music = db.open_multimap_table(TABLE_ALBUM_MUSIC)?;
+        let mut table_genre_music = db.open_multimap_table(TABLE_GENRE_MUSIC)?;
+        let mut table_artist = db.open_table(TABLE_ARTIST)?;
+        let mut table_artist_by_name = db.open_table(TABLE_ARTIST_BY_NAME)?;
+        let mut table_album = db.open_table(TABLE_ALBUM)?;
+        let mut table_album_by_key = db.open_table(TABLE_ALBUM_BY_KEY)?;
+        let mut table_genre = db.open_table(TABLE_GENRE)?;
+        let mut table_genre_by_name = db.open_table(TABLE_GENRE_BY_NAME)?;
+
+        let mut artists = m.artists.clone();
+        if let Some(album) = m.album {
+            table_album_music.remove(album, m.id)?;
+            if table_album_music.get(album)?.is_empty() {
+                let model = table_album.remove(album)?.map(|v| v.value());
+                if let Some(model) = model {
+                    table_album_by_key.remove(AlbumKey {
+                        title: library_name_key(&model.title),
+                        artist: model.artist,
+                    })?;
+                    artists.extend(model.artist);
+                }
+            }
+        }
+
+        for artist in m.artists.iter().copied() {
+            table_artist_music.remove(artist, m.id)?;
+        }
+        // Artists of the albums are collected once, and only when an artist
+        // lost its last music.
+        let mut album_artists: Option<HashSet<ArtistId>> = None;
+        for artist in artists {
+            if !table_artist_music.get(artist)?.is_empty() {
+                continue;
+            }
+            if album_artists.is_none() {
+                let mut set: HashSet<ArtistId> = Default::default();
+                for v in table_album.iter()? {
+                    set.extend(v?.1.value().artist);
+                }
+                album_artists = Some(set);
+            }
+            if album_artists.as_ref().is_some_and(|v| v.contains(&artist)) {
+                continue;
+            }
+            let model = table_artist.remove(artist)?.map(|v| v.value());
+            if let Some(model) = model {
+                table_artist_by_name.remove(library_name_key(&model.name))?;
+            }
+        }
+
+        for genre in m.genres.iter().copied() {
+            table_genre_music.remove(genre, m.id)?;
+            if table_genre_music.get(genre)?.is_empty() {
+                let model = table_genre.remove(genre)?.map(|v| v.value());
+                if let Some(model) = model {
+                    table_genre_by_name.remove(library_name_key(&model.name))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
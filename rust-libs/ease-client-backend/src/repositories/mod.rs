@@ -1,7 +1,14 @@
 pub mod app;
 pub mod blob;
 pub mod core;
+pub mod history;
+pub mod library;
 pub mod music;
 pub mod playlist;
 pub mod preference;
+pub mod queue;
+pub mod scrobble;
+pub mod search;
 pub mod storage;
+pub mod sync;
+pub mod user_data;
@@ -1,14 +1,19 @@
 use std::{sync::Arc, time::Duration};
 
 use ease_order_key::OrderKey;
-use redb::{ReadTransaction, ReadableMultimapTable, ReadableTable, WriteTransaction};
+use redb::{
+    ReadTransaction, ReadableMultimapTable, ReadableTable, ReadableTableMetadata, WriteTransaction,
+};
 
-use crate::error::BResult;
+use crate::error::{BError, BResult};
 
 use super::core::DatabaseServer;
 use ease_client_schema::{
-    BinSerde, BlobId, DbKeyAlloc, MusicId, MusicModel, PlaylistId, StorageEntryLoc, TABLE_MUSIC,
-    TABLE_MUSIC_BY_LOC, TABLE_PLAYLIST_MUSIC, TABLE_STORAGE_MUSIC,
+    AudioStreamInfo, BinSerde, BlobId, DbKeyAlloc, MusicFileStamp, MusicFileState,
+    MusicFingerprintModel, MusicId, MusicModel, PlaylistFolderModel, PlaylistId, ReplayGainModel,
+    SearchField, StorageEntryLoc, TABLE_BLOB_REF, TABLE_MUSIC, TABLE_MUSIC_BY_LOC,
+    TABLE_MUSIC_FINGERPRINT, TABLE_MUSIC_PLAY_STATS, TABLE_PLAYLIST_FOLDER, TABLE_PLAYLIST_MUSIC,
+    TABLE_STORAGE_MUSIC,
 };
 
 #[derive(Debug, Clone, uniffi::Record)]
@@ -23,6 +28,21 @@ pub struct ArgDBAddMusic {
     pub title: String,
 }
 
+#[derive(Debug)]
+pub struct ArgDBUpdateMusicFile {
+    pub id: MusicId,
+    pub stamp: Option<MusicFileStamp>,
+    pub state: MusicFileState,
+}
+
+#[derive(Debug)]
+pub struct ArgDBRemapMusic {
+    pub id: MusicId,
+    pub loc: StorageEntryLoc,
+    pub lyric: Option<StorageEntryLoc>,
+    pub stamp: Option<MusicFileStamp>,
+}
+
 impl DatabaseServer {
     pub fn load_musics_by_playlist_id(
         self: &Arc<Self>,
@@ -47,6 +67,16 @@ impl DatabaseServer {
         Ok(ret)
     }
 
+    pub fn load_all_musics(self: &Arc<Self>) -> BResult<Vec<MusicModel>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_MUSIC)?;
+        let mut ret: Vec<MusicModel> = Vec::with_capacity(table.len()? as usize);
+        for item in table.iter()? {
+            ret.push(item?.1.value());
+        }
+        Ok(ret)
+    }
+
     pub fn load_music(self: &Arc<Self>, id: MusicId) -> BResult<Option<MusicModel>> {
         let db = self.db().begin_read()?;
         self.load_music_impl(&db, id)
@@ -63,6 +93,14 @@ impl DatabaseServer {
         Ok(model)
     }
 
+    pub fn load_music_by_loc(
+        self: &Arc<Self>,
+        loc: StorageEntryLoc,
+    ) -> BResult<Option<MusicModel>> {
+        let db = self.db().begin_read()?;
+        self.load_music_by_key_impl(&db, loc)
+    }
+
     fn load_music_by_key_impl(
         self: &Arc<Self>,
         db: &ReadTransaction,
@@ -97,21 +135,36 @@ impl DatabaseServer {
         let mut table_music = db.open_table(TABLE_MUSIC)?;
         let mut table_music_by_loc = db.open_table(TABLE_MUSIC_BY_LOC)?;
         let mut table_storage_music = db.open_multimap_table(TABLE_STORAGE_MUSIC)?;
-        table_music.insert(
+        let music = MusicModel {
             id,
-            MusicModel {
-                id,
-                loc: arg.loc.clone(),
-                title: arg.title,
-                duration: None,
-                cover: None,
-                lyric: None,
-                lyric_default: true,
-                order: order.into_raw(),
-            },
-        )?;
+            loc: arg.loc.clone(),
+            title: arg.title,
+            duration: None,
+            cover: None,
+            cover_probed: false,
+            lyric: None,
+            lyric_default: true,
+            order: order.into_raw(),
+            artists: Default::default(),
+            album: None,
+            genres: Default::default(),
+            track_number: None,
+            disc_number: None,
+            stream_info: None,
+            stream_probed: false,
+            replay_gain: None,
+            loudness_analyzed: false,
+            added_time: std::time::UNIX_EPOCH
+                .elapsed()
+                .unwrap_or_default()
+                .as_millis() as i64,
+            file_stamp: None,
+            file_state: Default::default(),
+        };
+        table_music.insert(id, &music)?;
         table_storage_music.insert(arg.loc.storage_id, id)?;
         table_music_by_loc.insert(arg.loc, id)?;
+        self.index_music_impl(db, &music)?;
 
         Ok((id, false))
     }
@@ -153,19 +206,113 @@ impl DatabaseServer {
     }
 
     pub fn update_music_cover(self: &Arc<Self>, id: MusicId, cover: Vec<u8>) -> BResult<()> {
+        self.update_musics_cover(&[id], cover)
+    }
+
+    /// Sets one cover to all the musics, written once to a blob they share.
+    pub fn update_musics_cover(self: &Arc<Self>, ids: &[MusicId], cover: Vec<u8>) -> BResult<()> {
+        let mut to_remove_blobs: Vec<BlobId> = Default::default();
+        let db = self.db().begin_write()?;
+        {
+            let mut table_music = db.open_table(TABLE_MUSIC)?;
+            let mut musics: Vec<MusicModel> = Default::default();
+            for id in ids {
+                if let Some(m) = table_music.get(*id)?.map(|v| v.value()) {
+                    musics.push(m);
+                }
+            }
+            if musics.is_empty() {
+                return Ok(());
+            }
+
+            let cover_id = self.blob().write(cover)?;
+            self.share_blob_impl(&db, cover_id, musics.len() as u32)?;
+            for mut m in musics {
+                if let Some(id) = m.cover {
+                    if self.release_blob_impl(&db, id)? {
+                        to_remove_blobs.push(id);
+                    }
+                }
+                m.cover = Some(cover_id);
+                table_music.insert(m.id, m)?;
+            }
+        }
+        db.commit()?;
+
+        for id in to_remove_blobs {
+            self.blob().remove(id)?;
+        }
+        Ok(())
+    }
+
+    pub fn update_music_probed_cover(
+        self: &Arc<Self>,
+        id: MusicId,
+        cover: Option<Vec<u8>>,
+    ) -> BResult<()> {
         let db = self.db().begin_write()?;
         {
             let mut table_music = db.open_table(TABLE_MUSIC)?;
             let m = table_music.get(id)?.map(|v| v.value());
 
             if let Some(mut m) = m {
-                if let Some(id) = m.cover {
-                    self.blob().remove(id)?;
+                if let Some(cover) = cover {
+                    if let Some(id) = m.cover {
+                        if self.release_blob_impl(&db, id)? {
+                            self.blob().remove(id)?;
+                        }
+                    }
+                    m.cover = Some(self.blob().write(cover)?);
                 }
+                m.cover_probed = true;
+                table_music.insert(id, m)?;
+            }
+        }
+        db.commit()?;
 
-                let cover_id = self.blob().write(cover)?;
+        Ok(())
+    }
 
-                m.cover = Some(cover_id);
+    pub fn update_music_stream_info(
+        self: &Arc<Self>,
+        id: MusicId,
+        duration: Option<Duration>,
+        stream_info: Option<AudioStreamInfo>,
+    ) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let mut table_music = db.open_table(TABLE_MUSIC)?;
+            let m = table_music.get(id)?.map(|v| v.value());
+
+            if let Some(mut m) = m {
+                if duration.is_some() {
+                    m.duration = duration;
+                }
+                if stream_info.is_some() {
+                    m.stream_info = stream_info;
+                }
+                m.stream_probed = true;
+                table_music.insert(id, m)?;
+            }
+        }
+        db.commit()?;
+
+        Ok(())
+    }
+
+    pub fn update_music_replay_gain(
+        self: &Arc<Self>,
+        id: MusicId,
+        replay_gain: Option<ReplayGainModel>,
+    ) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let mut table_music = db.open_table(TABLE_MUSIC)?;
+            let m = table_music.get(id)?.map(|v| v.value());
+
+            if let Some(mut m) = m {
+                m.replay_gain = replay_gain;
+                m.loudness_analyzed = true;
                 table_music.insert(id, m)?;
             }
         }
@@ -174,6 +321,73 @@ impl DatabaseServer {
         Ok(())
     }
 
+    pub fn update_musics_album_gain(
+        self: &Arc<Self>,
+        ids: Vec<MusicId>,
+        album_gain: f64,
+        album_peak: Option<f64>,
+    ) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let mut table_music = db.open_table(TABLE_MUSIC)?;
+            for id in ids {
+                let m = table_music.get(id)?.map(|v| v.value());
+                let Some(mut m) = m else {
+                    continue;
+                };
+                if let Some(replay_gain) = m.replay_gain.as_mut() {
+                    replay_gain.album_gain = Some(album_gain);
+                    replay_gain.album_peak = album_peak;
+                    table_music.insert(id, m)?;
+                }
+            }
+        }
+        db.commit()?;
+
+        Ok(())
+    }
+
+    pub fn load_music_fingerprint(
+        self: &Arc<Self>,
+        id: MusicId,
+    ) -> BResult<Option<MusicFingerprintModel>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_MUSIC_FINGERPRINT)?;
+        let ret = table.get(id)?.map(|v| v.value());
+        Ok(ret)
+    }
+
+    pub fn load_music_fingerprints(
+        self: &Arc<Self>,
+    ) -> BResult<Vec<(MusicId, MusicFingerprintModel)>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_MUSIC_FINGERPRINT)?;
+        let mut ret = Vec::new();
+        for item in table.iter()? {
+            let (id, fingerprint) = item?;
+            ret.push((id.value(), fingerprint.value()));
+        }
+        Ok(ret)
+    }
+
+    pub fn update_music_fingerprint(
+        self: &Arc<Self>,
+        id: MusicId,
+        fingerprint: MusicFingerprintModel,
+    ) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let table_music = db.open_table(TABLE_MUSIC)?;
+            if table_music.get(id)?.is_some() {
+                let mut table = db.open_table(TABLE_MUSIC_FINGERPRINT)?;
+                table.insert(id, fingerprint)?;
+            }
+        }
+        db.commit()?;
+
+        Ok(())
+    }
+
     pub fn update_music_lyric(
         self: &Arc<Self>,
         id: MusicId,
@@ -188,6 +402,136 @@ impl DatabaseServer {
                 m.lyric = loc;
                 m.lyric_default = false;
                 table_music.insert(id, m)?;
+                self.update_music_search_impl(&db, id, &[SearchField::Lyric], vec![])?;
+            }
+        }
+        db.commit()?;
+
+        Ok(())
+    }
+
+    /// Records the file stamps and states found by a scan. Musics marked as
+    /// changed are probed, analyzed and fingerprinted again.
+    pub fn update_music_files(self: &Arc<Self>, files: Vec<ArgDBUpdateMusicFile>) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let mut table_music = db.open_table(TABLE_MUSIC)?;
+            let mut table_fingerprint = db.open_table(TABLE_MUSIC_FINGERPRINT)?;
+            for file in files {
+                let m = table_music.get(file.id)?.map(|v| v.value());
+                let Some(mut m) = m else {
+                    continue;
+                };
+                if file.state == MusicFileState::Changed {
+                    m.cover_probed = false;
+                    m.stream_probed = false;
+                    m.loudness_analyzed = false;
+                    table_fingerprint.remove(file.id)?;
+                }
+                m.file_stamp = file.stamp;
+                m.file_state = file.state;
+                table_music.insert(file.id, m)?;
+            }
+        }
+        db.commit()?;
+
+        Ok(())
+    }
+
+    /// Points a music at another storage entry, keeping its id and so its
+    /// playlists, order and library links. A title still equal to the old
+    /// file name follows the new one.
+    pub fn relink_music(
+        self: &Arc<Self>,
+        id: MusicId,
+        loc: StorageEntryLoc,
+        name: String,
+        stamp: Option<MusicFileStamp>,
+    ) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let mut table_music = db.open_table(TABLE_MUSIC)?;
+            let mut table_music_by_loc = db.open_table(TABLE_MUSIC_BY_LOC)?;
+            let mut table_storage_music = db.open_multimap_table(TABLE_STORAGE_MUSIC)?;
+            let Some(mut m) = table_music.get(id)?.map(|v| v.value()) else {
+                return Err(BError::MusicNotFound(id));
+            };
+            if let Some(other) = table_music_by_loc.get(loc.clone())?.map(|v| v.value()) {
+                if other != id {
+                    return Err(BError::MusicLocInUse(other));
+                }
+            }
+
+            table_music_by_loc.remove(m.loc.clone())?;
+            table_storage_music.remove(m.loc.storage_id, id)?;
+            table_music_by_loc.insert(loc.clone(), id)?;
+            table_storage_music.insert(loc.storage_id, id)?;
+
+            let old_name = m.loc.path.rsplit('/').next().unwrap_or_default();
+            let mut texts = vec![(SearchField::Path, loc.path.clone())];
+            if m.title == old_name {
+                m.title = name;
+                texts.push((SearchField::Title, m.title.clone()));
+            }
+            m.loc = loc;
+            m.file_stamp = stamp;
+            m.file_state = MusicFileState::Present;
+            table_music.insert(id, m)?;
+            let fields: Vec<SearchField> = texts.iter().map(|(field, _)| *field).collect();
+            self.update_music_search_impl(&db, id, &fields, texts)?;
+        }
+        db.commit()?;
+
+        Ok(())
+    }
+
+    /// Moves musics and folder bindings to other storage entries in one
+    /// transaction. Nothing is written if a target entry is linked to a music
+    /// that is not moved away.
+    pub fn remap_musics(
+        self: &Arc<Self>,
+        musics: Vec<ArgDBRemapMusic>,
+        folders: Vec<(PlaylistId, PlaylistFolderModel)>,
+    ) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let mut table_music = db.open_table(TABLE_MUSIC)?;
+            let mut table_music_by_loc = db.open_table(TABLE_MUSIC_BY_LOC)?;
+            let mut table_storage_music = db.open_multimap_table(TABLE_STORAGE_MUSIC)?;
+            let mut models: Vec<MusicModel> = Vec::with_capacity(musics.len());
+            for arg in musics.iter() {
+                let Some(m) = table_music.get(arg.id)?.map(|v| v.value()) else {
+                    return Err(BError::MusicNotFound(arg.id));
+                };
+                table_music_by_loc.remove(m.loc.clone())?;
+                table_storage_music.remove(m.loc.storage_id, m.id)?;
+                models.push(m);
+            }
+
+            for (arg, mut m) in musics.into_iter().zip(models) {
+                if let Some(other) = table_music_by_loc.get(arg.loc.clone())?.map(|v| v.value()) {
+                    return Err(BError::MusicLocInUse(other));
+                }
+                table_music_by_loc.insert(arg.loc.clone(), m.id)?;
+                table_storage_music.insert(arg.loc.storage_id, m.id)?;
+                m.loc = arg.loc;
+                m.lyric = arg.lyric;
+                if arg.stamp.is_some() {
+                    m.file_state = MusicFileState::Present;
+                }
+                m.file_stamp = arg.stamp;
+                self.update_music_search_impl(
+                    &db,
+                    m.id,
+                    &[SearchField::Path],
+                    vec![(SearchField::Path, m.loc.path.clone())],
+                )?;
+                table_music.insert(m.id, m)?;
+            }
+
+            let mut table_folder = db.open_table(TABLE_PLAYLIST_FOLDER)?;
+            for (id, folder) in folders {
+                table_folder.insert(id, folder)?;
             }
         }
         db.commit()?;
@@ -207,6 +551,7 @@ impl DatabaseServer {
 
         if ref_playlists == 0 {
             let m = self.load_music_impl(rdb, id)?.unwrap();
+            self.unlink_music_library_impl(db, &m)?;
 
             let mut table_loc = db.open_table(TABLE_MUSIC_BY_LOC)?;
             let mut table_storage = db.open_multimap_table(TABLE_STORAGE_MUSIC)?;
@@ -214,10 +559,43 @@ impl DatabaseServer {
             table_storage.remove(m.loc.storage_id, m.id)?;
             table_loc.remove(m.loc)?;
             table_m.remove(m.id)?;
+            db.open_table(TABLE_MUSIC_FINGERPRINT)?.remove(m.id)?;
+            db.open_table(TABLE_MUSIC_PLAY_STATS)?.remove(m.id)?;
+            self.remove_music_user_data_impl(db, m.id)?;
+            self.remove_music_search_impl(db, m.id)?;
             if let Some(id) = m.cover {
-                to_remove_blobs.push(id);
+                if self.release_blob_impl(db, id)? {
+                    to_remove_blobs.push(id);
+                }
             }
         }
         Ok(())
     }
+
+    /// Records that a blob is referenced `refs` times. A blob without a record
+    /// has a single reference.
+    fn share_blob_impl(
+        self: &Arc<Self>,
+        db: &WriteTransaction,
+        id: BlobId,
+        refs: u32,
+    ) -> BResult<()> {
+        if refs > 1 {
+            db.open_table(TABLE_BLOB_REF)?.insert(id, refs)?;
+        }
+        Ok(())
+    }
+
+    /// Drops a reference to a blob, and returns whether it was the last one so
+    /// the blob can be removed.
+    pub fn release_blob_impl(self: &Arc<Self>, db: &WriteTransaction, id: BlobId) -> BResult<bool> {
+        let mut table = db.open_table(TABLE_BLOB_REF)?;
+        let refs = table.get(id)?.map(|v| v.value()).unwrap_or(1);
+        if refs > 2 {
+            table.insert(id, refs - 1)?;
+        } else {
+            table.remove(id)?;
+        }
+        Ok(refs <= 1)
+    }
 }
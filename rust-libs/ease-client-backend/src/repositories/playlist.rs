@@ -1,4 +1,7 @@
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use ease_order_key::OrderKey;
 use redb::{ReadTransaction, ReadableMultimapTable, ReadableTable, ReadableTableMetadata};
@@ -10,8 +13,9 @@ use super::{
     music::{AddedMusic, ArgDBAddMusic},
 };
 use ease_client_schema::{
-    BlobId, DbKeyAlloc, MusicId, PlaylistId, PlaylistModel, PlaylistMusicModel, StorageEntryLoc,
-    TABLE_MUSIC_PLAYLIST, TABLE_PLAYLIST, TABLE_PLAYLIST_MUSIC,
+    BlobId, DbKeyAlloc, MusicId, PlaylistFolderModel, PlaylistId, PlaylistModel,
+    PlaylistMusicModel, SmartPlaylistModel, StorageEntryLoc, TABLE_MUSIC_PLAYLIST, TABLE_PLAYLIST,
+    TABLE_PLAYLIST_FOLDER, TABLE_PLAYLIST_MUSIC, TABLE_PLAYLIST_PLAY_STATS, TABLE_SMART_PLAYLIST,
 };
 
 impl DatabaseServer {
@@ -49,6 +53,125 @@ impl DatabaseServer {
         Ok(ret)
     }
 
+    pub fn load_smart_playlist(
+        self: &Arc<Self>,
+        id: PlaylistId,
+    ) -> BResult<Option<SmartPlaylistModel>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_SMART_PLAYLIST)?;
+        let p = table.get(id)?.map(|v| v.value());
+        Ok(p)
+    }
+
+    pub fn load_smart_playlists(
+        self: &Arc<Self>,
+    ) -> BResult<HashMap<PlaylistId, SmartPlaylistModel>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_SMART_PLAYLIST)?;
+        let mut ret: HashMap<PlaylistId, SmartPlaylistModel> = Default::default();
+        for v in table.iter()? {
+            let (id, rule) = v?;
+            ret.insert(id.value(), rule.value());
+        }
+        Ok(ret)
+    }
+
+    pub fn create_smart_playlist(
+        self: &Arc<Self>,
+        title: String,
+        picture: Option<StorageEntryLoc>,
+        rule: SmartPlaylistModel,
+        current_time_ms: i64,
+        order: OrderKey,
+    ) -> BResult<PlaylistId> {
+        let db = self.db().begin_write()?;
+        let id = PlaylistId::wrap(self.alloc_id(&db, DbKeyAlloc::Playlist)?);
+        {
+            let mut table = db.open_table(TABLE_PLAYLIST)?;
+            table.insert(
+                id,
+                PlaylistModel {
+                    id,
+                    title,
+                    created_time: current_time_ms,
+                    picture,
+                    order: order.into_raw(),
+                },
+            )?;
+            let mut table = db.open_table(TABLE_SMART_PLAYLIST)?;
+            table.insert(id, rule)?;
+        }
+        db.commit()?;
+        Ok(id)
+    }
+
+    /// Replaces the rule of a smart playlist. Returns false when the playlist
+    /// is not a smart one.
+    pub fn update_smart_playlist(
+        self: &Arc<Self>,
+        id: PlaylistId,
+        rule: SmartPlaylistModel,
+    ) -> BResult<bool> {
+        let db = self.db().begin_write()?;
+        let updated = {
+            let mut table = db.open_table(TABLE_SMART_PLAYLIST)?;
+            let existed = table.get(id)?.is_some();
+            if existed {
+                table.insert(id, rule)?;
+            }
+            existed
+        };
+        db.commit()?;
+        Ok(updated)
+    }
+
+    pub fn load_playlist_folder(
+        self: &Arc<Self>,
+        id: PlaylistId,
+    ) -> BResult<Option<PlaylistFolderModel>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_PLAYLIST_FOLDER)?;
+        let p = table.get(id)?.map(|v| v.value());
+        Ok(p)
+    }
+
+    pub fn load_playlist_folders(
+        self: &Arc<Self>,
+    ) -> BResult<Vec<(PlaylistId, PlaylistFolderModel)>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_PLAYLIST_FOLDER)?;
+        let mut ret: Vec<(PlaylistId, PlaylistFolderModel)> = Default::default();
+        for v in table.iter()? {
+            let (id, folder) = v?;
+            ret.push((id.value(), folder.value()));
+        }
+        Ok(ret)
+    }
+
+    /// Binds a playlist to a folder, or unbinds it when `folder` is `None`.
+    /// Does nothing when the playlist does not exist.
+    pub fn save_playlist_folder(
+        self: &Arc<Self>,
+        id: PlaylistId,
+        folder: Option<PlaylistFolderModel>,
+    ) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let existed = db.open_table(TABLE_PLAYLIST)?.get(id)?.is_some();
+            let mut table = db.open_table(TABLE_PLAYLIST_FOLDER)?;
+            match folder {
+                Some(folder) if existed => {
+                    table.insert(id, folder)?;
+                }
+                _ => {
+                    table.remove(id)?;
+                }
+            }
+        }
+        db.commit()?;
+        Ok(())
+    }
+
     pub fn create_playlist(
         self: &Arc<Self>,
         title: String,
@@ -161,6 +284,10 @@ impl DatabaseServer {
             let mut table_mp = db.open_multimap_table(TABLE_MUSIC_PLAYLIST)?;
 
             table_playlist.remove(playlist_id)?;
+            db.open_table(TABLE_SMART_PLAYLIST)?.remove(playlist_id)?;
+            db.open_table(TABLE_PLAYLIST_FOLDER)?.remove(playlist_id)?;
+            db.open_table(TABLE_PLAYLIST_PLAY_STATS)?
+                .remove(playlist_id)?;
 
             let ids = table_pm.get(playlist_id)?;
             for relation in ids {
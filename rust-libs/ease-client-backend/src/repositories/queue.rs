@@ -0,0 +1,78 @@
+use std::{collections::HashSet, sync::Arc};
+
+use ease_client_schema::{
+    MusicId, PlayQueueCursorModel, PlayQueueModel, TABLE_MUSIC, TABLE_PLAY_QUEUE,
+    TABLE_PLAY_QUEUE_CURSOR,
+};
+use redb::ReadableTable;
+
+use crate::error::BResult;
+
+use super::core::DatabaseServer;
+
+impl DatabaseServer {
+    pub fn load_play_queue(
+        self: &Arc<Self>,
+    ) -> BResult<Option<(PlayQueueModel, PlayQueueCursorModel)>> {
+        let db = self.db().begin_read()?;
+        let Some(queue) = db.open_table(TABLE_PLAY_QUEUE)?.get(())?.map(|v| v.value()) else {
+            return Ok(None);
+        };
+        let cursor = db
+            .open_table(TABLE_PLAY_QUEUE_CURSOR)?
+            .get(())?
+            .map(|v| v.value())
+            .unwrap_or_default();
+        Ok(Some((queue, cursor)))
+    }
+
+    pub fn save_play_queue(
+        self: &Arc<Self>,
+        queue: PlayQueueModel,
+        cursor: PlayQueueCursorModel,
+    ) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            db.open_table(TABLE_PLAY_QUEUE)?.insert((), queue)?;
+            db.open_table(TABLE_PLAY_QUEUE_CURSOR)?.insert((), cursor)?;
+        }
+        db.commit()?;
+        Ok(())
+    }
+
+    /// Saves the cursor alone. Returns false when there is no queue.
+    pub fn save_play_queue_cursor(self: &Arc<Self>, cursor: PlayQueueCursorModel) -> BResult<bool> {
+        let db = self.db().begin_write()?;
+        {
+            if db.open_table(TABLE_PLAY_QUEUE)?.get(())?.is_none() {
+                return Ok(false);
+            }
+            db.open_table(TABLE_PLAY_QUEUE_CURSOR)?.insert((), cursor)?;
+        }
+        db.commit()?;
+        Ok(true)
+    }
+
+    pub fn clear_play_queue(self: &Arc<Self>) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            db.open_table(TABLE_PLAY_QUEUE)?.remove(())?;
+            db.open_table(TABLE_PLAY_QUEUE_CURSOR)?.remove(())?;
+        }
+        db.commit()?;
+        Ok(())
+    }
+
+    /// Ids among `ids` of musics no longer in the library.
+    pub fn load_missing_music_ids(self: &Arc<Self>, ids: &[MusicId]) -> BResult<HashSet<MusicId>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_MUSIC)?;
+        let mut ret: HashSet<MusicId> = Default::default();
+        for id in ids {
+            if table.get(*id)?.is_none() {
+                ret.insert(*id);
+            }
+        }
+        Ok(ret)
+    }
+}
@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use ease_client_schema::{
+    MusicId, ScrobbleConfigModel, ScrobbleKey, ScrobbleModel, ScrobbleService,
+    TABLE_SCROBBLE_CONFIG, TABLE_SCROBBLE_QUEUE,
+};
+use redb::ReadableTable;
+
+use crate::error::BResult;
+
+use super::core::DatabaseServer;
+
+fn service_range(service: ScrobbleService) -> std::ops::RangeInclusive<ScrobbleKey> {
+    ScrobbleKey {
+        service,
+        listened_at: i64::MIN,
+        music: MusicId::wrap(i64::MIN),
+    }..=ScrobbleKey {
+        service,
+        listened_at: i64::MAX,
+        music: MusicId::wrap(i64::MAX),
+    }
+}
+
+impl DatabaseServer {
+    pub fn load_scrobble_config(self: &Arc<Self>) -> BResult<ScrobbleConfigModel> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_SCROBBLE_CONFIG)?;
+        let v = table.get(())?.map(|v| v.value()).unwrap_or_default();
+        Ok(v)
+    }
+
+    pub fn save_scrobble_config(self: &Arc<Self>, model: ScrobbleConfigModel) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let mut table = db.open_table(TABLE_SCROBBLE_CONFIG)?;
+            table.insert((), model)?;
+        }
+        db.commit()?;
+        Ok(())
+    }
+
+    /// Queues listens, leaving out the ones already queued or submitted.
+    pub fn enqueue_scrobbles(
+        self: &Arc<Self>,
+        listens: Vec<(ScrobbleKey, ScrobbleModel)>,
+    ) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let mut table = db.open_table(TABLE_SCROBBLE_QUEUE)?;
+            for (key, model) in listens {
+                if table.get(key)?.is_none() {
+                    table.insert(key, model)?;
+                }
+            }
+        }
+        db.commit()?;
+        Ok(())
+    }
+
+    /// Oldest listens of `service` not submitted yet, at most `limit`.
+    pub fn load_pending_scrobbles(
+        self: &Arc<Self>,
+        service: ScrobbleService,
+        limit: usize,
+    ) -> BResult<Vec<(ScrobbleKey, ScrobbleModel)>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_SCROBBLE_QUEUE)?;
+        let mut ret: Vec<(ScrobbleKey, ScrobbleModel)> = Default::default();
+        for v in table.range(service_range(service))? {
+            if ret.len() >= limit {
+                break;
+            }
+            let (key, model) = v?;
+            let model = model.value();
+            if !model.submitted {
+                ret.push((key.value(), model));
+            }
+        }
+        Ok(ret)
+    }
+
+    pub fn count_pending_scrobbles(self: &Arc<Self>, service: ScrobbleService) -> BResult<u64> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_SCROBBLE_QUEUE)?;
+        let mut ret = 0;
+        for v in table.range(service_range(service))? {
+            if !v?.1.value().submitted {
+                ret += 1;
+            }
+        }
+        Ok(ret)
+    }
+
+    pub fn mark_scrobbles_submitted(self: &Arc<Self>, keys: &[ScrobbleKey]) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let mut table = db.open_table(TABLE_SCROBBLE_QUEUE)?;
+            for key in keys {
+                let Some(mut model) = table.get(key)?.map(|v| v.value()) else {
+                    continue;
+                };
+                model.submitted = true;
+                table.insert(key, model)?;
+            }
+        }
+        db.commit()?;
+        Ok(())
+    }
+
+    /// Counts a failed attempt for listens, and drops the ones that reach
+    /// `max_attempts`. Returns how many are dropped.
+    pub fn fail_scrobbles(
+        self: &Arc<Self>,
+        keys: &[ScrobbleKey],
+        max_attempts: u32,
+    ) -> BResult<usize> {
+        let mut dropped = 0;
+        let db = self.db().begin_write()?;
+        {
+            let mut table = db.open_table(TABLE_SCROBBLE_QUEUE)?;
+            for key in keys {
+                let Some(mut model) = table.get(key)?.map(|v| v.value()) else {
+                    continue;
+                };
+                model.attempts += 1;
+                if model.attempts >= max_attempts {
+                    table.remove(key)?;
+                    dropped += 1;
+                } else {
+                    table.insert(key, model)?;
+                }
+            }
+        }
+        db.commit()?;
+        Ok(dropped)
+    }
+
+    pub fn remove_scrobbles(self: &Arc<Self>, keys: &[ScrobbleKey]) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let mut table = db.open_table(TABLE_SCROBBLE_QUEUE)?;
+            for key in keys {
+                table.remove(key)?;
+            }
+        }
+        db.commit()?;
+        Ok(())
+    }
+
+    /// Drops submitted listens started before `before`, in seconds since the
+    /// unix epoch.
+    pub fn prune_scrobbles(self: &Arc<Self>, before: i64) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let mut table = db.open_table(TABLE_SCROBBLE_QUEUE)?;
+            table.retain(|key, model| !model.submitted || key.listened_at >= before)?;
+        }
+        db.commit()?;
+        Ok(())
+    }
+}
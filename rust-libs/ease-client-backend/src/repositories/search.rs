@@ -0,0 +1,236 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use redb::{ReadableTable, ReadableTableMetadata, WriteTransaction};
+
+use crate::{
+    error::BResult,
+    utils::search::{path_reading_terms, reading_terms, tokenize, tokenize_path},
+};
+
+use super::core::DatabaseServer;
+use ease_client_schema::{
+    MusicId, MusicModel, SearchDocumentModel, SearchField, SearchPostingModel, TABLE_ALBUM,
+    TABLE_ARTIST, TABLE_GENRE, TABLE_MUSIC, TABLE_SEARCH_DOCUMENT, TABLE_SEARCH_TERM,
+};
+
+/// Fields indexed from the music model itself, as opposed to the lyric that
+/// is indexed once loaded.
+const MUSIC_FIELDS: [SearchField; 5] = [
+    SearchField::Title,
+    SearchField::Path,
+    SearchField::Artist,
+    SearchField::Album,
+    SearchField::Genre,
+];
+
+fn build_postings(id: MusicId, doc: &SearchDocumentModel) -> Vec<(String, SearchPostingModel)> {
+    let mut counts: BTreeMap<(String, SearchField), u32> = Default::default();
+    for (field, text) in doc.fields.iter() {
+        // Lyrics are matched by their own words only, as the readings of
+        // every line would outgrow the rest of the index.
+        let terms = match field {
+            SearchField::Path => [tokenize_path(text), path_reading_terms(text)].concat(),
+            SearchField::Lyric => tokenize(text),
+            _ => [tokenize(text), reading_terms(text)].concat(),
+        };
+        for term in terms {
+            *counts.entry((term, *field)).or_default() += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .map(|((term, field), count)| {
+            (
+                term,
+                SearchPostingModel {
+                    music_id: id,
+                    field,
+                    count,
+                },
+            )
+        })
+        .collect()
+}
+
+impl DatabaseServer {
+    /// Replaces the indexed texts of `fields` with `texts`, keeping the texts
+    /// of the other fields.
+    pub(crate) fn update_music_search_impl(
+        self: &Arc<Self>,
+        db: &WriteTransaction,
+        id: MusicId,
+        fields: &[SearchField],
+        texts: Vec<(SearchField, String)>,
+    ) -> BResult<()> {
+        let mut table_doc = db.open_table(TABLE_SEARCH_DOCUMENT)?;
+        let mut table_term = db.open_multimap_table(TABLE_SEARCH_TERM)?;
+
+        let mut doc = table_doc.get(id)?.map(|v| v.value()).unwrap_or_default();
+        for (term, posting) in build_postings(id, &doc) {
+            table_term.remove(term, posting)?;
+        }
+
+        doc.fields.retain(|(field, _)| !fields.contains(field));
+        doc.fields.extend(
+            texts
+                .into_iter()
+                .filter(|(_, text)| !text.trim().is_empty()),
+        );
+        doc.fields.sort_by_key(|(field, _)| *field);
+        for (term, posting) in build_postings(id, &doc) {
+            table_term.insert(term, posting)?;
+        }
+        table_doc.insert(id, doc)?;
+        Ok(())
+    }
+
+    pub(crate) fn remove_music_search_impl(
+        self: &Arc<Self>,
+        db: &WriteTransaction,
+        id: MusicId,
+    ) -> BResult<()> {
+        let mut table_doc = db.open_table(TABLE_SEARCH_DOCUMENT)?;
+        let mut table_term = db.open_multimap_table(TABLE_SEARCH_TERM)?;
+        let doc = table_doc.remove(id)?.map(|v| v.value());
+        if let Some(doc) = doc {
+            for (term, posting) in build_postings(id, &doc) {
+                table_term.remove(term, posting)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Indexes the title, path and library fields of a music.
+    pub(crate) fn index_music_impl(
+        self: &Arc<Self>,
+        db: &WriteTransaction,
+        music: &MusicModel,
+    ) -> BResult<()> {
+        let texts = self.music_search_texts_impl(db, music)?;
+        self.update_music_search_impl(db, music.id, &MUSIC_FIELDS, texts)
+    }
+
+    fn music_search_texts_impl(
+        self: &Arc<Self>,
+        db: &WriteTransaction,
+        music: &MusicModel,
+    ) -> BResult<Vec<(SearchField, String)>> {
+        let mut texts = vec![
+            (SearchField::Title, music.title.clone()),
+            (SearchField::Path, music.loc.path.clone()),
+        ];
+        let table_artist = db.open_table(TABLE_ARTIST)?;
+        for id in music.artists.iter() {
+            if let Some(artist) = table_artist.get(id)? {
+                texts.push((SearchField::Artist, artist.value().name));
+            }
+        }
+        if let Some(id) = music.album {
+            if let Some(album) = db.open_table(TABLE_ALBUM)?.get(id)? {
+                texts.push((SearchField::Album, album.value().title));
+            }
+        }
+        let table_genre = db.open_table(TABLE_GENRE)?;
+        for id in music.genres.iter() {
+            if let Some(genre) = table_genre.get(id)? {
+                texts.push((SearchField::Genre, genre.value().name));
+            }
+        }
+        Ok(texts)
+    }
+
+    /// Indexes the musics that are missing from the search index, such as the
+    /// musics of a database created before the index existed.
+    pub(crate) fn ensure_search_index(self: &Arc<Self>) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let table_music = db.open_table(TABLE_MUSIC)?;
+            let table_doc = db.open_table(TABLE_SEARCH_DOCUMENT)?;
+            if table_doc.len()? >= table_music.len()? {
+                return Ok(());
+            }
+            let mut missing: Vec<MusicModel> = Vec::new();
+            for item in table_music.iter()? {
+                let (id, music) = item?;
+                if table_doc.get(id.value())?.is_none() {
+                    missing.push(music.value());
+                }
+            }
+            drop(table_doc);
+            drop(table_music);
+
+            tracing::info!("index {} musics for search", missing.len());
+            for music in missing {
+                self.index_music_impl(&db, &music)?;
+            }
+        }
+        db.commit()?;
+        Ok(())
+    }
+
+    /// Indexes the text of the lyric of a music, skipping the write when the
+    /// text is already indexed.
+    pub fn update_music_search_lyric(self: &Arc<Self>, id: MusicId, text: String) -> BResult<()> {
+        {
+            let rdb = self.db().begin_read()?;
+            let table_doc = rdb.open_table(TABLE_SEARCH_DOCUMENT)?;
+            let indexed = table_doc.get(id)?.is_some_and(|doc| {
+                doc.value()
+                    .fields
+                    .iter()
+                    .any(|(field, v)| *field == SearchField::Lyric && *v == text)
+            });
+            if indexed || rdb.open_table(TABLE_MUSIC)?.get(id)?.is_none() {
+                return Ok(());
+            }
+        }
+
+        let db = self.db().begin_write()?;
+        self.update_music_search_impl(
+            &db,
+            id,
+            &[SearchField::Lyric],
+            vec![(SearchField::Lyric, text)],
+        )?;
+        db.commit()?;
+        Ok(())
+    }
+
+    /// Returns the postings of the terms starting with `prefix`, or of the
+    /// term equal to `prefix` when `exact` is set.
+    pub fn load_search_postings(
+        self: &Arc<Self>,
+        prefix: &str,
+        exact: bool,
+    ) -> BResult<Vec<(String, SearchPostingModel)>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_multimap_table(TABLE_SEARCH_TERM)?;
+        let mut ret = Vec::new();
+        for item in table.range(prefix.to_string()..)? {
+            let (term, postings) = item?;
+            let term = term.value();
+            if !term.starts_with(prefix) || (exact && term != prefix) {
+                break;
+            }
+            for posting in postings {
+                ret.push((term.clone(), posting?.value()));
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Returns the indexed terms starting with `prefix`.
+    pub fn load_search_terms(self: &Arc<Self>, prefix: &str) -> BResult<Vec<String>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_multimap_table(TABLE_SEARCH_TERM)?;
+        let mut ret = Vec::new();
+        for item in table.range(prefix.to_string()..)? {
+            let term = item?.0.value();
+            if !term.starts_with(prefix) {
+                break;
+            }
+            ret.push(term);
+        }
+        Ok(ret)
+    }
+}
@@ -7,7 +7,8 @@ use crate::{error::BResult, objects::ArgUpsertStorage};
 use super::core::DatabaseServer;
 use ease_client_schema::{
     BlobId, DbKeyAlloc, StorageId, StorageModel, TABLE_MUSIC, TABLE_MUSIC_BY_LOC,
-    TABLE_MUSIC_PLAYLIST, TABLE_PLAYLIST_MUSIC, TABLE_STORAGE, TABLE_STORAGE_MUSIC,
+    TABLE_MUSIC_FINGERPRINT, TABLE_MUSIC_PLAYLIST, TABLE_PLAYLIST_FOLDER, TABLE_PLAYLIST_MUSIC,
+    TABLE_STORAGE, TABLE_STORAGE_MUSIC,
 };
 
 impl DatabaseServer {
@@ -101,6 +102,8 @@ impl DatabaseServer {
             let mut table_storage = db.open_table(TABLE_STORAGE)?;
             let mut table_musics = db.open_table(TABLE_MUSIC)?;
             let mut table_music_by_loc = db.open_table(TABLE_MUSIC_BY_LOC)?;
+            let mut table_fingerprint = db.open_table(TABLE_MUSIC_FINGERPRINT)?;
+            let mut table_folder = db.open_table(TABLE_PLAYLIST_FOLDER)?;
 
             let mut music_iter = table_storage_musics.get(id)?;
 
@@ -125,16 +128,22 @@ impl DatabaseServer {
 
                 {
                     let m = self.load_music_impl(&rdb, id)?.unwrap();
+                    self.unlink_music_library_impl(&db, &m)?;
                     if let Some(id) = m.cover {
-                        to_remove_blobs.push(id);
+                        if self.release_blob_impl(&db, id)? {
+                            to_remove_blobs.push(id);
+                        }
                     }
                 }
 
                 table_musics.remove(id)?;
+                table_fingerprint.remove(id)?;
+                self.remove_music_search_impl(&db, id)?;
             }
             drop(music_iter);
 
             table_music_by_loc.retain(|v, _| v.storage_id != id)?;
+            table_folder.retain(|_, v| v.dir.storage_id != id)?;
             table_storage.remove(id)?;
             table_storage_musics.remove_all(id)?;
         }
@@ -0,0 +1,119 @@
+use std::{collections::HashMap, sync::Arc};
+
+use ease_client_schema::{
+    PlaylistId, SyncConfigModel, SyncFieldModel, SyncMusicKey, SyncTarget, TABLE_PLAYLIST_SYNC_ID,
+    TABLE_SYNC_CONFIG, TABLE_SYNC_FIELD,
+};
+use redb::ReadableTable;
+
+use crate::error::BResult;
+
+use super::core::DatabaseServer;
+
+impl DatabaseServer {
+    pub fn load_sync_config(self: &Arc<Self>) -> BResult<Option<SyncConfigModel>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_SYNC_CONFIG)?;
+        let v = table.get(())?.map(|v| v.value());
+        Ok(v)
+    }
+
+    pub fn save_sync_config(self: &Arc<Self>, model: SyncConfigModel) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let mut table = db.open_table(TABLE_SYNC_CONFIG)?;
+            table.insert((), model)?;
+        }
+        db.commit()?;
+        Ok(())
+    }
+
+    pub fn load_sync_fields(self: &Arc<Self>) -> BResult<HashMap<SyncTarget, SyncFieldModel>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_SYNC_FIELD)?;
+        let mut ret: HashMap<SyncTarget, SyncFieldModel> = Default::default();
+        for v in table.iter()? {
+            let (target, field) = v?;
+            ret.insert(target.value(), field.value());
+        }
+        Ok(ret)
+    }
+
+    /// Fields of the given targets and, for every sync id of `musics_of`, the
+    /// fields of the musics of that playlist.
+    pub fn load_sync_fields_of(
+        self: &Arc<Self>,
+        targets: &[SyncTarget],
+        musics_of: &[String],
+    ) -> BResult<HashMap<SyncTarget, SyncFieldModel>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_SYNC_FIELD)?;
+        let mut ret: HashMap<SyncTarget, SyncFieldModel> = Default::default();
+        for target in targets {
+            if let Some(field) = table.get(target)? {
+                ret.insert(target.clone(), field.value());
+            }
+        }
+        for id in musics_of {
+            let first = SyncTarget::PlaylistMusic {
+                id: id.clone(),
+                music: SyncMusicKey {
+                    storage: String::new(),
+                    path: String::new(),
+                },
+            };
+            for v in table.range(first..)? {
+                let (target, field) = v?;
+                let target = target.value();
+                match &target {
+                    SyncTarget::PlaylistMusic { id: v, .. } if v == id => {}
+                    _ => break,
+                }
+                ret.insert(target, field.value());
+            }
+        }
+        Ok(ret)
+    }
+
+    pub fn load_playlist_sync_id(self: &Arc<Self>, id: PlaylistId) -> BResult<Option<String>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_PLAYLIST_SYNC_ID)?;
+        let v = table.get(id)?.map(|v| v.value());
+        Ok(v)
+    }
+
+    pub fn load_playlist_sync_ids(self: &Arc<Self>) -> BResult<HashMap<PlaylistId, String>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_PLAYLIST_SYNC_ID)?;
+        let mut ret: HashMap<PlaylistId, String> = Default::default();
+        for v in table.iter()? {
+            let (id, sync_id) = v?;
+            ret.insert(id.value(), sync_id.value());
+        }
+        Ok(ret)
+    }
+
+    /// Writes fields along with the config holding the clock they were
+    /// stamped with, and the ids given to playlists seen the first time.
+    pub fn save_sync_fields(
+        self: &Arc<Self>,
+        config: SyncConfigModel,
+        fields: Vec<(SyncTarget, SyncFieldModel)>,
+        sync_ids: Vec<(PlaylistId, String)>,
+    ) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            let mut table = db.open_table(TABLE_SYNC_FIELD)?;
+            for (target, field) in fields {
+                table.insert(target, field)?;
+            }
+            let mut table = db.open_table(TABLE_PLAYLIST_SYNC_ID)?;
+            for (id, sync_id) in sync_ids {
+                table.insert(id, sync_id)?;
+            }
+            db.open_table(TABLE_SYNC_CONFIG)?.insert((), config)?;
+        }
+        db.commit()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+use std::{collections::HashMap, sync::Arc};
+
+use ease_client_schema::{
+    MusicId, MusicModel, TABLE_MUSIC, TABLE_MUSIC_FAVORITE, TABLE_MUSIC_RATING,
+    TABLE_MUSIC_USER_TAG, TABLE_RATING_MUSIC, TABLE_USER_TAG_MUSIC,
+};
+use redb::{ReadableMultimapTable, ReadableTable, WriteTransaction};
+
+use crate::error::{BError, BResult};
+
+use super::core::DatabaseServer;
+
+/// Favorite, rating and tags the user gave to a music.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MusicUserDataModel {
+    /// Time the music was favorited in milliseconds since the unix epoch.
+    pub favorite_time: Option<i64>,
+    pub rating: u8,
+    pub tags: Vec<String>,
+}
+
+impl DatabaseServer {
+    fn ensure_music_impl(db: &WriteTransaction, id: MusicId) -> BResult<()> {
+        match db.open_table(TABLE_MUSIC)?.get(id)? {
+            Some(_) => Ok(()),
+            None => Err(BError::MusicNotFound(id)),
+        }
+    }
+
+    pub fn load_music_user_data(self: &Arc<Self>, id: MusicId) -> BResult<MusicUserDataModel> {
+        let db = self.db().begin_read()?;
+        let favorite_time = db
+            .open_table(TABLE_MUSIC_FAVORITE)?
+            .get(id)?
+            .map(|v| v.value());
+        let rating = db
+            .open_table(TABLE_MUSIC_RATING)?
+            .get(id)?
+            .map(|v| v.value())
+            .unwrap_or_default();
+        let mut tags: Vec<String> = Default::default();
+        for v in db.open_multimap_table(TABLE_MUSIC_USER_TAG)?.get(id)? {
+            tags.push(v?.value());
+        }
+        Ok(MusicUserDataModel {
+            favorite_time,
+            rating,
+            tags,
+        })
+    }
+
+    /// User data of every music that has some.
+    pub fn load_all_music_user_data(
+        self: &Arc<Self>,
+    ) -> BResult<HashMap<MusicId, MusicUserDataModel>> {
+        let db = self.db().begin_read()?;
+        let mut ret: HashMap<MusicId, MusicUserDataModel> = Default::default();
+        for v in db.open_table(TABLE_MUSIC_FAVORITE)?.iter()? {
+            let (id, time) = v?;
+            ret.entry(id.value()).or_default().favorite_time = Some(time.value());
+        }
+        for v in db.open_table(TABLE_MUSIC_RATING)?.iter()? {
+            let (id, rating) = v?;
+            ret.entry(id.value()).or_default().rating = rating.value();
+        }
+        for v in db.open_multimap_table(TABLE_MUSIC_USER_TAG)?.iter()? {
+            let (id, tags) = v?;
+            let entry = ret.entry(id.value()).or_default();
+            for tag in tags {
+                entry.tags.push(tag?.value());
+            }
+        }
+        Ok(ret)
+    }
+
+    pub fn set_music_favorite(
+        self: &Arc<Self>,
+        id: MusicId,
+        favorite_time: Option<i64>,
+    ) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            Self::ensure_music_impl(&db, id)?;
+            let mut table = db.open_table(TABLE_MUSIC_FAVORITE)?;
+            match favorite_time {
+                Some(time) => table.insert(id, time)?,
+                None => table.remove(id)?,
+            };
+        }
+        db.commit()?;
+        Ok(())
+    }
+
+    /// Sets the rating of a music, 0 clearing it.
+    pub fn set_music_rating(self: &Arc<Self>, id: MusicId, rating: u8) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            Self::ensure_music_impl(&db, id)?;
+            let mut table = db.open_table(TABLE_MUSIC_RATING)?;
+            let mut table_rm = db.open_multimap_table(TABLE_RATING_MUSIC)?;
+            if let Some(old) = table.remove(id)?.map(|v| v.value()) {
+                table_rm.remove(old, id)?;
+            }
+            if rating > 0 {
+                table.insert(id, rating)?;
+                table_rm.insert(rating, id)?;
+            }
+        }
+        db.commit()?;
+        Ok(())
+    }
+
+    /// Replaces the tags of a music. Tags are expected to be normalized.
+    pub fn set_music_user_tags(self: &Arc<Self>, id: MusicId, tags: Vec<String>) -> BResult<()> {
+        let db = self.db().begin_write()?;
+        {
+            Self::ensure_music_impl(&db, id)?;
+            let mut table = db.open_multimap_table(TABLE_MUSIC_USER_TAG)?;
+            let mut table_tm = db.open_multimap_table(TABLE_USER_TAG_MUSIC)?;
+            for tag in table.remove_all(id)? {
+                table_tm.remove(tag?.value(), id)?;
+            }
+            for tag in tags {
+                table.insert(id, tag.clone())?;
+                table_tm.insert(tag, id)?;
+            }
+        }
+        db.commit()?;
+        Ok(())
+    }
+
+    /// Favorite musics with the time they were favorited.
+    pub fn load_favorite_musics(self: &Arc<Self>) -> BResult<Vec<(MusicModel, i64)>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_table(TABLE_MUSIC_FAVORITE)?;
+        let table_music = db.open_table(TABLE_MUSIC)?;
+        let mut ret: Vec<(MusicModel, i64)> = Default::default();
+        for v in table.iter()? {
+            let (id, time) = v?;
+            if let Some(m) = table_music.get(id.value())?.map(|v| v.value()) {
+                ret.push((m, time.value()));
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Musics rated from `min` to `max`, both included, highest first.
+    pub fn load_musics_by_rating(self: &Arc<Self>, min: u8, max: u8) -> BResult<Vec<MusicModel>> {
+        let db = self.db().begin_read()?;
+        let ids = {
+            let table = db.open_multimap_table(TABLE_RATING_MUSIC)?;
+            let mut ids: Vec<MusicId> = Default::default();
+            for v in table.range(min.max(1)..=max)?.rev() {
+                for id in v?.1 {
+                    ids.push(id?.value());
+                }
+            }
+            ids
+        };
+        self.load_musics_by_ids_impl(&db, ids)
+    }
+
+    pub fn load_musics_by_user_tag(self: &Arc<Self>, tag: String) -> BResult<Vec<MusicModel>> {
+        let db = self.db().begin_read()?;
+        let ids = {
+            let table = db.open_multimap_table(TABLE_USER_TAG_MUSIC)?;
+            let mut ids: Vec<MusicId> = Default::default();
+            for v in table.get(tag)? {
+                ids.push(v?.value());
+            }
+            ids
+        };
+        self.load_musics_by_ids_impl(&db, ids)
+    }
+
+    /// Every user tag with the number of musics tagged with it.
+    pub fn load_user_tags(self: &Arc<Self>) -> BResult<Vec<(String, u64)>> {
+        let db = self.db().begin_read()?;
+        let table = db.open_multimap_table(TABLE_USER_TAG_MUSIC)?;
+        let mut ret: Vec<(String, u64)> = Default::default();
+        for v in table.iter()? {
+            let (tag, musics) = v?;
+            ret.push((tag.value(), musics.len()));
+        }
+        Ok(ret)
+    }
+
+    pub fn remove_music_user_data_impl(
+        self: &Arc<Self>,
+        db: &WriteTransaction,
+        id: MusicId,
+    ) -> BResult<()> {
+        db.open_table(TABLE_MUSIC_FAVORITE)?.remove(id)?;
+        if let Some(rating) = db.open_table(TABLE_MUSIC_RATING)?.remove(id)? {
+            db.open_multimap_table(TABLE_RATING_MUSIC)?
+                .remove(rating.value(), id)?;
+        }
+        let mut table_tm = db.open_multimap_table(TABLE_USER_TAG_MUSIC)?;
+        for tag in db
+            .open_multimap_table(TABLE_MUSIC_USER_TAG)?
+            .remove_all(id)?
+        {
+            table_tm.remove(tag?.value(), id)?;
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,12 @@
 use ease_client_schema::{
-    upgrade_v1_to_v2, upgrade_v2_to_v3, upgrade_v3_to_v4, upgrade_v4_to_v5, StorageType,
+    upgrade_v1_to_v2, upgrade_v2_to_v3, upgrade_v3_to_v4, upgrade_v4_to_v5, upgrade_v5_to_v6,
+    StorageType,
 };
 
-use crate::{ctx::BackendContext, error::BResult, objects::ArgUpsertStorage};
+use crate::{
+    ctx::BackendContext, error::BResult, objects::ArgUpsertStorage,
+    services::spawn_playlist_folder_schedule,
+};
 
 #[derive(Debug, Clone, uniffi::Record)]
 pub struct ArgInitializeApp {
@@ -16,6 +20,7 @@ pub fn app_bootstrap(cx: &BackendContext, arg: ArgInitializeApp) -> BResult<()>
     cx.set_storage_path(&arg.storage_path);
     // Init
     init_database(cx, &arg)?;
+    spawn_playlist_folder_schedule(cx);
     Ok(())
 }
 
@@ -26,7 +31,7 @@ pub fn app_destroy(cx: &BackendContext) -> BResult<()> {
 }
 
 fn init_database(cx: &BackendContext, arg: &ArgInitializeApp) -> BResult<()> {
-    static SCHEMA_VERSION: u32 = 5;
+    static SCHEMA_VERSION: u32 = 6;
 
     cx.database_server().init(arg.app_document_dir.clone())?;
     let old_schema_version = cx.database_server().get_schema_version()?;
@@ -48,6 +53,9 @@ fn init_database(cx: &BackendContext, arg: &ArgInitializeApp) -> BResult<()> {
             if old_schema_version < 5 {
                 upgrade_v4_to_v5(&cx.database_server().db())?;
             }
+            if old_schema_version < 6 {
+                upgrade_v5_to_v6(&cx.database_server().db())?;
+            }
         }
     }
 
@@ -57,6 +65,7 @@ fn init_database(cx: &BackendContext, arg: &ArgInitializeApp) -> BResult<()> {
         old_schema_version,
         schema_version
     );
+    cx.database_server().ensure_search_index()?;
 
     Ok(())
 }
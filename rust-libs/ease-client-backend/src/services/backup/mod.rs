@@ -0,0 +1,374 @@
+use std::{collections::HashSet, num::NonZeroU32};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ease_client_schema::{
+    check_dump, dump_database, restore_database, upgrade_dump, BlobId, DatabaseDump,
+    ScrobbleConfigModel, StorageModel, TABLE_MUSIC, TABLE_PLAYLIST, TABLE_SCROBBLE_CONFIG,
+    TABLE_STORAGE,
+};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    digest::{digest, SHA256, SHA256_OUTPUT_LEN},
+    pbkdf2::{self, PBKDF2_HMAC_SHA256},
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ctx::BackendContext,
+    error::{BError, BResult},
+    services::{clear_storage_backend_cache, reset_sync_device},
+};
+
+/// An archive starts with this magic, the format version as a little endian
+/// u32 and the SHA-256 of the rest, which is the postcard encoded
+/// `BackupArchive`.
+const BACKUP_MAGIC: &[u8; 8] = b"EASEBKUP";
+const BACKUP_FORMAT_VERSION: u32 = 1;
+const BACKUP_HEADER_LEN: usize = BACKUP_MAGIC.len() + 4 + SHA256_OUTPUT_LEN;
+const PBKDF2_ITERATIONS: u32 = 100_000;
+
+#[derive(Debug, Clone, uniffi::Enum)]
+pub enum BackupCredentials {
+    /// Storage passwords and tokens, and scrobbling tokens, are written as
+    /// they are.
+    Keep,
+    /// Passwords and tokens are left out, usernames are kept.
+    Redact,
+    /// Passwords and tokens are encrypted with a key derived from
+    /// the passphrase, which is asked again on restore.
+    Encrypt { passphrase: String },
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgExportBackup {
+    pub credentials: BackupCredentials,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgImportBackup {
+    pub data: Vec<u8>,
+    /// Needed when the credentials of the backup are encrypted.
+    pub passphrase: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, uniffi::Record)]
+pub struct BackupImportResult {
+    /// Schema version the backup was written at.
+    pub schema_version: u32,
+    pub storages: u64,
+    pub playlists: u64,
+    pub musics: u64,
+    pub blobs: u64,
+    /// Storage passwords were left out of the backup and must be entered
+    /// again.
+    pub credentials_redacted: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum ArchivedCredentials {
+    Plain,
+    Redacted,
+    Encrypted { salt: Vec<u8>, iterations: u32 },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchivedBlob {
+    id: i64,
+    #[serde(with = "serde_bytes")]
+    data: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BackupArchive {
+    created_time: i64,
+    credentials: ArchivedCredentials,
+    database: DatabaseDump,
+    blobs: Vec<ArchivedBlob>,
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> BResult<LessSafeKey> {
+    let iterations = NonZeroU32::new(iterations)
+        .ok_or_else(|| BError::InvalidBackup("zero key iterations".to_string()))?;
+    let mut key = [0u8; 32];
+    pbkdf2::derive(
+        PBKDF2_HMAC_SHA256,
+        iterations,
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    let key = UnboundKey::new(&CHACHA20_POLY1305, &key)
+        .map_err(|_| BError::InvalidBackup("invalid key".to_string()))?;
+    Ok(LessSafeKey::new(key))
+}
+
+fn random_bytes<const N: usize>() -> BResult<[u8; N]> {
+    let mut ret = [0u8; N];
+    SystemRandom::new()
+        .fill(&mut ret)
+        .map_err(|_| BError::CustomError {
+            message: "fail to generate random bytes".to_string(),
+        })?;
+    Ok(ret)
+}
+
+/// Encrypts a secret as base64 of the nonce followed by the sealed bytes.
+fn encrypt_secret(key: &LessSafeKey, secret: &str) -> BResult<String> {
+    let nonce = random_bytes::<NONCE_LEN>()?;
+    let mut buf = secret.as_bytes().to_vec();
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut buf)
+        .map_err(|_| BError::CustomError {
+            message: "fail to encrypt credentials".to_string(),
+        })?;
+    let mut ret = nonce.to_vec();
+    ret.extend(buf);
+    Ok(STANDARD.encode(ret))
+}
+
+fn decrypt_secret(key: &LessSafeKey, secret: &str) -> BResult<String> {
+    let buf = STANDARD
+        .decode(secret)
+        .map_err(|_| BError::InvalidBackup("malformed credentials".to_string()))?;
+    if buf.len() < NONCE_LEN {
+        return Err(BError::InvalidBackup("malformed credentials".to_string()));
+    }
+    let (nonce, sealed) = buf.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce)
+        .map_err(|_| BError::InvalidBackup("malformed credentials".to_string()))?;
+    let mut sealed = sealed.to_vec();
+    let plain = key
+        .open_in_place(nonce, Aad::empty(), &mut sealed)
+        .map_err(|_| BError::BackupPassphraseMismatch)?;
+    String::from_utf8(plain.to_vec())
+        .map_err(|_| BError::InvalidBackup("malformed credentials".to_string()))
+}
+
+/// Applies `f` to the storage passwords and the scrobbling tokens of the
+/// dump. Errors of `f` are kept as they are instead of being wrapped by the
+/// dump.
+fn update_passwords(
+    dump: &mut DatabaseDump,
+    mut f: impl FnMut(&str) -> BResult<String>,
+) -> BResult<()> {
+    let mut error: Option<BError> = None;
+    let mut update = |secret: &mut String| {
+        if error.is_some() || secret.is_empty() {
+            return;
+        }
+        match f(secret) {
+            Ok(v) => *secret = v,
+            Err(e) => error = Some(e),
+        }
+    };
+    dump.update_values(TABLE_STORAGE, |storage: &mut StorageModel| {
+        update(&mut storage.password);
+        Ok(())
+    })?;
+    dump.update_values(TABLE_SCROBBLE_CONFIG, |config: &mut ScrobbleConfigModel| {
+        if let Some(v) = config.listenbrainz.as_mut() {
+            update(&mut v.token);
+        }
+        if let Some(v) = config.lastfm.as_mut() {
+            update(&mut v.api_secret);
+            update(&mut v.session_key);
+        }
+        Ok(())
+    })?;
+    match error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Writes the whole library as a single archive: a logical dump of every
+/// table of the database and the blob files, such as covers.
+pub fn export_backup(cx: &BackendContext, arg: ArgExportBackup) -> BResult<Vec<u8>> {
+    let db = cx.database_server();
+    let mut database = dump_database(&db.db())?;
+    let credentials = match arg.credentials {
+        BackupCredentials::Keep => ArchivedCredentials::Plain,
+        BackupCredentials::Redact => {
+            update_passwords(&mut database, |_| Ok(String::new()))?;
+            ArchivedCredentials::Redacted
+        }
+        BackupCredentials::Encrypt { passphrase } => {
+            let salt = random_bytes::<16>()?.to_vec();
+            let key = derive_key(&passphrase, &salt, PBKDF2_ITERATIONS)?;
+            update_passwords(&mut database, |v| encrypt_secret(&key, v))?;
+            ArchivedCredentials::Encrypted {
+                salt,
+                iterations: PBKDF2_ITERATIONS,
+            }
+        }
+    };
+
+    let blob = db.blob();
+    let mut blobs: Vec<ArchivedBlob> = Default::default();
+    for id in blob.ids()? {
+        blobs.push(ArchivedBlob {
+            id: *id.as_ref(),
+            data: blob.read(id)?,
+        });
+    }
+
+    let archive = BackupArchive {
+        created_time: cx.current_time().as_millis() as i64,
+        credentials,
+        database,
+        blobs,
+    };
+    write_archive(&archive)
+}
+
+fn write_archive(archive: &BackupArchive) -> BResult<Vec<u8>> {
+    let payload = postcard::to_allocvec(archive).map_err(|e| BError::CustomError {
+        message: format!("fail to write backup: {e}"),
+    })?;
+    let mut ret = Vec::with_capacity(BACKUP_HEADER_LEN + payload.len());
+    ret.extend_from_slice(BACKUP_MAGIC);
+    ret.extend_from_slice(&BACKUP_FORMAT_VERSION.to_le_bytes());
+    ret.extend_from_slice(digest(&SHA256, &payload).as_ref());
+    ret.extend(payload);
+    Ok(ret)
+}
+
+fn read_archive(data: &[u8]) -> BResult<BackupArchive> {
+    if data.len() < BACKUP_HEADER_LEN || !data.starts_with(BACKUP_MAGIC) {
+        return Err(BError::InvalidBackup("not a backup archive".to_string()));
+    }
+    let (version, rest) = data[BACKUP_MAGIC.len()..].split_at(4);
+    let version = u32::from_le_bytes(version.try_into().unwrap());
+    if version != BACKUP_FORMAT_VERSION {
+        return Err(BError::InvalidBackup(format!(
+            "unsupported format version {version}"
+        )));
+    }
+    let (checksum, payload) = rest.split_at(SHA256_OUTPUT_LEN);
+    if digest(&SHA256, payload).as_ref() != checksum {
+        return Err(BError::InvalidBackup("checksum mismatch".to_string()));
+    }
+    postcard::from_bytes(payload).map_err(|e| BError::InvalidBackup(e.to_string()))
+}
+
+/// Replaces the whole library with the content of a backup archive. Backups
+/// written at an older schema version are upgraded first. Nothing is changed
+/// when the archive is invalid or the passphrase is wrong.
+pub fn import_backup(cx: &BackendContext, arg: ArgImportBackup) -> BResult<BackupImportResult> {
+    let archive = read_archive(&arg.data)?;
+    // The checksum only catches accidental corruption, so the rows are
+    // checked before the dump is decoded.
+    check_dump(&archive.database).map_err(|e| BError::InvalidBackup(e.to_string()))?;
+    let schema_version = archive.database.schema_version;
+    let mut database = upgrade_dump(archive.database)?;
+    let credentials_redacted = match archive.credentials {
+        ArchivedCredentials::Plain => false,
+        ArchivedCredentials::Redacted => true,
+        ArchivedCredentials::Encrypted { salt, iterations } => {
+            let Some(passphrase) = arg.passphrase else {
+                return Err(BError::BackupPassphraseMismatch);
+            };
+            let key = derive_key(&passphrase, &salt, iterations)?;
+            update_passwords(&mut database, |v| decrypt_secret(&key, v))?;
+            false
+        }
+    };
+
+    // The database is restored in one transaction before any blob is
+    // written, so a failed restore leaves the current library untouched.
+    let db = cx.database_server();
+    restore_database(&db.db(), &database)?;
+    let blob = db.blob();
+    for v in archive.blobs.iter() {
+        blob.write_at(BlobId::wrap(v.id), &v.data)?;
+    }
+    clear_storage_backend_cache(cx);
+    db.ensure_search_index()?;
+    // The backup may come from another device, which publishes its own sync
+    // log under its id.
+    reset_sync_device(cx)?;
+
+    // Blobs not in the backup are not referenced by the restored library.
+    let restored: HashSet<i64> = archive.blobs.iter().map(|v| v.id).collect();
+    for id in blob.ids()? {
+        if !restored.contains(id.as_ref()) {
+            blob.remove(id)?;
+        }
+    }
+
+    Ok(BackupImportResult {
+        schema_version,
+        storages: database.count(TABLE_STORAGE) as u64,
+        playlists: database.count(TABLE_PLAYLIST) as u64,
+        musics: database.count(TABLE_MUSIC) as u64,
+        blobs: restored.len() as u64,
+        credentials_redacted,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        controllers::playlist::{ct_create_playlist, ct_list_playlist},
+        error::BError,
+        services::ArgCreatePlaylist,
+        test_utils::setup_backend,
+    };
+
+    use super::{
+        export_backup, import_backup, read_archive, write_archive, ArgExportBackup,
+        ArgImportBackup, BackupCredentials,
+    };
+
+    #[test]
+    fn rejects_truncated_row_of_checksummed_archive() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (_tempdir, backend) = setup_backend();
+            ct_create_playlist(
+                backend.clone(),
+                ArgCreatePlaylist {
+                    title: "kept".to_string(),
+                    cover: None,
+                    entries: vec![],
+                },
+            )
+            .await
+            .expect("create playlist");
+            let cx = backend.get_context();
+            let data = export_backup(
+                cx,
+                ArgExportBackup {
+                    credentials: BackupCredentials::Keep,
+                },
+            )
+            .expect("export backup");
+
+            let mut archive = read_archive(&data).expect("read archive");
+            let table = archive
+                .database
+                .tables
+                .iter_mut()
+                .find(|t| t.name == "v6_playlist")
+                .expect("playlist table");
+            let (_, value) = table.rows.first_mut().expect("playlist row");
+            value.truncate(value.len() / 2);
+            let data = write_archive(&archive).expect("write archive");
+
+            let result = import_backup(
+                cx,
+                ArgImportBackup {
+                    data,
+                    passphrase: None,
+                },
+            );
+            assert!(matches!(result, Err(BError::InvalidBackup(_))));
+            let titles: Vec<_> = ct_list_playlist(backend.clone())
+                .await
+                .expect("list playlists")
+                .into_iter()
+                .map(|v| v.meta.title)
+                .collect();
+            assert_eq!(vec!["kept"], titles);
+        })
+    }
+}
@@ -0,0 +1,168 @@
+use std::{collections::HashMap, time::Duration};
+
+use ease_client_schema::{MusicId, PlayEventKind, PlayEventModel, PlayStatsModel, PlaylistId};
+
+use crate::{
+    ctx::BackendContext,
+    error::BResult,
+    services::{get_music_abstract, note_play_event},
+    MusicAbstract,
+};
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgRecordPlayEvent {
+    pub music: MusicId,
+    /// Playlist the music is played from, if any.
+    pub playlist: Option<PlaylistId>,
+    pub kind: PlayEventKind,
+    /// Position in the music when the event happened.
+    pub position: Duration,
+    /// Time of the event since the unix epoch. Defaults to now.
+    pub time: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default, uniffi::Record)]
+pub struct PlayStats {
+    /// Plays that reached the end of the music.
+    pub play_count: u32,
+    pub skip_count: u32,
+    /// Time of the latest play event since the unix epoch.
+    pub last_played: Option<Duration>,
+    /// Time spent listening, from the position each play started at to the
+    /// position it completed or was skipped at.
+    pub listened: Duration,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct RecentlyPlayedMusic {
+    pub music: MusicAbstract,
+    /// Time the music was last started since the unix epoch.
+    pub played_time: Duration,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgMostPlayed {
+    /// Start of the window since the unix epoch.
+    pub since: Duration,
+    /// End of the window since the unix epoch. Defaults to now.
+    pub until: Option<Duration>,
+    pub limit: u32,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct MostPlayedMusic {
+    pub music: MusicAbstract,
+    /// Plays completed in the window.
+    pub play_count: u32,
+    /// Time spent listening in the window.
+    pub listened: Duration,
+}
+
+fn build_play_stats(model: PlayStatsModel) -> PlayStats {
+    PlayStats {
+        play_count: model.play_count,
+        skip_count: model.skip_count,
+        last_played: match model.last_played {
+            0 => None,
+            v => Some(Duration::from_millis(v as u64)),
+        },
+        listened: Duration::from_millis(model.listened),
+    }
+}
+
+pub(crate) fn record_play_event(cx: &BackendContext, arg: ArgRecordPlayEvent) -> BResult<()> {
+    let time = arg.time.unwrap_or_else(|| cx.current_time());
+    let listened = cx.database_server().record_play_event(
+        time.as_millis() as i64,
+        PlayEventModel {
+            music: arg.music,
+            playlist: arg.playlist,
+            kind: arg.kind,
+            position: arg.position.as_millis() as u64,
+        },
+    )?;
+    note_play_event(cx, arg.music, arg.kind, time, listened);
+    Ok(())
+}
+
+pub(crate) fn get_music_play_stats(cx: &BackendContext, id: MusicId) -> BResult<PlayStats> {
+    let stats = cx.database_server().load_music_play_stats(id)?;
+    Ok(build_play_stats(stats.unwrap_or_default()))
+}
+
+pub(crate) fn get_playlist_play_stats(cx: &BackendContext, id: PlaylistId) -> BResult<PlayStats> {
+    let stats = cx.database_server().load_playlist_play_stats(id)?;
+    Ok(build_play_stats(stats.unwrap_or_default()))
+}
+
+pub(crate) fn get_recently_played(
+    cx: &BackendContext,
+    limit: u32,
+) -> BResult<Vec<RecentlyPlayedMusic>> {
+    let played = cx.database_server().load_recently_played(limit as usize)?;
+    let mut ret: Vec<RecentlyPlayedMusic> = Vec::with_capacity(played.len());
+    for (id, time) in played {
+        if let Some(music) = get_music_abstract(cx, id)? {
+            ret.push(RecentlyPlayedMusic {
+                music,
+                played_time: Duration::from_millis(time as u64),
+            });
+        }
+    }
+    Ok(ret)
+}
+
+/// Musics with the most plays completed in a time window. The listening
+/// time of a play started before the window counts from the beginning of
+/// the music.
+pub(crate) fn get_most_played(
+    cx: &BackendContext,
+    arg: ArgMostPlayed,
+) -> BResult<Vec<MostPlayedMusic>> {
+    let until = arg.until.unwrap_or_else(|| cx.current_time());
+    let events = cx
+        .database_server()
+        .load_play_events(arg.since.as_millis() as i64, until.as_millis() as i64)?;
+
+    let mut started: HashMap<MusicId, u64> = Default::default();
+    let mut stats: HashMap<MusicId, PlayStatsModel> = Default::default();
+    for (_, event) in events {
+        let v = stats.entry(event.music).or_default();
+        match event.kind {
+            PlayEventKind::Started => {
+                started.insert(event.music, event.position);
+                continue;
+            }
+            PlayEventKind::Completed => v.play_count += 1,
+            PlayEventKind::Skipped => {}
+        }
+        let start = started.remove(&event.music).unwrap_or_default();
+        v.listened += event.position.saturating_sub(start);
+    }
+
+    let mut stats: Vec<(MusicId, PlayStatsModel)> = stats
+        .into_iter()
+        .filter(|(_, v)| v.play_count > 0)
+        .collect();
+    stats.sort_by(|(lid, lhs), (rid, rhs)| {
+        rhs.play_count
+            .cmp(&lhs.play_count)
+            .then(rhs.listened.cmp(&lhs.listened))
+            .then(lid.cmp(rid))
+    });
+
+    let mut ret: Vec<MostPlayedMusic> = Default::default();
+    for (id, v) in stats {
+        if ret.len() >= arg.limit as usize {
+            break;
+        }
+        if let Some(music) = get_music_abstract(cx, id)? {
+            ret.push(MostPlayedMusic {
+                music,
+                play_count: v.play_count,
+                listened: Duration::from_millis(v.listened),
+            });
+        }
+    }
+    Ok(ret)
+}
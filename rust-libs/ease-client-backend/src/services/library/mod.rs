@@ -0,0 +1,177 @@
+use ease_client_schema::{
+    AlbumId, AlbumModel, ArtistId, DataSourceKey, GenreId, MusicId, MusicModel,
+};
+
+use crate::{
+    ctx::BackendContext,
+    error::BResult,
+    objects::{Album, AlbumAbstract, Artist, ArtistAbstract, Genre, GenreAbstract},
+    repositories::library::ArgDBUpdateMusicTags,
+};
+
+use super::{music::build_music_abstract, playlist::compute_musics_duration};
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgUpdateMusicTags {
+    pub id: MusicId,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub year: Option<u32>,
+    pub genres: Vec<String>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+}
+
+pub(crate) fn update_music_tags(cx: &BackendContext, arg: ArgUpdateMusicTags) -> BResult<()> {
+    cx.database_server().update_music_tags(
+        arg.id,
+        ArgDBUpdateMusicTags {
+            artists: arg.artists,
+            album: arg.album,
+            album_artist: arg.album_artist,
+            year: arg.year,
+            genres: arg.genres,
+            track_number: arg.track_number,
+            disc_number: arg.disc_number,
+        },
+    )
+}
+
+fn build_artist_abstract(cx: &BackendContext, id: ArtistId) -> BResult<Option<ArtistAbstract>> {
+    let model = cx.database_server().load_artist(id)?;
+    let Some(model) = model else {
+        return Ok(None);
+    };
+    let music_count = cx.database_server().load_musics_by_artist(id)?.len() as u64;
+    Ok(Some(ArtistAbstract {
+        id: model.id,
+        name: model.name,
+        music_count,
+    }))
+}
+
+fn build_album_abstract(
+    cx: &BackendContext,
+    model: AlbumModel,
+) -> BResult<(AlbumAbstract, Vec<MusicModel>)> {
+    let musics = cx.database_server().load_musics_by_album(model.id)?;
+    let show_cover = musics
+        .iter()
+        .find(|m| m.cover.is_some())
+        .map(|m| DataSourceKey::Cover { id: m.id });
+    let artist = match model.artist {
+        Some(id) => build_artist_abstract(cx, id)?,
+        None => None,
+    };
+
+    let abstr = AlbumAbstract {
+        id: model.id,
+        title: model.title,
+        artist,
+        year: model.year,
+        music_count: musics.len() as u64,
+        show_cover,
+    };
+    Ok((abstr, musics))
+}
+
+pub(crate) fn list_artists(cx: &BackendContext) -> BResult<Vec<ArtistAbstract>> {
+    let artists = cx.database_server().load_artists()?;
+    Ok(artists
+        .into_iter()
+        .map(|(model, music_count)| ArtistAbstract {
+            id: model.id,
+            name: model.name,
+            music_count,
+        })
+        .collect())
+}
+
+pub(crate) fn list_albums(cx: &BackendContext) -> BResult<Vec<AlbumAbstract>> {
+    let albums = cx.database_server().load_albums()?;
+
+    let mut ret: Vec<AlbumAbstract> = Vec::with_capacity(albums.len());
+    for (model, _) in albums {
+        let (abstr, _) = build_album_abstract(cx, model)?;
+        ret.push(abstr);
+    }
+    Ok(ret)
+}
+
+pub(crate) fn list_genres(cx: &BackendContext) -> BResult<Vec<GenreAbstract>> {
+    let genres = cx.database_server().load_genres()?;
+    Ok(genres
+        .into_iter()
+        .map(|(model, music_count)| GenreAbstract {
+            id: model.id,
+            name: model.name,
+            music_count,
+        })
+        .collect())
+}
+
+pub(crate) fn get_album(cx: &BackendContext, id: AlbumId) -> BResult<Option<Album>> {
+    let model = cx.database_server().load_album(id)?;
+    let Some(model) = model else {
+        return Ok(None);
+    };
+
+    let (abstr, musics) = build_album_abstract(cx, model)?;
+    let musics = musics
+        .into_iter()
+        .map(|m| build_music_abstract(cx, m))
+        .collect();
+    let duration = compute_musics_duration(&musics);
+
+    Ok(Some(Album {
+        abstr,
+        duration,
+        musics,
+    }))
+}
+
+pub(crate) fn get_artist(cx: &BackendContext, id: ArtistId) -> BResult<Option<Artist>> {
+    let abstr = build_artist_abstract(cx, id)?;
+    let Some(abstr) = abstr else {
+        return Ok(None);
+    };
+
+    let mut albums: Vec<AlbumAbstract> = Default::default();
+    for model in cx.database_server().load_albums_by_artist(id)? {
+        let (album, _) = build_album_abstract(cx, model)?;
+        albums.push(album);
+    }
+    let musics = cx
+        .database_server()
+        .load_musics_by_artist(id)?
+        .into_iter()
+        .map(|m| build_music_abstract(cx, m))
+        .collect();
+
+    Ok(Some(Artist {
+        abstr,
+        albums,
+        musics,
+    }))
+}
+
+pub(crate) fn get_genre(cx: &BackendContext, id: GenreId) -> BResult<Option<Genre>> {
+    let genre = cx.database_server().load_genre(id)?;
+    let Some((model, music_count)) = genre else {
+        return Ok(None);
+    };
+    let abstr = GenreAbstract {
+        id: model.id,
+        name: model.name,
+        music_count,
+    };
+
+    let musics = cx
+        .database_server()
+        .load_musics_by_genre(id)?
+        .into_iter()
+        .map(|m| build_music_abstract(cx, m))
+        .collect();
+    Ok(Some(Genre { abstr, musics }))
+}
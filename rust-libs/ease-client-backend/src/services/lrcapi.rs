@@ -6,9 +6,11 @@ use reqwest::{
     Client, StatusCode,
 };
 
-use super::lyrics::{decode_lyric_text, parse_lrc};
+use super::{
+    lyrics::{decode_lyric_text, parse_lrc},
+    USER_AGENT,
+};
 
-const LRCAPI_USER_AGENT: &str = "ListenToList/0.3.0 (+https://github.com/fengyuanluo/ListenToList)";
 const AUTHENTICATION_HEADER: &str = "authentication";
 const LYRICS_NOT_FOUND_TEXT: &str = "Lyrics not found.";
 
@@ -126,7 +128,7 @@ fn normalize_base_url(input: &str) -> Option<String> {
 
 fn build_client() -> reqwest::Result<Client> {
     reqwest::Client::builder()
-        .user_agent(LRCAPI_USER_AGENT)
+        .user_agent(USER_AGENT)
         .connect_timeout(Duration::from_secs(10))
         .timeout(Duration::from_secs(20))
         .redirect(reqwest::redirect::Policy::limited(10))
@@ -175,6 +175,26 @@ fn detect_utf16_without_bom(bytes: &[u8]) -> Option<&'static Encoding> {
     }
 }
 
+/// Splits untimed lyrics into lines, for embedded lyrics that aren't in LRC
+/// format.
+pub(crate) fn parse_plain_lyric(lyric: &str) -> Lyrics {
+    let lines = lyric
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|text| LyricLine {
+            duration: Duration::ZERO,
+            text: text.to_string(),
+        })
+        .collect();
+    Lyrics {
+        metdata: Default::default(),
+        lines,
+        synced: false,
+    }
+}
+
 pub(crate) fn parse_lrc(lyric: impl Into<String>) -> Result<Lyrics, LrcParseError> {
     let lyric_lines: String = lyric.into();
     let lyric_lines = lyric_lines.trim_start_matches("\u{feff}");
@@ -187,6 +207,7 @@ pub(crate) fn parse_lrc(lyric: impl Into<String>) -> Result<Lyrics, LrcParseErro
     let mut lyrics = Lyrics {
         metdata: Default::default(),
         lines: Default::default(),
+        synced: true,
     };
 
     for (i, line) in lyric_lines.into_iter().enumerate() {
@@ -1,14 +1,36 @@
 mod app;
+mod backup;
+mod history;
+mod library;
 mod lrcapi;
 mod lyrics;
 mod music;
 mod playlist;
 mod preference;
+mod queue;
+mod scan;
+mod scrobble;
+mod search;
 mod storage;
+mod sync;
+mod tags;
+mod user_data;
+
+/// User agent of the requests to lyric and scrobble services.
+const USER_AGENT: &str = "ListenToList/0.3.0 (+https://github.com/fengyuanluo/ListenToList)";
 
 pub use app::*;
+pub use backup::*;
+pub use history::*;
+pub use library::*;
 pub use lrcapi::*;
 pub use music::*;
 pub use playlist::*;
 pub(crate) use preference::*;
+pub use queue::*;
+pub use scan::*;
+pub use scrobble::*;
+pub(crate) use search::*;
 pub use storage::*;
+pub use sync::*;
+pub use user_data::*;
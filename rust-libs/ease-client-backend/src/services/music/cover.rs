@@ -0,0 +1,64 @@
+use ease_client_schema::{MusicId, MusicModel};
+
+use crate::{
+    ctx::BackendContext,
+    error::BResult,
+    services::{
+        get_storage_backend,
+        tags::{choose_front_cover, read_embedded_tags, EntryRangeReader, TagQuery},
+    },
+};
+
+#[derive(Debug, uniffi::Record)]
+pub struct ArgExtractMusicCover {
+    pub id: MusicId,
+    /// Extract again even if the music already has a cover or was probed before.
+    pub force: bool,
+}
+
+/// Extracts the embedded front cover of a music into the blob store.
+///
+/// Musics are probed once; later calls are no-ops unless `force` is set.
+/// Returns whether the music has a cover afterwards.
+pub(crate) async fn extract_music_cover(
+    cx: &BackendContext,
+    arg: ArgExtractMusicCover,
+) -> BResult<bool> {
+    let Some(model) = cx.database_server().load_music(arg.id)? else {
+        return Ok(false);
+    };
+    if !arg.force && (model.cover.is_some() || model.cover_probed) {
+        return Ok(model.cover.is_some());
+    }
+
+    let Some(backend) = get_storage_backend(cx, model.loc.storage_id)? else {
+        return Ok(model.cover.is_some());
+    };
+    let mut reader = EntryRangeReader::new(backend, model.loc.path.clone());
+    extract_cover_from(cx, &model, &mut reader).await
+}
+
+/// Extracts the front cover of `model` through an already opened reader.
+pub(super) async fn extract_cover_from(
+    cx: &BackendContext,
+    model: &MusicModel,
+    reader: &mut EntryRangeReader,
+) -> BResult<bool> {
+    let query = TagQuery {
+        pictures: true,
+        ..Default::default()
+    };
+    let tags = match read_embedded_tags(reader, query).await {
+        Ok(tags) => tags,
+        Err(e) => {
+            tracing::warn!("fail to read embedded tags of {:?}: {}", model.loc, e);
+            return Ok(model.cover.is_some());
+        }
+    };
+
+    let picture = choose_front_cover(tags.pictures);
+    let found = picture.is_some();
+    cx.database_server()
+        .update_music_probed_cover(model.id, picture.map(|p| p.data))?;
+    Ok(found || model.cover.is_some())
+}
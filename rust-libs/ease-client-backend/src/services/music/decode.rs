@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use ease_client_schema::StorageEntryLoc;
+use ease_remote_storage::StorageBackend;
+use symphonia::core::{
+    audio::SampleBuffer, codecs::DecoderOptions, errors::Error as SymphoniaError,
+    formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint,
+};
+
+use crate::error::BResult;
+
+/// A music file downloaded in full, ready to be decoded.
+pub(crate) struct EncodedAudio {
+    pub bytes: Vec<u8>,
+    pub extension: Option<String>,
+}
+
+pub(crate) async fn load_encoded_audio(
+    backend: Arc<dyn StorageBackend + Send + Sync>,
+    loc: &StorageEntryLoc,
+) -> BResult<EncodedAudio> {
+    let bytes = backend.get(loc.path.clone(), 0).await?.bytes().await?;
+    let extension = loc.path.rsplit_once('.').map(|(_, ext)| ext.to_lowercase());
+    Ok(EncodedAudio {
+        bytes: bytes.to_vec(),
+        extension,
+    })
+}
+
+/// Decodes the default track of `audio`, passing the interleaved samples of
+/// each packet along with the sample rate and channel count to `on_samples`.
+/// Decoding stops early when `on_samples` returns false.
+///
+/// Returns `None` when the container or codec is not supported.
+pub(crate) fn decode_audio(
+    audio: EncodedAudio,
+    mut on_samples: impl FnMut(u32, usize, &[f32]) -> bool,
+) -> Option<()> {
+    let source = MediaSourceStream::new(
+        Box::new(std::io::Cursor::new(audio.bytes)),
+        Default::default(),
+    );
+    let mut hint = Hint::new();
+    if let Some(extension) = audio.extension.as_deref() {
+        hint.with_extension(extension);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .ok()?;
+    let mut format = probed.format;
+    let track = format.default_track()?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .ok()?;
+
+    let mut samples: Option<SampleBuffer<f32>> = None;
+    while let Ok(packet) = format.next_packet() {
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(_) => break,
+        };
+        let spec = *decoded.spec();
+        let buf = match samples.as_mut() {
+            Some(buf) if buf.capacity() >= decoded.capacity() * spec.channels.count() => buf,
+            _ => samples.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+        };
+        buf.copy_interleaved_ref(decoded);
+        if !on_samples(spec.rate, spec.channels.count(), buf.samples()) {
+            break;
+        }
+    }
+    Some(())
+}
@@ -0,0 +1,318 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use ease_client_schema::{MusicFingerprintModel, MusicId, MusicModel, PlaylistId, StorageEntryLoc};
+use ease_remote_storage::StorageBackend;
+use rusty_chromaprint::{Configuration, Fingerprinter};
+
+use super::{
+    build_music_abstract,
+    decode::{decode_audio, load_encoded_audio, EncodedAudio},
+};
+use crate::{
+    ctx::BackendContext, error::BResult, objects::MusicAbstract, services::get_storage_backend,
+};
+
+/// Seconds of audio fingerprinted from the start of a music, the same as the
+/// default of `fpcalc`.
+const FINGERPRINT_SECONDS: usize = 120;
+/// Largest shift, in fingerprint items of about 0.12s, tried when aligning
+/// two fingerprints.
+const MAX_ALIGN_OFFSET: usize = 80;
+/// Fingerprints sharing fewer items than this are never considered equal.
+const MIN_OVERLAP: usize = 40;
+/// Ratio of equal bits from which two fingerprints are the same recording.
+const MATCH_THRESHOLD: f64 = 0.85;
+/// Musics whose durations differ more than this are never compared.
+const MAX_DURATION_DIFF: Duration = Duration::from_secs(5);
+/// Fingerprint items are indexed without their lowest bits, which differ
+/// most often between two encodings of a recording.
+const INDEX_SHIFT: u32 = 4;
+/// Only the items whose key is a multiple of this are indexed, which keeps
+/// the index small while every recording still has many indexed items.
+const INDEX_SAMPLE: u32 = 8;
+/// Keys found in more musics than this, like those of silence, don't make
+/// candidates.
+const MAX_KEY_MUSICS: usize = 64;
+
+#[derive(Debug, uniffi::Record)]
+pub struct ArgFingerprintMusics {
+    pub ids: Vec<MusicId>,
+    /// Fingerprint again even if the music was fingerprinted before.
+    pub force: bool,
+}
+
+/// Musics that are the same recording according to their fingerprints.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct DuplicateMusicGroup {
+    pub musics: Vec<MusicAbstract>,
+}
+
+fn fingerprint_config() -> Configuration {
+    Configuration::preset_test2()
+}
+
+fn compute_fingerprint(audio: EncodedAudio) -> Option<Vec<u32>> {
+    let config = fingerprint_config();
+    let mut printer = Fingerprinter::new(&config);
+    let mut remaining: Option<usize> = None;
+    let mut pcm: Vec<i16> = Vec::new();
+    decode_audio(audio, |rate, channels, samples| {
+        let remaining = match remaining.as_mut() {
+            Some(remaining) => remaining,
+            None => {
+                if printer.start(rate, channels as u32).is_err() {
+                    return false;
+                }
+                remaining.insert(rate as usize * channels * FINGERPRINT_SECONDS)
+            }
+        };
+        let take = samples.len().min(*remaining);
+        pcm.clear();
+        pcm.extend(
+            samples[..take]
+                .iter()
+                .map(|v| (v.clamp(-1.0, 1.0) * i16::MAX as f32) as i16),
+        );
+        printer.consume(&pcm);
+        *remaining -= take;
+        *remaining > 0
+    })?;
+    remaining?;
+    printer.finish();
+    Some(printer.fingerprint().to_vec())
+}
+
+/// Returns the ratio of equal bits of two fingerprints at their best
+/// alignment.
+pub(crate) fn fingerprint_similarity(lhs: &[u32], rhs: &[u32]) -> f64 {
+    let mut best = 0.0;
+    for offset in 0..=MAX_ALIGN_OFFSET {
+        for (a, b) in [(lhs, rhs), (rhs, lhs)] {
+            let Some(a) = a.get(offset..) else {
+                continue;
+            };
+            let len = a.len().min(b.len());
+            if len < MIN_OVERLAP {
+                continue;
+            }
+            let errors: u32 = a
+                .iter()
+                .zip(b.iter())
+                .map(|(x, y)| (x ^ y).count_ones())
+                .sum();
+            let similarity = 1.0 - errors as f64 / (len * 32) as f64;
+            if similarity > best {
+                best = similarity;
+            }
+        }
+    }
+    best
+}
+
+fn is_close_duration(lhs: &MusicModel, rhs: &MusicModel) -> bool {
+    match (lhs.duration, rhs.duration) {
+        (Some(a), Some(b)) => a.abs_diff(b) <= MAX_DURATION_DIFF,
+        _ => true,
+    }
+}
+
+fn is_same_recording(
+    lhs: (&MusicModel, &MusicFingerprintModel),
+    rhs: (&MusicModel, &MusicFingerprintModel),
+) -> bool {
+    lhs.1.algorithm == rhs.1.algorithm
+        && is_close_duration(lhs.0, rhs.0)
+        && fingerprint_similarity(&lhs.1.fingerprint, &rhs.1.fingerprint) >= MATCH_THRESHOLD
+}
+
+/// Returns the pairs of musics worth aligning: those of close durations whose
+/// fingerprints share an indexed item. Aligning every pair is too slow for a
+/// whole library.
+fn candidate_pairs(musics: &[(MusicModel, MusicFingerprintModel)]) -> Vec<(usize, usize)> {
+    let mut index: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (i, (_, fingerprint)) in musics.iter().enumerate() {
+        let keys: HashSet<u32> = fingerprint
+            .fingerprint
+            .iter()
+            .map(|v| v >> INDEX_SHIFT)
+            .filter(|key| key % INDEX_SAMPLE == 0)
+            .collect();
+        for key in keys {
+            index.entry(key).or_default().push(i);
+        }
+    }
+
+    let mut pairs = HashSet::new();
+    for musics_of_key in index.values() {
+        if musics_of_key.len() > MAX_KEY_MUSICS {
+            continue;
+        }
+        for (k, &i) in musics_of_key.iter().enumerate() {
+            for &j in &musics_of_key[k + 1..] {
+                if is_close_duration(&musics[i].0, &musics[j].0) {
+                    pairs.insert((i, j));
+                }
+            }
+        }
+    }
+    let mut pairs: Vec<_> = pairs.into_iter().collect();
+    pairs.sort_unstable();
+    pairs
+}
+
+/// Groups the musics that match each other, keeping the order of `musics`
+/// inside and across groups. Musics without a fingerprint are skipped.
+fn group_same_recordings(musics: Vec<(MusicModel, MusicFingerprintModel)>) -> Vec<Vec<MusicModel>> {
+    let musics: Vec<_> = musics
+        .into_iter()
+        .filter(|(_, f)| !f.fingerprint.is_empty())
+        .collect();
+
+    let mut parent: Vec<usize> = (0..musics.len()).collect();
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for (i, j) in candidate_pairs(&musics) {
+        let (a, b) = (&musics[i], &musics[j]);
+        if is_same_recording((&a.0, &a.1), (&b.0, &b.1)) {
+            let (ri, rj) = (find(&mut parent, i), find(&mut parent, j));
+            parent[rj.max(ri)] = rj.min(ri);
+        }
+    }
+
+    let mut groups: Vec<Vec<MusicModel>> = Vec::new();
+    let mut group_of_root: Vec<Option<usize>> = vec![None; musics.len()];
+    for (i, (music, _)) in musics.into_iter().enumerate() {
+        let root = find(&mut parent, i);
+        match group_of_root[root] {
+            Some(group) => groups[group].push(music),
+            None => {
+                group_of_root[root] = Some(groups.len());
+                groups.push(vec![music]);
+            }
+        }
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+/// Computes the fingerprint of a storage entry without storing it, or `None`
+/// if it could not be decoded.
+pub(crate) async fn fingerprint_entry(
+    backend: Arc<dyn StorageBackend + Send + Sync>,
+    loc: &StorageEntryLoc,
+) -> BResult<Option<Vec<u32>>> {
+    let audio = load_encoded_audio(backend, loc).await?;
+    let fingerprint = ease_client_tokio::tokio_runtime()
+        .spawn_blocking(move || compute_fingerprint(audio))
+        .await
+        .map_err(anyhow::Error::from)?;
+    Ok(fingerprint)
+}
+
+/// Returns whether two fingerprints computed with the current algorithm are
+/// the same recording.
+pub(crate) fn is_same_fingerprint(lhs: &MusicFingerprintModel, rhs: &[u32]) -> bool {
+    lhs.algorithm == fingerprint_config().id()
+        && fingerprint_similarity(&lhs.fingerprint, rhs) >= MATCH_THRESHOLD
+}
+
+/// Computes the chromaprint fingerprint of the first two minutes of a music.
+/// Musics are fingerprinted once unless `force` is set.
+pub(crate) async fn fingerprint_music(
+    cx: &BackendContext,
+    id: MusicId,
+    force: bool,
+) -> BResult<()> {
+    let Some(model) = cx.database_server().load_music(id)? else {
+        return Ok(());
+    };
+    if !force && cx.database_server().load_music_fingerprint(id)?.is_some() {
+        return Ok(());
+    }
+    let Some(backend) = get_storage_backend(cx, model.loc.storage_id)? else {
+        return Ok(());
+    };
+
+    let fingerprint = fingerprint_entry(backend, &model.loc).await?;
+    if fingerprint.is_none() {
+        tracing::warn!("fail to fingerprint {:?}", model.loc);
+    }
+    cx.database_server().update_music_fingerprint(
+        id,
+        MusicFingerprintModel {
+            fingerprint: fingerprint.unwrap_or_default(),
+            algorithm: fingerprint_config().id(),
+        },
+    )?;
+    Ok(())
+}
+
+pub(crate) async fn fingerprint_musics(
+    cx: &BackendContext,
+    arg: ArgFingerprintMusics,
+) -> BResult<()> {
+    for id in arg.ids {
+        fingerprint_music(cx, id, arg.force).await?;
+    }
+    Ok(())
+}
+
+/// Finds the fingerprinted musics that exist in more than one storage.
+pub(crate) fn find_cross_storage_duplicates(
+    cx: &BackendContext,
+) -> BResult<Vec<DuplicateMusicGroup>> {
+    let mut musics = Vec::new();
+    for (id, fingerprint) in cx.database_server().load_music_fingerprints()? {
+        if let Some(model) = cx.database_server().load_music(id)? {
+            musics.push((model, fingerprint));
+        }
+    }
+
+    let ret = group_same_recordings(musics)
+        .into_iter()
+        .filter(|group| {
+            let storages: HashSet<_> = group.iter().map(|m| m.loc.storage_id).collect();
+            storages.len() > 1
+        })
+        .map(|group| DuplicateMusicGroup {
+            musics: group
+                .into_iter()
+                .map(|m| build_music_abstract(cx, m))
+                .collect(),
+        })
+        .collect();
+    Ok(ret)
+}
+
+/// Finds the fingerprinted musics of a playlist that are the same recording.
+pub(crate) fn find_playlist_duplicates(
+    cx: &BackendContext,
+    id: PlaylistId,
+) -> BResult<Vec<DuplicateMusicGroup>> {
+    let mut musics = Vec::new();
+    for model in cx.database_server().load_musics_by_playlist_id(id)? {
+        if let Some(fingerprint) = cx.database_server().load_music_fingerprint(model.id)? {
+            musics.push((model, fingerprint));
+        }
+    }
+
+    let ret = group_same_recordings(musics)
+        .into_iter()
+        .map(|group| DuplicateMusicGroup {
+            musics: group
+                .into_iter()
+                .map(|m| build_music_abstract(cx, m))
+                .collect(),
+        })
+        .collect();
+    Ok(ret)
+}
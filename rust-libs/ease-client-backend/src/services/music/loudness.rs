@@ -0,0 +1,169 @@
+use ease_client_schema::{AlbumId, MusicId, ReplayGainModel};
+use ebur128::{EbuR128, Mode};
+
+use super::decode::{decode_audio, load_encoded_audio, EncodedAudio};
+use crate::{
+    ctx::BackendContext,
+    error::BResult,
+    services::{
+        get_storage_backend,
+        tags::{read_embedded_tags, EntryRangeReader, TagQuery},
+    },
+};
+
+/// ReplayGain 2.0 reference level in LUFS.
+const REFERENCE_LOUDNESS: f64 = -18.0;
+
+#[derive(Debug, uniffi::Record)]
+pub struct ArgAnalyzeLoudness {
+    pub ids: Vec<MusicId>,
+    /// Analyze again even if the music was analyzed before.
+    pub force: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MeasuredLoudness {
+    integrated: f64,
+    true_peak: f64,
+}
+
+fn measure_loudness(audio: EncodedAudio) -> Option<MeasuredLoudness> {
+    let mut meter: Option<EbuR128> = None;
+    let mut failed = false;
+    decode_audio(audio, |rate, channels, samples| {
+        let meter = match meter.as_mut() {
+            Some(meter) => meter,
+            None => match EbuR128::new(channels as u32, rate, Mode::I | Mode::TRUE_PEAK) {
+                Ok(v) => meter.insert(v),
+                Err(_) => {
+                    failed = true;
+                    return false;
+                }
+            },
+        };
+        failed = meter.add_frames_f32(samples).is_err();
+        !failed
+    })?;
+    if failed {
+        return None;
+    }
+
+    let meter = meter?;
+    let integrated = meter.loudness_global().ok()?;
+    if !integrated.is_finite() {
+        return None;
+    }
+    let true_peak = (0..meter.channels())
+        .filter_map(|channel| meter.true_peak(channel).ok())
+        .fold(0.0, f64::max);
+    Some(MeasuredLoudness {
+        integrated,
+        true_peak,
+    })
+}
+
+/// Derives the album gain of the tracks whose album values are not tagged,
+/// by averaging the energy of their track loudness weighted by duration.
+fn update_album_gain(cx: &BackendContext, album: AlbumId) -> BResult<()> {
+    let musics = cx.database_server().load_musics_by_album(album)?;
+    let tracks: Vec<(MusicId, ReplayGainModel, f64)> = musics
+        .iter()
+        .filter_map(|m| {
+            let gain = m.replay_gain?;
+            let weight = m.duration.map(|d| d.as_secs_f64()).unwrap_or(1.0);
+            Some((m.id, gain, weight.max(1.0)))
+        })
+        .filter(|(_, gain, _)| !gain.album_from_tags)
+        .collect();
+    if tracks.is_empty() {
+        return Ok(());
+    }
+
+    let total_weight: f64 = tracks.iter().map(|(_, _, w)| w).sum();
+    let energy: f64 = tracks
+        .iter()
+        .map(|(_, gain, w)| w * 10f64.powf((REFERENCE_LOUDNESS - gain.track_gain) / 10.0))
+        .sum::<f64>()
+        / total_weight;
+    let album_gain = REFERENCE_LOUDNESS - 10.0 * energy.log10();
+    let album_peak = tracks
+        .iter()
+        .filter_map(|(_, gain, _)| gain.track_peak)
+        .reduce(f64::max);
+
+    let ids = tracks.into_iter().map(|(id, _, _)| id).collect();
+    cx.database_server()
+        .update_musics_album_gain(ids, album_gain, album_peak)?;
+    Ok(())
+}
+
+/// Computes the ReplayGain values of a music, preferring ReplayGain or R128
+/// tags and decoding the audio when they are missing.
+pub(crate) async fn analyze_music_loudness(
+    cx: &BackendContext,
+    id: MusicId,
+    force: bool,
+) -> BResult<()> {
+    let Some(model) = cx.database_server().load_music(id)? else {
+        return Ok(());
+    };
+    if model.loudness_analyzed && !force {
+        return Ok(());
+    }
+    let Some(backend) = get_storage_backend(cx, model.loc.storage_id)? else {
+        return Ok(());
+    };
+
+    let query = TagQuery {
+        replay_gain: true,
+        ..Default::default()
+    };
+    let mut reader = EntryRangeReader::new(backend.clone(), model.loc.path.clone());
+    let tags = match read_embedded_tags(&mut reader, query).await {
+        Ok(tags) => tags.replay_gain,
+        Err(e) => {
+            tracing::warn!("fail to read embedded tags of {:?}: {}", model.loc, e);
+            return Ok(());
+        }
+    };
+
+    let replay_gain = if let Some(track_gain) = tags.track_gain {
+        Some(ReplayGainModel {
+            track_gain,
+            track_peak: tags.track_peak,
+            album_gain: tags.album_gain,
+            album_peak: tags.album_peak,
+            album_from_tags: tags.album_gain.is_some(),
+        })
+    } else {
+        let audio = load_encoded_audio(backend, &model.loc).await?;
+        let measured = ease_client_tokio::tokio_runtime()
+            .spawn_blocking(move || measure_loudness(audio))
+            .await
+            .map_err(anyhow::Error::from)?;
+        measured.map(|measured| ReplayGainModel {
+            track_gain: REFERENCE_LOUDNESS - measured.integrated,
+            track_peak: Some(measured.true_peak),
+            album_gain: None,
+            album_peak: None,
+            album_from_tags: false,
+        })
+    };
+
+    cx.database_server()
+        .update_music_replay_gain(id, replay_gain)?;
+    if let Some(album) = model.album {
+        update_album_gain(cx, album)?;
+    }
+    Ok(())
+}
+
+pub(crate) async fn analyze_musics_loudness(
+    cx: &BackendContext,
+    arg: ArgAnalyzeLoudness,
+) -> BResult<()> {
+    for id in arg.ids {
+        analyze_music_loudness(cx, id, arg.force).await?;
+    }
+    Ok(())
+}
@@ -1,17 +1,32 @@
+mod cover;
+mod decode;
+mod fingerprint;
+mod loudness;
+mod probe;
+
 use std::time::Duration;
 
+pub use cover::*;
+pub use fingerprint::*;
+pub use loudness::*;
+pub use probe::*;
+
 use ease_client_schema::{DataSourceKey, MusicId, MusicModel, PlaylistId, StorageEntryLoc};
 
 use crate::{
     ctx::BackendContext,
     error::BResult,
-    objects::{LyricLoadState, Music, MusicAbstract, MusicLyric, MusicMeta},
+    objects::{
+        LyricLoadState, LyricSource, Lyrics, Music, MusicAbstract, MusicLyric, MusicMeta,
+        ReplayGain,
+    },
     StorageEntry,
 };
 
 use super::{
-    lyrics::{decode_lyric_text, parse_lrc},
+    lyrics::{decode_lyric_text, parse_lrc, parse_plain_lyric},
     storage::load_storage_entry_data,
+    tags::{read_embedded_tags, EntryRangeReader, TagQuery},
 };
 
 #[derive(Debug, uniffi::Record)]
@@ -60,8 +75,9 @@ pub struct ArgUpdateMusicLyric {
 async fn load_lyric(
     cx: &BackendContext,
     loc: Option<StorageEntryLoc>,
-    is_fallback: bool,
+    source: LyricSource,
 ) -> Option<MusicLyric> {
+    let is_fallback = source == LyricSource::SiblingFile;
     let loc = match loc {
         Some(loc) => loc,
         None => {
@@ -79,6 +95,7 @@ async fn load_lyric(
             } else {
                 LyricLoadState::Failed
             },
+            source,
         });
     }
     let data = data.unwrap();
@@ -91,6 +108,7 @@ async fn load_lyric(
             } else {
                 LyricLoadState::Failed
             },
+            source,
         });
     }
     let data = data.unwrap();
@@ -112,6 +130,7 @@ async fn load_lyric(
             loc,
             data: Default::default(),
             loaded_state: LyricLoadState::Failed,
+            source,
         });
     }
     let lyric = lyric.unwrap();
@@ -120,6 +139,48 @@ async fn load_lyric(
         loc,
         data: lyric,
         loaded_state: LyricLoadState::Loaded,
+        source,
+    })
+}
+
+async fn load_embedded_lyric(
+    reader: &mut EntryRangeReader,
+    loc: &StorageEntryLoc,
+) -> Option<MusicLyric> {
+    let query = TagQuery {
+        lyrics: true,
+        ..Default::default()
+    };
+    let tags = match read_embedded_tags(reader, query).await {
+        Ok(tags) => tags,
+        Err(e) => {
+            tracing::warn!("fail to read embedded tags of {:?}: {}", loc, e);
+            return None;
+        }
+    };
+
+    let data = if !tags.synced_lyrics.is_empty() {
+        Lyrics {
+            metdata: Default::default(),
+            lines: tags.synced_lyrics,
+            synced: true,
+        }
+    } else {
+        let text = tags.unsynced_lyrics?;
+        match parse_lrc(text.as_str()) {
+            Ok(lyric) if !lyric.lines.is_empty() => lyric,
+            _ => parse_plain_lyric(&text),
+        }
+    };
+    if data.lines.is_empty() {
+        return None;
+    }
+
+    Some(MusicLyric {
+        loc: loc.clone(),
+        data,
+        loaded_state: LyricLoadState::Loaded,
+        source: LyricSource::Embedded,
     })
 }
 
@@ -129,6 +190,13 @@ pub(crate) fn build_music_meta(model: MusicModel) -> MusicMeta {
         title: model.title,
         duration: model.duration,
         order: model.order,
+        replay_gain: model.replay_gain.map(|v| ReplayGain {
+            track_gain: v.track_gain,
+            track_peak: v.track_peak,
+            album_gain: v.album_gain,
+            album_peak: v.album_peak,
+        }),
+        file_state: model.file_state,
     }
 }
 
@@ -202,8 +270,8 @@ pub(crate) async fn get_music(cx: &BackendContext, id: MusicId) -> BResult<Optio
 
     let model = model.unwrap();
     let meta = build_music_meta(model.clone());
-    let loc = model.loc;
-    let mut lyric_loc = model.lyric;
+    let loc = model.loc.clone();
+    let mut lyric_loc = model.lyric.clone();
     let using_fallback = lyric_loc.is_none() && model.lyric_default;
     if using_fallback {
         lyric_loc = Some(StorageEntryLoc {
@@ -220,7 +288,42 @@ pub(crate) async fn get_music(cx: &BackendContext, id: MusicId) -> BResult<Optio
         });
     }
 
-    let lyric: Option<MusicLyric> = load_lyric(cx, lyric_loc, using_fallback).await;
+    let source = if using_fallback {
+        LyricSource::SiblingFile
+    } else {
+        LyricSource::File
+    };
+    let mut lyric: Option<MusicLyric> = load_lyric(cx, lyric_loc, source).await;
+    let fallback_loaded = lyric
+        .as_ref()
+        .is_some_and(|l| l.loaded_state == LyricLoadState::Loaded);
+    // The reader is shared with the background probe so that the head of the
+    // file is fetched once.
+    let mut reader = None;
+    if using_fallback && !fallback_loaded {
+        reader = open_music_reader(cx, &model);
+        if let Some(reader) = reader.as_mut() {
+            if let Some(embedded) = load_embedded_lyric(reader, &loc).await {
+                lyric = Some(embedded);
+            }
+        }
+    }
+    spawn_probe_music_file(cx, &model, reader);
+    if let Some(lyric) = lyric
+        .as_ref()
+        .filter(|l| l.loaded_state == LyricLoadState::Loaded)
+    {
+        let text = lyric
+            .data
+            .lines
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if let Err(e) = cx.database_server().update_music_search_lyric(id, text) {
+            tracing::warn!("fail to index lyric of music {:?}: {}", id, e);
+        }
+    }
     let cover = if model.cover.is_none() {
         Default::default()
     } else {
@@ -232,6 +335,7 @@ pub(crate) async fn get_music(cx: &BackendContext, id: MusicId) -> BResult<Optio
         loc,
         cover,
         lyric,
+        stream_info: model.stream_info,
     };
     Ok(Some(music))
 }
@@ -259,36 +363,22 @@ pub(crate) fn get_music_abstract(
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
+    use std::time::Duration;
 
-    use ease_client_schema::StorageType;
     use encoding_rs::GBK;
-    use tempfile::TempDir;
 
     use crate::{
-        create_backend,
-        objects::LyricLoadState,
+        objects::{LyricLoadState, LyricSource},
         repositories::music::ArgDBAddMusic,
-        services::{list_storage, ArgInitializeApp},
+        services::{list_albums, list_artists},
+        test_utils::{local_storage, local_storage_id, setup_backend},
     };
 
-    use super::get_music;
-
-    fn setup_backend() -> (TempDir, Arc<crate::Backend>) {
-        let tempdir = tempfile::tempdir().expect("create tempdir");
-        let documents_dir = tempdir.path().join("documents");
-        let cache_dir = tempdir.path().join("cache");
-        std::fs::create_dir_all(&documents_dir).expect("create documents dir");
-        std::fs::create_dir_all(&cache_dir).expect("create cache dir");
-
-        let backend = create_backend(ArgInitializeApp {
-            app_document_dir: format!("{}/", documents_dir.display()),
-            app_cache_dir: format!("{}/", cache_dir.display()),
-            storage_path: "/".to_string(),
-        });
-        backend.init().expect("init backend");
-        (tempdir, backend)
-    }
+    use super::{
+        analyze_musics_loudness, extract_music_cover, find_playlist_duplicates, fingerprint_musics,
+        get_music, get_music_cover_bytes, wait_probe_music_file, ArgAnalyzeLoudness,
+        ArgExtractMusicCover, ArgFingerprintMusics,
+    };
 
     #[test]
     fn loads_gbk_fallback_lyric_file() {
@@ -305,12 +395,7 @@ mod tests {
             assert!(!had_errors, "gbk sample should encode cleanly");
             std::fs::write(&lyric_path, encoded.as_ref()).expect("write gbk lyric");
 
-            let local_storage = list_storage(backend.get_context())
-                .await
-                .expect("list storages")
-                .into_iter()
-                .find(|storage| storage.typ == StorageType::Local)
-                .expect("local storage");
+            let local_storage = local_storage(&backend).await;
 
             let created = backend
                 .get_context()
@@ -348,12 +433,7 @@ mod tests {
             std::fs::write(&lyric_path, utf16le_bytes_without_bom("[00:01.00]后来\n"))
                 .expect("write utf16 lyric");
 
-            let local_storage = list_storage(backend.get_context())
-                .await
-                .expect("list storages")
-                .into_iter()
-                .find(|storage| storage.typ == StorageType::Local)
-                .expect("local storage");
+            let local_storage = local_storage(&backend).await;
 
             let created = backend
                 .get_context()
@@ -378,6 +458,512 @@ mod tests {
         });
     }
 
+    fn apic_frame(picture_type: u8, data: &[u8]) -> Vec<u8> {
+        let mut body = vec![0u8];
+        body.extend_from_slice(b"image/jpeg\0");
+        body.push(picture_type);
+        body.extend_from_slice(b"\0");
+        body.extend_from_slice(data);
+
+        let mut frame = b"APIC".to_vec();
+        frame.extend_from_slice((body.len() as u32).to_be_bytes().as_slice());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(body.as_slice());
+        frame
+    }
+
+    fn id3_tagged_file(frames: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = frames.concat();
+        let size = body.len();
+        let mut file = b"ID3\x03\x00\x00".to_vec();
+        file.extend_from_slice(&[
+            ((size >> 21) & 0x7f) as u8,
+            ((size >> 14) & 0x7f) as u8,
+            ((size >> 7) & 0x7f) as u8,
+            (size & 0x7f) as u8,
+        ]);
+        file.extend_from_slice(body.as_slice());
+        file.extend_from_slice(&[0xff, 0xfb, 0x90, 0x00]);
+        file
+    }
+
+    fn text_frame(id: &[u8; 4], text: &str) -> Vec<u8> {
+        let mut body = vec![3u8];
+        body.extend_from_slice(text.as_bytes());
+
+        let mut frame = id.to_vec();
+        frame.extend_from_slice((body.len() as u32).to_be_bytes().as_slice());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(body.as_slice());
+        frame
+    }
+
+    #[test]
+    fn links_probed_music_to_tagged_artist_and_album() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let music_path = tempdir.path().join("tagged.mp3");
+            let file = id3_tagged_file(&[
+                text_frame(b"TPE1", "Some Artist"),
+                text_frame(b"TALB", "Some Album"),
+            ]);
+            std::fs::write(&music_path, file).expect("write music");
+
+            let cx = backend.get_context();
+            let storage_id = local_storage_id(&backend).await;
+            let created = cx
+                .database_server()
+                .upsert_musics(vec![ArgDBAddMusic {
+                    loc: ease_client_schema::StorageEntryLoc {
+                        storage_id,
+                        path: music_path.to_string_lossy().to_string(),
+                    },
+                    title: "tagged".to_string(),
+                }])
+                .expect("create music");
+            let id = created[0].id;
+
+            get_music(cx, id).await.expect("load music").expect("music");
+            wait_probe_music_file(cx, id).await;
+            let artists = list_artists(cx).expect("list artists");
+            assert_eq!(1, artists.len());
+            assert_eq!("Some Artist", artists[0].name);
+            assert_eq!(1, artists[0].music_count);
+            let albums = list_albums(cx).expect("list albums");
+            assert_eq!(1, albums.len());
+            assert_eq!("Some Album", albums[0].title);
+            assert_eq!(Some(artists[0].id), albums[0].artist.as_ref().map(|v| v.id));
+        });
+    }
+
+    #[test]
+    fn extracts_embedded_front_cover() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let media_dir = tempdir.path().join("media");
+            std::fs::create_dir_all(&media_dir).expect("create media dir");
+
+            let music_path = media_dir.join("cover-song.mp3");
+            let file = id3_tagged_file(&[apic_frame(4, &[9, 9]), apic_frame(3, &[1, 2, 3])]);
+            std::fs::write(&music_path, file).expect("write music");
+
+            let local_storage = local_storage(&backend).await;
+
+            let cx = backend.get_context();
+            let created = cx
+                .database_server()
+                .upsert_musics(vec![ArgDBAddMusic {
+                    loc: ease_client_schema::StorageEntryLoc {
+                        storage_id: local_storage.id,
+                        path: music_path.to_string_lossy().to_string(),
+                    },
+                    title: "cover-song".to_string(),
+                }])
+                .expect("create music");
+            let id = created[0].id;
+
+            let music = get_music(cx, id).await.expect("load music").expect("music");
+            assert!(music.cover.is_none());
+            wait_probe_music_file(cx, id).await;
+            let music = get_music(cx, id).await.expect("load music").expect("music");
+            assert!(music.cover.is_some());
+            assert_eq!(get_music_cover_bytes(cx, id).expect("cover"), vec![1, 2, 3]);
+
+            cx.database_server()
+                .update_music_cover(id, vec![7, 7])
+                .expect("update cover");
+            let arg = ArgExtractMusicCover { id, force: false };
+            assert!(extract_music_cover(cx, arg).await.expect("extract"));
+            assert_eq!(get_music_cover_bytes(cx, id).expect("cover"), vec![7, 7]);
+
+            let arg = ArgExtractMusicCover { id, force: true };
+            assert!(extract_music_cover(cx, arg).await.expect("extract"));
+            assert_eq!(get_music_cover_bytes(cx, id).expect("cover"), vec![1, 2, 3]);
+        });
+    }
+
+    #[test]
+    fn loads_embedded_lyric_when_no_sibling_file() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let media_dir = tempdir.path().join("media");
+            std::fs::create_dir_all(&media_dir).expect("create media dir");
+
+            let mut body = vec![3u8];
+            body.extend_from_slice(b"chi\0");
+            body.extend_from_slice("[00:01.00]后来\n[00:03.50]我总算学会了\n".as_bytes());
+            let mut uslt = b"USLT".to_vec();
+            uslt.extend_from_slice((body.len() as u32).to_be_bytes().as_slice());
+            uslt.extend_from_slice(&[0, 0]);
+            uslt.extend_from_slice(body.as_slice());
+
+            let music_path = media_dir.join("embedded-lyric.mp3");
+            std::fs::write(&music_path, id3_tagged_file(&[uslt])).expect("write music");
+
+            let local_storage = local_storage(&backend).await;
+
+            let created = backend
+                .get_context()
+                .database_server()
+                .upsert_musics(vec![ArgDBAddMusic {
+                    loc: ease_client_schema::StorageEntryLoc {
+                        storage_id: local_storage.id,
+                        path: music_path.to_string_lossy().to_string(),
+                    },
+                    title: "embedded-lyric".to_string(),
+                }])
+                .expect("create music");
+
+            let music = get_music(backend.get_context(), created[0].id)
+                .await
+                .expect("load music")
+                .expect("music");
+            let lyric = music.lyric.expect("embedded lyric should load");
+            assert_eq!(lyric.source, LyricSource::Embedded);
+            assert_eq!(lyric.loaded_state, LyricLoadState::Loaded);
+            assert_eq!(lyric.data.lines.len(), 2);
+            assert_eq!(lyric.data.lines[1].text, "我总算学会了");
+            assert!(lyric.data.synced);
+        });
+    }
+
+    #[test]
+    fn loads_plain_embedded_lyric() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let media_dir = tempdir.path().join("media");
+            std::fs::create_dir_all(&media_dir).expect("create media dir");
+
+            let music_path = media_dir.join("plain-lyric.opus");
+            let file = opus_file(&["LYRICS=后来\n我总算学会了\n", "TITLE=plain"]);
+            std::fs::write(&music_path, file).expect("write music");
+
+            let cx = backend.get_context();
+            let storage_id = local_storage_id(&backend).await;
+            let created = cx
+                .database_server()
+                .upsert_musics(vec![ArgDBAddMusic {
+                    loc: ease_client_schema::StorageEntryLoc {
+                        storage_id,
+                        path: music_path.to_string_lossy().to_string(),
+                    },
+                    title: "plain-lyric".to_string(),
+                }])
+                .expect("create music");
+
+            let music = get_music(cx, created[0].id)
+                .await
+                .expect("load music")
+                .expect("music");
+            let lyric = music.lyric.expect("embedded lyric should load");
+            assert_eq!(lyric.source, LyricSource::Embedded);
+            assert_eq!(lyric.loaded_state, LyricLoadState::Loaded);
+            assert!(!lyric.data.synced);
+            let lines: Vec<_> = lyric.data.lines.iter().map(|l| l.text.as_str()).collect();
+            assert_eq!(lines, vec!["后来", "我总算学会了"]);
+        });
+    }
+
+    #[test]
+    fn probes_wav_duration_and_stream_info() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let media_dir = tempdir.path().join("media");
+            std::fs::create_dir_all(&media_dir).expect("create media dir");
+
+            let file = stereo_wav_file(&vec![0; 44100 * 3]);
+
+            let music_path = media_dir.join("probe.wav");
+            std::fs::write(&music_path, file).expect("write music");
+
+            let local_storage = local_storage(&backend).await;
+
+            let created = backend
+                .get_context()
+                .database_server()
+                .upsert_musics(vec![ArgDBAddMusic {
+                    loc: ease_client_schema::StorageEntryLoc {
+                        storage_id: local_storage.id,
+                        path: music_path.to_string_lossy().to_string(),
+                    },
+                    title: "probe".to_string(),
+                }])
+                .expect("create music");
+
+            let cx = backend.get_context();
+            let id = created[0].id;
+            get_music(cx, id).await.expect("load music");
+            wait_probe_music_file(cx, id).await;
+            let music = get_music(cx, id).await.expect("load music").expect("music");
+            assert_eq!(music.meta.duration, Some(Duration::from_secs(3)));
+            let info = music.stream_info.expect("stream info");
+            assert_eq!(info.sample_rate, 44100);
+            assert_eq!(info.channels, 2);
+            assert_eq!(info.bit_depth, Some(16));
+            assert_eq!(info.bitrate, Some(1411));
+        });
+    }
+
+    #[test]
+    fn records_failed_probe() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let music_path = tempdir.path().join("missing.mp3");
+
+            let cx = backend.get_context();
+            let storage_id = local_storage_id(&backend).await;
+            let created = cx
+                .database_server()
+                .upsert_musics(vec![ArgDBAddMusic {
+                    loc: ease_client_schema::StorageEntryLoc {
+                        storage_id,
+                        path: music_path.to_string_lossy().to_string(),
+                    },
+                    title: "missing".to_string(),
+                }])
+                .expect("create music");
+            let id = created[0].id;
+
+            get_music(cx, id).await.expect("load music").expect("music");
+            wait_probe_music_file(cx, id).await;
+            let model = cx
+                .database_server()
+                .load_music(id)
+                .expect("load music")
+                .expect("music");
+            assert!(model.stream_probed);
+            assert!(model.stream_info.is_none());
+        });
+    }
+
+    fn stereo_wav_file(frames: &[i16]) -> Vec<u8> {
+        let data_len = (frames.len() * 4) as u32;
+        let mut file = b"RIFF".to_vec();
+        file.extend_from_slice((36 + data_len).to_le_bytes().as_slice());
+        file.extend_from_slice(b"WAVEfmt ");
+        file.extend_from_slice(16u32.to_le_bytes().as_slice());
+        file.extend_from_slice(1u16.to_le_bytes().as_slice());
+        file.extend_from_slice(2u16.to_le_bytes().as_slice());
+        file.extend_from_slice(44100u32.to_le_bytes().as_slice());
+        file.extend_from_slice((44100u32 * 4).to_le_bytes().as_slice());
+        file.extend_from_slice(4u16.to_le_bytes().as_slice());
+        file.extend_from_slice(16u16.to_le_bytes().as_slice());
+        file.extend_from_slice(b"data");
+        file.extend_from_slice(data_len.to_le_bytes().as_slice());
+        for frame in frames {
+            file.extend_from_slice(frame.to_le_bytes().as_slice());
+            file.extend_from_slice(frame.to_le_bytes().as_slice());
+        }
+        file
+    }
+
+    #[test]
+    fn analyzes_loudness_of_untagged_music() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let media_dir = tempdir.path().join("media");
+            std::fs::create_dir_all(&media_dir).expect("create media dir");
+
+            // a 1 kHz sine at -20 dBFS
+            let frames: Vec<i16> = (0..44100 * 3)
+                .map(|i| {
+                    let t = i as f64 / 44100.0;
+                    (0.1 * (2.0 * std::f64::consts::PI * 1000.0 * t).sin() * i16::MAX as f64) as i16
+                })
+                .collect();
+            let music_path = media_dir.join("sine.wav");
+            std::fs::write(&music_path, stereo_wav_file(&frames)).expect("write music");
+
+            let local_storage = local_storage(&backend).await;
+
+            let cx = backend.get_context();
+            let created = cx
+                .database_server()
+                .upsert_musics(vec![ArgDBAddMusic {
+                    loc: ease_client_schema::StorageEntryLoc {
+                        storage_id: local_storage.id,
+                        path: music_path.to_string_lossy().to_string(),
+                    },
+                    title: "sine".to_string(),
+                }])
+                .expect("create music");
+            let id = created[0].id;
+
+            analyze_musics_loudness(
+                cx,
+                ArgAnalyzeLoudness {
+                    ids: vec![id],
+                    force: false,
+                },
+            )
+            .await
+            .expect("analyze loudness");
+
+            let music = get_music(cx, id).await.expect("load music").expect("music");
+            let replay_gain = music.meta.replay_gain.expect("replay gain");
+            assert!(
+                (replay_gain.track_gain - 1.3).abs() < 1.0,
+                "unexpected track gain {}",
+                replay_gain.track_gain
+            );
+            let peak = replay_gain.track_peak.expect("peak");
+            assert!((peak - 0.1).abs() < 0.01, "unexpected peak {}", peak);
+        });
+    }
+
+    /// Builds an Ogg page holding whole `packets`. The checksum is left empty
+    /// as the tag reader doesn't check it.
+    fn ogg_page(sequence: u32, packets: &[&[u8]]) -> Vec<u8> {
+        let mut lacing = Vec::new();
+        for packet in packets {
+            lacing.extend(std::iter::repeat_n(255u8, packet.len() / 255));
+            lacing.push((packet.len() % 255) as u8);
+        }
+        let mut page = b"OggS\0".to_vec();
+        page.push(if sequence == 0 { 2 } else { 0 });
+        page.extend_from_slice(&[0; 8]);
+        page.extend_from_slice(&[1, 0, 0, 0]);
+        page.extend_from_slice(sequence.to_le_bytes().as_slice());
+        page.extend_from_slice(&[0; 4]);
+        page.push(lacing.len() as u8);
+        page.extend_from_slice(lacing.as_slice());
+        for packet in packets {
+            page.extend_from_slice(packet);
+        }
+        page
+    }
+
+    /// Builds the header pages of an Opus stream tagged with `comments`.
+    fn opus_file(comments: &[&str]) -> Vec<u8> {
+        let mut head = b"OpusHead\x01\x02".to_vec();
+        head.extend_from_slice(312u16.to_le_bytes().as_slice());
+        head.extend_from_slice(48000u32.to_le_bytes().as_slice());
+        head.extend_from_slice(&[0, 0, 0]);
+
+        let mut tags = b"OpusTags".to_vec();
+        tags.extend_from_slice(4u32.to_le_bytes().as_slice());
+        tags.extend_from_slice(b"test");
+        tags.extend_from_slice((comments.len() as u32).to_le_bytes().as_slice());
+        for comment in comments {
+            tags.extend_from_slice((comment.len() as u32).to_le_bytes().as_slice());
+            tags.extend_from_slice(comment.as_bytes());
+        }
+
+        let mut file = ogg_page(0, &[head.as_slice()]);
+        file.extend(ogg_page(1, &[tags.as_slice()]));
+        file
+    }
+
+    #[test]
+    fn reads_replay_gain_from_opus_comments() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let media_dir = tempdir.path().join("media");
+            std::fs::create_dir_all(&media_dir).expect("create media dir");
+
+            let music_path = media_dir.join("gain.opus");
+            let padding = "x".repeat(600);
+            let comment = format!("COMMENT={padding}");
+            let file = opus_file(&["R128_TRACK_GAIN=-512", comment.as_str()]);
+            std::fs::write(&music_path, file).expect("write music");
+
+            let cx = backend.get_context();
+            let storage_id = local_storage_id(&backend).await;
+            let created = cx
+                .database_server()
+                .upsert_musics(vec![ArgDBAddMusic {
+                    loc: ease_client_schema::StorageEntryLoc {
+                        storage_id,
+                        path: music_path.to_string_lossy().to_string(),
+                    },
+                    title: "gain".to_string(),
+                }])
+                .expect("create music");
+            let id = created[0].id;
+
+            analyze_musics_loudness(
+                cx,
+                ArgAnalyzeLoudness {
+                    ids: vec![id],
+                    force: false,
+                },
+            )
+            .await
+            .expect("analyze loudness");
+
+            let music = get_music(cx, id).await.expect("load music").expect("music");
+            let replay_gain = music.meta.replay_gain.expect("replay gain");
+            assert_eq!(replay_gain.track_gain, 3.0);
+        });
+    }
+
+    /// Builds a melody of random notes so that different seeds produce
+    /// different recordings.
+    fn melody_frames(seed: u64, seconds: usize) -> Vec<i16> {
+        let mut state = seed;
+        let mut frequency = 0.0;
+        (0..44100 * seconds)
+            .map(|i| {
+                if i % 11025 == 0 {
+                    state = state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    let note = (state >> 33) % 24;
+                    frequency = 220.0 * 2f64.powf(note as f64 / 12.0);
+                }
+                let t = i as f64 / 44100.0;
+                (0.3 * (2.0 * std::f64::consts::PI * frequency * t).sin() * i16::MAX as f64) as i16
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_duplicate_recordings_in_playlist() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let (tempdir, backend) = setup_backend();
+            let media_dir = tempdir.path().join("media");
+            std::fs::create_dir_all(&media_dir).expect("create media dir");
+
+            let local_storage = local_storage(&backend).await;
+
+            let mut musics = Vec::new();
+            for (name, seed) in [("a.wav", 1), ("b.wav", 2), ("a copy.wav", 1)] {
+                let music_path = media_dir.join(name);
+                std::fs::write(&music_path, stereo_wav_file(&melody_frames(seed, 20)))
+                    .expect("write music");
+                musics.push(ArgDBAddMusic {
+                    loc: ease_client_schema::StorageEntryLoc {
+                        storage_id: local_storage.id,
+                        path: music_path.to_string_lossy().to_string(),
+                    },
+                    title: name.to_string(),
+                });
+            }
+
+            let cx = backend.get_context();
+            let (playlist_id, added) = cx
+                .database_server()
+                .create_playlist("playlist".to_string(), None, musics, 0, Default::default())
+                .expect("create playlist");
+            let ids: Vec<_> = added.iter().map(|m| m.id).collect();
+
+            fingerprint_musics(
+                cx,
+                ArgFingerprintMusics {
+                    ids: ids.clone(),
+                    force: false,
+                },
+            )
+            .await
+            .expect("fingerprint musics");
+
+            let groups = find_playlist_duplicates(cx, playlist_id).expect("find duplicates");
+            assert_eq!(groups.len(), 1);
+            let group: Vec<_> = groups[0].musics.iter().map(|m| m.meta.id).collect();
+            assert_eq!(group, vec![ids[0], ids[2]]);
+        });
+    }
+
     fn utf16le_bytes_without_bom(text: &str) -> Vec<u8> {
         let mut out = Vec::new();
         for unit in text.encode_utf16() {
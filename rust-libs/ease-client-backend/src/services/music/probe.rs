@@ -0,0 +1,237 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use ease_client_schema::{AudioStreamInfo, MusicId, MusicModel};
+
+use crate::{
+    ctx::BackendContext,
+    error::BResult,
+    repositories::{library::ArgDBUpdateMusicTags, music::AddedMusic},
+    services::{
+        get_storage_backend,
+        tags::{probe_stream_info, read_embedded_tags, EntryRangeReader, TagQuery},
+    },
+};
+
+use super::extract_cover_from;
+
+#[derive(Default)]
+pub(crate) struct MusicState {
+    /// Musics whose file is being read in the background, so loading a music
+    /// again doesn't start a second read of the same file.
+    probing: Mutex<HashSet<MusicId>>,
+}
+
+#[derive(Debug, uniffi::Record)]
+pub struct ArgProbeMusics {
+    pub ids: Vec<MusicId>,
+    /// Probe again and overwrite the duration reported by the player.
+    pub force: bool,
+}
+
+/// Probes the duration and audio stream info of a music from its container
+/// headers. Musics are probed once unless `force` is set.
+pub(crate) async fn probe_music(cx: &BackendContext, id: MusicId, force: bool) -> BResult<()> {
+    let Some(model) = cx.database_server().load_music(id)? else {
+        return Ok(());
+    };
+    if model.stream_probed && !force {
+        return Ok(());
+    }
+
+    let Some(backend) = get_storage_backend(cx, model.loc.storage_id)? else {
+        return Ok(());
+    };
+    let mut reader = EntryRangeReader::new(backend, model.loc.path.clone());
+    probe_music_from(cx, &model, force, &mut reader).await
+}
+
+/// Probes the stream of `model` through an already opened reader.
+async fn probe_music_from(
+    cx: &BackendContext,
+    model: &MusicModel,
+    force: bool,
+    reader: &mut EntryRangeReader,
+) -> BResult<()> {
+    let info = match probe_stream_info(reader).await {
+        Ok(info) => info,
+        Err(e) => {
+            // Recorded as probed so that loading the music doesn't read the
+            // file again. A rescan probes it again once the file changes.
+            tracing::warn!("fail to probe stream info of {:?}: {}", model.loc, e);
+            cx.database_server()
+                .update_music_stream_info(model.id, None, None)?;
+            return Ok(());
+        }
+    };
+
+    let duration = info
+        .and_then(|info| info.duration)
+        .filter(|_| force || model.duration.is_none());
+    let stream_info = info.map(|info| AudioStreamInfo {
+        sample_rate: info.sample_rate,
+        channels: info.channels,
+        bit_depth: info.bit_depth,
+        bitrate: info.bitrate,
+    });
+    cx.database_server()
+        .update_music_stream_info(model.id, duration, stream_info)?;
+    link_music_tags_from(cx, model, reader).await
+}
+
+/// Links a music to the artist and album of its tags, reading them from its
+/// file. Returns whether the music has an artist afterwards.
+pub(crate) async fn link_music_tags(cx: &BackendContext, id: MusicId) -> BResult<bool> {
+    let Some(model) = cx.database_server().load_music(id)? else {
+        return Ok(false);
+    };
+    if let Some(mut reader) = open_music_reader(cx, &model) {
+        link_music_tags_from(cx, &model, &mut reader).await?;
+    }
+    let linked = cx.database_server().load_music(id)?;
+    Ok(linked.is_some_and(|v| !v.artists.is_empty()))
+}
+
+/// Links a music to the artist and album of its tags, so that scanned musics
+/// show up in the library. Musics already linked, such as by the user, are
+/// left as they are.
+async fn link_music_tags_from(
+    cx: &BackendContext,
+    model: &MusicModel,
+    reader: &mut EntryRangeReader,
+) -> BResult<()> {
+    if !model.artists.is_empty() || model.album.is_some() || !model.genres.is_empty() {
+        return Ok(());
+    }
+    let query = TagQuery {
+        text: true,
+        ..Default::default()
+    };
+    let text = match read_embedded_tags(reader, query).await {
+        Ok(tags) => tags.text,
+        Err(e) => {
+            tracing::warn!("fail to read tags of {:?}: {}", model.loc, e);
+            return Ok(());
+        }
+    };
+    if text.artist.is_none() && text.album.is_none() {
+        return Ok(());
+    }
+    cx.database_server().link_unlinked_music_tags(
+        model.id,
+        ArgDBUpdateMusicTags {
+            artists: text.artist.into_iter().collect(),
+            album: text.album,
+            track_number: model.track_number,
+            disc_number: model.disc_number,
+            ..Default::default()
+        },
+    )?;
+    Ok(())
+}
+
+pub(crate) async fn probe_musics(cx: &BackendContext, arg: ArgProbeMusics) -> BResult<()> {
+    for id in arg.ids {
+        probe_music(cx, id, arg.force).await?;
+    }
+    Ok(())
+}
+
+pub(crate) fn new_music_ids(added: &[AddedMusic]) -> Vec<MusicId> {
+    added.iter().filter(|m| !m.existed).map(|m| m.id).collect()
+}
+
+/// Probes newly added musics in the background so that playlists show their
+/// total duration before the musics are played.
+pub(crate) fn spawn_probe_musics(cx: &BackendContext, ids: Vec<MusicId>) {
+    spawn_probe(cx, ids, false);
+}
+
+/// Probes musics whose file changed in the background, overwriting what was
+/// probed from the previous file.
+pub(crate) fn spawn_reprobe_musics(cx: &BackendContext, ids: Vec<MusicId>) {
+    spawn_probe(cx, ids, true);
+}
+
+fn spawn_probe(cx: &BackendContext, ids: Vec<MusicId>, force: bool) {
+    if ids.is_empty() {
+        return;
+    }
+    let cx = cx.weak();
+    ease_client_tokio::tokio_runtime().spawn(async move {
+        for id in ids {
+            let Some(cx) = cx.upgrade() else {
+                return;
+            };
+            if let Err(e) = probe_music(&cx, id, force).await {
+                tracing::warn!("fail to probe music {:?}: {}", id, e);
+            }
+        }
+    });
+}
+
+/// Extracts the cover and probes the stream of a music in the background,
+/// whichever the row still lacks. `reader` is reused when given, so the head
+/// of the file already fetched for the embedded lyric isn't fetched again.
+pub(crate) fn spawn_probe_music_file(
+    cx: &BackendContext,
+    model: &MusicModel,
+    reader: Option<EntryRangeReader>,
+) {
+    let need_cover = model.cover.is_none() && !model.cover_probed;
+    let need_stream = !model.stream_probed;
+    if !need_cover && !need_stream {
+        return;
+    }
+    if !cx.music_state().probing.lock().unwrap().insert(model.id) {
+        return;
+    }
+    let model = model.clone();
+    let weak = cx.weak();
+    ease_client_tokio::tokio_runtime().spawn(async move {
+        let Some(cx) = weak.upgrade() else {
+            return;
+        };
+        let id = model.id;
+        if let Some(mut reader) = reader.or_else(|| open_music_reader(&cx, &model)) {
+            if need_cover {
+                if let Err(e) = extract_cover_from(&cx, &model, &mut reader).await {
+                    tracing::warn!("fail to extract cover of music {:?}: {}", id, e);
+                }
+            }
+            if need_stream {
+                if let Err(e) = probe_music_from(&cx, &model, false, &mut reader).await {
+                    tracing::warn!("fail to probe music {:?}: {}", id, e);
+                }
+            }
+        }
+        cx.music_state().probing.lock().unwrap().remove(&id);
+    });
+}
+
+/// Opens a reader on the file of a music, or returns `None` when its storage
+/// is gone or can't be loaded.
+pub(crate) fn open_music_reader(
+    cx: &BackendContext,
+    model: &MusicModel,
+) -> Option<EntryRangeReader> {
+    match get_storage_backend(cx, model.loc.storage_id) {
+        Ok(backend) => Some(EntryRangeReader::new(backend?, model.loc.path.clone())),
+        Err(e) => {
+            tracing::warn!(
+                "fail to get storage backend {:?}: {}",
+                model.loc.storage_id,
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Waits until the background read started by [`spawn_probe_music_file`] is
+/// done.
+#[cfg(test)]
+pub(crate) async fn wait_probe_music_file(cx: &BackendContext, id: MusicId) {
+    while cx.music_state().probing.lock().unwrap().contains(&id) {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+}
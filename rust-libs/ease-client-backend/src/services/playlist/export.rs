@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+
+use ease_client_schema::{PlaylistId, StorageEntryLoc, StorageId, StorageModel, StorageType};
+use quick_xml::escape::escape;
+use serde::Serialize;
+
+use crate::{
+    ctx::BackendContext,
+    error::{BError, BResult},
+    services::get_storage_backend,
+};
+
+use super::get_playlist;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum PlaylistExportFormat {
+    /// Extended M3U in UTF-8, with `#EXTINF` titles and durations.
+    M3u8,
+    Xspf,
+    /// Versioned JSON with tags and storage paths, see `JsonPlaylist`.
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Enum)]
+pub enum PlaylistExportPaths {
+    /// `file://` URLs for local storages and HTTP URLs for WebDAV and
+    /// OpenList. Musics on a storage without file URLs, like OneDrive, get
+    /// their storage path.
+    AbsoluteUrl,
+    /// Paths from the root of the storage of each music.
+    StorageRelative,
+    /// Paths relative to the directory the playlist file is written to.
+    /// Musics on another storage get absolute URLs.
+    RelativeTo { dir: StorageEntryLoc },
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgExportPlaylist {
+    pub id: PlaylistId,
+    pub format: PlaylistExportFormat,
+    pub paths: PlaylistExportPaths,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgExportPlaylistToStorage {
+    pub export: ArgExportPlaylist,
+    /// Directory the file is written to, under its suggested name. Pass it as
+    /// `PlaylistExportPaths::RelativeTo` too to write relative paths.
+    pub dir: StorageEntryLoc,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ExportedPlaylist {
+    /// Suggested file name, from the playlist title.
+    pub file_name: String,
+    pub bytes: Vec<u8>,
+}
+
+/// The JSON export format. Fields may be added in the same version, so
+/// readers should ignore unknown ones.
+#[derive(Debug, Serialize)]
+pub(crate) struct JsonPlaylist {
+    /// Currently 1.
+    pub version: u32,
+    pub title: String,
+    /// Milliseconds since the Unix epoch.
+    pub created_time: i64,
+    pub musics: Vec<JsonPlaylistMusic>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct JsonPlaylistMusic {
+    pub title: String,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    pub duration_ms: Option<u64>,
+    /// Location written as asked by `PlaylistExportPaths`.
+    pub location: String,
+    /// Alias of the storage of the music.
+    pub storage: String,
+    /// Path of the music from the root of its storage.
+    pub path: String,
+}
+
+fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(|v| urlencoding::encode(v).into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn storage_url(storage: &StorageModel, path: &str) -> Option<String> {
+    let addr = storage.addr.trim_end_matches('/');
+    match storage.typ {
+        StorageType::Local => Some(format!("file://{}", encode_path(path))),
+        StorageType::Webdav => Some(format!("{addr}{}", encode_path(path))),
+        StorageType::OpenList => Some(format!("{addr}/d{}", encode_path(path))),
+        StorageType::OneDrive => None,
+    }
+}
+
+/// Path of `path` from the directory `dir`, both absolute.
+fn relative_path(dir: &str, path: &str) -> String {
+    let dir: Vec<&str> = dir.split('/').filter(|v| !v.is_empty()).collect();
+    let path: Vec<&str> = path.split('/').filter(|v| !v.is_empty()).collect();
+    let file_dir = &path[..path.len().saturating_sub(1)];
+    let common = dir
+        .iter()
+        .zip(file_dir.iter())
+        .take_while(|(lhs, rhs)| lhs == rhs)
+        .count();
+    let mut parts: Vec<&str> = vec![".."; dir.len() - common];
+    parts.extend(&path[common..]);
+    parts.join("/")
+}
+
+/// A location as written to the file, and whether it is a URL already.
+struct Location {
+    value: String,
+    is_url: bool,
+}
+
+fn locate(
+    storages: &HashMap<StorageId, StorageModel>,
+    paths: &PlaylistExportPaths,
+    loc: &StorageEntryLoc,
+) -> Location {
+    let url_or_path = |loc: &StorageEntryLoc| match storages
+        .get(&loc.storage_id)
+        .and_then(|storage| storage_url(storage, &loc.path))
+    {
+        Some(value) => Location {
+            value,
+            is_url: true,
+        },
+        None => Location {
+            value: loc.path.clone(),
+            is_url: false,
+        },
+    };
+    match paths {
+        PlaylistExportPaths::AbsoluteUrl => url_or_path(loc),
+        PlaylistExportPaths::StorageRelative => Location {
+            value: loc.path.clone(),
+            is_url: false,
+        },
+        PlaylistExportPaths::RelativeTo { dir } if dir.storage_id == loc.storage_id => Location {
+            value: relative_path(&dir.path, &loc.path),
+            is_url: false,
+        },
+        PlaylistExportPaths::RelativeTo { .. } => url_or_path(loc),
+    }
+}
+
+struct ExportedMusic {
+    title: String,
+    artists: Vec<String>,
+    album: Option<String>,
+    duration_ms: Option<u64>,
+    location: Location,
+    storage: String,
+    path: String,
+}
+
+fn write_m3u8(title: &str, musics: &[ExportedMusic]) -> String {
+    let mut ret = format!("#EXTM3U\n#PLAYLIST:{}\n", title.replace('\n', " "));
+    for music in musics {
+        let secs = music
+            .duration_ms
+            .map(|v| ((v + 500) / 1000) as i64)
+            .unwrap_or(-1);
+        ret.push_str(&format!(
+            "#EXTINF:{secs},{}\n{}\n",
+            music.title.replace('\n', " "),
+            music.location.value
+        ));
+    }
+    ret
+}
+
+fn write_xspf(title: &str, musics: &[ExportedMusic]) -> String {
+    let mut ret = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+    );
+    ret.push_str(&format!(
+        "  <title>{}</title>\n  <trackList>\n",
+        escape(title)
+    ));
+    for music in musics {
+        let location = match music.location.is_url {
+            true => music.location.value.clone(),
+            false => encode_path(&music.location.value),
+        };
+        ret.push_str("    <track>\n");
+        ret.push_str(&format!(
+            "      <location>{}</location>\n",
+            escape(&location)
+        ));
+        ret.push_str(&format!("      <title>{}</title>\n", escape(&music.title)));
+        if !music.artists.is_empty() {
+            let creator = music.artists.join(", ");
+            ret.push_str(&format!("      <creator>{}</creator>\n", escape(&creator)));
+        }
+        if let Some(album) = music.album.as_ref() {
+            ret.push_str(&format!("      <album>{}</album>\n", escape(album)));
+        }
+        if let Some(duration) = music.duration_ms {
+            ret.push_str(&format!("      <duration>{duration}</duration>\n"));
+        }
+        ret.push_str("    </track>\n");
+    }
+    ret.push_str("  </trackList>\n</playlist>\n");
+    ret
+}
+
+fn write_json(title: &str, created_time: i64, musics: Vec<ExportedMusic>) -> BResult<Vec<u8>> {
+    let playlist = JsonPlaylist {
+        version: 1,
+        title: title.to_string(),
+        created_time,
+        musics: musics
+            .into_iter()
+            .map(|m| JsonPlaylistMusic {
+                title: m.title,
+                artists: m.artists,
+                album: m.album,
+                duration_ms: m.duration_ms,
+                location: m.location.value,
+                storage: m.storage,
+                path: m.path,
+            })
+            .collect(),
+    };
+    serde_json::to_vec_pretty(&playlist).map_err(|e| BError::CustomError {
+        message: format!("fail to write playlist: {e}"),
+    })
+}
+
+fn file_name(title: &str, format: PlaylistExportFormat) -> String {
+    let stem: String = title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let stem = match stem.trim() {
+        "" => "playlist",
+        stem => stem,
+    };
+    let ext = match format {
+        PlaylistExportFormat::M3u8 => "m3u8",
+        PlaylistExportFormat::Xspf => "xspf",
+        PlaylistExportFormat::Json => "json",
+    };
+    format!("{stem}.{ext}")
+}
+
+/// Writes a playlist, smart ones as currently evaluated, as a playlist file.
+pub fn export_playlist(cx: &BackendContext, arg: ArgExportPlaylist) -> BResult<ExportedPlaylist> {
+    let Some(playlist) = get_playlist(cx, arg.id)? else {
+        return Err(BError::PlaylistNotFound(arg.id));
+    };
+    let db = cx.database_server();
+    let storages: HashMap<StorageId, StorageModel> =
+        db.load_storages()?.into_iter().map(|v| (v.id, v)).collect();
+
+    let mut musics: Vec<ExportedMusic> = Vec::with_capacity(playlist.musics.len());
+    for abstr in playlist.musics.iter() {
+        let Some(model) = db.load_music(abstr.meta.id)? else {
+            continue;
+        };
+        let mut artists: Vec<String> = Default::default();
+        for id in model.artists.iter() {
+            artists.extend(db.load_artist(*id)?.map(|v| v.name));
+        }
+        let album = match model.album {
+            Some(id) => db.load_album(id)?.map(|v| v.title),
+            None => None,
+        };
+        musics.push(ExportedMusic {
+            title: abstr.meta.title.clone(),
+            artists,
+            album,
+            duration_ms: abstr.meta.duration.map(|v| v.as_millis() as u64),
+            location: locate(&storages, &arg.paths, &model.loc),
+            storage: storages
+                .get(&model.loc.storage_id)
+                .map(|v| v.alias.clone())
+                .unwrap_or_default(),
+            path: model.loc.path,
+        });
+    }
+
+    let title = playlist.abstr.meta.title.as_str();
+    let created_time = playlist.abstr.meta.created_time.as_millis() as i64;
+    let bytes = match arg.format {
+        PlaylistExportFormat::M3u8 => write_m3u8(title, &musics).into_bytes(),
+        PlaylistExportFormat::Xspf => write_xspf(title, &musics).into_bytes(),
+        PlaylistExportFormat::Json => write_json(title, created_time, musics)?,
+    };
+    Ok(ExportedPlaylist {
+        file_name: file_name(title, arg.format),
+        bytes,
+    })
+}
+
+/// Exports a playlist and writes the file to a storage directory, replacing
+/// a file of the same name. Returns the location of the file.
+pub async fn export_playlist_to_storage(
+    cx: &BackendContext,
+    arg: ArgExportPlaylistToStorage,
+) -> BResult<StorageEntryLoc> {
+    let Some(backend) = get_storage_backend(cx, arg.dir.storage_id)? else {
+        return Err(BError::CustomError {
+            message: format!("storage {:?} not found", arg.dir.storage_id),
+        });
+    };
+    let exported = export_playlist(cx, arg.export)?;
+    let path = format!(
+        "{}/{}",
+        arg.dir.path.trim_end_matches('/'),
+        exported.file_name
+    );
+    backend.put(path.clone(), exported.bytes.into()).await?;
+    Ok(StorageEntryLoc {
+        storage_id: arg.dir.storage_id,
+        path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::relative_path;
+
+    #[test]
+    fn writes_paths_relative_to_export_dir() {
+        assert_eq!("a.mp3", relative_path("/music", "/music/a.mp3"));
+        assert_eq!("b/a.mp3", relative_path("/music/", "/music/b/a.mp3"));
+        assert_eq!(
+            "../songs/a.mp3",
+            relative_path("/music/lists", "/music/songs/a.mp3")
+        );
+        assert_eq!("../../a.mp3", relative_path("/x/y", "/a.mp3"));
+    }
+}
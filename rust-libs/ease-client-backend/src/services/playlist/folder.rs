@@ -0,0 +1,207 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use ease_client_schema::{
+    FolderRemovedPolicy, MusicId, PlaylistFolderModel, PlaylistId, StorageEntryLoc,
+};
+use ease_order_key::OrderKey;
+use ease_remote_storage::Entry;
+use globset::{Glob, GlobMatcher};
+
+use crate::{
+    ctx::BackendContext,
+    error::{BError, BResult},
+    objects::StorageEntryType,
+    repositories::music::ArgDBAddMusic,
+    services::{get_storage_backend, new_music_ids, spawn_probe_musics, storage_entry_type},
+};
+
+/// How often the background task looks for folder playlists due to refresh.
+const FOLDER_SCHEDULE_TICK: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Default, uniffi::Record)]
+pub struct FolderRefreshResult {
+    pub added: Vec<MusicId>,
+    pub removed: Vec<MusicId>,
+    /// Musics whose file is gone but that are kept, marked as missing.
+    pub missing: Vec<MusicId>,
+    pub unchanged: u64,
+}
+
+pub(crate) fn compile_folder_glob(glob: Option<&str>) -> BResult<Option<GlobMatcher>> {
+    let Some(glob) = glob else {
+        return Ok(None);
+    };
+    Glob::new(glob)
+        .map(|v| Some(v.compile_matcher()))
+        .map_err(|e| BError::CustomError {
+            message: format!("invalid glob {glob:?}: {e}"),
+        })
+}
+
+fn dir_prefix(dir: &str) -> String {
+    format!("{}/", dir.trim_end_matches('/'))
+}
+
+/// Returns the path of `path` relative to the bound folder when the folder
+/// covers it.
+fn relative_in_folder<'a>(folder: &PlaylistFolderModel, path: &'a str) -> Option<&'a str> {
+    let rel = path.strip_prefix(&dir_prefix(&folder.dir.path))?;
+    if !folder.recursive && rel.contains('/') {
+        return None;
+    }
+    Some(rel)
+}
+
+async fn list_folder_musics(
+    cx: &BackendContext,
+    folder: &PlaylistFolderModel,
+) -> BResult<Vec<Entry>> {
+    let Some(backend) = get_storage_backend(cx, folder.dir.storage_id)? else {
+        return Err(BError::AssetNotFound);
+    };
+    let glob = compile_folder_glob(folder.glob.as_deref())?;
+
+    let mut ret: Vec<Entry> = Default::default();
+    let mut dirs = vec![folder.dir.path.clone()];
+    while let Some(dir) = dirs.pop() {
+        for entry in backend.list(dir).await? {
+            if entry.is_dir {
+                if folder.recursive {
+                    dirs.push(entry.path);
+                }
+                continue;
+            }
+            if storage_entry_type(&entry.path, false) != StorageEntryType::Music {
+                continue;
+            }
+            let matched = match (relative_in_folder(folder, &entry.path), glob.as_ref()) {
+                (Some(rel), Some(glob)) => glob.is_match(rel),
+                (Some(_), None) => true,
+                (None, _) => false,
+            };
+            if matched {
+                ret.push(entry);
+            }
+        }
+    }
+    ret.sort_by(|lhs, rhs| lhs.path.cmp(&rhs.path));
+    Ok(ret)
+}
+
+/// Lists the bound folder of a playlist and syncs the playlist with it. New
+/// music files are appended, so the order of the musics that remain is kept.
+/// Musics added to the playlist from outside the folder are left alone.
+/// Fails with [`BError::AssetNotFound`] when the storage of the folder is
+/// gone.
+pub async fn refresh_playlist_folder(
+    cx: &BackendContext,
+    id: PlaylistId,
+) -> BResult<FolderRefreshResult> {
+    let Some(mut folder) = cx.database_server().load_playlist_folder(id)? else {
+        return Err(BError::PlaylistNotFound(id));
+    };
+    let entries = list_folder_musics(cx, &folder).await?;
+    let musics = cx.database_server().load_musics_by_playlist_id(id)?;
+
+    let listed: HashSet<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+    let in_playlist: HashMap<&str, MusicId> = musics
+        .iter()
+        .filter(|m| m.loc.storage_id == folder.dir.storage_id)
+        .map(|m| (m.loc.path.as_str(), m.id))
+        .collect();
+
+    let mut ret = FolderRefreshResult::default();
+    let mut gone: Vec<MusicId> = Default::default();
+    for m in musics.iter() {
+        if m.loc.storage_id != folder.dir.storage_id
+            || relative_in_folder(&folder, &m.loc.path).is_none()
+        {
+            continue;
+        }
+        if listed.contains(m.loc.path.as_str()) {
+            ret.unchanged += 1;
+        } else {
+            gone.push(m.id);
+        }
+    }
+
+    let to_add: Vec<ArgDBAddMusic> = entries
+        .iter()
+        .filter(|e| !in_playlist.contains_key(e.path.as_str()))
+        .map(|e| ArgDBAddMusic {
+            loc: StorageEntryLoc {
+                storage_id: folder.dir.storage_id,
+                path: e.path.clone(),
+            },
+            title: e.name.clone(),
+        })
+        .collect();
+    if !to_add.is_empty() {
+        let last_order = musics
+            .last()
+            .map(|m| OrderKey::wrap(m.order.clone()))
+            .unwrap_or_default();
+        let added = cx
+            .database_server()
+            .add_musics_to_playlist(id, to_add, last_order)?;
+        spawn_probe_musics(cx, new_music_ids(&added));
+        ret.added = added.into_iter().map(|m| m.id).collect();
+    }
+
+    match folder.on_removed {
+        FolderRemovedPolicy::Remove => {
+            for music_id in gone.iter() {
+                cx.database_server()
+                    .remove_music_from_playlist(id, *music_id)?;
+            }
+            ret.removed = gone;
+            folder.missing.clear();
+        }
+        FolderRemovedPolicy::Mark => {
+            ret.missing = gone.clone();
+            folder.missing = gone;
+        }
+    }
+
+    folder.last_refreshed = cx.current_time().as_millis() as i64;
+    cx.database_server()
+        .save_playlist_folder(id, Some(folder))?;
+    Ok(ret)
+}
+
+/// Refreshes the folder playlists whose refresh interval has passed.
+pub(crate) async fn refresh_due_playlist_folders(cx: &BackendContext) -> BResult<()> {
+    let now = cx.current_time().as_millis() as i64;
+    for (id, folder) in cx.database_server().load_playlist_folders()? {
+        let Some(interval) = folder.refresh_interval else {
+            continue;
+        };
+        if now - folder.last_refreshed < interval.as_millis() as i64 {
+            continue;
+        }
+        if let Err(e) = refresh_playlist_folder(cx, id).await {
+            tracing::warn!("fail to refresh folder of playlist {:?}: {}", id, e);
+        }
+    }
+    Ok(())
+}
+
+/// Periodically refreshes the folder playlists that are due, until the
+/// backend is dropped.
+pub(crate) fn spawn_playlist_folder_schedule(cx: &BackendContext) {
+    let cx = cx.weak();
+    ease_client_tokio::tokio_runtime().spawn(async move {
+        loop {
+            tokio::time::sleep(FOLDER_SCHEDULE_TICK).await;
+            let Some(cx) = cx.upgrade() else {
+                return;
+            };
+            if let Err(e) = refresh_due_playlist_folders(&cx).await {
+                tracing::warn!("fail to refresh folder playlists: {}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,542 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    time::Duration,
+};
+
+use ease_client_schema::{MusicId, PlaylistId, StorageEntryLoc};
+use ease_remote_storage::Entry;
+use quick_xml::{events::Event, Reader};
+
+use crate::{
+    ctx::BackendContext,
+    error::{BError, BResult},
+    objects::StorageEntryType,
+    repositories::music::ArgDBAddMusic,
+    services::{
+        get_storage_backend, load_storage_entry_data, lyrics::decode_lyric_text,
+        spawn_probe_musics, spawn_reprobe_musics, storage_entry_type,
+    },
+};
+
+use super::next_playlist_order;
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgImportPlaylist {
+    /// M3U, M3U8, PLS or XSPF file to import.
+    pub loc: StorageEntryLoc,
+    /// Title of the created playlist. Defaults to the title in the file, then
+    /// to the file name.
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum UnresolvedPlaylistEntryReason {
+    /// The entry is a URL other than `file://`.
+    Remote,
+    NotFound,
+    NotMusic,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct UnresolvedPlaylistEntry {
+    /// Location as written in the playlist file.
+    pub location: String,
+    pub title: Option<String>,
+    pub reason: UnresolvedPlaylistEntryReason,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct PlaylistImportResult {
+    pub id: PlaylistId,
+    pub music_ids: Vec<MusicId>,
+    pub unresolved: Vec<UnresolvedPlaylistEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlaylistFormat {
+    M3u,
+    Pls,
+    Xspf,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct ParsedPlaylistEntry {
+    location: String,
+    title: Option<String>,
+    duration: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct ParsedPlaylist {
+    title: Option<String>,
+    entries: Vec<ParsedPlaylistEntry>,
+}
+
+fn detect_format(path: &str, text: &str) -> PlaylistFormat {
+    let ext = path.rsplit_once('.').map(|v| v.1.to_lowercase());
+    match ext.as_deref() {
+        Some("pls") => return PlaylistFormat::Pls,
+        Some("xspf") => return PlaylistFormat::Xspf,
+        Some("m3u") | Some("m3u8") => return PlaylistFormat::M3u,
+        _ => {}
+    }
+    let head = text.trim_start();
+    if head.starts_with('<') {
+        PlaylistFormat::Xspf
+    } else if head.to_lowercase().starts_with("[playlist]") {
+        PlaylistFormat::Pls
+    } else {
+        PlaylistFormat::M3u
+    }
+}
+
+/// Parses a duration in seconds. Players write -1 for an unknown duration.
+fn parse_secs(v: &str) -> Option<Duration> {
+    let secs: f64 = v.trim().parse().ok()?;
+    (secs.is_finite() && secs > 0.0).then(|| Duration::from_secs_f64(secs))
+}
+
+fn non_empty(v: &str) -> Option<String> {
+    let v = v.trim();
+    (!v.is_empty()).then(|| v.to_string())
+}
+
+fn parse_m3u(text: &str) -> ParsedPlaylist {
+    let mut ret = ParsedPlaylist::default();
+    let mut info: Option<(Option<Duration>, Option<String>)> = None;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(v) = line.strip_prefix("#EXTINF:") {
+            // `#EXTINF:<secs> [attributes],<title>`
+            let (head, title) = v.split_once(',').unwrap_or((v, ""));
+            let secs = head.split_whitespace().next().unwrap_or_default();
+            info = Some((parse_secs(secs), non_empty(title)));
+        } else if let Some(v) = line.strip_prefix("#PLAYLIST:") {
+            ret.title = non_empty(v);
+        } else if !line.starts_with('#') {
+            let (duration, title) = info.take().unwrap_or_default();
+            ret.entries.push(ParsedPlaylistEntry {
+                location: line.to_string(),
+                title,
+                duration,
+            });
+        }
+    }
+    ret
+}
+
+fn parse_pls(text: &str) -> ParsedPlaylist {
+    let mut entries: BTreeMap<u32, ParsedPlaylistEntry> = Default::default();
+    for line in text.lines().map(str::trim) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let field = ["file", "title", "length"]
+            .into_iter()
+            .find_map(|f| Some((f, key.strip_prefix(f)?.parse::<u32>().ok()?)));
+        let Some((field, index)) = field else {
+            continue;
+        };
+        let entry = entries.entry(index).or_default();
+        match field {
+            "file" => entry.location = value.trim().to_string(),
+            "title" => entry.title = non_empty(value),
+            _ => entry.duration = parse_secs(value),
+        }
+    }
+    ParsedPlaylist {
+        title: None,
+        entries: entries
+            .into_values()
+            .filter(|v| !v.location.is_empty())
+            .collect(),
+    }
+}
+
+fn parse_xspf(text: &str) -> BResult<ParsedPlaylist> {
+    let mut reader = Reader::from_str(text);
+    reader.trim_text(true);
+    let mut ret = ParsedPlaylist::default();
+    let mut path: Vec<String> = Default::default();
+    let mut track: Option<ParsedPlaylistEntry> = None;
+    loop {
+        let text = match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_lowercase();
+                if name == "track" {
+                    track = Some(Default::default());
+                }
+                path.push(name);
+                continue;
+            }
+            Ok(Event::End(_)) => {
+                if path.pop().as_deref() == Some("track") {
+                    ret.entries
+                        .extend(track.take().filter(|v| !v.location.is_empty()));
+                }
+                continue;
+            }
+            Ok(Event::Text(e)) => e.unescape().map(|v| v.into_owned()),
+            Ok(Event::CData(e)) => Ok(String::from_utf8_lossy(&e.into_inner()).into_owned()),
+            Ok(Event::Eof) => break,
+            Ok(_) => continue,
+            Err(e) => Err(e),
+        }
+        .map_err(|e| BError::CustomError {
+            message: format!("invalid xspf playlist: {e}"),
+        })?;
+
+        let path: Vec<&str> = path.iter().map(String::as_str).collect();
+        match (path.as_slice(), track.as_mut()) {
+            (["playlist", "title"], _) => ret.title = non_empty(&text),
+            ([.., "track", "location"], Some(track)) if track.location.is_empty() => {
+                track.location = text.trim().to_string()
+            }
+            ([.., "track", "title"], Some(track)) => track.title = non_empty(&text),
+            ([.., "track", "duration"], Some(track)) => {
+                track.duration = text
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|v| *v > 0)
+                    .map(Duration::from_millis)
+            }
+            _ => {}
+        }
+    }
+    Ok(ret)
+}
+
+fn parse_playlist(format: PlaylistFormat, text: &str) -> BResult<ParsedPlaylist> {
+    match format {
+        PlaylistFormat::M3u => Ok(parse_m3u(text)),
+        PlaylistFormat::Pls => Ok(parse_pls(text)),
+        PlaylistFormat::Xspf => parse_xspf(text),
+    }
+}
+
+fn parent_dir(path: &str) -> &str {
+    match path.rsplit_once('/') {
+        Some(("", _)) | None => "/",
+        Some((parent, _)) => parent,
+    }
+}
+
+/// Whether a location is a URL, and not a Windows drive like `C:/`.
+fn url_scheme(location: &str) -> Option<&str> {
+    let (scheme, _) = location.split_once("://")?;
+    let valid = scheme.len() > 1
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then_some(scheme)
+}
+
+/// Turns a path written by any player into an absolute storage path. Windows
+/// separators and drive letters are dropped, relative paths are joined to
+/// `base_dir`, and `.` and `..` are folded.
+fn normalize_location(base_dir: &str, location: &str) -> String {
+    let path = location.replace('\\', "/");
+    let mut rest = path.as_str();
+    let drive = rest.strip_prefix('/').unwrap_or(rest);
+    let mut absolute = rest.starts_with('/');
+    if let [letter, b':', tail @ ..] = drive.as_bytes() {
+        if letter.is_ascii_alphabetic() && (tail.is_empty() || tail[0] == b'/') {
+            rest = &drive[2..];
+            absolute = true;
+        }
+    }
+
+    let mut parts: Vec<&str> = Default::default();
+    if !absolute {
+        parts.extend(base_dir.split('/').filter(|v| !v.is_empty()));
+    }
+    for part in rest.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+/// Lists the storage paths a playlist location may refer to, most likely
+/// first, or `None` for a remote URL. Plain paths are tried as written, then
+/// URL-decoded, since both are found in the wild.
+fn resolve_location(base_dir: &str, location: &str) -> Option<Vec<String>> {
+    let mut raw: Vec<String> = Default::default();
+    match url_scheme(location) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("file") => {
+            let rest = &location[scheme.len() + 3..];
+            let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+            raw.extend(urlencoding::decode(rest).ok().map(|v| v.into_owned()));
+        }
+        Some(_) => return None,
+        None => {
+            raw.push(location.to_string());
+            if location.contains('%') {
+                raw.extend(urlencoding::decode(location).ok().map(|v| v.into_owned()));
+            }
+        }
+    }
+
+    let mut ret: Vec<String> = Default::default();
+    for path in raw {
+        let path = normalize_location(base_dir, &path);
+        if !ret.contains(&path) {
+            ret.push(path);
+        }
+    }
+    Some(ret)
+}
+
+/// Finds the entry of a path in the listed directories. Paths written on
+/// Windows may differ in case from the file, so a case-insensitive match is
+/// accepted when there is no exact one.
+fn find_entry<'a>(listings: &'a HashMap<String, Vec<Entry>>, path: &str) -> Option<&'a Entry> {
+    let entries = listings.get(parent_dir(path))?;
+    entries
+        .iter()
+        .find(|e| e.path == path)
+        .or_else(|| {
+            let path = path.to_lowercase();
+            entries.iter().find(|e| e.path.to_lowercase() == path)
+        })
+        .filter(|e| !e.is_dir)
+}
+
+fn default_title(name: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem.to_string(),
+        _ => name.to_string(),
+    }
+}
+
+/// Creates a playlist from a playlist file on a storage. Entries are
+/// resolved against the directory of the file, and the ones whose file
+/// cannot be found are reported instead of added.
+pub async fn import_playlist(
+    cx: &BackendContext,
+    arg: ArgImportPlaylist,
+) -> BResult<PlaylistImportResult> {
+    let Some(data) = load_storage_entry_data(cx, &arg.loc).await? else {
+        return Err(BError::AssetNotFound);
+    };
+    let text = decode_lyric_text(data.bytes.as_slice(), data.content_type.as_deref()).text;
+    let parsed = parse_playlist(detect_format(&arg.loc.path, &text), &text)?;
+    let Some(backend) = get_storage_backend(cx, arg.loc.storage_id)? else {
+        return Err(BError::AssetNotFound);
+    };
+
+    let base_dir = parent_dir(&arg.loc.path);
+    let mut listings: HashMap<String, Vec<Entry>> = Default::default();
+    let mut unresolved: Vec<UnresolvedPlaylistEntry> = Default::default();
+    let mut resolved: Vec<(Entry, ParsedPlaylistEntry)> = Default::default();
+    let mut seen: HashSet<String> = Default::default();
+    for entry in parsed.entries {
+        let Some(paths) = resolve_location(base_dir, &entry.location) else {
+            unresolved.push(UnresolvedPlaylistEntry {
+                location: entry.location,
+                title: entry.title,
+                reason: UnresolvedPlaylistEntryReason::Remote,
+            });
+            continue;
+        };
+        for path in paths.iter() {
+            let dir = parent_dir(path);
+            if listings.contains_key(dir) {
+                continue;
+            }
+            let entries = match backend.list(dir.to_string()).await {
+                Ok(entries) => entries,
+                Err(e) if e.is_not_found() => Default::default(),
+                Err(e) => return Err(e.into()),
+            };
+            listings.insert(dir.to_string(), entries);
+        }
+
+        let Some(found) = paths.iter().find_map(|p| find_entry(&listings, p)) else {
+            unresolved.push(UnresolvedPlaylistEntry {
+                location: entry.location,
+                title: entry.title,
+                reason: UnresolvedPlaylistEntryReason::NotFound,
+            });
+            continue;
+        };
+        if storage_entry_type(&found.path, false) != StorageEntryType::Music {
+            unresolved.push(UnresolvedPlaylistEntry {
+                location: entry.location,
+                title: entry.title,
+                reason: UnresolvedPlaylistEntryReason::NotMusic,
+            });
+            continue;
+        }
+        if seen.insert(found.path.clone()) {
+            resolved.push((found.clone(), entry));
+        }
+    }
+
+    let title = arg
+        .title
+        .or(parsed.title)
+        .unwrap_or_else(|| default_title(&data.name));
+    let musics = resolved
+        .iter()
+        .map(|(found, entry)| ArgDBAddMusic {
+            loc: StorageEntryLoc {
+                storage_id: arg.loc.storage_id,
+                path: found.path.clone(),
+            },
+            title: entry.title.clone().unwrap_or_else(|| found.name.clone()),
+        })
+        .collect();
+    let (id, added) = cx.database_server().create_playlist(
+        title,
+        None,
+        musics,
+        cx.current_time().as_millis() as i64,
+        next_playlist_order(cx)?,
+    )?;
+
+    // Durations from the file show until the musics are probed, and the
+    // probe overwrites them with the exact ones.
+    let mut to_probe: Vec<MusicId> = Default::default();
+    let mut to_reprobe: Vec<MusicId> = Default::default();
+    for (music, (_, entry)) in added.iter().zip(resolved.iter()) {
+        if music.existed {
+            continue;
+        }
+        match entry.duration {
+            Some(duration) => {
+                cx.database_server()
+                    .update_music_total_duration(music.id, duration)?;
+                to_reprobe.push(music.id);
+            }
+            None => to_probe.push(music.id),
+        }
+    }
+    spawn_probe_musics(cx, to_probe);
+    spawn_reprobe_musics(cx, to_reprobe);
+
+    Ok(PlaylistImportResult {
+        id,
+        music_ids: added.into_iter().map(|m| m.id).collect(),
+        unresolved,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{
+        detect_format, parse_m3u, parse_pls, parse_xspf, resolve_location, PlaylistFormat,
+    };
+
+    #[test]
+    fn parses_extended_m3u() {
+        let parsed = parse_m3u(
+            "#EXTM3U\n#PLAYLIST:Road trip\n#EXTINF:215 tvg-id=\"x\",Artist - Song\nsong.mp3\n\n#EXTINF:-1,\nother.flac\nlast.ogg\n",
+        );
+        assert_eq!(Some("Road trip".to_string()), parsed.title);
+        assert_eq!(3, parsed.entries.len());
+        assert_eq!("song.mp3", parsed.entries[0].location);
+        assert_eq!(Some("Artist - Song".to_string()), parsed.entries[0].title);
+        assert_eq!(Some(Duration::from_secs(215)), parsed.entries[0].duration);
+        assert_eq!(None, parsed.entries[1].title);
+        assert_eq!(None, parsed.entries[1].duration);
+        assert_eq!(None, parsed.entries[2].title);
+    }
+
+    #[test]
+    fn parses_pls_by_index() {
+        let parsed = parse_pls(
+            "[playlist]\nFile2=b.mp3\nTitle2=B\nFile1=a.mp3\nLength1=61\nLength2=-1\nNumberOfEntries=2\nVersion=2\n",
+        );
+        let locations: Vec<_> = parsed.entries.iter().map(|v| v.location.as_str()).collect();
+        assert_eq!(vec!["a.mp3", "b.mp3"], locations);
+        assert_eq!(Some(Duration::from_secs(61)), parsed.entries[0].duration);
+        assert_eq!(Some("B".to_string()), parsed.entries[1].title);
+        assert_eq!(None, parsed.entries[1].duration);
+    }
+
+    #[test]
+    fn parses_xspf_tracks() {
+        let parsed = parse_xspf(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<playlist version="1" xmlns="http://xspf.org/ns/0/">
+  <title>Evening &amp; Night</title>
+  <trackList>
+    <track>
+      <location>file:///C:/Music/A%20Song.mp3</location>
+      <title>A Song</title>
+      <duration>180500</duration>
+    </track>
+    <track><location>b.mp3</location></track>
+    <track><title>No location</title></track>
+  </trackList>
+</playlist>"#,
+        )
+        .expect("parse xspf");
+        assert_eq!(Some("Evening & Night".to_string()), parsed.title);
+        assert_eq!(2, parsed.entries.len());
+        assert_eq!("file:///C:/Music/A%20Song.mp3", parsed.entries[0].location);
+        assert_eq!(Some("A Song".to_string()), parsed.entries[0].title);
+        assert_eq!(
+            Some(Duration::from_millis(180500)),
+            parsed.entries[0].duration
+        );
+        assert_eq!(None, parsed.entries[1].title);
+    }
+
+    #[test]
+    fn detects_format_by_extension_then_content() {
+        assert_eq!(PlaylistFormat::M3u, detect_format("/a.M3U8", ""));
+        assert_eq!(PlaylistFormat::Pls, detect_format("/a.txt", "[playlist]\n"));
+        assert_eq!(PlaylistFormat::Xspf, detect_format("/a", "<?xml ?>"));
+    }
+
+    #[test]
+    fn resolves_relative_absolute_and_windows_paths() {
+        let base = "/music/lists";
+        let resolve = |v: &str| resolve_location(base, v);
+        assert_eq!(
+            Some(vec!["/music/lists/a.mp3".to_string()]),
+            resolve("./a.mp3")
+        );
+        assert_eq!(
+            Some(vec!["/music/b/c.mp3".to_string()]),
+            resolve("../b/c.mp3")
+        );
+        assert_eq!(
+            Some(vec!["/other/d.mp3".to_string()]),
+            resolve("/other/d.mp3")
+        );
+        assert_eq!(
+            Some(vec!["/music/b/e.mp3".to_string()]),
+            resolve("..\\b\\e.mp3")
+        );
+        assert_eq!(
+            Some(vec!["/Music/f.mp3".to_string()]),
+            resolve("C:\\Music\\f.mp3")
+        );
+        assert_eq!(
+            Some(vec!["/Music/A Song.mp3".to_string()]),
+            resolve("file:///C:/Music/A%20Song.mp3")
+        );
+        assert_eq!(
+            Some(vec![
+                "/music/lists/100%25.mp3".to_string(),
+                "/music/lists/100%.mp3".to_string()
+            ]),
+            resolve("100%25.mp3")
+        );
+        assert_eq!(None, resolve("https://example.com/stream.mp3"));
+    }
+}
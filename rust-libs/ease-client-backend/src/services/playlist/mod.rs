@@ -1,6 +1,7 @@
 use std::time::Duration;
 
-use ease_client_schema::{DataSourceKey, MusicId, PlaylistId, PlaylistModel};
+use ease_client_schema::{DataSourceKey, MusicId, MusicModel, PlaylistId, PlaylistModel};
+use ease_order_key::OrderKey;
 
 use crate::{
     ctx::BackendContext,
@@ -10,7 +11,17 @@ use crate::{
 
 use super::music::build_music_abstract;
 
-fn compute_musics_duration(list: &Vec<MusicAbstract>) -> Option<Duration> {
+mod export;
+mod folder;
+mod import;
+mod smart;
+
+pub use export::*;
+pub use folder::*;
+pub use import::*;
+pub(crate) use smart::*;
+
+pub(crate) fn compute_musics_duration(list: &Vec<MusicAbstract>) -> Option<Duration> {
     let mut sum: Duration = Default::default();
     for v in list {
         if let Some(v) = v.meta.duration {
@@ -26,6 +37,7 @@ pub(crate) fn build_playlist_meta(
     _cx: &BackendContext,
     model: PlaylistModel,
     first_cover_music_id: Option<MusicId>,
+    is_smart: bool,
 ) -> PlaylistMeta {
     let cover_loc = model.picture;
     let show_cover = if let Some(loc) = cover_loc.clone() {
@@ -45,17 +57,18 @@ pub(crate) fn build_playlist_meta(
         show_cover,
         created_time: Duration::from_millis(model.created_time as u64),
         order: model.order,
+        is_smart,
     }
 }
 
-pub(crate) fn build_playlist_abstract(
+fn build_playlist_abstract_with_musics(
     cx: &BackendContext,
     model: PlaylistModel,
-) -> BResult<(PlaylistAbstract, Vec<MusicAbstract>)> {
-    let id = model.id;
-    let musics = cx.database_server().load_musics_by_playlist_id(id)?;
+    musics: Vec<MusicModel>,
+    is_smart: bool,
+) -> (PlaylistAbstract, Vec<MusicAbstract>) {
     let first_cover_music_id = musics.iter().find(|m| m.cover.is_some()).map(|v| v.id);
-    let meta = build_playlist_meta(cx, model, first_cover_music_id);
+    let meta = build_playlist_meta(cx, model, first_cover_music_id, is_smart);
 
     let musics = musics
         .into_iter()
@@ -69,7 +82,26 @@ pub(crate) fn build_playlist_abstract(
         duration,
     };
 
-    Ok((abstr, musics))
+    (abstr, musics)
+}
+
+/// Builds a playlist with its musics. The musics of a smart playlist are
+/// evaluated from its rule on each call.
+pub(crate) fn build_playlist_abstract(
+    cx: &BackendContext,
+    model: PlaylistModel,
+) -> BResult<(PlaylistAbstract, Vec<MusicAbstract>)> {
+    let smart = cx.database_server().load_smart_playlist(model.id)?;
+    let musics = match smart.as_ref() {
+        Some(rule) => evaluate_smart_playlist(&SmartLibrary::load(cx)?, rule)?,
+        None => cx.database_server().load_musics_by_playlist_id(model.id)?,
+    };
+    Ok(build_playlist_abstract_with_musics(
+        cx,
+        model,
+        musics,
+        smart.is_some(),
+    ))
 }
 
 pub fn get_playlist(cx: &BackendContext, arg: PlaylistId) -> BResult<Option<Playlist>> {
@@ -84,12 +116,34 @@ pub fn get_playlist(cx: &BackendContext, arg: PlaylistId) -> BResult<Option<Play
     Ok(Some(Playlist { abstr, musics }))
 }
 
+/// Returns an order after every playlist, for a playlist added at the end.
+pub(crate) fn next_playlist_order(cx: &BackendContext) -> BResult<OrderKey> {
+    let last_order = cx
+        .database_server()
+        .load_playlists()?
+        .pop()
+        .map(|v| OrderKey::wrap(v.order))
+        .unwrap_or_default();
+    Ok(OrderKey::greater(&last_order))
+}
+
 pub(crate) fn get_all_playlist_abstracts(cx: &BackendContext) -> BResult<Vec<PlaylistAbstract>> {
     let models = cx.database_server().load_playlists()?;
+    let smarts = cx.database_server().load_smart_playlists()?;
+    let library = if smarts.is_empty() {
+        None
+    } else {
+        Some(SmartLibrary::load(cx)?)
+    };
 
     let mut ret: Vec<PlaylistAbstract> = Default::default();
     for model in models {
-        let (abstr, _) = build_playlist_abstract(cx, model)?;
+        let musics = match (smarts.get(&model.id), library.as_ref()) {
+            (Some(rule), Some(library)) => evaluate_smart_playlist(library, rule)?,
+            _ => cx.database_server().load_musics_by_playlist_id(model.id)?,
+        };
+        let is_smart = smarts.contains_key(&model.id);
+        let (abstr, _) = build_playlist_abstract_with_musics(cx, model, musics, is_smart);
         ret.push(abstr)
     }
 
@@ -0,0 +1,233 @@
+use std::{cmp::Reverse, collections::HashMap, sync::Arc, time::Duration};
+
+use ease_client_schema::{
+    AlbumId, ArtistId, GenreId, MusicId, MusicModel, SmartCondition, SmartConditionGroup,
+    SmartPlaylistModel, SmartRuleCombinator, SmartSortField, SmartTagField,
+};
+use ease_order_key::OrderKey;
+use globset::{Glob, GlobMatcher};
+
+use crate::{
+    ctx::BackendContext,
+    error::{BError, BResult},
+    repositories::core::DatabaseServer,
+    utils::search::normalize,
+};
+
+/// Per-music facts the rules match against, besides the music model.
+#[derive(Debug, Default, Clone, Copy)]
+struct MusicFacts {
+    play_count: u32,
+    rating: u8,
+}
+
+fn load_facts(db: &Arc<DatabaseServer>) -> BResult<HashMap<MusicId, MusicFacts>> {
+    let mut ret: HashMap<MusicId, MusicFacts> = Default::default();
+    for (id, stats) in db.load_all_music_play_stats()? {
+        ret.entry(id).or_default().play_count = stats.play_count;
+    }
+    for (id, data) in db.load_all_music_user_data()? {
+        ret.entry(id).or_default().rating = data.rating;
+    }
+    Ok(ret)
+}
+
+/// Snapshot of the library a smart playlist is evaluated against. Loading it
+/// once lets several smart playlists be evaluated with a single scan.
+pub(crate) struct SmartLibrary {
+    musics: Vec<MusicModel>,
+    artists: HashMap<ArtistId, String>,
+    albums: HashMap<AlbumId, String>,
+    genres: HashMap<GenreId, String>,
+    facts: HashMap<MusicId, MusicFacts>,
+}
+
+impl SmartLibrary {
+    pub(crate) fn load(cx: &BackendContext) -> BResult<Self> {
+        let db = cx.database_server();
+        Ok(Self {
+            musics: db.load_all_musics()?,
+            artists: db
+                .load_artists()?
+                .into_iter()
+                .map(|(v, _)| (v.id, normalize(&v.name)))
+                .collect(),
+            albums: db
+                .load_albums()?
+                .into_iter()
+                .map(|(v, _)| (v.id, normalize(&v.title)))
+                .collect(),
+            genres: db
+                .load_genres()?
+                .into_iter()
+                .map(|(v, _)| (v.id, normalize(&v.name)))
+                .collect(),
+            facts: load_facts(db)?,
+        })
+    }
+
+    fn facts(&self, id: MusicId) -> MusicFacts {
+        self.facts.get(&id).copied().unwrap_or_default()
+    }
+
+    fn tag_contains(&self, music: &MusicModel, field: Option<SmartTagField>, text: &str) -> bool {
+        let wants = |f: SmartTagField| field.is_none() || field == Some(f);
+        (wants(SmartTagField::Artist)
+            && music
+                .artists
+                .iter()
+                .filter_map(|id| self.artists.get(id))
+                .any(|v| v.contains(text)))
+            || (wants(SmartTagField::Album)
+                && music
+                    .album
+                    .and_then(|id| self.albums.get(&id))
+                    .is_some_and(|v| v.contains(text)))
+            || (wants(SmartTagField::Genre)
+                && music
+                    .genres
+                    .iter()
+                    .filter_map(|id| self.genres.get(id))
+                    .any(|v| v.contains(text)))
+    }
+}
+
+/// A condition prepared for matching, with its texts normalized and its
+/// glob compiled.
+enum Matcher<'a> {
+    Glob(GlobMatcher),
+    Title(String),
+    Tag(Option<SmartTagField>, String),
+    Other(&'a SmartCondition),
+}
+
+fn in_range<T: PartialOrd>(v: T, min: &Option<T>, max: &Option<T>) -> bool {
+    min.as_ref().is_none_or(|min| v >= *min) && max.as_ref().is_none_or(|max| v <= *max)
+}
+
+fn prepare(condition: &SmartCondition) -> BResult<Matcher<'_>> {
+    Ok(match condition {
+        SmartCondition::PathGlob { pattern } => Matcher::Glob(
+            Glob::new(pattern)
+                .map_err(|e| BError::CustomError {
+                    message: format!("invalid glob {pattern:?}: {e}"),
+                })?
+                .compile_matcher(),
+        ),
+        SmartCondition::TitleContains { text } => Matcher::Title(normalize(text)),
+        SmartCondition::TagContains { field, text } => Matcher::Tag(*field, normalize(text)),
+        _ => Matcher::Other(condition),
+    })
+}
+
+impl Matcher<'_> {
+    fn matches(&self, library: &SmartLibrary, music: &MusicModel) -> bool {
+        let condition = match self {
+            Matcher::Glob(glob) => return glob.is_match(&music.loc.path),
+            Matcher::Title(text) => return normalize(&music.title).contains(text.as_str()),
+            Matcher::Tag(field, text) => return library.tag_contains(music, *field, text),
+            Matcher::Other(condition) => condition,
+        };
+        match condition {
+            SmartCondition::Storage { id } => music.loc.storage_id == *id,
+            SmartCondition::PathPrefix { prefix } => music.loc.path.starts_with(prefix.as_str()),
+            SmartCondition::DurationRange { min, max } => music
+                .duration
+                .is_some_and(|duration| in_range(duration, min, max)),
+            SmartCondition::AddedTimeRange { after, before } => in_range(
+                Duration::from_millis(music.added_time.max(0) as u64),
+                after,
+                before,
+            ),
+            SmartCondition::PlayCountRange { min, max } => {
+                in_range(library.facts(music.id).play_count, min, max)
+            }
+            SmartCondition::RatingRange { min, max } => {
+                in_range(library.facts(music.id).rating, min, max)
+            }
+            SmartCondition::PathGlob { .. }
+            | SmartCondition::TitleContains { .. }
+            | SmartCondition::TagContains { .. } => unreachable!(),
+        }
+    }
+}
+
+fn combine<T>(combinator: SmartRuleCombinator, items: &[T], f: impl Fn(&T) -> bool) -> bool {
+    match combinator {
+        SmartRuleCombinator::All => items.iter().all(f),
+        SmartRuleCombinator::Any => items.iter().any(f),
+    }
+}
+
+/// Checks that the globs of a rule compile.
+pub(crate) fn check_smart_playlist_rule(rule: &SmartPlaylistModel) -> BResult<()> {
+    for condition in rule.groups.iter().flat_map(|g| g.conditions.iter()) {
+        prepare(condition)?;
+    }
+    Ok(())
+}
+
+/// Key a smart playlist sorts its musics by, computed once per music.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    Text(String),
+    Duration(Option<Duration>),
+    Number(i64),
+}
+
+/// Returns the musics matching a smart playlist rule, sorted and limited,
+/// with their order keys following the sort.
+pub(crate) fn evaluate_smart_playlist(
+    library: &SmartLibrary,
+    rule: &SmartPlaylistModel,
+) -> BResult<Vec<MusicModel>> {
+    let groups = rule
+        .groups
+        .iter()
+        .map(|group: &SmartConditionGroup| {
+            let matchers = group
+                .conditions
+                .iter()
+                .map(prepare)
+                .collect::<BResult<Vec<_>>>()?;
+            Ok((group.combinator, matchers))
+        })
+        .collect::<BResult<Vec<_>>>()?;
+
+    let mut musics: Vec<MusicModel> = library
+        .musics
+        .iter()
+        .filter(|music| {
+            combine(rule.combinator, &groups, |(combinator, matchers)| {
+                combine(*combinator, matchers, |m| m.matches(library, music))
+            })
+        })
+        .cloned()
+        .collect();
+
+    let sort_key = |music: &MusicModel| match rule.sort.field {
+        SmartSortField::Title => SortKey::Text(normalize(&music.title)),
+        SmartSortField::Path => SortKey::Text(music.loc.path.clone()),
+        SmartSortField::Duration => SortKey::Duration(music.duration),
+        SmartSortField::AddedTime => SortKey::Number(music.added_time),
+        SmartSortField::PlayCount => SortKey::Number(library.facts(music.id).play_count as i64),
+        SmartSortField::Rating => SortKey::Number(library.facts(music.id).rating as i64),
+    };
+    // Ties keep the ascending order of the ids either way.
+    if rule.sort.descending {
+        musics.sort_by_cached_key(|music| (sort_key(music), Reverse(music.id)));
+        musics.reverse();
+    } else {
+        musics.sort_by_cached_key(|music| (sort_key(music), music.id));
+    }
+    if let Some(limit) = rule.limit {
+        musics.truncate(limit as usize);
+    }
+
+    let mut order = OrderKey::default();
+    for music in musics.iter_mut() {
+        music.order = order.clone().into_raw();
+        order = OrderKey::greater(&order);
+    }
+    Ok(musics)
+}
@@ -0,0 +1,212 @@
+use ease_client_schema::{MusicId, PlayMode, PlayQueueCursorModel, PlayQueueModel, ShuffleWeight};
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::{
+    ctx::BackendContext,
+    error::{BError, BResult},
+};
+
+use super::{
+    load_play_queue_state,
+    shuffle::{next_cycle_seed, shuffle_order},
+};
+
+/// Rating assumed for unrated musics when shuffling by rating.
+const UNRATED_WEIGHT: f64 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Record)]
+pub struct PlayQueueItem {
+    /// Index of the music in the queue.
+    pub index: u32,
+    pub music: MusicId,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgSetPlayQueueShuffle {
+    pub enabled: bool,
+    /// Seed of the order, so the same queue shuffles the same way on every
+    /// client. A random one is used when unset.
+    pub seed: Option<u64>,
+    pub weight: ShuffleWeight,
+}
+
+fn random_seed() -> BResult<u64> {
+    let mut bytes = [0u8; 8];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| BError::CustomError {
+            message: "fail to generate shuffle seed".to_string(),
+        })?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn load_shuffle_weights(
+    cx: &BackendContext,
+    musics: &[MusicId],
+    weight: ShuffleWeight,
+) -> BResult<Vec<f64>> {
+    let db = cx.database_server();
+    let weights = match weight {
+        ShuffleWeight::Uniform => vec![1.0; musics.len()],
+        ShuffleWeight::Rating => {
+            let data = db.load_all_music_user_data()?;
+            musics
+                .iter()
+                .map(|id| match data.get(id).map(|v| v.rating) {
+                    Some(rating) if rating > 0 => rating as f64,
+                    _ => UNRATED_WEIGHT,
+                })
+                .collect()
+        }
+        ShuffleWeight::PlayCount => {
+            let stats = db.load_all_music_play_stats()?;
+            musics
+                .iter()
+                .map(|id| {
+                    let count = stats.get(id).map(|v| v.play_count).unwrap_or_default();
+                    1.0 + (count as f64).ln_1p()
+                })
+                .collect()
+        }
+    };
+    Ok(weights)
+}
+
+/// Builds the play order of a shuffled queue from its seed, starting with
+/// the current music, or clears it when the queue is not shuffled.
+pub(super) fn reshuffle(
+    cx: &BackendContext,
+    queue: &mut PlayQueueModel,
+    index: u32,
+) -> BResult<()> {
+    queue.order = match queue.shuffle_seed {
+        Some(seed) => {
+            let weights = load_shuffle_weights(cx, &queue.musics, queue.shuffle_weight)?;
+            shuffle_order(seed, &weights, Some(index))
+        }
+        None => Default::default(),
+    };
+    Ok(())
+}
+
+pub(crate) fn set_play_queue_shuffle(
+    cx: &BackendContext,
+    arg: ArgSetPlayQueueShuffle,
+) -> BResult<()> {
+    let Some((mut queue, cursor)) = load_play_queue_state(cx)? else {
+        return Ok(());
+    };
+    queue.shuffle_seed = match (arg.enabled, arg.seed) {
+        (true, Some(seed)) => Some(seed),
+        (true, None) => Some(random_seed()?),
+        (false, _) => None,
+    };
+    queue.shuffle_weight = arg.weight;
+    reshuffle(cx, &mut queue, cursor.index)?;
+    cx.database_server().save_play_queue(queue, cursor)
+}
+
+/// Moves the cursor to `index` from the start of the music.
+fn move_to(
+    cx: &BackendContext,
+    queue: &PlayQueueModel,
+    index: u32,
+) -> BResult<Option<PlayQueueItem>> {
+    cx.database_server()
+        .save_play_queue_cursor(PlayQueueCursorModel { index, position: 0 })?;
+    Ok(Some(PlayQueueItem {
+        index,
+        music: queue.musics[index as usize],
+    }))
+}
+
+/// Music to play after the current one, following the saved play mode. A
+/// shuffled queue plays in its shuffled order, so `List` and `ListLoop`
+/// become shuffle and shuffle-loop. Every music is played once per cycle,
+/// and a looping shuffled queue starts a new cycle from the next seed
+/// without repeating the last music. `ended` tells the current music
+/// finished by itself rather than being skipped, which only `Single` and
+/// `SingleLoop` treat differently. Returns `None` when playback stops.
+pub(crate) fn play_queue_next(cx: &BackendContext, ended: bool) -> BResult<Option<PlayQueueItem>> {
+    let Some((mut queue, cursor)) = load_play_queue_state(cx)? else {
+        return Ok(None);
+    };
+    let mode = cx.database_server().load_preference()?.playmode;
+    match (mode, ended) {
+        (PlayMode::Single, true) => return Ok(None),
+        (PlayMode::SingleLoop, true) => return move_to(cx, &queue, cursor.index),
+        _ => {}
+    }
+    let looping = matches!(mode, PlayMode::SingleLoop | PlayMode::ListLoop);
+
+    if queue.shuffle_seed.is_some() && queue.order.len() != queue.musics.len() {
+        reshuffle(cx, &mut queue, cursor.index)?;
+        cx.database_server()
+            .save_play_queue(queue.clone(), cursor)?;
+    }
+    let Some(seed) = queue.shuffle_seed else {
+        let next = cursor.index as usize + 1;
+        return match (next < queue.musics.len(), looping) {
+            (true, _) => move_to(cx, &queue, next as u32),
+            (false, true) => move_to(cx, &queue, 0),
+            (false, false) => Ok(None),
+        };
+    };
+
+    let pos = queue.order.iter().position(|v| *v == cursor.index);
+    if let Some(next) = pos.and_then(|pos| queue.order.get(pos + 1)).copied() {
+        return move_to(cx, &queue, next);
+    }
+    if !looping {
+        return Ok(None);
+    }
+    let seed = next_cycle_seed(seed);
+    let weights = load_shuffle_weights(cx, &queue.musics, queue.shuffle_weight)?;
+    queue.shuffle_seed = Some(seed);
+    queue.order = shuffle_order(seed, &weights, None);
+    if queue.order.len() > 1 && queue.order[0] == cursor.index {
+        queue.order.swap(0, 1);
+    }
+    let next = queue.order[0];
+    cx.database_server().save_play_queue(
+        queue.clone(),
+        PlayQueueCursorModel {
+            index: next,
+            position: 0,
+        },
+    )?;
+    Ok(Some(PlayQueueItem {
+        index: next,
+        music: queue.musics[next as usize],
+    }))
+}
+
+/// Music to play before the current one, the reverse of
+/// [`play_queue_next`] within the current cycle. Looping queues wrap to
+/// the end of the cycle.
+pub(crate) fn play_queue_previous(cx: &BackendContext) -> BResult<Option<PlayQueueItem>> {
+    let Some((mut queue, cursor)) = load_play_queue_state(cx)? else {
+        return Ok(None);
+    };
+    let mode = cx.database_server().load_preference()?.playmode;
+    let looping = matches!(mode, PlayMode::SingleLoop | PlayMode::ListLoop);
+
+    if queue.shuffle_seed.is_some() && queue.order.len() != queue.musics.len() {
+        reshuffle(cx, &mut queue, cursor.index)?;
+        cx.database_server()
+            .save_play_queue(queue.clone(), cursor)?;
+    }
+    let order: Vec<u32> = match queue.shuffle_seed {
+        Some(_) => queue.order.clone(),
+        None => (0..queue.musics.len() as u32).collect(),
+    };
+    let pos = order
+        .iter()
+        .position(|v| *v == cursor.index)
+        .unwrap_or_default();
+    match (pos, looping) {
+        (0, false) => Ok(None),
+        (0, true) => move_to(cx, &queue, order[order.len() - 1]),
+        (pos, _) => move_to(cx, &queue, order[pos - 1]),
+    }
+}
@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use ease_client_schema::{
+    MusicId, PlayQueueCursorModel, PlayQueueModel, PlaylistId, ShuffleWeight,
+};
+
+use crate::{
+    ctx::BackendContext,
+    error::{BError, BResult},
+};
+
+mod engine;
+mod shuffle;
+
+pub use engine::*;
+
+#[derive(Debug, Clone, PartialEq, uniffi::Record)]
+pub struct PlayQueue {
+    /// Playlist the queue was started from.
+    pub playlist: Option<PlaylistId>,
+    pub musics: Vec<MusicId>,
+    /// Index of the current music in `musics`.
+    pub index: u32,
+    /// Position in the current music.
+    pub position: Duration,
+    /// Set while the queue is shuffled.
+    pub shuffle_seed: Option<u64>,
+    pub shuffle_weight: ShuffleWeight,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgSavePlayQueuePosition {
+    pub index: u32,
+    pub position: Duration,
+}
+
+fn check_index(len: usize, index: u32) -> BResult<()> {
+    if index as usize >= len {
+        return Err(BError::InvalidQueueIndex(index));
+    }
+    Ok(())
+}
+
+/// Saves the whole queue. An empty queue clears it.
+pub(crate) fn save_play_queue(cx: &BackendContext, arg: PlayQueue) -> BResult<()> {
+    let db = cx.database_server();
+    if arg.musics.is_empty() {
+        return db.clear_play_queue();
+    }
+    check_index(arg.musics.len(), arg.index)?;
+    let mut queue = PlayQueueModel {
+        playlist: arg.playlist,
+        musics: arg.musics,
+        shuffle_seed: arg.shuffle_seed,
+        shuffle_weight: arg.shuffle_weight,
+        order: Default::default(),
+    };
+    reshuffle(cx, &mut queue, arg.index)?;
+    db.save_play_queue(
+        queue,
+        PlayQueueCursorModel {
+            index: arg.index,
+            position: arg.position.as_millis() as u64,
+        },
+    )
+}
+
+/// Saves the current music and position only, cheap enough to call every
+/// few seconds while playing. Ignored when there is no queue.
+pub(crate) fn save_play_queue_position(
+    cx: &BackendContext,
+    arg: ArgSavePlayQueuePosition,
+) -> BResult<()> {
+    let cursor = PlayQueueCursorModel {
+        index: arg.index,
+        position: arg.position.as_millis() as u64,
+    };
+    cx.database_server().save_play_queue_cursor(cursor)?;
+    Ok(())
+}
+
+/// Loads the saved queue. Musics removed since it was saved are dropped.
+/// If the current music is one of them, the queue goes on with the next
+/// music left from the start.
+fn load_play_queue_state(
+    cx: &BackendContext,
+) -> BResult<Option<(PlayQueueModel, PlayQueueCursorModel)>> {
+    let db = cx.database_server();
+    let Some((mut queue, mut cursor)) = db.load_play_queue()? else {
+        return Ok(None);
+    };
+    if queue.musics.is_empty() {
+        return Ok(None);
+    }
+    let missing = db.load_missing_music_ids(&queue.musics)?;
+    let playlist_removed = match queue.playlist {
+        Some(id) => db.load_playlist(id)?.is_none(),
+        None => false,
+    };
+
+    if !missing.is_empty() || playlist_removed {
+        let index = (cursor.index as usize).min(queue.musics.len());
+        let current_removed = queue
+            .musics
+            .get(index)
+            .is_none_or(|id| missing.contains(id));
+        // New index of every music left.
+        let mut kept = 0;
+        let remap: Vec<Option<u32>> = queue
+            .musics
+            .iter()
+            .map(|id| {
+                if missing.contains(id) {
+                    None
+                } else {
+                    kept += 1;
+                    Some(kept - 1)
+                }
+            })
+            .collect();
+        let before = remap[..index].iter().flatten().count();
+        queue.musics.retain(|id| !missing.contains(id));
+        if queue.musics.is_empty() {
+            db.clear_play_queue()?;
+            return Ok(None);
+        }
+        queue.order = queue
+            .order
+            .iter()
+            .filter_map(|i| remap.get(*i as usize).copied().flatten())
+            .collect();
+        cursor.index = if before < queue.musics.len() {
+            before as u32
+        } else {
+            0
+        };
+        if current_removed {
+            cursor.position = 0;
+        }
+        if playlist_removed {
+            queue.playlist = None;
+        }
+        db.save_play_queue(queue.clone(), cursor)?;
+    }
+    cursor.index = cursor.index.min(queue.musics.len() as u32 - 1);
+    Ok(Some((queue, cursor)))
+}
+
+/// Restores the saved queue, see [`load_play_queue_state`].
+pub(crate) fn get_play_queue(cx: &BackendContext) -> BResult<Option<PlayQueue>> {
+    let Some((queue, cursor)) = load_play_queue_state(cx)? else {
+        return Ok(None);
+    };
+    Ok(Some(PlayQueue {
+        playlist: queue.playlist,
+        musics: queue.musics,
+        index: cursor.index,
+        position: Duration::from_millis(cursor.position),
+        shuffle_seed: queue.shuffle_seed,
+        shuffle_weight: queue.shuffle_weight,
+    }))
+}
+
+pub(crate) fn clear_play_queue(cx: &BackendContext) -> BResult<()> {
+    cx.database_server().clear_play_queue()
+}
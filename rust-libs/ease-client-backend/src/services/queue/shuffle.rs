@@ -0,0 +1,81 @@
+/// SplitMix64. Small and stable across platforms and releases, so a seed
+/// gives the same order on every client.
+struct ShuffleRng(u64);
+
+impl ShuffleRng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in (0, 1].
+    fn next_unit(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Seed of the cycle following the one shuffled with `seed`.
+pub(crate) fn next_cycle_seed(seed: u64) -> u64 {
+    ShuffleRng::new(seed).next_u64()
+}
+
+/// Play order of `weights.len()` musics. Each music draws the key
+/// `ln(u) / weight` and musics are played by descending key, so heavier
+/// musics tend to come first while every music is played once per cycle.
+/// `first` is moved to the front, to keep playing the current music.
+pub(crate) fn shuffle_order(seed: u64, weights: &[f64], first: Option<u32>) -> Vec<u32> {
+    let mut rng = ShuffleRng::new(seed);
+    let mut keys: Vec<(f64, u32)> = weights
+        .iter()
+        .enumerate()
+        .map(|(i, w)| (rng.next_unit().ln() / w.max(f64::MIN_POSITIVE), i as u32))
+        .collect();
+    keys.sort_by(|lhs, rhs| rhs.0.total_cmp(&lhs.0).then(lhs.1.cmp(&rhs.1)));
+    let mut order: Vec<u32> = keys.into_iter().map(|(_, i)| i).collect();
+    if let Some(first) = first {
+        if let Some(pos) = order.iter().position(|v| *v == first) {
+            order[..=pos].rotate_right(1);
+        }
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shuffles_reproducibly_with_weights() {
+        let uniform = vec![1.0; 20];
+        let order = shuffle_order(7, &uniform, None);
+        assert_eq!(order, shuffle_order(7, &uniform, None));
+        assert_ne!(order, shuffle_order(8, &uniform, None));
+        let mut sorted = order.clone();
+        sorted.sort();
+        assert_eq!((0..20).collect::<Vec<u32>>(), sorted);
+
+        let order = shuffle_order(7, &uniform, Some(13));
+        assert_eq!(13, order[0]);
+        assert_eq!(20, order.len());
+
+        // The heavy music comes first far more often than a light one.
+        let mut weights = vec![1.0; 10];
+        weights[3] = 50.0;
+        let mut seed = 1;
+        let mut firsts = 0;
+        for _ in 0..200 {
+            if shuffle_order(seed, &weights, None)[0] == 3 {
+                firsts += 1;
+            }
+            seed = next_cycle_seed(seed);
+        }
+        assert!(firsts > 100, "heavy music first {firsts} times");
+    }
+}
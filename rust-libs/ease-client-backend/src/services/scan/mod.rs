@@ -0,0 +1,452 @@
+mod relink;
+mod rescan;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+};
+
+use ease_client_schema::{MusicFileStamp, MusicFileState, MusicId, StorageEntryLoc, StorageId};
+use ease_remote_storage::{Entry, StorageBackend};
+use tokio::task::JoinSet;
+
+use crate::{
+    ctx::{BackendContext, WeakBackendContext},
+    error::{BError, BResult},
+    objects::StorageEntryType,
+    repositories::music::{ArgDBAddMusic, ArgDBUpdateMusicFile},
+    services::{get_storage_backend, new_music_ids, spawn_probe_musics, storage_entry_type},
+};
+
+pub use relink::*;
+pub use rescan::*;
+
+const DEFAULT_SCAN_CONCURRENCY: usize = 4;
+const MAX_SCAN_CONCURRENCY: usize = 16;
+/// Directories nested deeper than this below the scanned one are skipped, so
+/// that a symlink to a parent directory doesn't make the scan endless.
+const MAX_SCAN_DEPTH: usize = 32;
+/// Bytes read from files without a known extension to sniff their type.
+const SNIFF_LEN: usize = 12;
+/// File stems of the images used as the cover of the musics of a folder,
+/// in order of preference.
+const FOLDER_COVER_STEMS: [&str; 4] = ["cover", "folder", "front", "album"];
+
+type DynStorageBackend = Arc<dyn StorageBackend + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum LibraryScanStatus {
+    Running,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct LibraryScanProgress {
+    pub id: u64,
+    pub status: LibraryScanStatus,
+    pub dirs_scanned: u64,
+    pub dirs_pending: u64,
+    pub musics_found: u64,
+    pub musics_added: u64,
+    pub lyrics_paired: u64,
+    pub covers_paired: u64,
+    /// Directories that could not be listed or are nested too deep. They are
+    /// skipped.
+    pub failed_dirs: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgStartLibraryScan {
+    pub dir: StorageEntryLoc,
+    /// Directories listed at the same time, 0 for the default.
+    pub max_concurrency: u32,
+}
+
+struct ScanJob {
+    progress: Mutex<LibraryScanProgress>,
+    cancelled: AtomicBool,
+}
+
+impl ScanJob {
+    fn update(&self, f: impl FnOnce(&mut LibraryScanProgress)) {
+        f(&mut self.progress.lock().unwrap());
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct ScanState {
+    next_id: AtomicU64,
+    jobs: RwLock<HashMap<u64, Arc<ScanJob>>>,
+}
+
+/// Entries of one directory, classified.
+#[derive(Default)]
+struct ListedDir {
+    dirs: Vec<String>,
+    musics: Vec<Entry>,
+    lyrics: Vec<Entry>,
+    images: Vec<Entry>,
+}
+
+/// Classifies a file by its magic bytes.
+pub(crate) fn sniff_entry_type(head: &[u8]) -> StorageEntryType {
+    let is_music = head.starts_with(b"ID3")
+        || head.starts_with(b"fLaC")
+        || head.starts_with(b"OggS")
+        || (head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WAVE"))
+        || head.get(4..8) == Some(b"ftyp")
+        // MPEG audio and ADTS frame sync.
+        || (head.len() >= 2 && head[0] == 0xFF && head[1] & 0xE0 == 0xE0);
+    if is_music {
+        StorageEntryType::Music
+    } else if head.starts_with(&[0xFF, 0xD8, 0xFF]) || head.starts_with(b"\x89PNG") {
+        StorageEntryType::Image
+    } else {
+        StorageEntryType::Other
+    }
+}
+
+async fn read_head(backend: &DynStorageBackend, path: String) -> BResult<Vec<u8>> {
+    let rx = backend.get(path, 0).await?.into_rx();
+    let mut head: Vec<u8> = Default::default();
+    while head.len() < SNIFF_LEN {
+        let Ok(chunk) = rx.recv().await else {
+            break;
+        };
+        head.extend_from_slice(&chunk?);
+    }
+    head.truncate(SNIFF_LEN);
+    Ok(head)
+}
+
+async fn sniff_entry(backend: DynStorageBackend, entry: &Entry) -> StorageEntryType {
+    match read_head(&backend, entry.path.clone()).await {
+        Ok(head) => sniff_entry_type(&head),
+        Err(e) => {
+            tracing::warn!("fail to sniff {}: {}", entry.path, e);
+            StorageEntryType::Other
+        }
+    }
+}
+
+fn has_extension(name: &str) -> bool {
+    matches!(name.rsplit_once('.'), Some((stem, _)) if !stem.is_empty())
+}
+
+/// Lists a directory. Files without an extension are sniffed, up to
+/// `concurrency` at the same time.
+async fn list_dir(
+    backend: DynStorageBackend,
+    dir: String,
+    concurrency: usize,
+) -> BResult<ListedDir> {
+    let mut entries: Vec<(Entry, StorageEntryType)> = Default::default();
+    let mut to_sniff: Vec<usize> = Default::default();
+    for entry in backend.list(dir).await? {
+        let typ = storage_entry_type(&entry.path, entry.is_dir);
+        if typ == StorageEntryType::Other && !has_extension(&entry.name) {
+            to_sniff.push(entries.len());
+        }
+        entries.push((entry, typ));
+    }
+
+    let mut sniffing: JoinSet<(usize, StorageEntryType)> = JoinSet::new();
+    let handle = ease_client_tokio::tokio_runtime().handle().clone();
+    loop {
+        while sniffing.len() < concurrency {
+            let Some(index) = to_sniff.pop() else {
+                break;
+            };
+            let backend = backend.clone();
+            let entry = entries[index].0.clone();
+            sniffing.spawn_on(
+                async move { (index, sniff_entry(backend, &entry).await) },
+                &handle,
+            );
+        }
+        let Some(joined) = sniffing.join_next().await else {
+            break;
+        };
+        let (index, typ) = joined.map_err(|e| BError::CustomError {
+            message: format!("sniff task failed: {e}"),
+        })?;
+        entries[index].1 = typ;
+    }
+
+    let mut ret = ListedDir::default();
+    for (entry, typ) in entries {
+        match typ {
+            StorageEntryType::Folder => ret.dirs.push(entry.path),
+            StorageEntryType::Music => ret.musics.push(entry),
+            StorageEntryType::Lyric => ret.lyrics.push(entry),
+            StorageEntryType::Image => ret.images.push(entry),
+            StorageEntryType::Other => {}
+        }
+    }
+    Ok(ret)
+}
+
+pub(crate) fn entry_stamp(entry: &Entry) -> MusicFileStamp {
+    MusicFileStamp {
+        size: entry.size.map(|v| v as u64),
+        modified: entry.modified,
+        etag: entry.etag.clone(),
+    }
+}
+
+fn file_stem(name: &str) -> String {
+    let stem = name.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(name);
+    stem.to_lowercase()
+}
+
+/// Returns the lyric file named like the music, ignoring case.
+fn pair_lyric<'a>(music: &Entry, lyrics: &'a [Entry]) -> Option<&'a Entry> {
+    let stem = file_stem(&music.name);
+    lyrics.iter().find(|lyric| file_stem(&lyric.name) == stem)
+}
+
+/// Returns the image to use as the cover of the musics of a folder: a well
+/// known name, or the only image of the folder.
+fn pick_folder_cover(images: &[Entry]) -> Option<&Entry> {
+    for name in FOLDER_COVER_STEMS {
+        if let Some(image) = images.iter().find(|v| file_stem(&v.name) == name) {
+            return Some(image);
+        }
+    }
+    match images {
+        [image] => Some(image),
+        _ => None,
+    }
+}
+
+/// Imports the musics of a listed directory and pairs the newly added ones
+/// with their lyric and the folder cover. The musics are added in one
+/// transaction, and their stamps, lyrics and covers are saved after it.
+async fn import_dir(
+    cx: &BackendContext,
+    backend: &DynStorageBackend,
+    storage_id: StorageId,
+    listed: ListedDir,
+    job: &ScanJob,
+) -> BResult<()> {
+    if listed.musics.is_empty() {
+        return Ok(());
+    }
+    let added = cx.database_server().upsert_musics(
+        listed
+            .musics
+            .iter()
+            .map(|entry| ArgDBAddMusic {
+                loc: StorageEntryLoc {
+                    storage_id,
+                    path: entry.path.clone(),
+                },
+                title: entry.name.clone(),
+            })
+            .collect(),
+    )?;
+
+    let mut lyrics_paired = 0;
+    let mut need_cover: Vec<MusicId> = Default::default();
+    let mut stamps: Vec<ArgDBUpdateMusicFile> = Default::default();
+    for (entry, added) in listed.musics.iter().zip(added.iter()) {
+        let Some(model) = cx.database_server().load_music(added.id)? else {
+            continue;
+        };
+        if model.file_stamp.is_none() {
+            stamps.push(ArgDBUpdateMusicFile {
+                id: added.id,
+                stamp: Some(entry_stamp(entry)),
+                state: MusicFileState::Present,
+            });
+        }
+        if model.lyric.is_none() && model.lyric_default {
+            if let Some(lyric) = pair_lyric(entry, &listed.lyrics) {
+                cx.database_server().update_music_lyric(
+                    added.id,
+                    Some(StorageEntryLoc {
+                        storage_id,
+                        path: lyric.path.clone(),
+                    }),
+                )?;
+                lyrics_paired += 1;
+            }
+        }
+        if !added.existed && model.cover.is_none() {
+            need_cover.push(added.id);
+        }
+    }
+
+    cx.database_server().update_music_files(stamps)?;
+
+    let mut covers_paired = 0;
+    if let (false, Some(image)) = (need_cover.is_empty(), pick_folder_cover(&listed.images)) {
+        let bytes = match backend.get(image.path.clone(), 0).await {
+            Ok(file) => file.bytes().await,
+            Err(e) => Err(e),
+        };
+        match bytes {
+            Ok(bytes) => {
+                // Embedded covers found when probing replace the folder cover.
+                cx.database_server()
+                    .update_musics_cover(&need_cover, bytes.to_vec())?;
+                covers_paired += need_cover.len() as u64;
+            }
+            Err(e) => tracing::warn!("fail to load folder cover {}: {}", image.path, e),
+        }
+    }
+
+    let new_ids = new_music_ids(&added);
+    job.update(|p| {
+        p.musics_found += listed.musics.len() as u64;
+        p.musics_added += new_ids.len() as u64;
+        p.lyrics_paired += lyrics_paired;
+        p.covers_paired += covers_paired;
+    });
+    spawn_probe_musics(cx, new_ids);
+    Ok(())
+}
+
+async fn run_scan(cx: WeakBackendContext, job: Arc<ScanJob>, arg: ArgStartLibraryScan) {
+    let result = run_scan_impl(cx, &job, arg).await;
+    job.update(|p| {
+        p.status = match result {
+            Ok(()) if job.cancelled.load(Ordering::Relaxed) => LibraryScanStatus::Cancelled,
+            Ok(()) => LibraryScanStatus::Completed,
+            Err(e) => {
+                p.error = Some(e.to_string());
+                LibraryScanStatus::Failed
+            }
+        };
+        p.dirs_pending = 0;
+    });
+}
+
+async fn run_scan_impl(
+    cx: WeakBackendContext,
+    job: &ScanJob,
+    arg: ArgStartLibraryScan,
+) -> BResult<()> {
+    let backend = {
+        let Some(cx) = cx.upgrade() else {
+            return Ok(());
+        };
+        get_storage_backend(&cx, arg.dir.storage_id)?
+    };
+    let Some(backend) = backend else {
+        return Err(BError::CustomError {
+            message: format!("storage {:?} not found", arg.dir.storage_id),
+        });
+    };
+    let concurrency = match arg.max_concurrency as usize {
+        0 => DEFAULT_SCAN_CONCURRENCY,
+        v => v.min(MAX_SCAN_CONCURRENCY),
+    };
+
+    let mut pending = vec![(arg.dir.path.clone(), 0)];
+    let mut visited: HashSet<String> = HashSet::from([arg.dir.path.clone()]);
+    let mut listing: JoinSet<(String, usize, BResult<ListedDir>)> = JoinSet::new();
+    let handle = ease_client_tokio::tokio_runtime().handle().clone();
+    loop {
+        if job.cancelled.load(Ordering::Relaxed) {
+            listing.abort_all();
+            return Ok(());
+        }
+        while listing.len() < concurrency {
+            let Some((dir, depth)) = pending.pop() else {
+                break;
+            };
+            let backend = backend.clone();
+            listing.spawn_on(
+                async move {
+                    let listed = list_dir(backend, dir.clone(), concurrency).await;
+                    (dir, depth, listed)
+                },
+                &handle,
+            );
+        }
+        job.update(|p| p.dirs_pending = (pending.len() + listing.len()) as u64);
+
+        let Some(joined) = listing.join_next().await else {
+            return Ok(());
+        };
+        let (dir, depth, listed) = joined.map_err(|e| BError::CustomError {
+            message: format!("scan task failed: {e}"),
+        })?;
+        let listed = match listed {
+            Ok(listed) => listed,
+            Err(e) if dir == arg.dir.path => return Err(e),
+            Err(e) => {
+                tracing::warn!("fail to list {}: {}", dir, e);
+                job.update(|p| p.failed_dirs += 1);
+                continue;
+            }
+        };
+        job.update(|p| p.dirs_scanned += 1);
+        for sub in listed.dirs.iter().rev() {
+            if depth == MAX_SCAN_DEPTH {
+                tracing::warn!("skip {} nested too deep", sub);
+                job.update(|p| p.failed_dirs += 1);
+            } else if visited.insert(sub.clone()) {
+                pending.push((sub.clone(), depth + 1));
+            }
+        }
+
+        let Some(cx) = cx.upgrade() else {
+            return Ok(());
+        };
+        import_dir(&cx, &backend, arg.dir.storage_id, listed, job).await?;
+    }
+}
+
+/// Starts scanning a storage directory recursively in the background and
+/// returns the id of the scan job.
+pub fn start_library_scan(cx: &BackendContext, arg: ArgStartLibraryScan) -> u64 {
+    let state = cx.scan_state();
+    let id = state.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+    let job = Arc::new(ScanJob {
+        progress: Mutex::new(LibraryScanProgress {
+            id,
+            status: LibraryScanStatus::Running,
+            dirs_scanned: 0,
+            dirs_pending: 1,
+            musics_found: 0,
+            musics_added: 0,
+            lyrics_paired: 0,
+            covers_paired: 0,
+            failed_dirs: 0,
+            error: None,
+        }),
+        cancelled: AtomicBool::new(false),
+    });
+    state.jobs.write().unwrap().insert(id, job.clone());
+    ease_client_tokio::tokio_runtime().spawn(run_scan(cx.weak(), job, arg));
+    id
+}
+
+/// Returns the progress of a scan. A finished scan is forgotten once its
+/// final progress has been returned.
+pub fn get_library_scan_progress(cx: &BackendContext, id: u64) -> Option<LibraryScanProgress> {
+    let mut jobs = cx.scan_state().jobs.write().unwrap();
+    let progress = jobs.get(&id)?.progress.lock().unwrap().clone();
+    if progress.status != LibraryScanStatus::Running {
+        jobs.remove(&id);
+    }
+    Some(progress)
+}
+
+/// Requests a running scan to stop. Musics imported so far are kept.
+pub fn cancel_library_scan(cx: &BackendContext, id: u64) -> bool {
+    let jobs = cx.scan_state().jobs.read().unwrap();
+    let Some(job) = jobs.get(&id) else {
+        return false;
+    };
+    job.cancelled.store(true, Ordering::Relaxed);
+    true
+}
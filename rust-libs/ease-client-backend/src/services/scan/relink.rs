@@ -0,0 +1,340 @@
+use std::collections::HashSet;
+
+use ease_client_schema::{MusicFileStamp, MusicFileState, MusicId, MusicModel, StorageEntryLoc};
+use ease_remote_storage::Entry;
+
+use crate::{
+    ctx::BackendContext,
+    error::{BError, BResult},
+    objects::StorageEntryType,
+    services::{
+        fingerprint_entry, get_storage_backend, is_same_fingerprint, storage_entry_type,
+        tags::{read_embedded_tags, EntryRangeReader, TagQuery},
+    },
+    utils::search::{normalize, tokenize, tokenize_path},
+};
+
+use super::entry_stamp;
+
+/// Candidates whose tags, and fingerprint if asked, are read for each music.
+const MIN_DEEP_CANDIDATES: usize = 8;
+/// Candidates sharing less of the file name than this are only kept when
+/// their size matches.
+const MIN_NAME_SIMILARITY: f64 = 0.3;
+/// Score from which the best candidate is relinked automatically.
+const AUTO_RELINK_MIN_SCORE: f64 = 0.7;
+/// Lead over the second best candidate needed to relink automatically.
+const AUTO_RELINK_MIN_LEAD: f64 = 0.1;
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgFindRelinkCandidates {
+    pub id: MusicId,
+    /// Directory searched recursively for the moved file.
+    pub root: StorageEntryLoc,
+    /// Also fingerprint the best candidates. This downloads and decodes them,
+    /// and only helps when the music was fingerprinted before it went missing.
+    pub use_fingerprint: bool,
+    pub limit: u32,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct RelinkCandidate {
+    pub loc: StorageEntryLoc,
+    /// From 0 to 1, higher is a better match.
+    pub score: f64,
+    /// Share of the file name terms in common, 1 for the same file name.
+    pub name_similarity: f64,
+    pub size_matched: bool,
+    pub tags_matched: bool,
+    /// `None` when fingerprints were not compared.
+    pub fingerprint_matched: Option<bool>,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgRelinkMusic {
+    pub id: MusicId,
+    pub loc: StorageEntryLoc,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgAutoRelinkMusics {
+    /// Musics to relink, or every missing music when empty.
+    pub ids: Vec<MusicId>,
+    pub root: StorageEntryLoc,
+    pub use_fingerprint: bool,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct RelinkedMusic {
+    pub id: MusicId,
+    pub loc: StorageEntryLoc,
+}
+
+#[derive(Debug, Clone, Default, uniffi::Record)]
+pub struct AutoRelinkResult {
+    pub relinked: Vec<RelinkedMusic>,
+    /// Musics without a candidate good and distinct enough to pick.
+    pub unresolved: Vec<MusicId>,
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Compares file names by their terms, ignoring extensions, case and width.
+fn name_similarity(lhs: &str, rhs: &str) -> f64 {
+    let lhs: HashSet<String> = tokenize_path(file_name(lhs)).into_iter().collect();
+    let rhs: HashSet<String> = tokenize_path(file_name(rhs)).into_iter().collect();
+    if lhs.is_empty() || rhs.is_empty() {
+        return 0.0;
+    }
+    lhs.intersection(&rhs).count() as f64 / lhs.union(&rhs).count() as f64
+}
+
+/// Lists the music files under `root` that no other music is linked to.
+async fn list_unlinked_musics(cx: &BackendContext, root: &StorageEntryLoc) -> BResult<Vec<Entry>> {
+    let Some(backend) = get_storage_backend(cx, root.storage_id)? else {
+        return Ok(Default::default());
+    };
+    let mut ret: Vec<Entry> = Default::default();
+    let mut dirs = vec![root.path.clone()];
+    while let Some(dir) = dirs.pop() {
+        for entry in backend.list(dir).await? {
+            if entry.is_dir {
+                dirs.push(entry.path);
+                continue;
+            }
+            if storage_entry_type(&entry.path, false) != StorageEntryType::Music {
+                continue;
+            }
+            let loc = StorageEntryLoc {
+                storage_id: root.storage_id,
+                path: entry.path.clone(),
+            };
+            if cx.database_server().load_music_by_loc(loc)?.is_none() {
+                ret.push(entry);
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Whether the title and artist tags of a candidate agree with the music.
+async fn tags_match(cx: &BackendContext, music: &MusicModel, loc: &StorageEntryLoc) -> bool {
+    let Ok(Some(backend)) = get_storage_backend(cx, loc.storage_id) else {
+        return false;
+    };
+    let query = TagQuery {
+        text: true,
+        ..Default::default()
+    };
+    let mut reader = EntryRangeReader::new(backend, loc.path.clone());
+    let tags = match read_embedded_tags(&mut reader, query).await {
+        Ok(tags) => tags.text,
+        Err(e) => {
+            tracing::warn!("fail to read tags of {:?}: {}", loc, e);
+            return false;
+        }
+    };
+    let Some(title) = tags.title else {
+        return false;
+    };
+    // Titles often appear in file names after a track number.
+    let name_terms: HashSet<String> = tokenize_path(file_name(&music.loc.path))
+        .into_iter()
+        .collect();
+    let title_terms = tokenize(&title);
+    let title_matched = normalize(&title) == normalize(&music.title)
+        || (!title_terms.is_empty() && title_terms.iter().all(|v| name_terms.contains(v)));
+    if !title_matched {
+        return false;
+    }
+
+    let mut artists: Vec<String> = Default::default();
+    for id in music.artists.iter() {
+        if let Ok(Some(artist)) = cx.database_server().load_artist(*id) {
+            artists.push(normalize(&artist.name));
+        }
+    }
+    match tags.artist {
+        Some(artist) if !artists.is_empty() => artists.contains(&normalize(&artist)),
+        _ => true,
+    }
+}
+
+fn score(candidate: &RelinkCandidate) -> f64 {
+    let base = 0.45 * candidate.name_similarity
+        + if candidate.size_matched { 0.3 } else { 0.0 }
+        + if candidate.tags_matched { 0.25 } else { 0.0 };
+    match candidate.fingerprint_matched {
+        Some(matched) => 0.5 * base + if matched { 0.5 } else { 0.0 },
+        None => base,
+    }
+}
+
+/// A candidate with the stamp of its file, recorded if it is relinked.
+struct Ranked {
+    candidate: RelinkCandidate,
+    name: String,
+    stamp: MusicFileStamp,
+}
+
+/// Ranks the files a missing music may have moved to. Every file is scored
+/// by its name and size, then the best `deep` ones by their tags and
+/// fingerprint too.
+async fn rank_candidates(
+    cx: &BackendContext,
+    music: &MusicModel,
+    root: &StorageEntryLoc,
+    entries: &[Entry],
+    use_fingerprint: bool,
+    deep: usize,
+) -> BResult<Vec<Ranked>> {
+    let size = music.file_stamp.as_ref().and_then(|v| v.size);
+    let mut ret: Vec<Ranked> = entries
+        .iter()
+        .filter_map(|entry| {
+            let name_similarity = name_similarity(&music.loc.path, &entry.path);
+            let size_matched = size.is_some() && entry.size.map(|v| v as u64) == size;
+            if name_similarity < MIN_NAME_SIMILARITY && !size_matched {
+                return None;
+            }
+            let mut candidate = RelinkCandidate {
+                loc: StorageEntryLoc {
+                    storage_id: root.storage_id,
+                    path: entry.path.clone(),
+                },
+                score: 0.0,
+                name_similarity,
+                size_matched,
+                tags_matched: false,
+                fingerprint_matched: None,
+            };
+            candidate.score = score(&candidate);
+            Some(Ranked {
+                candidate,
+                name: entry.name.clone(),
+                stamp: entry_stamp(entry),
+            })
+        })
+        .collect();
+    ret.sort_by(|lhs, rhs| rhs.candidate.score.total_cmp(&lhs.candidate.score));
+    ret.truncate(deep.max(MIN_DEEP_CANDIDATES));
+
+    let fingerprint = match use_fingerprint {
+        true => cx
+            .database_server()
+            .load_music_fingerprint(music.id)?
+            .filter(|v| !v.fingerprint.is_empty()),
+        false => None,
+    };
+    let backend = get_storage_backend(cx, root.storage_id)?;
+    for Ranked { candidate, .. } in ret.iter_mut() {
+        candidate.tags_matched = tags_match(cx, music, &candidate.loc).await;
+        if let (Some(fingerprint), Some(backend)) = (fingerprint.as_ref(), backend.as_ref()) {
+            candidate.fingerprint_matched =
+                match fingerprint_entry(backend.clone(), &candidate.loc).await {
+                    Ok(v) => Some(v.is_some_and(|v| is_same_fingerprint(fingerprint, &v))),
+                    Err(e) => {
+                        tracing::warn!("fail to fingerprint {:?}: {}", candidate.loc, e);
+                        None
+                    }
+                };
+        }
+        candidate.score = score(candidate);
+    }
+    ret.sort_by(|lhs, rhs| rhs.candidate.score.total_cmp(&lhs.candidate.score));
+    Ok(ret)
+}
+
+/// Proposes the files under a directory that a missing music may have been
+/// moved to, best first.
+pub async fn find_relink_candidates(
+    cx: &BackendContext,
+    arg: ArgFindRelinkCandidates,
+) -> BResult<Vec<RelinkCandidate>> {
+    let Some(music) = cx.database_server().load_music(arg.id)? else {
+        return Err(BError::MusicNotFound(arg.id));
+    };
+    let entries = list_unlinked_musics(cx, &arg.root).await?;
+    let limit = arg.limit.max(1) as usize;
+    let mut ret =
+        rank_candidates(cx, &music, &arg.root, &entries, arg.use_fingerprint, limit).await?;
+    ret.truncate(limit);
+    Ok(ret.into_iter().map(|v| v.candidate).collect())
+}
+
+/// Links a music to the storage entry its file was moved to. Playlists keep
+/// the music at the same place since its id does not change.
+pub async fn relink_music(cx: &BackendContext, arg: ArgRelinkMusic) -> BResult<()> {
+    let Some(backend) = get_storage_backend(cx, arg.loc.storage_id)? else {
+        return Err(BError::AssetNotFound);
+    };
+    let parent = match arg.loc.path.rsplit_once('/') {
+        Some(("", _)) | None => "/".to_string(),
+        Some((parent, _)) => parent.to_string(),
+    };
+    let entries = backend.list(parent).await?;
+    let Some(entry) = entries.iter().find(|e| !e.is_dir && e.path == arg.loc.path) else {
+        return Err(BError::AssetNotFound);
+    };
+    cx.database_server().relink_music(
+        arg.id,
+        arg.loc,
+        entry.name.clone(),
+        Some(entry_stamp(entry)),
+    )
+}
+
+/// Relinks every given music whose best candidate under `root` is good and
+/// clearly ahead of the others. A file is relinked to one music at most.
+pub async fn auto_relink_musics(
+    cx: &BackendContext,
+    arg: ArgAutoRelinkMusics,
+) -> BResult<AutoRelinkResult> {
+    let musics: Vec<MusicModel> = if arg.ids.is_empty() {
+        cx.database_server()
+            .load_all_musics()?
+            .into_iter()
+            .filter(|m| m.file_state == MusicFileState::Missing)
+            .collect()
+    } else {
+        let mut musics = Vec::with_capacity(arg.ids.len());
+        for id in arg.ids.iter() {
+            musics.extend(cx.database_server().load_music(*id)?);
+        }
+        musics
+    };
+
+    let mut entries = list_unlinked_musics(cx, &arg.root).await?;
+    let mut ret = AutoRelinkResult::default();
+    for music in musics {
+        let ranked =
+            rank_candidates(cx, &music, &arg.root, &entries, arg.use_fingerprint, 2).await?;
+        let best = match ranked.as_slice() {
+            [best] => Some(best),
+            [best, second, ..]
+                if best.candidate.score - second.candidate.score >= AUTO_RELINK_MIN_LEAD =>
+            {
+                Some(best)
+            }
+            _ => None,
+        };
+        let Some(best) = best.filter(|v| v.candidate.score >= AUTO_RELINK_MIN_SCORE) else {
+            ret.unresolved.push(music.id);
+            continue;
+        };
+        cx.database_server().relink_music(
+            music.id,
+            best.candidate.loc.clone(),
+            best.name.clone(),
+            Some(best.stamp.clone()),
+        )?;
+        entries.retain(|e| e.path != best.candidate.loc.path);
+        ret.relinked.push(RelinkedMusic {
+            id: music.id,
+            loc: best.candidate.loc.clone(),
+        });
+    }
+    Ok(ret)
+}
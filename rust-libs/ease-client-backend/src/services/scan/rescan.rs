@@ -0,0 +1,149 @@
+use std::collections::{BTreeMap, HashMap};
+
+use ease_client_schema::{
+    MusicFileStamp, MusicFileState, MusicId, MusicModel, StorageEntryLoc, StorageId,
+};
+use ease_remote_storage::Entry;
+
+use crate::{
+    ctx::BackendContext,
+    error::BResult,
+    repositories::music::ArgDBUpdateMusicFile,
+    services::{get_storage_backend, spawn_reprobe_musics},
+};
+
+use super::entry_stamp;
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgRescanLibrary {
+    /// Rescans the musics of one storage, or of every storage when `None`.
+    pub storage_id: Option<StorageId>,
+}
+
+#[derive(Debug, Clone, Default, uniffi::Record)]
+pub struct LibraryRescanResult {
+    pub unchanged: u64,
+    /// Musics imported before stamps were recorded, stamped by this rescan.
+    pub stamped: u64,
+    pub changed: Vec<MusicId>,
+    pub missing: Vec<MusicId>,
+    /// Musics that were missing and whose file is back.
+    pub restored: Vec<MusicId>,
+    /// Directories that could not be listed. Their musics are left as they
+    /// were.
+    pub failed_dirs: Vec<StorageEntryLoc>,
+}
+
+fn parent_dir(path: &str) -> String {
+    match path.rsplit_once('/') {
+        Some(("", _)) | None => "/".to_string(),
+        Some((parent, _)) => parent.to_string(),
+    }
+}
+
+/// Whether a value reported by both stamps differs. Values a storage does not
+/// report are not compared.
+fn stamp_changed(old: &MusicFileStamp, new: &MusicFileStamp) -> bool {
+    fn differs<T: PartialEq>(lhs: &Option<T>, rhs: &Option<T>) -> bool {
+        matches!((lhs, rhs), (Some(lhs), Some(rhs)) if lhs != rhs)
+    }
+    differs(&old.size, &new.size)
+        || differs(&old.modified, &new.modified)
+        || differs(&old.etag, &new.etag)
+}
+
+fn diff_music(
+    music: &MusicModel,
+    entry: Option<&Entry>,
+    ret: &mut LibraryRescanResult,
+) -> Option<ArgDBUpdateMusicFile> {
+    let Some(entry) = entry else {
+        ret.missing.push(music.id);
+        return (music.file_state != MusicFileState::Missing).then(|| ArgDBUpdateMusicFile {
+            id: music.id,
+            stamp: music.file_stamp.clone(),
+            state: MusicFileState::Missing,
+        });
+    };
+
+    let stamp = entry_stamp(entry);
+    if music.file_state == MusicFileState::Missing {
+        ret.restored.push(music.id);
+    }
+    let state = match music.file_stamp.as_ref() {
+        Some(old) if stamp_changed(old, &stamp) => {
+            ret.changed.push(music.id);
+            MusicFileState::Changed
+        }
+        Some(_) => {
+            ret.unchanged += 1;
+            MusicFileState::Present
+        }
+        None => {
+            ret.stamped += 1;
+            MusicFileState::Present
+        }
+    };
+    (music.file_stamp.as_ref() != Some(&stamp) || music.file_state != state).then_some(
+        ArgDBUpdateMusicFile {
+            id: music.id,
+            stamp: Some(stamp),
+            state,
+        },
+    )
+}
+
+/// Lists the directories of the musics in the library again and compares
+/// the files with the stamps recorded when they were last listed. Musics
+/// whose file is gone are marked as missing, and changed files are probed
+/// again in the background.
+pub async fn rescan_library(
+    cx: &BackendContext,
+    arg: ArgRescanLibrary,
+) -> BResult<LibraryRescanResult> {
+    let mut dirs: BTreeMap<(StorageId, String), Vec<MusicModel>> = Default::default();
+    for music in cx.database_server().load_all_musics()? {
+        if arg.storage_id.is_some_and(|id| id != music.loc.storage_id) {
+            continue;
+        }
+        dirs.entry((music.loc.storage_id, parent_dir(&music.loc.path)))
+            .or_default()
+            .push(music);
+    }
+
+    let mut ret = LibraryRescanResult::default();
+    let mut updates: Vec<ArgDBUpdateMusicFile> = Default::default();
+    for ((storage_id, dir), musics) in dirs {
+        let failed_dir = StorageEntryLoc {
+            storage_id,
+            path: dir.clone(),
+        };
+        let Some(backend) = get_storage_backend(cx, storage_id)? else {
+            ret.failed_dirs.push(failed_dir);
+            continue;
+        };
+        let entries = match backend.list(dir).await {
+            Ok(entries) => entries,
+            // A removed directory means its musics are gone too.
+            Err(e) if e.is_not_found() => Default::default(),
+            Err(e) => {
+                tracing::warn!("fail to list {:?}: {}", failed_dir, e);
+                ret.failed_dirs.push(failed_dir);
+                continue;
+            }
+        };
+        let entries: HashMap<&str, &Entry> = entries
+            .iter()
+            .filter(|e| !e.is_dir)
+            .map(|e| (e.path.as_str(), e))
+            .collect();
+        for music in musics.iter() {
+            let entry = entries.get(music.loc.path.as_str()).copied();
+            updates.extend(diff_music(music, entry, &mut ret));
+        }
+    }
+
+    cx.database_server().update_music_files(updates)?;
+    spawn_reprobe_musics(cx, ret.changed.clone());
+    Ok(ret)
+}
@@ -0,0 +1,552 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use ease_client_schema::{
+    LastFmConfig, ListenBrainzConfig, MusicId, PlayEventKind, ScrobbleConfigModel, ScrobbleKey,
+    ScrobbleModel, ScrobbleService,
+};
+use md5::{Digest, Md5};
+use reqwest::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    Client, StatusCode,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    ctx::BackendContext,
+    error::{BError, BResult},
+    services::{link_music_tags, USER_AGENT},
+};
+
+const LISTENBRAINZ_BATCH: usize = 100;
+/// Last.fm takes at most 50 scrobbles per call.
+const LASTFM_BATCH: usize = 50;
+/// Submitted listens are kept this long, in seconds, to drop listens
+/// recorded again.
+const SUBMITTED_KEEP_SECS: i64 = 14 * 24 * 3600;
+/// Musics shorter than this, in milliseconds, are never scrobbled.
+const MIN_SCROBBLE_DURATION: u64 = 30_000;
+/// A listen counts once half of the music, or this many milliseconds, are
+/// played.
+const MAX_SCROBBLE_THRESHOLD: u64 = 240_000;
+/// Listens the service failed to take this many times are dropped.
+pub(crate) const MAX_SCROBBLE_ATTEMPTS: u32 = 10;
+
+#[derive(Default)]
+pub(crate) struct ScrobbleState {
+    /// Held while the queue is submitted, so a listen is sent once.
+    flush: tokio::sync::Mutex<()>,
+    /// Error of the last submission to each service, cleared on success.
+    errors: Mutex<HashMap<ScrobbleService, String>>,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ScrobbleStatus {
+    pub service: ScrobbleService,
+    /// Listens waiting to be submitted.
+    pub pending: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug)]
+enum SubmitError {
+    /// The service could not be reached. The listens are submitted again
+    /// later, however long it stays offline.
+    Unreachable(String),
+    /// The service refused the credentials or failed. The listens are
+    /// submitted again later, at most [`MAX_SCROBBLE_ATTEMPTS`] times.
+    Retry(String),
+    /// The service rejected the listens, which are dropped.
+    Rejected(String),
+}
+
+fn configured_services(config: &ScrobbleConfigModel) -> Vec<ScrobbleService> {
+    let mut ret: Vec<ScrobbleService> = Default::default();
+    if config.listenbrainz.is_some() {
+        ret.push(ScrobbleService::ListenBrainz);
+    }
+    if config.lastfm.is_some() {
+        ret.push(ScrobbleService::LastFm);
+    }
+    ret
+}
+
+fn is_listen(listened: u64, duration: Option<u64>) -> bool {
+    match duration {
+        Some(duration) if duration < MIN_SCROBBLE_DURATION => false,
+        Some(duration) => listened >= (duration / 2).min(MAX_SCROBBLE_THRESHOLD),
+        None => listened >= MAX_SCROBBLE_THRESHOLD,
+    }
+}
+
+/// Tags a listen is submitted with, or `None` when the music is gone or has
+/// no artist, which scrobbling requires.
+fn load_track(cx: &BackendContext, id: MusicId) -> BResult<Option<ScrobbleModel>> {
+    let db = cx.database_server();
+    let Some(model) = db.load_music(id)? else {
+        return Ok(None);
+    };
+    let mut artists: Vec<String> = Default::default();
+    for id in model.artists.iter() {
+        artists.extend(db.load_artist(*id)?.map(|v| v.name));
+    }
+    if artists.is_empty() {
+        return Ok(None);
+    }
+    let album = match model.album {
+        Some(id) => db.load_album(id)?.map(|v| v.title),
+        None => None,
+    };
+    Ok(Some(ScrobbleModel {
+        title: model.title,
+        artist: artists.join(", "),
+        album,
+        duration: model.duration.map(|v| v.as_millis() as u64),
+        attempts: 0,
+        submitted: false,
+    }))
+}
+
+fn build_client() -> reqwest::Result<Client> {
+    Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(Duration::from_secs(10))
+        .timeout(Duration::from_secs(20))
+        .build()
+}
+
+fn listenbrainz_listen(listened_at: Option<i64>, track: &ScrobbleModel) -> Value {
+    let mut metadata = json!({
+        "artist_name": track.artist,
+        "track_name": track.title,
+    });
+    if let Some(album) = track.album.as_ref() {
+        metadata["release_name"] = json!(album);
+    }
+    if let Some(duration) = track.duration {
+        metadata["additional_info"] = json!({ "duration_ms": duration });
+    }
+    match listened_at {
+        Some(listened_at) => json!({ "listened_at": listened_at, "track_metadata": metadata }),
+        None => json!({ "track_metadata": metadata }),
+    }
+}
+
+async fn submit_listenbrainz(
+    client: &Client,
+    config: &ListenBrainzConfig,
+    listen_type: &str,
+    payload: Vec<Value>,
+) -> Result<(), SubmitError> {
+    let url = format!("{}/1/submit-listens", config.base_url.trim_end_matches('/'));
+    let resp = client
+        .post(url)
+        .header(AUTHORIZATION, format!("Token {}", config.token))
+        .header(CONTENT_TYPE, "application/json")
+        .body(json!({ "listen_type": listen_type, "payload": payload }).to_string())
+        .send()
+        .await
+        .map_err(|e| SubmitError::Unreachable(e.to_string()))?;
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let message = format!(
+        "ListenBrainz: {} {}",
+        status,
+        resp.text().await.unwrap_or_default()
+    );
+    match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS => {
+            Err(SubmitError::Retry(message))
+        }
+        status if status.is_server_error() => Err(SubmitError::Retry(message)),
+        _ => Err(SubmitError::Rejected(message)),
+    }
+}
+
+/// Signs a Last.fm call with the MD5 of its parameters sorted by name,
+/// followed by the shared secret.
+fn lastfm_signature(params: &[(String, String)], secret: &str) -> String {
+    let mut params: Vec<&(String, String)> = params.iter().collect();
+    params.sort();
+    let mut hasher = Md5::new();
+    for (name, value) in params {
+        hasher.update(name.as_bytes());
+        hasher.update(value.as_bytes());
+    }
+    hasher.update(secret.as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|v| format!("{v:02x}"))
+        .collect()
+}
+
+async fn call_lastfm(
+    client: &Client,
+    config: &LastFmConfig,
+    method: &str,
+    mut params: Vec<(String, String)>,
+) -> Result<(), SubmitError> {
+    params.push(("method".to_string(), method.to_string()));
+    params.push(("api_key".to_string(), config.api_key.clone()));
+    params.push(("sk".to_string(), config.session_key.clone()));
+    let signature = lastfm_signature(&params, &config.api_secret);
+    params.push(("api_sig".to_string(), signature));
+    params.push(("format".to_string(), "json".to_string()));
+
+    let resp = client
+        .post(config.base_url.as_str())
+        .form(&params)
+        .send()
+        .await
+        .map_err(|e| SubmitError::Unreachable(e.to_string()))?;
+    let status = resp.status();
+    let body: Option<Value> = match resp.bytes().await {
+        Ok(body) => serde_json::from_slice(&body).ok(),
+        Err(_) => None,
+    };
+    let error = body
+        .as_ref()
+        .and_then(|v| v.get("error"))
+        .and_then(|v| v.as_i64());
+    let message = || {
+        let text = body
+            .as_ref()
+            .and_then(|v| v.get("message"))
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+        format!("Last.fm: {status} {text}")
+    };
+    match error {
+        None if status.is_success() => Ok(()),
+        None if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS => {
+            Err(SubmitError::Rejected(message()))
+        }
+        None => Err(SubmitError::Retry(message())),
+        // Authentication failures and the service being unavailable or
+        // rate limited.
+        Some(4 | 9 | 10 | 11 | 16 | 26 | 29) => Err(SubmitError::Retry(message())),
+        Some(_) => Err(SubmitError::Rejected(message())),
+    }
+}
+
+fn lastfm_track_params(track: &ScrobbleModel, index: Option<usize>) -> Vec<(String, String)> {
+    let name = |v: &str| match index {
+        Some(i) => format!("{v}[{i}]"),
+        None => v.to_string(),
+    };
+    let mut ret = vec![
+        (name("artist"), track.artist.clone()),
+        (name("track"), track.title.clone()),
+    ];
+    if let Some(album) = track.album.as_ref() {
+        ret.push((name("album"), album.clone()));
+    }
+    if let Some(duration) = track.duration {
+        ret.push((name("duration"), (duration / 1000).to_string()));
+    }
+    ret
+}
+
+async fn submit_now_playing(
+    client: &Client,
+    config: &ScrobbleConfigModel,
+    service: ScrobbleService,
+    track: &ScrobbleModel,
+) -> Result<(), SubmitError> {
+    match (service, config) {
+        (
+            ScrobbleService::ListenBrainz,
+            ScrobbleConfigModel {
+                listenbrainz: Some(config),
+                ..
+            },
+        ) => {
+            let payload = vec![listenbrainz_listen(None, track)];
+            submit_listenbrainz(client, config, "playing_now", payload).await
+        }
+        (
+            ScrobbleService::LastFm,
+            ScrobbleConfigModel {
+                lastfm: Some(config),
+                ..
+            },
+        ) => {
+            let params = lastfm_track_params(track, None);
+            call_lastfm(client, config, "track.updateNowPlaying", params).await
+        }
+        _ => Ok(()),
+    }
+}
+
+async fn submit_listens(
+    client: &Client,
+    config: &ScrobbleConfigModel,
+    service: ScrobbleService,
+    listens: &[(ScrobbleKey, ScrobbleModel)],
+) -> Result<(), SubmitError> {
+    match (service, config) {
+        (
+            ScrobbleService::ListenBrainz,
+            ScrobbleConfigModel {
+                listenbrainz: Some(config),
+                ..
+            },
+        ) => {
+            let listen_type = match listens.len() {
+                1 => "single",
+                _ => "import",
+            };
+            let payload = listens
+                .iter()
+                .map(|(key, track)| listenbrainz_listen(Some(key.listened_at), track))
+                .collect();
+            submit_listenbrainz(client, config, listen_type, payload).await
+        }
+        (
+            ScrobbleService::LastFm,
+            ScrobbleConfigModel {
+                lastfm: Some(config),
+                ..
+            },
+        ) => {
+            let mut params: Vec<(String, String)> = Default::default();
+            for (i, (key, track)) in listens.iter().enumerate() {
+                params.extend(lastfm_track_params(track, Some(i)));
+                params.push((format!("timestamp[{i}]"), key.listened_at.to_string()));
+            }
+            call_lastfm(client, config, "track.scrobble", params).await
+        }
+        _ => Ok(()),
+    }
+}
+
+fn batch_size(service: ScrobbleService) -> usize {
+    match service {
+        ScrobbleService::ListenBrainz => LISTENBRAINZ_BATCH,
+        ScrobbleService::LastFm => LASTFM_BATCH,
+    }
+}
+
+fn set_error(cx: &BackendContext, service: ScrobbleService, error: Option<String>) {
+    let mut errors = cx.scrobble_state().errors.lock().unwrap();
+    match error {
+        Some(error) => errors.insert(service, error),
+        None => errors.remove(&service),
+    };
+}
+
+pub(crate) fn get_scrobble_config(cx: &BackendContext) -> BResult<ScrobbleConfigModel> {
+    cx.database_server().load_scrobble_config()
+}
+
+/// Saves the services to scrobble to and submits the listens queued for
+/// them. Listens of a service turned off stay queued until it is turned on
+/// again.
+pub(crate) fn set_scrobble_config(cx: &BackendContext, config: ScrobbleConfigModel) -> BResult<()> {
+    cx.database_server().save_scrobble_config(config)?;
+    cx.scrobble_state().errors.lock().unwrap().clear();
+    spawn_flush_scrobbles(cx);
+    Ok(())
+}
+
+pub(crate) fn get_scrobble_status(cx: &BackendContext) -> BResult<Vec<ScrobbleStatus>> {
+    let db = cx.database_server();
+    let config = db.load_scrobble_config()?;
+    let errors = cx.scrobble_state().errors.lock().unwrap().clone();
+    let mut ret: Vec<ScrobbleStatus> = Default::default();
+    for service in configured_services(&config) {
+        ret.push(ScrobbleStatus {
+            service,
+            pending: db.count_pending_scrobbles(service)?,
+            last_error: errors.get(&service).cloned(),
+        });
+    }
+    Ok(ret)
+}
+
+/// Submits queued listens in batches, oldest first. A service that cannot be
+/// reached keeps its listens for the next flush, and one that keeps failing
+/// them drops them after [`MAX_SCROBBLE_ATTEMPTS`] flushes.
+pub(crate) async fn flush_scrobbles(cx: &BackendContext) -> BResult<Vec<ScrobbleStatus>> {
+    let state = cx.scrobble_state().clone();
+    let _guard = state.flush.lock().await;
+    let db = cx.database_server();
+    let config = db.load_scrobble_config()?;
+    let now = cx.current_time().as_secs() as i64;
+    db.prune_scrobbles(now - SUBMITTED_KEEP_SECS)?;
+    let client = build_client().map_err(|e| BError::CustomError {
+        message: format!("fail to build scrobble client: {e}"),
+    })?;
+
+    for service in configured_services(&config) {
+        loop {
+            let listens = db.load_pending_scrobbles(service, batch_size(service))?;
+            if listens.is_empty() {
+                set_error(cx, service, None);
+                break;
+            }
+            let keys: Vec<ScrobbleKey> = listens.iter().map(|(key, _)| *key).collect();
+            match submit_listens(&client, &config, service, &listens).await {
+                Ok(()) => db.mark_scrobbles_submitted(&keys)?,
+                Err(SubmitError::Unreachable(e)) => {
+                    tracing::warn!("fail to reach {:?}: {}", service, e);
+                    set_error(cx, service, Some(e));
+                    break;
+                }
+                Err(SubmitError::Retry(e)) => {
+                    tracing::warn!("fail to scrobble to {:?}: {}", service, e);
+                    let dropped = db.fail_scrobbles(&keys, MAX_SCROBBLE_ATTEMPTS)?;
+                    if dropped > 0 {
+                        tracing::warn!("{} listens dropped after failing to scrobble", dropped);
+                    }
+                    set_error(cx, service, Some(e));
+                    break;
+                }
+                Err(SubmitError::Rejected(e)) => {
+                    tracing::warn!("{} listens rejected by {:?}: {}", keys.len(), service, e);
+                    db.remove_scrobbles(&keys)?;
+                    set_error(cx, service, Some(e));
+                }
+            }
+        }
+    }
+    get_scrobble_status(cx)
+}
+
+fn spawn_flush_scrobbles(cx: &BackendContext) {
+    let cx = cx.weak();
+    ease_client_tokio::tokio_runtime().spawn(async move {
+        let Some(cx) = cx.upgrade() else {
+            return;
+        };
+        if let Err(e) = flush_scrobbles(&cx).await {
+            tracing::warn!("fail to flush scrobbles: {}", e);
+        }
+    });
+}
+
+fn spawn_now_playing(config: ScrobbleConfigModel, track: ScrobbleModel) {
+    ease_client_tokio::tokio_runtime().spawn(async move {
+        let client = match build_client() {
+            Ok(client) => client,
+            Err(e) => {
+                tracing::warn!("fail to build scrobble client: {}", e);
+                return;
+            }
+        };
+        for service in configured_services(&config) {
+            if let Err(e) = submit_now_playing(&client, &config, service, &track).await {
+                tracing::warn!("fail to send now playing to {:?}: {:?}", service, e);
+            }
+        }
+    });
+}
+
+/// Sends "now playing" for a started music, or queues the play when it is
+/// long enough to count as a listen.
+fn record_play(
+    cx: &BackendContext,
+    config: ScrobbleConfigModel,
+    music: MusicId,
+    track: ScrobbleModel,
+    kind: PlayEventKind,
+    time: Duration,
+    listened: Option<u64>,
+) -> BResult<()> {
+    if kind == PlayEventKind::Started {
+        spawn_now_playing(config, track);
+        return Ok(());
+    }
+    let Some(listened) = listened.filter(|v| is_listen(*v, track.duration)) else {
+        return Ok(());
+    };
+    let listened_at = (time.as_millis() as u64).saturating_sub(listened) / 1000;
+    let listens = configured_services(&config)
+        .into_iter()
+        .map(|service| {
+            let key = ScrobbleKey {
+                service,
+                listened_at: listened_at as i64,
+                music,
+            };
+            (key, track.clone())
+        })
+        .collect();
+    cx.database_server().enqueue_scrobbles(listens)?;
+    spawn_flush_scrobbles(cx);
+    Ok(())
+}
+
+/// Records the play of a music not linked to an artist yet, such as one not
+/// probed, once its artist and album are read from its embedded tags.
+fn spawn_record_untagged_play(
+    cx: &BackendContext,
+    config: ScrobbleConfigModel,
+    music: MusicId,
+    kind: PlayEventKind,
+    time: Duration,
+    listened: Option<u64>,
+) {
+    let cx = cx.weak();
+    ease_client_tokio::tokio_runtime().spawn(async move {
+        let Some(cx) = cx.upgrade() else {
+            return;
+        };
+        let ret = match link_music_tags(&cx, music).await {
+            Ok(true) => load_track(&cx, music),
+            Ok(false) => Ok(None),
+            Err(e) => Err(e),
+        };
+        let ret = match ret {
+            Ok(Some(track)) => record_play(&cx, config, music, track, kind, time, listened),
+            Ok(None) => {
+                tracing::debug!("play of {:?} not scrobbled: the music has no artist", music);
+                Ok(())
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = ret {
+            tracing::warn!("fail to scrobble play of {:?}: {}", music, e);
+        }
+    });
+}
+
+fn note_play_event_impl(
+    cx: &BackendContext,
+    music: MusicId,
+    kind: PlayEventKind,
+    time: Duration,
+    listened: Option<u64>,
+) -> BResult<()> {
+    let db = cx.database_server();
+    let config = db.load_scrobble_config()?;
+    if configured_services(&config).is_empty() {
+        return Ok(());
+    }
+    if db.load_music(music)?.is_none() {
+        return Ok(());
+    }
+    match load_track(cx, music)? {
+        Some(track) => record_play(cx, config, music, track, kind, time, listened),
+        None => {
+            spawn_record_untagged_play(cx, config, music, kind, time, listened);
+            Ok(())
+        }
+    }
+}
+
+/// Sends "now playing" for started musics and queues the plays long enough
+/// to count as listens. Failures are logged and do not fail the play event.
+pub(crate) fn note_play_event(
+    cx: &BackendContext,
+    music: MusicId,
+    kind: PlayEventKind,
+    time: Duration,
+    listened: Option<u64>,
+) {
+    if let Err(e) = note_play_event_impl(cx, music, kind, time, listened) {
+        tracing::warn!("fail to scrobble play of {:?}: {}", music, e);
+    }
+}
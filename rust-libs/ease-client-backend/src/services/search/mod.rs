@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use ease_client_schema::{MusicId, SearchField, SearchPostingModel};
+
+use crate::{
+    ctx::BackendContext,
+    error::BResult,
+    objects::{ArgSearchLibrary, LibrarySearchPage},
+    utils::search::tokenize,
+};
+
+use super::music::build_music_abstract;
+
+const EXACT_MATCH_WEIGHT: f64 = 1.0;
+const PREFIX_MATCH_WEIGHT: f64 = 0.6;
+const FUZZY_MATCH_WEIGHT: f64 = 0.4;
+/// Keywords shorter than this, in characters, are never matched fuzzily.
+const FUZZY_MIN_CHARS: usize = 4;
+
+fn field_weight(field: SearchField) -> f64 {
+    match field {
+        SearchField::Title => 4.0,
+        SearchField::Artist => 3.0,
+        SearchField::Album => 2.0,
+        SearchField::Genre => 1.0,
+        SearchField::Path => 1.0,
+        SearchField::Lyric => 0.5,
+    }
+}
+
+fn fuzzy_max_distance(keyword: &str) -> usize {
+    let len = keyword.chars().count();
+    if len < FUZZY_MIN_CHARS || !keyword.chars().all(|c| c.is_ascii_alphanumeric()) {
+        0
+    } else if len < 8 {
+        1
+    } else {
+        2
+    }
+}
+
+fn posting_score(posting: &SearchPostingModel, match_weight: f64) -> f64 {
+    match_weight * field_weight(posting.field) * (1.0 + (posting.count as f64).ln())
+}
+
+/// Scores the musics matching one keyword, by exact, prefix or fuzzy match of
+/// the indexed terms. A music keeps the score of its best matching term.
+fn match_keyword(cx: &BackendContext, keyword: &str) -> BResult<HashMap<MusicId, f64>> {
+    let mut scores: HashMap<MusicId, f64> = Default::default();
+    let mut add = |posting: &SearchPostingModel, match_weight: f64| {
+        let score = posting_score(posting, match_weight);
+        let entry = scores.entry(posting.music_id).or_default();
+        if score > *entry {
+            *entry = score;
+        }
+    };
+
+    for (term, posting) in cx.database_server().load_search_postings(keyword, false)? {
+        let weight = if term == keyword {
+            EXACT_MATCH_WEIGHT
+        } else {
+            PREFIX_MATCH_WEIGHT
+        };
+        add(&posting, weight);
+    }
+
+    // Typos, including swapped letters, are looked up among the terms
+    // sharing the first character of the keyword, which keeps the scan small.
+    let max_distance = fuzzy_max_distance(keyword);
+    if max_distance > 0 {
+        let first: String = keyword.chars().take(1).collect();
+        for term in cx.database_server().load_search_terms(&first)? {
+            if term.starts_with(keyword) {
+                continue;
+            }
+            if strsim::osa_distance(&term, keyword) <= max_distance {
+                for (_, posting) in cx.database_server().load_search_postings(&term, true)? {
+                    add(&posting, FUZZY_MATCH_WEIGHT);
+                }
+            }
+        }
+    }
+    Ok(scores)
+}
+
+/// Searches the library for musics matching every keyword, ranked by how and
+/// where the keywords matched.
+pub(crate) fn search_library(
+    cx: &BackendContext,
+    arg: ArgSearchLibrary,
+) -> BResult<LibrarySearchPage> {
+    let page = arg.page.max(1);
+    let per_page = arg.per_page.max(1);
+    let mut keywords = tokenize(&arg.keywords);
+    keywords.dedup();
+
+    let mut ranked: Option<HashMap<MusicId, f64>> = None;
+    for keyword in keywords.iter() {
+        let scores = match_keyword(cx, keyword)?;
+        ranked = Some(match ranked {
+            None => scores,
+            Some(ranked) => ranked
+                .into_iter()
+                .filter_map(|(id, score)| scores.get(&id).map(|v| (id, score + v)))
+                .collect(),
+        });
+        if ranked.as_ref().is_some_and(|v| v.is_empty()) {
+            break;
+        }
+    }
+    let mut ranked: Vec<(MusicId, f64)> = ranked.unwrap_or_default().into_iter().collect();
+    ranked.sort_by(|lhs, rhs| rhs.1.total_cmp(&lhs.1).then(lhs.0.cmp(&rhs.0)));
+
+    let total = ranked.len() as u64;
+    let mut musics = Vec::new();
+    let start = (page as usize - 1).saturating_mul(per_page as usize);
+    for (id, _) in ranked.into_iter().skip(start).take(per_page as usize) {
+        if let Some(model) = cx.database_server().load_music(id)? {
+            musics.push(build_music_abstract(cx, model));
+        }
+    }
+    Ok(LibrarySearchPage {
+        musics,
+        total,
+        page,
+        per_page,
+    })
+}
@@ -1,3 +1,6 @@
+mod remap;
+mod search;
+
 use std::{
     collections::HashMap,
     sync::{Arc, RwLock},
@@ -7,8 +10,8 @@ use std::{
 use crate::{
     ctx::BackendContext,
     error::BResult,
-    objects::{ArgUpsertStorage, Storage},
-    services::{get_music, get_music_cover_bytes, get_music_storage_entry_loc},
+    objects::{ArgUpsertStorage, Storage, StorageEntryType},
+    services::{get_music_cover_bytes, get_music_storage_entry_loc},
 };
 use ease_client_schema::{
     DataSourceKey, MusicId, StorageEntryLoc, StorageId, StorageModel, StorageType,
@@ -19,6 +22,9 @@ use ease_remote_storage::{
 };
 use tracing::instrument;
 
+pub use remap::*;
+pub(crate) use search::*;
+
 #[derive(Default)]
 pub(crate) struct StorageState {
     cache: RwLock<HashMap<StorageId, Arc<dyn StorageBackend + Send + Sync + 'static>>>,
@@ -31,6 +37,31 @@ pub(crate) struct LoadedStorageEntryData {
     pub name: String,
 }
 
+const MUSIC_EXTS: [&str; 7] = ["wav", "mp3", "aac", "flac", "ogg", "opus", "m4a"];
+const IMAGE_EXTS: [&str; 3] = ["jpg", "jpeg", "png"];
+const LYRIC_EXTS: [&str; 1] = ["lrc"];
+
+/// Classifies a storage entry by the extension of its path, the same way the
+/// app does.
+pub(crate) fn storage_entry_type(path: &str, is_dir: bool) -> StorageEntryType {
+    if is_dir {
+        return StorageEntryType::Folder;
+    }
+    let ext = path
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_lowercase())
+        .unwrap_or_default();
+    if MUSIC_EXTS.contains(&ext.as_str()) {
+        StorageEntryType::Music
+    } else if IMAGE_EXTS.contains(&ext.as_str()) {
+        StorageEntryType::Image
+    } else if LYRIC_EXTS.contains(&ext.as_str()) {
+        StorageEntryType::Lyric
+    } else {
+        StorageEntryType::Other
+    }
+}
+
 pub(crate) fn normalize_default_storage_path(path: &str) -> String {
     let trimmed = path.trim();
     if trimmed.is_empty() || trimmed == "/" {
@@ -154,6 +185,10 @@ pub(crate) fn evict_storage_backend_cache(cx: &BackendContext, storage_id: Stora
     w.remove(&storage_id);
 }
 
+pub(crate) fn clear_storage_backend_cache(cx: &BackendContext) {
+    cx.storage_state().cache.write().unwrap().clear();
+}
+
 #[cfg(test)]
 pub(crate) fn storage_backend_cache_contains(cx: &BackendContext, storage_id: StorageId) -> bool {
     let state = cx.storage_state().cache.read().unwrap();
@@ -252,11 +287,11 @@ pub(crate) async fn get_asset_file(
 ) -> BResult<Option<StreamFile>> {
     match key {
         DataSourceKey::Music { id } => {
-            let m = get_music(cx, id).await?;
-            let Some(m) = m else {
+            let loc = get_music_storage_entry_loc(cx, id)?;
+            let Some(loc) = loc else {
                 return Ok(None);
             };
-            get_asset_file_by_loc(cx, m.loc, byte_offset).await
+            get_asset_file_by_loc(cx, loc, byte_offset).await
         }
         DataSourceKey::Cover { id } => {
             let buf = get_music_cover_bytes(cx, id)?;
@@ -0,0 +1,157 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use ease_client_schema::{MusicId, StorageEntryLoc};
+use ease_remote_storage::Entry;
+
+use crate::{
+    ctx::BackendContext,
+    error::{BError, BResult},
+    repositories::music::ArgDBRemapMusic,
+    services::{entry_stamp, get_storage_backend},
+};
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgRemapStorage {
+    /// Storage and path prefix the musics are moved from.
+    pub from: StorageEntryLoc,
+    /// Storage and path prefix the musics are moved to.
+    pub to: StorageEntryLoc,
+    /// List the target directories and refuse to remap if a file is absent.
+    pub verify: bool,
+}
+
+#[derive(Debug, Clone, Default, uniffi::Record)]
+pub struct RemapStorageResult {
+    /// Remapped musics. Empty when the remap was refused.
+    pub remapped: Vec<MusicId>,
+    /// Musics whose target file was not found by the verification.
+    pub missing_targets: Vec<MusicId>,
+    /// Musics whose target entry is already linked to another music.
+    pub conflicts: Vec<MusicId>,
+}
+
+fn trim_prefix(prefix: &str) -> &str {
+    prefix.trim_end_matches('/')
+}
+
+/// Rewrites `path` from under `from` to under `to`, or returns `None` when
+/// it is not under `from`.
+fn remap_path(path: &str, from: &str, to: &str) -> Option<String> {
+    let rest = path.strip_prefix(trim_prefix(from))?;
+    if !rest.is_empty() && !rest.starts_with('/') {
+        return None;
+    }
+    Some(format!("{}{}", trim_prefix(to), rest))
+}
+
+fn remap_loc(loc: &StorageEntryLoc, arg: &ArgRemapStorage) -> Option<StorageEntryLoc> {
+    if loc.storage_id != arg.from.storage_id {
+        return None;
+    }
+    Some(StorageEntryLoc {
+        storage_id: arg.to.storage_id,
+        path: remap_path(&loc.path, &arg.from.path, &arg.to.path)?,
+    })
+}
+
+fn parent_dir(path: &str) -> &str {
+    match path.rsplit_once('/') {
+        Some(("", _)) | None => "/",
+        Some((parent, _)) => parent,
+    }
+}
+
+/// Lists the directories of the target entries, keyed by path. Directories
+/// that do not exist have no entries.
+async fn list_targets(
+    cx: &BackendContext,
+    arg: &ArgRemapStorage,
+    paths: impl Iterator<Item = &str>,
+) -> BResult<HashMap<String, Entry>> {
+    let Some(backend) = get_storage_backend(cx, arg.to.storage_id)? else {
+        return Err(BError::AssetNotFound);
+    };
+    let dirs: HashSet<&str> = paths.map(parent_dir).collect();
+    let mut ret: HashMap<String, Entry> = Default::default();
+    for dir in dirs {
+        match backend.list(dir.to_string()).await {
+            Ok(entries) => ret.extend(
+                entries
+                    .into_iter()
+                    .filter(|e| !e.is_dir)
+                    .map(|e| (e.path.clone(), e)),
+            ),
+            Err(e) if e.is_not_found() => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(ret)
+}
+
+/// Moves the musics under a path prefix of one storage to a path prefix of
+/// another, for a library copied to another server. Musics keep their ids,
+/// so playlists, covers, durations and tags are kept. Lyric files and
+/// folder bindings under the prefix move along. Either every music is
+/// remapped or none is.
+pub async fn remap_storage(
+    cx: &BackendContext,
+    arg: ArgRemapStorage,
+) -> BResult<RemapStorageResult> {
+    let db = cx.database_server();
+    let mut planned: BTreeMap<MusicId, (StorageEntryLoc, Option<StorageEntryLoc>)> =
+        Default::default();
+    for music in db.load_all_musics()? {
+        let Some(loc) = remap_loc(&music.loc, &arg) else {
+            continue;
+        };
+        let lyric = music
+            .lyric
+            .map(|lyric| remap_loc(&lyric, &arg).unwrap_or(lyric));
+        planned.insert(music.id, (loc, lyric));
+    }
+
+    let mut ret = RemapStorageResult::default();
+    for (id, (loc, _)) in planned.iter() {
+        if let Some(other) = db.load_music_by_loc(loc.clone())? {
+            if !planned.contains_key(&other.id) {
+                ret.conflicts.push(*id);
+            }
+        }
+    }
+
+    let targets = if arg.verify {
+        let targets = list_targets(cx, &arg, planned.values().map(|v| v.0.path.as_str())).await?;
+        ret.missing_targets = planned
+            .iter()
+            .filter(|(_, (loc, _))| !targets.contains_key(&loc.path))
+            .map(|(id, _)| *id)
+            .collect();
+        targets
+    } else {
+        Default::default()
+    };
+    if !ret.conflicts.is_empty() || !ret.missing_targets.is_empty() {
+        return Ok(ret);
+    }
+
+    let folders = db
+        .load_playlist_folders()?
+        .into_iter()
+        .filter_map(|(id, mut folder)| {
+            folder.dir = remap_loc(&folder.dir, &arg)?;
+            Some((id, folder))
+        })
+        .collect();
+    let musics = planned
+        .into_iter()
+        .map(|(id, (loc, lyric))| ArgDBRemapMusic {
+            id,
+            stamp: targets.get(&loc.path).map(entry_stamp),
+            loc,
+            lyric,
+        })
+        .collect::<Vec<_>>();
+    ret.remapped = musics.iter().map(|v| v.id).collect();
+    db.remap_musics(musics, folders)?;
+    Ok(ret)
+}
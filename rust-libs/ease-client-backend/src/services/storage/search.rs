@@ -0,0 +1,101 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+use ease_remote_storage::{Entry, SearchScope, StorageBackend, StorageBackendResult};
+use tokio::task::JoinSet;
+
+use crate::utils::search::{fold_width, matches_keywords, normalize};
+
+/// Directories listed at most when a storage is searched by walking it.
+const MAX_SEARCH_DIRS: usize = 256;
+/// Directories listed at the same time while walking.
+const SEARCH_CONCURRENCY: usize = 4;
+/// Results asked from storages that search on their server.
+const MAX_SERVER_RESULTS: usize = 500;
+
+fn in_scope(entry: &Entry, scope: SearchScope) -> bool {
+    match scope {
+        SearchScope::All => true,
+        SearchScope::Directory => entry.is_dir,
+        SearchScope::File => !entry.is_dir,
+    }
+}
+
+/// Finds the entries under `parent` whose name matches `keywords` the way
+/// the library search does, so pinyin, romaji and traditional Chinese find
+/// them too. Servers can't match those, so the storage is walked up to
+/// [`MAX_SEARCH_DIRS`] directories. Storages that search on their server
+/// add the deeper results they find for the keywords as typed.
+///
+/// Fails only when `parent` itself can't be listed. Results are sorted by
+/// path.
+pub(crate) async fn search_storage_entries(
+    backend: Arc<dyn StorageBackend + Send + Sync>,
+    parent: &str,
+    keywords: &str,
+    scope: SearchScope,
+) -> StorageBackendResult<Vec<Entry>> {
+    if normalize(keywords).trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut found: HashMap<String, Entry> = HashMap::new();
+    let mut pending: VecDeque<String> = VecDeque::new();
+    let mut visit = |entries: Vec<Entry>, pending: &mut VecDeque<String>| {
+        for entry in entries {
+            if entry.is_dir {
+                pending.push_back(entry.path.clone());
+            }
+            if in_scope(&entry, scope) && matches_keywords(&entry.name, keywords) {
+                found.insert(entry.path.clone(), entry);
+            }
+        }
+    };
+
+    visit(backend.list(parent.to_string()).await?, &mut pending);
+    let mut listed = 1;
+    let mut tasks = JoinSet::new();
+    loop {
+        while tasks.len() < SEARCH_CONCURRENCY && listed < MAX_SEARCH_DIRS {
+            let Some(dir) = pending.pop_front() else {
+                break;
+            };
+            listed += 1;
+            let backend = backend.clone();
+            tasks.spawn(async move { (backend.list(dir.clone()).await, dir) });
+        }
+        let Some(joined) = tasks.join_next().await else {
+            break;
+        };
+        match joined {
+            Ok((Ok(entries), _)) => visit(entries, &mut pending),
+            Ok((Err(e), dir)) => tracing::warn!("fail to list {} while searching, {e:?}", dir),
+            Err(e) => tracing::warn!("fail to list a directory while searching, {e:?}"),
+        }
+    }
+
+    let server = backend
+        .search(
+            parent.to_string(),
+            fold_width(keywords),
+            scope,
+            1,
+            MAX_SERVER_RESULTS,
+        )
+        .await;
+    match server {
+        Ok(result) => {
+            for entry in result.entries {
+                found.entry(entry.path.clone()).or_insert(entry);
+            }
+        }
+        Err(e) if e.is_search_unavailable() => {}
+        Err(e) => tracing::warn!("fail to search on the storage server, {e:?}"),
+    }
+
+    let mut ret: Vec<Entry> = found.into_values().collect();
+    ret.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(ret)
+}
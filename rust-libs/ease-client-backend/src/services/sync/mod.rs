@@ -0,0 +1,869 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use ease_client_schema::{
+    MusicId, MusicModel, PlaylistId, PlaylistModel, StorageEntryLoc, StorageId, StorageModel,
+    StorageType, SyncConfigModel, SyncFieldModel, SyncMusicKey, SyncStamp, SyncTarget, SyncValue,
+};
+use ease_order_key::OrderKey;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ctx::BackendContext,
+    error::{BError, BResult},
+    repositories::{core::DatabaseServer, music::ArgDBAddMusic, user_data::MusicUserDataModel},
+    services::{
+        get_storage_backend, new_music_ids, next_playlist_order, normalize_user_tags,
+        spawn_probe_musics, MAX_RATING,
+    },
+};
+
+/// Version of the published logs. Logs of a newer version are ignored.
+const SYNC_LOG_VERSION: u32 = 1;
+const SYNC_LOG_EXT: &str = ".json";
+
+/// Part of the library changed by a call. Only its fields are compared with
+/// the synced ones, the whole library is compared by a sync.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum SyncChange {
+    /// Creation, title and order of a playlist.
+    Playlist(PlaylistId),
+    /// Fields of a playlist and the musics in it.
+    PlaylistMusics(PlaylistId),
+    /// Lyric and user data of a music.
+    Music(MusicId),
+}
+
+#[derive(Default)]
+pub(crate) struct SyncState {
+    /// Held while the library is compared with or merged into the synced
+    /// fields.
+    lock: Mutex<()>,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgEnableSync {
+    /// Folder shared by the devices, on a storage every device has. Each
+    /// device publishes its log there as `<device id>.json`.
+    pub dir: StorageEntryLoc,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct SyncStatus {
+    pub device_id: String,
+    /// Unset when sync is off.
+    pub dir: Option<StorageEntryLoc>,
+    /// Time of the last successful sync since the unix epoch.
+    pub last_synced: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default, uniffi::Record)]
+pub struct SyncResult {
+    /// Logs of other devices that were read.
+    pub devices: u64,
+    /// Ops of other devices applied to the library.
+    pub applied: u64,
+    /// Ops about musics on storages this device does not have. They are
+    /// tried again by the next sync.
+    pub skipped: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SyncOp {
+    stamp: SyncStamp,
+    target: SyncTarget,
+    value: SyncValue,
+}
+
+/// Log published by a device. It holds the latest op of every field the
+/// device knows, including the ones merged from other devices, so a device
+/// catches up from any log, even of a device that stopped syncing.
+#[derive(Debug, Serialize, Deserialize)]
+struct SyncLog {
+    version: u32,
+    device: String,
+    ops: Vec<SyncOp>,
+}
+
+/// A log as read, with ops parsed one by one, so ops about fields added by
+/// newer versions are skipped without dropping the rest of the log.
+#[derive(Debug, Deserialize)]
+struct RawSyncLog {
+    version: u32,
+    device: String,
+    ops: Vec<serde_json::Value>,
+}
+
+impl RawSyncLog {
+    fn parse(self) -> SyncLog {
+        let ops = self
+            .ops
+            .into_iter()
+            .filter_map(|op| match serde_json::from_value::<SyncOp>(op) {
+                Ok(op) => Some(op),
+                Err(e) => {
+                    tracing::warn!("skip unknown sync op of {}: {e}", self.device);
+                    None
+                }
+            })
+            .collect();
+        SyncLog {
+            version: self.version,
+            device: self.device,
+            ops,
+        }
+    }
+}
+
+enum Applied {
+    Yes,
+    /// Recorded without effect, such as an op about a removed playlist.
+    Kept,
+    Skipped,
+}
+
+/// Storages by the key identifying them across devices.
+struct SyncStorages {
+    keys: HashMap<StorageId, String>,
+    ids: HashMap<String, StorageId>,
+}
+
+impl SyncStorages {
+    fn load(db: &Arc<DatabaseServer>) -> BResult<Self> {
+        let mut ret = Self {
+            keys: Default::default(),
+            ids: Default::default(),
+        };
+        for storage in db.load_storages()? {
+            let key = storage_sync_key(&storage);
+            ret.ids.entry(key.clone()).or_insert(storage.id);
+            ret.keys.insert(storage.id, key);
+        }
+        Ok(ret)
+    }
+
+    fn key(&self, loc: &StorageEntryLoc) -> Option<SyncMusicKey> {
+        self.keys.get(&loc.storage_id).map(|storage| SyncMusicKey {
+            storage: storage.clone(),
+            path: loc.path.clone(),
+        })
+    }
+
+    fn loc(&self, key: &SyncMusicKey) -> Option<StorageEntryLoc> {
+        self.ids
+            .get(&key.storage)
+            .map(|storage_id| StorageEntryLoc {
+                storage_id: *storage_id,
+                path: key.path.clone(),
+            })
+    }
+}
+
+/// Synced fields as currently found in the library.
+#[derive(Default)]
+struct LibraryFields {
+    fields: HashMap<SyncTarget, SyncValue>,
+    playlists: HashMap<String, PlaylistId>,
+    /// Ids given to playlists seen the first time.
+    new_sync_ids: Vec<(PlaylistId, String)>,
+}
+
+fn storage_sync_key(storage: &StorageModel) -> String {
+    let typ = match storage.typ {
+        StorageType::Local => return "local".to_string(),
+        StorageType::Webdav => "webdav",
+        StorageType::OneDrive => "onedrive",
+        StorageType::OpenList => "openlist",
+    };
+    format!("{typ}:{}", storage.addr.trim_end_matches('/'))
+}
+
+fn new_device_id() -> BResult<String> {
+    let mut bytes = [0u8; 8];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| BError::CustomError {
+            message: "fail to generate device id".to_string(),
+        })?;
+    Ok(bytes.iter().map(|v| format!("{v:02x}")).collect())
+}
+
+fn is_unset(value: &SyncValue) -> bool {
+    matches!(
+        value,
+        SyncValue::Playlist { created_time: None }
+            | SyncValue::PlaylistMusic { order: None }
+            | SyncValue::Lyric { lyric: None }
+            | SyncValue::Favorite {
+                favorite_time: None
+            }
+            | SyncValue::Rating { rating: 0 }
+    ) || matches!(value, SyncValue::UserTags { tags } if tags.is_empty())
+}
+
+fn next_stamp(cx: &BackendContext, config: &mut SyncConfigModel) -> SyncStamp {
+    let now = cx.current_time().as_millis() as i64;
+    config.clock = now.max(config.clock + 1);
+    SyncStamp {
+        time: config.clock,
+        device: config.device_id.clone(),
+    }
+}
+
+fn join_path(dir: &str, name: &str) -> String {
+    format!("{}/{name}", dir.trim_end_matches('/'))
+}
+
+fn build_status(config: SyncConfigModel) -> SyncStatus {
+    SyncStatus {
+        device_id: config.device_id,
+        dir: config.dir,
+        last_synced: (config.last_synced > 0)
+            .then(|| Duration::from_millis(config.last_synced as u64)),
+    }
+}
+
+impl LibraryFields {
+    /// Sync id of a playlist, given the first time the playlist is seen.
+    fn sync_id(&mut self, id: PlaylistId, known: Option<String>, device_id: &str) -> String {
+        match known {
+            Some(v) => v,
+            None => {
+                let v = format!("{device_id}-{}", id.as_ref());
+                self.new_sync_ids.push((id, v.clone()));
+                v
+            }
+        }
+    }
+
+    fn add_playlist(
+        &mut self,
+        db: &Arc<DatabaseServer>,
+        storages: &SyncStorages,
+        playlist: PlaylistModel,
+        sync_id: String,
+        with_musics: bool,
+    ) -> BResult<()> {
+        self.fields.insert(
+            SyncTarget::Playlist {
+                id: sync_id.clone(),
+            },
+            SyncValue::Playlist {
+                created_time: Some(playlist.created_time),
+            },
+        );
+        self.fields.insert(
+            SyncTarget::PlaylistTitle {
+                id: sync_id.clone(),
+            },
+            SyncValue::Title {
+                title: playlist.title,
+            },
+        );
+        self.fields.insert(
+            SyncTarget::PlaylistOrder {
+                id: sync_id.clone(),
+            },
+            SyncValue::Order {
+                order: playlist.order,
+            },
+        );
+        if with_musics {
+            for music in db.load_musics_by_playlist_id(playlist.id)? {
+                let Some(key) = storages.key(&music.loc) else {
+                    continue;
+                };
+                self.fields.insert(
+                    SyncTarget::PlaylistMusic {
+                        id: sync_id.clone(),
+                        music: key,
+                    },
+                    SyncValue::PlaylistMusic {
+                        order: Some(music.order),
+                    },
+                );
+            }
+        }
+        self.playlists.insert(sync_id, playlist.id);
+        Ok(())
+    }
+
+    fn add_music(&mut self, storages: &SyncStorages, music: MusicModel, data: MusicUserDataModel) {
+        let Some(key) = storages.key(&music.loc) else {
+            return;
+        };
+        self.fields.insert(
+            SyncTarget::MusicFavorite { music: key.clone() },
+            SyncValue::Favorite {
+                favorite_time: data.favorite_time,
+            },
+        );
+        self.fields.insert(
+            SyncTarget::MusicRating { music: key.clone() },
+            SyncValue::Rating {
+                rating: data.rating,
+            },
+        );
+        self.fields.insert(
+            SyncTarget::MusicUserTags { music: key.clone() },
+            SyncValue::UserTags { tags: data.tags },
+        );
+
+        // Only lyrics bound by the user, found ones are found again on every
+        // device.
+        if music.lyric_default {
+            return;
+        }
+        let lyric = match music.lyric {
+            Some(loc) => match storages.key(&loc) {
+                Some(v) => Some(v),
+                None => return,
+            },
+            None => None,
+        };
+        self.fields.insert(
+            SyncTarget::MusicLyric { music: key },
+            SyncValue::Lyric { lyric },
+        );
+    }
+}
+
+/// Smart playlists are left out, their musics follow from their rule.
+fn read_library_fields(
+    db: &Arc<DatabaseServer>,
+    storages: &SyncStorages,
+    device_id: &str,
+) -> BResult<LibraryFields> {
+    let smart = db.load_smart_playlists()?;
+    let mut sync_ids = db.load_playlist_sync_ids()?;
+    let mut ret = LibraryFields::default();
+
+    for playlist in db.load_playlists()? {
+        if smart.contains_key(&playlist.id) {
+            continue;
+        }
+        let sync_id = ret.sync_id(playlist.id, sync_ids.remove(&playlist.id), device_id);
+        ret.add_playlist(db, storages, playlist, sync_id, true)?;
+    }
+
+    let mut user_data = db.load_all_music_user_data()?;
+    for music in db.load_all_musics()? {
+        let data = user_data.remove(&music.id).unwrap_or_default();
+        ret.add_music(storages, music, data);
+    }
+    Ok(ret)
+}
+
+/// Fields of the changed parts of the library, along with the synced fields
+/// of the same targets.
+fn read_changed_fields(
+    db: &Arc<DatabaseServer>,
+    storages: &SyncStorages,
+    device_id: &str,
+    changes: &[SyncChange],
+) -> BResult<(LibraryFields, HashMap<SyncTarget, SyncFieldModel>)> {
+    let mut ret = LibraryFields::default();
+    let mut targets: Vec<SyncTarget> = Default::default();
+    let mut musics_of: Vec<String> = Default::default();
+    for change in changes {
+        match *change {
+            SyncChange::Playlist(id) | SyncChange::PlaylistMusics(id) => {
+                if db.load_smart_playlist(id)?.is_some() {
+                    continue;
+                }
+                let with_musics = matches!(change, SyncChange::PlaylistMusics(_));
+                let known = db.load_playlist_sync_id(id)?;
+                let sync_id = match (db.load_playlist(id)?, known) {
+                    (Some(playlist), known) => {
+                        let sync_id = ret.sync_id(id, known, device_id);
+                        ret.add_playlist(db, storages, playlist, sync_id.clone(), with_musics)?;
+                        sync_id
+                    }
+                    (None, Some(known)) => known,
+                    // Removed before it was ever recorded.
+                    (None, None) => continue,
+                };
+                targets.push(SyncTarget::Playlist {
+                    id: sync_id.clone(),
+                });
+                targets.push(SyncTarget::PlaylistTitle {
+                    id: sync_id.clone(),
+                });
+                targets.push(SyncTarget::PlaylistOrder {
+                    id: sync_id.clone(),
+                });
+                if with_musics {
+                    musics_of.push(sync_id);
+                }
+            }
+            SyncChange::Music(id) => {
+                let Some(music) = db.load_music(id)? else {
+                    continue;
+                };
+                let Some(key) = storages.key(&music.loc) else {
+                    continue;
+                };
+                targets.push(SyncTarget::MusicLyric { music: key.clone() });
+                targets.push(SyncTarget::MusicFavorite { music: key.clone() });
+                targets.push(SyncTarget::MusicRating { music: key.clone() });
+                targets.push(SyncTarget::MusicUserTags { music: key });
+                ret.add_music(storages, music, db.load_music_user_data(id)?);
+            }
+        }
+    }
+    let stored = db.load_sync_fields_of(&targets, &musics_of)?;
+    Ok((ret, stored))
+}
+
+/// Stamps the fields of the library that differ from the synced ones as ops
+/// of this device.
+fn record_fields_locked(
+    cx: &BackendContext,
+    config: &mut SyncConfigModel,
+    storages: &SyncStorages,
+    library: LibraryFields,
+    stored: &HashMap<SyncTarget, SyncFieldModel>,
+) -> BResult<()> {
+    let mut changes: Vec<(SyncTarget, SyncValue)> = Default::default();
+    for (target, value) in library.fields.iter() {
+        match stored.get(target) {
+            Some(field) if field.value == *value => {}
+            None if is_unset(value) => {}
+            _ => changes.push((target.clone(), value.clone())),
+        }
+    }
+    for (target, field) in stored.iter() {
+        if library.fields.contains_key(target) || is_unset(&field.value) {
+            continue;
+        }
+        let removed = match target {
+            SyncTarget::Playlist { id } if !library.playlists.contains_key(id) => {
+                SyncValue::Playlist { created_time: None }
+            }
+            SyncTarget::PlaylistMusic { id, music }
+                if library.playlists.contains_key(id)
+                    && storages.ids.contains_key(&music.storage) =>
+            {
+                SyncValue::PlaylistMusic { order: None }
+            }
+            _ => continue,
+        };
+        changes.push((target.clone(), removed));
+    }
+
+    if changes.is_empty() && library.new_sync_ids.is_empty() {
+        return Ok(());
+    }
+    let stamp = next_stamp(cx, config);
+    let fields = changes
+        .into_iter()
+        .map(|(target, value)| {
+            (
+                target,
+                SyncFieldModel {
+                    stamp: stamp.clone(),
+                    value,
+                },
+            )
+        })
+        .collect();
+    cx.database_server()
+        .save_sync_fields(config.clone(), fields, library.new_sync_ids)
+}
+
+/// Stamps the fields changed since they were last recorded as ops of this
+/// device, comparing the whole library with the synced fields.
+fn record_changes_locked(cx: &BackendContext, config: &mut SyncConfigModel) -> BResult<()> {
+    let db = cx.database_server();
+    let storages = SyncStorages::load(db)?;
+    let library = read_library_fields(db, &storages, &config.device_id)?;
+    let stored = db.load_sync_fields()?;
+    record_fields_locked(cx, config, &storages, library, &stored)
+}
+
+fn load_enabled_config(cx: &BackendContext) -> BResult<SyncConfigModel> {
+    match cx.database_server().load_sync_config()? {
+        Some(config) if config.dir.is_some() => Ok(config),
+        _ => Err(BError::SyncNotEnabled),
+    }
+}
+
+/// Records the changed parts of the library as ops of this device. Does
+/// nothing when sync is off.
+fn record_sync_changes(cx: &BackendContext, changes: &[SyncChange]) -> BResult<()> {
+    let _guard = cx.sync_state().lock.lock().unwrap();
+    let mut config = match load_enabled_config(cx) {
+        Ok(v) => v,
+        Err(BError::SyncNotEnabled) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let db = cx.database_server();
+    let storages = SyncStorages::load(db)?;
+    let (library, stored) = read_changed_fields(db, &storages, &config.device_id, changes)?;
+    record_fields_locked(cx, &mut config, &storages, library, &stored)
+}
+
+/// Called after a change of the library. Failures are only logged, as the
+/// changes are recorded by the next sync anyway.
+pub(crate) fn note_sync_changes(cx: &BackendContext, changes: &[SyncChange]) {
+    if let Err(e) = record_sync_changes(cx, changes) {
+        tracing::warn!("fail to record sync changes: {e:?}");
+    }
+}
+
+struct MergeState<'a> {
+    storages: SyncStorages,
+    /// Playlists of the library by sync id.
+    playlists: HashMap<String, PlaylistId>,
+    created: Vec<(PlaylistId, String)>,
+    probe: Vec<MusicId>,
+    stored: &'a HashMap<SyncTarget, SyncFieldModel>,
+    incoming: &'a BTreeMap<SyncTarget, SyncFieldModel>,
+}
+
+impl MergeState<'_> {
+    fn latest(&self, target: &SyncTarget) -> Option<&SyncValue> {
+        self.incoming
+            .get(target)
+            .or_else(|| self.stored.get(target))
+            .map(|v| &v.value)
+    }
+}
+
+/// Music of the library a synced key refers to. `None` when its storage is
+/// not on this device, `Some(None)` when the library does not have it.
+fn load_synced_music(
+    db: &Arc<DatabaseServer>,
+    state: &MergeState<'_>,
+    music: &SyncMusicKey,
+) -> BResult<Option<Option<MusicModel>>> {
+    let Some(loc) = state.storages.loc(music) else {
+        return Ok(None);
+    };
+    Ok(Some(db.load_music_by_loc(loc)?))
+}
+
+fn apply_field(
+    cx: &BackendContext,
+    state: &mut MergeState<'_>,
+    target: &SyncTarget,
+    value: &SyncValue,
+) -> BResult<Applied> {
+    let db = cx.database_server();
+    match (target, value) {
+        (SyncTarget::Playlist { id }, SyncValue::Playlist { created_time }) => {
+            match (created_time, state.playlists.get(id).copied()) {
+                (Some(_), Some(_)) => {}
+                (Some(created_time), None) => {
+                    let title = match state.latest(&SyncTarget::PlaylistTitle { id: id.clone() }) {
+                        Some(SyncValue::Title { title }) => title.clone(),
+                        _ => Default::default(),
+                    };
+                    let order = match state.latest(&SyncTarget::PlaylistOrder { id: id.clone() }) {
+                        Some(SyncValue::Order { order }) => OrderKey::wrap(order.clone()),
+                        _ => next_playlist_order(cx)?,
+                    };
+                    let (playlist_id, _) =
+                        db.create_playlist(title, None, vec![], *created_time, order)?;
+                    state.playlists.insert(id.clone(), playlist_id);
+                    state.created.push((playlist_id, id.clone()));
+                }
+                (None, Some(playlist_id)) => {
+                    db.remove_playlist(playlist_id)?;
+                    state.playlists.remove(id);
+                }
+                (None, None) => return Ok(Applied::Kept),
+            }
+            Ok(Applied::Yes)
+        }
+        (SyncTarget::PlaylistTitle { id }, SyncValue::Title { title }) => {
+            let Some(playlist_id) = state.playlists.get(id).copied() else {
+                return Ok(Applied::Kept);
+            };
+            let picture = db.load_playlist(playlist_id)?.and_then(|v| v.picture);
+            db.update_playlist(playlist_id, title.clone(), picture)?;
+            Ok(Applied::Yes)
+        }
+        (SyncTarget::PlaylistOrder { id }, SyncValue::Order { order }) => {
+            let Some(playlist_id) = state.playlists.get(id).copied() else {
+                return Ok(Applied::Kept);
+            };
+            db.set_playlist_order(playlist_id, OrderKey::wrap(order.clone()))?;
+            Ok(Applied::Yes)
+        }
+        (SyncTarget::PlaylistMusic { id, music }, SyncValue::PlaylistMusic { order }) => {
+            let Some(loc) = state.storages.loc(music) else {
+                return Ok(Applied::Skipped);
+            };
+            let Some(playlist_id) = state.playlists.get(id).copied() else {
+                return Ok(Applied::Kept);
+            };
+            match order {
+                Some(order) => {
+                    let title = loc.path.rsplit('/').next().unwrap_or_default().to_string();
+                    let added = db.add_musics_to_playlist(
+                        playlist_id,
+                        vec![ArgDBAddMusic {
+                            loc: loc.clone(),
+                            title,
+                        }],
+                        OrderKey::default(),
+                    )?;
+                    state.probe.extend(new_music_ids(&added));
+                    if let Some(model) = db.load_music_by_loc(loc)? {
+                        db.set_playlist_music_order(
+                            playlist_id,
+                            model.id,
+                            OrderKey::wrap(order.clone()),
+                        )?;
+                    }
+                }
+                None => {
+                    if let Some(model) = db.load_music_by_loc(loc)? {
+                        db.remove_music_from_playlist(playlist_id, model.id)?;
+                    }
+                }
+            }
+            Ok(Applied::Yes)
+        }
+        (SyncTarget::MusicLyric { music }, SyncValue::Lyric { lyric }) => {
+            let Some(loc) = state.storages.loc(music) else {
+                return Ok(Applied::Skipped);
+            };
+            let lyric = match lyric {
+                Some(key) => match state.storages.loc(key) {
+                    Some(v) => Some(v),
+                    None => return Ok(Applied::Skipped),
+                },
+                None => None,
+            };
+            let Some(model) = db.load_music_by_loc(loc)? else {
+                return Ok(Applied::Kept);
+            };
+            db.update_music_lyric(model.id, lyric)?;
+            Ok(Applied::Yes)
+        }
+        (SyncTarget::MusicFavorite { music }, SyncValue::Favorite { favorite_time }) => {
+            let Some(model) = load_synced_music(db, state, music)? else {
+                return Ok(Applied::Skipped);
+            };
+            let Some(model) = model else {
+                return Ok(Applied::Kept);
+            };
+            db.set_music_favorite(model.id, *favorite_time)?;
+            Ok(Applied::Yes)
+        }
+        (SyncTarget::MusicRating { music }, SyncValue::Rating { rating }) => {
+            let Some(model) = load_synced_music(db, state, music)? else {
+                return Ok(Applied::Skipped);
+            };
+            let Some(model) = model else {
+                return Ok(Applied::Kept);
+            };
+            db.set_music_rating(model.id, (*rating).min(MAX_RATING))?;
+            Ok(Applied::Yes)
+        }
+        (SyncTarget::MusicUserTags { music }, SyncValue::UserTags { tags }) => {
+            let Some(model) = load_synced_music(db, state, music)? else {
+                return Ok(Applied::Skipped);
+            };
+            let Some(model) = model else {
+                return Ok(Applied::Kept);
+            };
+            db.set_music_user_tags(model.id, normalize_user_tags(tags.clone()))?;
+            Ok(Applied::Yes)
+        }
+        _ => {
+            tracing::warn!("skip sync op with mismatched value: {target:?} {value:?}");
+            Ok(Applied::Skipped)
+        }
+    }
+}
+
+/// Applies the ops of other devices newer than the fields known here, and
+/// returns the log to publish.
+fn merge_logs_locked(cx: &BackendContext, logs: Vec<SyncLog>) -> BResult<(SyncResult, SyncLog)> {
+    let db = cx.database_server();
+    let mut config = load_enabled_config(cx)?;
+    // Changes not recorded yet compete with the ops of other devices.
+    record_changes_locked(cx, &mut config)?;
+    let stored = db.load_sync_fields()?;
+
+    let mut result = SyncResult {
+        devices: logs.len() as u64,
+        ..Default::default()
+    };
+    let mut incoming: BTreeMap<SyncTarget, SyncFieldModel> = Default::default();
+    for op in logs.into_iter().flat_map(|v| v.ops) {
+        config.clock = config.clock.max(op.stamp.time);
+        let newer = |v: Option<&SyncFieldModel>| v.is_none_or(|v| v.stamp < op.stamp);
+        if newer(stored.get(&op.target)) && newer(incoming.get(&op.target)) {
+            incoming.insert(
+                op.target,
+                SyncFieldModel {
+                    stamp: op.stamp,
+                    value: op.value,
+                },
+            );
+        }
+    }
+
+    let live: HashSet<PlaylistId> = db.load_playlists()?.into_iter().map(|v| v.id).collect();
+    let mut state = MergeState {
+        storages: SyncStorages::load(db)?,
+        playlists: db
+            .load_playlist_sync_ids()?
+            .into_iter()
+            .filter(|(id, _)| live.contains(id))
+            .map(|(id, sync_id)| (sync_id, id))
+            .collect(),
+        created: Default::default(),
+        probe: Default::default(),
+        stored: &stored,
+        incoming: &incoming,
+    };
+    // Targets sort playlists first, then their fields and musics, so a
+    // playlist exists before its musics are added.
+    for (target, field) in incoming.iter() {
+        match apply_field(cx, &mut state, target, &field.value)? {
+            Applied::Yes => result.applied += 1,
+            Applied::Kept => {}
+            Applied::Skipped => {
+                result.skipped += 1;
+                continue;
+            }
+        }
+        // Each field is stored as soon as it is applied. Otherwise a failure
+        // halfway would leave changes in the library that the next record
+        // stamps as newer ops of this device.
+        db.save_sync_fields(
+            config.clone(),
+            vec![(target.clone(), field.clone())],
+            std::mem::take(&mut state.created),
+        )?;
+    }
+    spawn_probe_musics(cx, state.probe);
+
+    let mut ops: Vec<SyncOp> = db
+        .load_sync_fields()?
+        .into_iter()
+        .map(|(target, field)| SyncOp {
+            stamp: field.stamp,
+            target,
+            value: field.value,
+        })
+        .collect();
+    ops.sort_by(|lhs, rhs| lhs.target.cmp(&rhs.target));
+    let log = SyncLog {
+        version: SYNC_LOG_VERSION,
+        device: config.device_id,
+        ops,
+    };
+    Ok((result, log))
+}
+
+fn save_enabled_config(cx: &BackendContext, dir: StorageEntryLoc) -> BResult<SyncStatus> {
+    let _guard = cx.sync_state().lock.lock().unwrap();
+    let db = cx.database_server();
+    let mut config = db.load_sync_config()?.unwrap_or_default();
+    if config.device_id.is_empty() {
+        config.device_id = new_device_id()?;
+    }
+    config.dir = Some(dir);
+    db.save_sync_config(config.clone())?;
+    // Playlists that existed before are published by the first sync.
+    record_changes_locked(cx, &mut config)?;
+    Ok(build_status(config))
+}
+
+/// Turns sync on with the given shared folder, which is created when
+/// missing.
+pub async fn enable_sync(cx: &BackendContext, arg: ArgEnableSync) -> BResult<SyncStatus> {
+    let Some(backend) = get_storage_backend(cx, arg.dir.storage_id)? else {
+        return Err(BError::AssetNotFound);
+    };
+    backend.create_dir(arg.dir.path.clone()).await?;
+    save_enabled_config(cx, arg.dir)
+}
+
+/// Turns sync off. The synced fields are kept, so turning it on again only
+/// publishes the changes made in between.
+pub fn disable_sync(cx: &BackendContext) -> BResult<()> {
+    let _guard = cx.sync_state().lock.lock().unwrap();
+    let db = cx.database_server();
+    if let Some(mut config) = db.load_sync_config()? {
+        config.dir = None;
+        db.save_sync_config(config)?;
+    }
+    Ok(())
+}
+
+pub fn get_sync_status(cx: &BackendContext) -> BResult<Option<SyncStatus>> {
+    Ok(cx.database_server().load_sync_config()?.map(build_status))
+}
+
+/// Gives the device a new id, for a library restored from a backup of
+/// another device.
+pub(crate) fn reset_sync_device(cx: &BackendContext) -> BResult<()> {
+    let _guard = cx.sync_state().lock.lock().unwrap();
+    let db = cx.database_server();
+    if let Some(mut config) = db.load_sync_config()? {
+        config.device_id = new_device_id()?;
+        db.save_sync_config(config)?;
+    }
+    Ok(())
+}
+
+/// Merges the logs published by the other devices into the library, then
+/// publishes the log of this device. Devices may change their library
+/// while offline, conflicting fields keep the latest change.
+pub async fn sync_now(cx: &BackendContext) -> BResult<SyncResult> {
+    let config = load_enabled_config(cx)?;
+    let dir = config.dir.unwrap();
+    let Some(backend) = get_storage_backend(cx, dir.storage_id)? else {
+        return Err(BError::AssetNotFound);
+    };
+    backend.create_dir(dir.path.clone()).await?;
+
+    let own_name = format!("{}{SYNC_LOG_EXT}", config.device_id);
+    let mut logs: Vec<SyncLog> = Default::default();
+    for entry in backend.list(dir.path.clone()).await? {
+        if entry.is_dir || entry.name == own_name || !entry.name.ends_with(SYNC_LOG_EXT) {
+            continue;
+        }
+        let bytes = backend.get(entry.path.clone(), 0).await?.bytes().await?;
+        match serde_json::from_slice::<RawSyncLog>(&bytes) {
+            Ok(log) if log.version <= SYNC_LOG_VERSION => logs.push(log.parse()),
+            Ok(log) => tracing::warn!("skip sync log {} of version {}", entry.path, log.version),
+            Err(e) => tracing::warn!("skip malformed sync log {}: {e}", entry.path),
+        }
+    }
+
+    let (result, log) = {
+        let _guard = cx.sync_state().lock.lock().unwrap();
+        merge_logs_locked(cx, logs)?
+    };
+    let data = serde_json::to_vec(&log).map_err(|e| BError::CustomError {
+        message: format!("fail to write sync log: {e}"),
+    })?;
+    backend
+        .put(
+            join_path(&dir.path, &format!("{}{SYNC_LOG_EXT}", log.device)),
+            data.into(),
+        )
+        .await?;
+
+    {
+        let _guard = cx.sync_state().lock.lock().unwrap();
+        let db = cx.database_server();
+        if let Some(mut config) = db.load_sync_config()? {
+            config.last_synced = cx.current_time().as_millis() as i64;
+            db.save_sync_config(config)?;
+        }
+    }
+    Ok(result)
+}
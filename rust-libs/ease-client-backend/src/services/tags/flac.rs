@@ -0,0 +1,119 @@
+use super::EmbeddedPicture;
+
+pub(crate) const MAGIC: &[u8; 4] = b"fLaC";
+pub(crate) const BLOCK_HEADER_SIZE: usize = 4;
+
+pub(crate) const BLOCK_STREAMINFO: u8 = 0;
+pub(crate) const BLOCK_VORBIS_COMMENT: u8 = 4;
+pub(crate) const BLOCK_PICTURE: u8 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BlockHeader {
+    pub is_last: bool,
+    pub typ: u8,
+    pub len: usize,
+}
+
+pub(crate) fn parse_block_header(buf: &[u8]) -> Option<BlockHeader> {
+    if buf.len() < BLOCK_HEADER_SIZE {
+        return None;
+    }
+    Some(BlockHeader {
+        is_last: buf[0] & 0x80 != 0,
+        typ: buf[0] & 0x7f,
+        len: (buf[1] as usize) << 16 | (buf[2] as usize) << 8 | buf[3] as usize,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct StreamInfo {
+    pub sample_rate: u32,
+    pub channels: u32,
+    pub bits_per_sample: u32,
+    pub total_samples: u64,
+}
+
+pub(crate) fn parse_stream_info(block: &[u8]) -> Option<StreamInfo> {
+    let buf = block.get(10..18)?;
+    let packed = u64::from_be_bytes(buf.try_into().ok()?);
+    Some(StreamInfo {
+        sample_rate: (packed >> 44) as u32,
+        channels: ((packed >> 41) & 0x07) as u32 + 1,
+        bits_per_sample: ((packed >> 36) & 0x1f) as u32 + 1,
+        total_samples: packed & 0x0f_ffff_ffff,
+    })
+}
+
+struct Cursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn u32(&mut self) -> Option<u32> {
+        let v = self.buf.get(self.pos..self.pos + 4)?;
+        self.pos += 4;
+        Some(u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+    }
+
+    fn u32_le(&mut self) -> Option<u32> {
+        let v = self.buf.get(self.pos..self.pos + 4)?;
+        self.pos += 4;
+        Some(u32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let v = self.buf.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(v)
+    }
+}
+
+pub(crate) fn parse_picture(block: &[u8]) -> Option<EmbeddedPicture> {
+    let mut cursor = Cursor { buf: block, pos: 0 };
+    let picture_type = cursor.u32()?;
+    let mime_len = cursor.u32()? as usize;
+    let mime = String::from_utf8_lossy(cursor.take(mime_len)?).to_string();
+    let description_len = cursor.u32()? as usize;
+    cursor.take(description_len)?;
+    // width, height, color depth and number of indexed colors
+    cursor.take(16)?;
+    let data_len = cursor.u32()? as usize;
+    let data = cursor.take(data_len)?;
+    if data.is_empty() {
+        return None;
+    }
+    Some(EmbeddedPicture {
+        picture_type: picture_type.min(u8::MAX as u32) as u8,
+        mime: if mime.is_empty() { None } else { Some(mime) },
+        data: data.to_vec(),
+    })
+}
+
+/// Parses a Vorbis comment block into `(KEY, value)` pairs with upper-cased keys.
+pub(crate) fn parse_vorbis_comments(block: &[u8]) -> Vec<(String, String)> {
+    let mut ret = Vec::new();
+    let mut cursor = Cursor { buf: block, pos: 0 };
+    let Some(vendor_len) = cursor.u32_le() else {
+        return ret;
+    };
+    if cursor.take(vendor_len as usize).is_none() {
+        return ret;
+    }
+    let Some(count) = cursor.u32_le() else {
+        return ret;
+    };
+    for _ in 0..count {
+        let Some(len) = cursor.u32_le() else {
+            break;
+        };
+        let Some(comment) = cursor.take(len as usize) else {
+            break;
+        };
+        let comment = String::from_utf8_lossy(comment);
+        if let Some((key, value)) = comment.split_once('=') {
+            ret.push((key.to_ascii_uppercase(), value.to_string()));
+        }
+    }
+    ret
+}
@@ -0,0 +1,369 @@
+use std::time::Duration;
+
+use encoding_rs::{UTF_16BE, UTF_16LE};
+
+use super::EmbeddedPicture;
+use crate::{services::lyrics::decode_lyric_text, LyricLine};
+
+pub(crate) const HEADER_SIZE: usize = 10;
+
+const FLAG_UNSYNCHRONISATION: u8 = 0x80;
+const FLAG_EXTENDED_HEADER: u8 = 0x40;
+const FLAG_FOOTER: u8 = 0x10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Id3Frame {
+    pub id: String,
+    pub data: Vec<u8>,
+}
+
+fn synchsafe(buf: &[u8]) -> usize {
+    buf.iter()
+        .take(4)
+        .fold(0usize, |acc, v| (acc << 7) | (*v as usize & 0x7f))
+}
+
+fn be_u32(buf: &[u8]) -> usize {
+    buf.iter()
+        .take(4)
+        .fold(0usize, |acc, v| (acc << 8) | *v as usize)
+}
+
+fn remove_unsynchronisation(buf: &[u8]) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(buf.len());
+    let mut i = 0;
+    while i < buf.len() {
+        ret.push(buf[i]);
+        if buf[i] == 0xff && i + 1 < buf.len() && buf[i + 1] == 0x00 {
+            i += 1;
+        }
+        i += 1;
+    }
+    ret
+}
+
+/// Returns the total size of the ID3v2 tag starting at `header`, including
+/// the header and the optional footer.
+pub(crate) fn tag_size(header: &[u8]) -> Option<usize> {
+    if header.len() < HEADER_SIZE || &header[0..3] != b"ID3" {
+        return None;
+    }
+    let flags = header[5];
+    let footer = if flags & FLAG_FOOTER != 0 { 10 } else { 0 };
+    Some(HEADER_SIZE + synchsafe(&header[6..10]) + footer)
+}
+
+pub(crate) fn parse_frames(tag: &[u8]) -> Vec<Id3Frame> {
+    let mut ret: Vec<Id3Frame> = Default::default();
+    let Some(size) = tag_size(tag) else {
+        return ret;
+    };
+    let version = tag[3];
+    let flags = tag[5];
+    let end = (size.min(tag.len())).max(HEADER_SIZE);
+    let body = &tag[HEADER_SIZE..end];
+    let body = if version <= 3 && flags & FLAG_UNSYNCHRONISATION != 0 {
+        remove_unsynchronisation(body)
+    } else {
+        body.to_vec()
+    };
+
+    let mut pos = 0;
+    if flags & FLAG_EXTENDED_HEADER != 0 && body.len() >= 4 {
+        pos = match version {
+            3 => be_u32(&body[0..4]) + 4,
+            4 => synchsafe(&body[0..4]),
+            _ => 0,
+        };
+    }
+
+    let (id_len, header_len) = if version == 2 { (3, 6) } else { (4, 10) };
+    while pos + header_len <= body.len() {
+        let header = &body[pos..pos + header_len];
+        if header[0] == 0 {
+            break;
+        }
+        let id = String::from_utf8_lossy(&header[0..id_len]).to_string();
+        let frame_size = match version {
+            2 => header[3..6]
+                .iter()
+                .fold(0usize, |acc, v| (acc << 8) | *v as usize),
+            3 => be_u32(&header[4..8]),
+            _ => synchsafe(&header[4..8]),
+        };
+        let data_start = pos + header_len;
+        let data_end = data_start + frame_size;
+        if data_end > body.len() {
+            break;
+        }
+        pos = data_end;
+
+        let mut data = body[data_start..data_end].to_vec();
+        if version >= 3 {
+            let format_flags = header[9];
+            let (compressed, encrypted, length_indicator, unsync) = if version == 3 {
+                (
+                    format_flags & 0x80 != 0,
+                    format_flags & 0x40 != 0,
+                    false,
+                    false,
+                )
+            } else {
+                (
+                    format_flags & 0x08 != 0,
+                    format_flags & 0x04 != 0,
+                    format_flags & 0x01 != 0,
+                    format_flags & 0x02 != 0,
+                )
+            };
+            if compressed || encrypted {
+                continue;
+            }
+            if length_indicator {
+                if data.len() < 4 {
+                    continue;
+                }
+                data.drain(0..4);
+            }
+            if unsync {
+                data = remove_unsynchronisation(&data);
+            }
+        }
+        ret.push(Id3Frame { id, data });
+    }
+    ret
+}
+
+/// Splits `buf` at the first string terminator of the given ID3 text encoding,
+/// returning the bytes before and after it.
+pub(crate) fn split_terminated(buf: &[u8], encoding: u8) -> (&[u8], &[u8]) {
+    if encoding == 1 || encoding == 2 {
+        let mut i = 0;
+        while i + 1 < buf.len() {
+            if buf[i] == 0 && buf[i + 1] == 0 {
+                return (&buf[..i], &buf[i + 2..]);
+            }
+            i += 2;
+        }
+    } else if let Some(i) = buf.iter().position(|v| *v == 0) {
+        return (&buf[..i], &buf[i + 1..]);
+    }
+    (buf, &[])
+}
+
+/// Decodes an ID3 text field. ISO-8859-1 fields are run through charset
+/// detection because many taggers store local code pages there.
+pub(crate) fn decode_text(buf: &[u8], encoding: u8) -> String {
+    let text = match encoding {
+        1 => {
+            if let Some((encoding, bom_len)) = encoding_rs::Encoding::for_bom(buf) {
+                encoding.decode_without_bom_handling(&buf[bom_len..]).0
+            } else {
+                UTF_16LE.decode_without_bom_handling(buf).0
+            }
+        }
+        2 => UTF_16BE.decode_without_bom_handling(buf).0,
+        3 => String::from_utf8_lossy(buf),
+        _ => decode_lyric_text(buf, None).text.into(),
+    };
+    text.trim_end_matches('\0').to_string()
+}
+
+/// Parses an USLT (or v2.2 ULT) frame into its lyric text.
+pub(crate) fn parse_unsynced_lyrics(frame: &Id3Frame) -> Option<String> {
+    if frame.id != "USLT" && frame.id != "ULT" {
+        return None;
+    }
+    let (&encoding, rest) = frame.data.split_first()?;
+    let rest = rest.get(3..)?;
+    let (_description, text) = split_terminated(rest, encoding);
+    let text = decode_text(text, encoding);
+    if text.trim().is_empty() {
+        return None;
+    }
+    Some(text)
+}
+
+/// Parses a TXXX (or v2.2 TXX) frame into its description and value.
+pub(crate) fn parse_user_text(frame: &Id3Frame) -> Option<(String, String)> {
+    if frame.id != "TXXX" && frame.id != "TXX" {
+        return None;
+    }
+    let (&encoding, rest) = frame.data.split_first()?;
+    let (description, value) = split_terminated(rest, encoding);
+    Some((
+        decode_text(description, encoding),
+        decode_text(value, encoding),
+    ))
+}
+
+/// Parses a title, artist or album text frame (or their v2.2 forms) into the
+/// Vorbis comment key of the field and its first value.
+pub(crate) fn parse_text(frame: &Id3Frame) -> Option<(&'static str, String)> {
+    let key = match frame.id.as_str() {
+        "TIT2" | "TT2" => "TITLE",
+        "TPE1" | "TP1" => "ARTIST",
+        "TALB" | "TAL" => "ALBUM",
+        _ => return None,
+    };
+    let (&encoding, rest) = frame.data.split_first()?;
+    let (value, _) = split_terminated(rest, encoding);
+    Some((key, decode_text(value, encoding)))
+}
+
+/// Parses a SYLT (or v2.2 SLT) frame with millisecond timestamps into lyric lines.
+pub(crate) fn parse_synced_lyrics(frame: &Id3Frame) -> Option<Vec<LyricLine>> {
+    const TIMESTAMP_MILLISECONDS: u8 = 2;
+
+    if frame.id != "SYLT" && frame.id != "SLT" {
+        return None;
+    }
+    let (&encoding, rest) = frame.data.split_first()?;
+    // language, timestamp format and content type
+    let header = rest.get(0..5)?;
+    if header[3] != TIMESTAMP_MILLISECONDS {
+        return None;
+    }
+    let (_description, mut rest) = split_terminated(&rest[5..], encoding);
+
+    let mut lines: Vec<LyricLine> = Default::default();
+    while !rest.is_empty() {
+        let (text, after) = split_terminated(rest, encoding);
+        let Some(timestamp) = after.get(0..4) else {
+            break;
+        };
+        let millis = u32::from_be_bytes([timestamp[0], timestamp[1], timestamp[2], timestamp[3]]);
+        rest = &after[4..];
+
+        let text = decode_text(text, encoding);
+        let text = text.trim();
+        if !text.is_empty() {
+            lines.push(LyricLine {
+                duration: Duration::from_millis(millis as u64),
+                text: text.to_string(),
+            });
+        }
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    lines.sort_by_key(|line| line.duration);
+    Some(lines)
+}
+
+fn mime_of_v22_format(format: &[u8]) -> Option<String> {
+    match format.to_ascii_uppercase().as_slice() {
+        b"JPG" => Some("image/jpeg".to_string()),
+        b"PNG" => Some("image/png".to_string()),
+        b"GIF" => Some("image/gif".to_string()),
+        b"BMP" => Some("image/bmp".to_string()),
+        _ => None,
+    }
+}
+
+pub(crate) fn parse_picture(frame: &Id3Frame) -> Option<EmbeddedPicture> {
+    let data = frame.data.as_slice();
+    let (&encoding, rest) = data.split_first()?;
+    let (mime, rest) = match frame.id.as_str() {
+        "APIC" => {
+            let (mime, rest) = split_terminated(rest, 0);
+            let mime = String::from_utf8_lossy(mime).trim().to_string();
+            (if mime.is_empty() { None } else { Some(mime) }, rest)
+        }
+        "PIC" => {
+            if rest.len() < 3 {
+                return None;
+            }
+            (mime_of_v22_format(&rest[0..3]), &rest[3..])
+        }
+        _ => return None,
+    };
+    let (&picture_type, rest) = rest.split_first()?;
+    let (_description, data) = split_terminated(rest, encoding);
+    if data.is_empty() {
+        return None;
+    }
+    Some(EmbeddedPicture {
+        picture_type,
+        mime,
+        data: data.to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_frames, parse_picture, parse_synced_lyrics, parse_text, tag_size};
+
+    fn v3_tag(frames: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let mut body: Vec<u8> = Vec::new();
+        for (id, data) in frames {
+            body.extend_from_slice(id.as_slice());
+            body.extend_from_slice((data.len() as u32).to_be_bytes().as_slice());
+            body.extend_from_slice(&[0, 0]);
+            body.extend_from_slice(data.as_slice());
+        }
+        let size = body.len();
+        let mut tag = b"ID3\x03\x00\x00".to_vec();
+        tag.extend_from_slice(&[
+            ((size >> 21) & 0x7f) as u8,
+            ((size >> 14) & 0x7f) as u8,
+            ((size >> 7) & 0x7f) as u8,
+            (size & 0x7f) as u8,
+        ]);
+        tag.extend_from_slice(body.as_slice());
+        tag
+    }
+
+    #[test]
+    fn parses_apic_frame() {
+        let mut apic = vec![0u8];
+        apic.extend_from_slice(b"image/png\0");
+        apic.push(3);
+        apic.extend_from_slice(b"cover\0");
+        apic.extend_from_slice(&[1, 2, 3]);
+        let tag = v3_tag(&[(b"TIT2", b"\0title".to_vec()), (b"APIC", apic)]);
+
+        assert_eq!(tag_size(&tag), Some(tag.len()));
+        let frames = parse_frames(&tag);
+        assert_eq!(frames.len(), 2);
+        let picture = parse_picture(&frames[1]).expect("picture");
+        assert_eq!(picture.picture_type, 3);
+        assert_eq!(picture.mime.as_deref(), Some("image/png"));
+        assert_eq!(picture.data, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn parses_text_frames() {
+        let tag = v3_tag(&[
+            (b"TIT2", b"\x03Song\0Alt".to_vec()),
+            (b"TPE1", b"\x01\xFF\xFEA\x00b\x00".to_vec()),
+            (b"TCON", b"\x03Rock".to_vec()),
+        ]);
+        let frames = parse_frames(&tag);
+        assert_eq!(parse_text(&frames[0]), Some(("TITLE", "Song".to_string())));
+        assert_eq!(parse_text(&frames[1]), Some(("ARTIST", "Ab".to_string())));
+        assert_eq!(parse_text(&frames[2]), None);
+    }
+
+    #[test]
+    fn parses_sylt_frame() {
+        let mut sylt = vec![3u8];
+        sylt.extend_from_slice(b"eng");
+        sylt.extend_from_slice(&[2, 1]);
+        sylt.extend_from_slice(b"\0");
+        sylt.extend_from_slice("第二句\0".as_bytes());
+        sylt.extend_from_slice(2500u32.to_be_bytes().as_slice());
+        sylt.extend_from_slice("第一句\0".as_bytes());
+        sylt.extend_from_slice(1000u32.to_be_bytes().as_slice());
+        let tag = v3_tag(&[(b"SYLT", sylt)]);
+
+        let frames = parse_frames(&tag);
+        let lines = parse_synced_lyrics(&frames[0]).expect("lines");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].duration, Duration::from_millis(1000));
+        assert_eq!(lines[0].text, "第一句");
+        assert_eq!(lines[1].text, "第二句");
+    }
+}
@@ -0,0 +1,659 @@
+mod flac;
+mod id3;
+mod mp4;
+mod mpeg;
+mod ogg;
+mod reader;
+mod wav;
+
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+pub(crate) use reader::EntryRangeReader;
+
+use crate::{error::BResult, LyricLine};
+
+pub(crate) const PICTURE_TYPE_OTHER: u8 = 0;
+pub(crate) const PICTURE_TYPE_FRONT_COVER: u8 = 3;
+
+// Tags larger than this are ignored instead of being downloaded.
+const MAX_TAG_SIZE: u64 = 32 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct EmbeddedPicture {
+    pub picture_type: u8,
+    pub mime: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// Selects which tags to parse, so that large picture blocks are only
+/// downloaded when needed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct TagQuery {
+    pub pictures: bool,
+    pub lyrics: bool,
+    pub replay_gain: bool,
+    pub text: bool,
+}
+
+/// ReplayGain values found in tags, with R128 gains converted to the -18 LUFS
+/// ReplayGain reference.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct ReplayGainTags {
+    pub track_gain: Option<f64>,
+    pub track_peak: Option<f64>,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
+}
+
+impl ReplayGainTags {
+    fn apply(&mut self, key: &str, value: &str) {
+        let parse_gain = |v: &str| {
+            v.trim()
+                .trim_end_matches(|c: char| c.is_ascii_alphabetic())
+                .trim()
+                .parse::<f64>()
+                .ok()
+        };
+        // R128 gains are Q7.8 fixed point numbers relative to -23 LUFS.
+        let parse_r128 = |v: &str| v.trim().parse::<i32>().ok().map(|v| v as f64 / 256.0 + 5.0);
+        match key.to_ascii_uppercase().as_str() {
+            "REPLAYGAIN_TRACK_GAIN" => self.track_gain = parse_gain(value).or(self.track_gain),
+            "REPLAYGAIN_TRACK_PEAK" => self.track_peak = parse_gain(value).or(self.track_peak),
+            "REPLAYGAIN_ALBUM_GAIN" => self.album_gain = parse_gain(value).or(self.album_gain),
+            "REPLAYGAIN_ALBUM_PEAK" => self.album_peak = parse_gain(value).or(self.album_peak),
+            "R128_TRACK_GAIN" => self.track_gain = self.track_gain.or(parse_r128(value)),
+            "R128_ALBUM_GAIN" => self.album_gain = self.album_gain.or(parse_r128(value)),
+            _ => {}
+        }
+    }
+}
+
+/// Title, artist and album found in tags. The first value of each is kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct TextTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+impl TextTags {
+    fn apply(&mut self, key: &str, value: &str) {
+        let value = value.trim();
+        if value.is_empty() {
+            return;
+        }
+        let slot = match key.to_ascii_uppercase().as_str() {
+            "TITLE" => &mut self.title,
+            "ARTIST" => &mut self.artist,
+            "ALBUM" => &mut self.album,
+            _ => return,
+        };
+        slot.get_or_insert_with(|| value.to_string());
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct EmbeddedTags {
+    pub pictures: Vec<EmbeddedPicture>,
+    pub synced_lyrics: Vec<LyricLine>,
+    pub unsynced_lyrics: Option<String>,
+    pub replay_gain: ReplayGainTags,
+    pub text: TextTags,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct StreamInfo {
+    pub duration: Option<Duration>,
+    pub sample_rate: u32,
+    pub channels: u32,
+    pub bit_depth: Option<u32>,
+    /// Average bitrate in kbps.
+    pub bitrate: Option<u32>,
+}
+
+/// Picks the front cover, falling back to an untyped picture and then to the
+/// first picture found.
+pub(crate) fn choose_front_cover(pictures: Vec<EmbeddedPicture>) -> Option<EmbeddedPicture> {
+    let index = pictures
+        .iter()
+        .position(|p| p.picture_type == PICTURE_TYPE_FRONT_COVER)
+        .or_else(|| {
+            pictures
+                .iter()
+                .position(|p| p.picture_type == PICTURE_TYPE_OTHER)
+        })
+        .unwrap_or(0);
+    pictures.into_iter().nth(index)
+}
+
+async fn read_id3_tags(
+    reader: &mut EntryRangeReader,
+    query: TagQuery,
+    tags: &mut EmbeddedTags,
+) -> BResult<u64> {
+    let header = reader.read(0, id3::HEADER_SIZE).await?;
+    let Some(size) = id3::tag_size(&header) else {
+        return Ok(0);
+    };
+    if size as u64 > MAX_TAG_SIZE {
+        tracing::warn!("skip oversized id3 tag of {} bytes", size);
+        return Ok(size as u64);
+    }
+    let tag = reader.read(0, size).await?;
+    for frame in id3::parse_frames(&tag) {
+        if query.pictures {
+            if let Some(picture) = id3::parse_picture(&frame) {
+                tags.pictures.push(picture);
+            }
+        }
+        if query.lyrics {
+            if let Some(lines) = id3::parse_synced_lyrics(&frame) {
+                if tags.synced_lyrics.is_empty() {
+                    tags.synced_lyrics = lines;
+                }
+            }
+            if let Some(text) = id3::parse_unsynced_lyrics(&frame) {
+                tags.unsynced_lyrics.get_or_insert(text);
+            }
+        }
+        if query.replay_gain {
+            if let Some((key, value)) = id3::parse_user_text(&frame) {
+                tags.replay_gain.apply(&key, &value);
+            }
+        }
+        if query.text {
+            if let Some((key, value)) = id3::parse_text(&frame) {
+                tags.text.apply(key, &value);
+            }
+        }
+    }
+    Ok(size as u64)
+}
+
+fn apply_vorbis_comments(
+    comments: Vec<(String, String)>,
+    query: TagQuery,
+    tags: &mut EmbeddedTags,
+) {
+    for (key, value) in comments {
+        let is_lyrics = key == "LYRICS" || key == "UNSYNCEDLYRICS";
+        if query.lyrics && is_lyrics && !value.trim().is_empty() {
+            tags.unsynced_lyrics.get_or_insert(value);
+            continue;
+        }
+        // Ogg streams carry their pictures as base64 encoded FLAC picture
+        // blocks.
+        if key == "METADATA_BLOCK_PICTURE" {
+            if query.pictures {
+                let picture = STANDARD.decode(value.trim()).ok();
+                tags.pictures
+                    .extend(picture.and_then(|v| flac::parse_picture(&v)));
+            }
+            continue;
+        }
+        if query.replay_gain {
+            tags.replay_gain.apply(&key, &value);
+        }
+        if query.text {
+            tags.text.apply(&key, &value);
+        }
+    }
+}
+
+async fn read_flac_tags(
+    reader: &mut EntryRangeReader,
+    offset: u64,
+    query: TagQuery,
+    tags: &mut EmbeddedTags,
+) -> BResult<()> {
+    let mut offset = offset + flac::MAGIC.len() as u64;
+    loop {
+        let header = reader.read(offset, flac::BLOCK_HEADER_SIZE).await?;
+        let Some(header) = flac::parse_block_header(&header) else {
+            break;
+        };
+        offset += flac::BLOCK_HEADER_SIZE as u64;
+        let wanted = match header.typ {
+            flac::BLOCK_PICTURE => query.pictures,
+            flac::BLOCK_VORBIS_COMMENT => query.lyrics || query.replay_gain || query.text,
+            _ => false,
+        };
+        if wanted && header.len as u64 <= MAX_TAG_SIZE {
+            let block = reader.read(offset, header.len).await?;
+            if header.typ == flac::BLOCK_PICTURE {
+                tags.pictures.extend(flac::parse_picture(&block));
+            } else {
+                apply_vorbis_comments(flac::parse_vorbis_comments(&block), query, tags);
+            }
+        }
+        offset += header.len as u64;
+        if header.is_last {
+            break;
+        }
+    }
+    Ok(())
+}
+
+async fn read_mp4_tags(
+    reader: &mut EntryRangeReader,
+    query: TagQuery,
+    tags: &mut EmbeddedTags,
+) -> BResult<()> {
+    let mut offset = 0u64;
+    loop {
+        let buf = reader.read(offset, 16).await?;
+        let Some(header) = mp4::parse_atom_header(&buf) else {
+            break;
+        };
+        if &header.typ == b"moov" {
+            let Some(size) = header.size else {
+                break;
+            };
+            if size > MAX_TAG_SIZE {
+                tracing::warn!("skip oversized moov atom of {} bytes", size);
+                break;
+            }
+            let moov = reader
+                .read(
+                    offset + header.header_len as u64,
+                    size as usize - header.header_len,
+                )
+                .await?;
+            if query.pictures {
+                tags.pictures.extend(mp4::parse_pictures(&moov));
+            }
+            if query.lyrics {
+                tags.unsynced_lyrics = mp4::parse_lyrics(&moov);
+            }
+            if query.replay_gain {
+                for (key, value) in mp4::parse_freeform_items(&moov) {
+                    tags.replay_gain.apply(&key, &value);
+                }
+            }
+            if query.text {
+                for (key, value) in mp4::parse_text_items(&moov) {
+                    tags.text.apply(key, &value);
+                }
+            }
+            break;
+        }
+        match header.size.and_then(|size| offset.checked_add(size)) {
+            Some(next) => offset = next,
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+/// Reads the comment header, the second packet of an Ogg stream. It starts on
+/// the second page and may span several pages when it holds pictures.
+async fn read_ogg_comment_packet(reader: &mut EntryRangeReader) -> BResult<Option<Vec<u8>>> {
+    let mut offset = 0u64;
+    let mut packet_index = 0;
+    let mut packet = Vec::new();
+    loop {
+        let header = reader.read(offset, ogg::PAGE_HEADER_SIZE).await?;
+        let Some(segment_count) = ogg::page_segment_count(&header) else {
+            return Ok(None);
+        };
+        let lacing = reader
+            .read(offset + ogg::PAGE_HEADER_SIZE as u64, segment_count)
+            .await?;
+        if lacing.len() < segment_count {
+            return Ok(None);
+        }
+        let body = offset + (ogg::PAGE_HEADER_SIZE + segment_count) as u64;
+        for piece in ogg::split_packets(&lacing) {
+            if packet_index == 1 {
+                if (packet.len() + piece.len) as u64 > MAX_TAG_SIZE {
+                    tracing::warn!("skip oversized ogg comment header");
+                    return Ok(None);
+                }
+                let data = reader.read(body + piece.start as u64, piece.len).await?;
+                packet.extend_from_slice(&data);
+                if piece.complete {
+                    return Ok(Some(packet));
+                }
+            }
+            if piece.complete {
+                packet_index += 1;
+            }
+        }
+        offset = body + lacing.iter().map(|v| *v as u64).sum::<u64>();
+    }
+}
+
+async fn read_ogg_tags(
+    reader: &mut EntryRangeReader,
+    query: TagQuery,
+    tags: &mut EmbeddedTags,
+) -> BResult<()> {
+    let Some(packet) = read_ogg_comment_packet(reader).await? else {
+        return Ok(());
+    };
+    if let Some(body) = ogg::comment_packet_body(&packet) {
+        apply_vorbis_comments(flac::parse_vorbis_comments(body), query, tags);
+    }
+    Ok(())
+}
+
+/// Reads the tags embedded in an ID3v2 tagged file, a FLAC stream, an Ogg
+/// Vorbis or Opus stream or an MP4 container, fetching only the byte ranges
+/// that hold them.
+pub(crate) async fn read_embedded_tags(
+    reader: &mut EntryRangeReader,
+    query: TagQuery,
+) -> BResult<EmbeddedTags> {
+    let mut tags = EmbeddedTags::default();
+
+    let head = reader.read(0, 12).await?;
+    if mp4::is_mp4(&head) {
+        read_mp4_tags(reader, query, &mut tags).await?;
+        return Ok(tags);
+    }
+    if ogg::is_ogg(&head) {
+        read_ogg_tags(reader, query, &mut tags).await?;
+        return Ok(tags);
+    }
+
+    let offset = read_id3_tags(reader, query, &mut tags).await?;
+    let magic = reader.read(offset, flac::MAGIC.len()).await?;
+    if magic.as_slice() == flac::MAGIC {
+        read_flac_tags(reader, offset, query, &mut tags).await?;
+    }
+    Ok(tags)
+}
+
+fn average_bitrate(audio_len: Option<u64>, duration: Option<Duration>) -> Option<u32> {
+    let secs = duration?.as_secs_f64();
+    if secs <= 0.0 {
+        return None;
+    }
+    Some((audio_len? as f64 * 8.0 / secs / 1000.0).round() as u32)
+}
+
+async fn probe_flac(reader: &mut EntryRangeReader, offset: u64) -> BResult<Option<StreamInfo>> {
+    let offset = offset + flac::MAGIC.len() as u64;
+    let buf = reader.read(offset, flac::BLOCK_HEADER_SIZE).await?;
+    let Some(header) = flac::parse_block_header(&buf) else {
+        return Ok(None);
+    };
+    if header.typ != flac::BLOCK_STREAMINFO {
+        return Ok(None);
+    }
+    let block = reader
+        .read(offset + flac::BLOCK_HEADER_SIZE as u64, header.len)
+        .await?;
+    let Some(info) = flac::parse_stream_info(&block) else {
+        return Ok(None);
+    };
+    if info.sample_rate == 0 {
+        return Ok(None);
+    }
+    let duration = if info.total_samples > 0 {
+        Some(Duration::from_secs_f64(
+            info.total_samples as f64 / info.sample_rate as f64,
+        ))
+    } else {
+        None
+    };
+    let total = reader.total_len().await?;
+    Ok(Some(StreamInfo {
+        duration,
+        sample_rate: info.sample_rate,
+        channels: info.channels,
+        bit_depth: Some(info.bits_per_sample),
+        bitrate: average_bitrate(total, duration),
+    }))
+}
+
+async fn probe_mp3(reader: &mut EntryRangeReader, offset: u64) -> BResult<Option<StreamInfo>> {
+    const SCAN_SIZE: usize = 64 * 1024;
+
+    let buf = reader.read(offset, SCAN_SIZE).await?;
+    let Some((pos, header)) = mpeg::find_first_frame(&buf) else {
+        return Ok(None);
+    };
+    let audio_start = offset + pos as u64;
+    let audio_len = reader
+        .total_len()
+        .await?
+        .map(|total| total.saturating_sub(audio_start));
+
+    let frame = &buf[pos..(pos + header.frame_len).min(buf.len())];
+    let (duration, bitrate) = if let Some(frames) = mpeg::parse_vbr_frames(frame, &header) {
+        let duration = mpeg::frames_duration(frames, &header);
+        (Some(duration), average_bitrate(audio_len, Some(duration)))
+    } else {
+        let bitrate = mpeg::scan_average_bitrate(&buf[pos..]).unwrap_or(header.bitrate);
+        let duration = audio_len
+            .map(|len| Duration::from_secs_f64(len as f64 * 8.0 / (bitrate as f64 * 1000.0)));
+        (duration, Some(bitrate))
+    };
+    Ok(Some(StreamInfo {
+        duration,
+        sample_rate: header.sample_rate,
+        channels: header.channels,
+        bit_depth: None,
+        bitrate,
+    }))
+}
+
+async fn probe_mp4(reader: &mut EntryRangeReader) -> BResult<Option<StreamInfo>> {
+    let mut offset = 0u64;
+    loop {
+        let buf = reader.read(offset, 16).await?;
+        let Some(header) = mp4::parse_atom_header(&buf) else {
+            return Ok(None);
+        };
+        let Some(size) = header.size else {
+            return Ok(None);
+        };
+        if &header.typ == b"moov" {
+            if size > MAX_TAG_SIZE {
+                return Ok(None);
+            }
+            let moov = reader
+                .read(
+                    offset + header.header_len as u64,
+                    size as usize - header.header_len,
+                )
+                .await?;
+            let Some(track) = mp4::parse_audio_track(&moov) else {
+                return Ok(None);
+            };
+            let duration = mp4::parse_movie_duration(&moov)
+                .filter(|(_, timescale)| *timescale > 0)
+                .map(|(duration, timescale)| {
+                    Duration::from_secs_f64(duration as f64 / timescale as f64)
+                });
+            let total = reader.total_len().await?;
+            return Ok(Some(StreamInfo {
+                duration,
+                sample_rate: track.sample_rate,
+                channels: track.channels,
+                bit_depth: if track.sample_size > 0 {
+                    Some(track.sample_size)
+                } else {
+                    None
+                },
+                bitrate: average_bitrate(total, duration),
+            }));
+        }
+        let Some(next) = offset.checked_add(size) else {
+            return Ok(None);
+        };
+        offset = next;
+    }
+}
+
+async fn probe_ogg(reader: &mut EntryRangeReader) -> BResult<Option<StreamInfo>> {
+    const TAIL_SIZE: u64 = 64 * 1024;
+    const OPUS_SAMPLE_RATE: u32 = 48000;
+
+    let page = reader.read(0, 512).await?;
+    let Some(ident) = ogg::parse_identification(&page) else {
+        return Ok(None);
+    };
+    let total = reader.total_len().await?;
+    let granule = match total {
+        Some(total) => {
+            let start = total.saturating_sub(TAIL_SIZE);
+            let tail = reader.read(start, (total - start) as usize).await?;
+            ogg::last_granule_position(&tail)
+        }
+        None => None,
+    };
+
+    let (samples, rate, nominal) = match ident.codec {
+        ogg::OggCodec::Vorbis { bitrate_nominal } => (granule, ident.sample_rate, bitrate_nominal),
+        ogg::OggCodec::Opus { pre_skip } => (
+            granule.map(|g| g.saturating_sub(pre_skip as u64)),
+            OPUS_SAMPLE_RATE,
+            None,
+        ),
+    };
+    let duration = samples
+        .filter(|_| rate > 0)
+        .map(|samples| Duration::from_secs_f64(samples as f64 / rate as f64));
+    let sample_rate = if ident.sample_rate > 0 {
+        ident.sample_rate
+    } else {
+        rate
+    };
+    Ok(Some(StreamInfo {
+        duration,
+        sample_rate,
+        channels: ident.channels,
+        bit_depth: None,
+        bitrate: average_bitrate(total, duration).or(nominal.map(|v| v / 1000)),
+    }))
+}
+
+async fn probe_wav(reader: &mut EntryRangeReader) -> BResult<Option<StreamInfo>> {
+    let mut offset = wav::HEADER_SIZE as u64;
+    let mut format: Option<wav::WavFormat> = None;
+    loop {
+        let buf = reader.read(offset, wav::CHUNK_HEADER_SIZE).await?;
+        let Some((id, size)) = wav::parse_chunk_header(&buf) else {
+            return Ok(None);
+        };
+        offset += wav::CHUNK_HEADER_SIZE as u64;
+        if &id == b"fmt " {
+            let buf = reader.read(offset, size.min(64) as usize).await?;
+            format = wav::parse_format(&buf);
+        } else if &id == b"data" {
+            let Some(format) = format else {
+                return Ok(None);
+            };
+            let duration = if format.byte_rate > 0 {
+                Some(Duration::from_secs_f64(
+                    size as f64 / format.byte_rate as f64,
+                ))
+            } else {
+                None
+            };
+            return Ok(Some(StreamInfo {
+                duration,
+                sample_rate: format.sample_rate,
+                channels: format.channels,
+                bit_depth: Some(format.bits_per_sample),
+                bitrate: u32::try_from(format.byte_rate as u64 * 8 / 1000).ok(),
+            }));
+        }
+        offset += size + (size & 1);
+    }
+}
+
+/// Probes the container headers of a music file for its duration and audio
+/// stream parameters, fetching only the byte ranges that hold them.
+pub(crate) async fn probe_stream_info(
+    reader: &mut EntryRangeReader,
+) -> BResult<Option<StreamInfo>> {
+    let head = reader.read(0, 12).await?;
+    if mp4::is_mp4(&head) {
+        return probe_mp4(reader).await;
+    }
+    if ogg::is_ogg(&head) {
+        return probe_ogg(reader).await;
+    }
+    if wav::is_wav(&head) {
+        return probe_wav(reader).await;
+    }
+
+    let header = reader.read(0, id3::HEADER_SIZE).await?;
+    let offset = id3::tag_size(&header).unwrap_or(0) as u64;
+    let magic = reader.read(offset, flac::MAGIC.len()).await?;
+    if magic.as_slice() == flac::MAGIC {
+        return probe_flac(reader, offset).await;
+    }
+    probe_mp3(reader, offset).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ease_remote_storage::LocalBackend;
+
+    use super::{probe_stream_info, read_embedded_tags, EntryRangeReader, TagQuery};
+
+    fn reader_of(dir: &tempfile::TempDir, name: &str, file: &[u8]) -> EntryRangeReader {
+        let path = dir.path().join(name);
+        std::fs::write(&path, file).expect("write music");
+        EntryRangeReader::new(
+            Arc::new(LocalBackend::new()),
+            path.to_string_lossy().to_string(),
+        )
+    }
+
+    /// An `ftyp` atom followed by a 64-bit sized atom claiming `u64::MAX`
+    /// bytes.
+    fn mp4_with_huge_atom() -> Vec<u8> {
+        let mut file = b"\x00\x00\x00\x10ftypM4A \x00\x00\x00\x00".to_vec();
+        file.extend_from_slice(b"\x00\x00\x00\x01mdat");
+        file.extend_from_slice(u64::MAX.to_be_bytes().as_slice());
+        file
+    }
+
+    #[test]
+    fn stops_reading_mp4_tags_at_huge_atom() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let dir = tempfile::tempdir().expect("create tempdir");
+            let mut reader = reader_of(&dir, "huge.m4a", &mp4_with_huge_atom());
+            let query = TagQuery {
+                text: true,
+                pictures: true,
+                lyrics: true,
+                replay_gain: true,
+            };
+            let tags = read_embedded_tags(&mut reader, query).await.expect("read");
+            assert_eq!(None, tags.text.title);
+            assert!(tags.pictures.is_empty());
+        });
+    }
+
+    #[test]
+    fn stops_probing_mp4_at_huge_atom() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let dir = tempfile::tempdir().expect("create tempdir");
+            let mut reader = reader_of(&dir, "huge.m4a", &mp4_with_huge_atom());
+            let info = probe_stream_info(&mut reader).await.expect("probe");
+            assert_eq!(None, info);
+        });
+    }
+
+    #[test]
+    fn reads_nothing_past_address_space() {
+        ease_client_tokio::tokio_runtime().block_on(async {
+            let dir = tempfile::tempdir().expect("create tempdir");
+            let mut reader = reader_of(&dir, "huge.m4a", &mp4_with_huge_atom());
+            let buf = reader.read(u64::MAX - 4, 16).await.expect("read");
+            assert!(buf.is_empty());
+            let buf = reader.read(16, usize::MAX).await.expect("read");
+            assert!(buf.is_empty());
+            assert_eq!(8, reader.read(24, 16).await.expect("read").len());
+        });
+    }
+}
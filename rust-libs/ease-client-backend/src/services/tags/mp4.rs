@@ -0,0 +1,236 @@
+use super::{EmbeddedPicture, PICTURE_TYPE_FRONT_COVER, PICTURE_TYPE_OTHER};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct AtomHeader {
+    pub typ: [u8; 4],
+    pub header_len: usize,
+    /// Total atom size including the header, `None` when it extends to the end of file.
+    pub size: Option<u64>,
+}
+
+pub(crate) fn is_mp4(head: &[u8]) -> bool {
+    head.len() >= 8 && &head[4..8] == b"ftyp"
+}
+
+/// Parses an atom header. `buf` should hold at least 16 bytes to cover
+/// 64-bit atom sizes.
+pub(crate) fn parse_atom_header(buf: &[u8]) -> Option<AtomHeader> {
+    if buf.len() < 8 {
+        return None;
+    }
+    let size = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as u64;
+    let typ = [buf[4], buf[5], buf[6], buf[7]];
+    match size {
+        0 => Some(AtomHeader {
+            typ,
+            header_len: 8,
+            size: None,
+        }),
+        1 => {
+            let large = buf.get(8..16)?;
+            let size = u64::from_be_bytes(large.try_into().ok()?);
+            if size < 16 {
+                return None;
+            }
+            Some(AtomHeader {
+                typ,
+                header_len: 16,
+                size: Some(size),
+            })
+        }
+        size if size < 8 => None,
+        size => Some(AtomHeader {
+            typ,
+            header_len: 8,
+            size: Some(size),
+        }),
+    }
+}
+
+/// Iterates the child atoms stored in `buf`, yielding their type and payload.
+pub(crate) fn children(buf: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut ret = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        let Some(header) = parse_atom_header(&buf[pos..]) else {
+            break;
+        };
+        let end = match header.size {
+            Some(size) => usize::try_from(size).ok().and_then(|v| pos.checked_add(v)),
+            None => Some(buf.len()),
+        };
+        let Some(end) = end.filter(|v| *v <= buf.len() && *v >= pos + header.header_len) else {
+            break;
+        };
+        ret.push((header.typ, &buf[pos + header.header_len..end]));
+        pos = end;
+    }
+    ret
+}
+
+/// Finds the payload of the atom at `path` below `buf`. The `meta` atom is a
+/// full box and its version and flags are skipped.
+pub(crate) fn find_path<'a>(buf: &'a [u8], path: &[&[u8; 4]]) -> Option<&'a [u8]> {
+    let mut current = buf;
+    for typ in path {
+        let (_, payload) = children(current).into_iter().find(|(t, _)| t == *typ)?;
+        current = if *typ == b"meta" {
+            payload.get(4..)?
+        } else {
+            payload
+        };
+    }
+    Some(current)
+}
+
+/// Returns the payloads of the `data` atoms of the `ilst` item `typ`, skipping
+/// the type indicator and locale.
+pub(crate) fn item_values<'a>(moov: &'a [u8], typ: &[u8; 4]) -> Vec<(u32, &'a [u8])> {
+    let Some(item) = find_path(moov, &[b"udta", b"meta", b"ilst", typ]) else {
+        return Default::default();
+    };
+    children(item)
+        .into_iter()
+        .filter(|(t, payload)| t == b"data" && payload.len() >= 8)
+        .map(|(_, payload)| {
+            let kind = u32::from_be_bytes([0, payload[1], payload[2], payload[3]]);
+            (kind, &payload[8..])
+        })
+        .collect()
+}
+
+pub(crate) fn parse_pictures(moov: &[u8]) -> Vec<EmbeddedPicture> {
+    item_values(moov, b"covr")
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (_, data))| !data.is_empty())
+        .map(|(i, (kind, data))| EmbeddedPicture {
+            picture_type: if i == 0 {
+                PICTURE_TYPE_FRONT_COVER
+            } else {
+                PICTURE_TYPE_OTHER
+            },
+            mime: match kind {
+                13 => Some("image/jpeg".to_string()),
+                14 => Some("image/png".to_string()),
+                27 => Some("image/bmp".to_string()),
+                _ => None,
+            },
+            data: data.to_vec(),
+        })
+        .collect()
+}
+
+/// Returns the title, artist and album items as Vorbis comment keys and
+/// their text values.
+pub(crate) fn parse_text_items(moov: &[u8]) -> Vec<(&'static str, String)> {
+    [
+        (b"\xa9nam", "TITLE"),
+        (b"\xa9ART", "ARTIST"),
+        (b"\xa9alb", "ALBUM"),
+    ]
+    .into_iter()
+    .flat_map(|(typ, key)| {
+        item_values(moov, typ)
+            .into_iter()
+            .filter(|(kind, _)| *kind == 1)
+            .map(move |(_, data)| (key, String::from_utf8_lossy(data).to_string()))
+    })
+    .collect()
+}
+
+/// Returns the `name` and text value of the freeform `----` items.
+pub(crate) fn parse_freeform_items(moov: &[u8]) -> Vec<(String, String)> {
+    let Some(ilst) = find_path(moov, &[b"udta", b"meta", b"ilst"]) else {
+        return Default::default();
+    };
+    children(ilst)
+        .into_iter()
+        .filter(|(t, _)| t == b"----")
+        .filter_map(|(_, item)| {
+            let parts = children(item);
+            let name = parts
+                .iter()
+                .find(|(t, payload)| t == b"name" && payload.len() >= 4)
+                .map(|(_, payload)| String::from_utf8_lossy(&payload[4..]).to_string())?;
+            let value = parts
+                .iter()
+                .find(|(t, payload)| t == b"data" && payload.len() >= 8)
+                .map(|(_, payload)| String::from_utf8_lossy(&payload[8..]).to_string())?;
+            Some((name, value))
+        })
+        .collect()
+}
+
+pub(crate) fn parse_lyrics(moov: &[u8]) -> Option<String> {
+    item_values(moov, b"\xa9lyr")
+        .into_iter()
+        .map(|(_, data)| String::from_utf8_lossy(data).to_string())
+        .find(|text| !text.trim().is_empty())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct AudioTrackInfo {
+    pub channels: u32,
+    pub sample_size: u32,
+    pub sample_rate: u32,
+}
+
+/// Reads the movie duration from `mvhd` as `(duration, timescale)`.
+pub(crate) fn parse_movie_duration(moov: &[u8]) -> Option<(u64, u32)> {
+    let mvhd = find_path(moov, &[b"mvhd"])?;
+    let version = *mvhd.first()?;
+    let u32_at = |i: usize| -> Option<u32> {
+        Some(u32::from_be_bytes(mvhd.get(i..i + 4)?.try_into().ok()?))
+    };
+    if version == 1 {
+        let timescale = u32_at(20)?;
+        let duration = u64::from_be_bytes(mvhd.get(24..32)?.try_into().ok()?);
+        Some((duration, timescale))
+    } else {
+        let timescale = u32_at(12)?;
+        let duration = u32_at(16)? as u64;
+        Some((duration, timescale))
+    }
+}
+
+/// Reads the sample entry of the first sound track.
+pub(crate) fn parse_audio_track(moov: &[u8]) -> Option<AudioTrackInfo> {
+    children(moov)
+        .into_iter()
+        .filter(|(t, _)| t == b"trak")
+        .find_map(|(_, trak)| {
+            let hdlr = find_path(trak, &[b"mdia", b"hdlr"])?;
+            if hdlr.get(8..12)? != b"soun" {
+                return None;
+            }
+            let stsd = find_path(trak, &[b"mdia", b"minf", b"stbl", b"stsd"])?;
+            // version, flags and entry count, followed by the first sample entry
+            let (_, entry) = children(stsd.get(8..)?).into_iter().next()?;
+            let u16_at = |i: usize| -> Option<u32> {
+                Some(u16::from_be_bytes(entry.get(i..i + 2)?.try_into().ok()?) as u32)
+            };
+            Some(AudioTrackInfo {
+                channels: u16_at(16)?,
+                sample_size: u16_at(18)?,
+                sample_rate: u16_at(24)?,
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::children;
+
+    #[test]
+    fn stops_at_atom_larger_than_address_space() {
+        let mut buf = b"\x00\x00\x00\x08free".to_vec();
+        buf.extend_from_slice(b"\x00\x00\x00\x01huge");
+        buf.extend_from_slice(u64::MAX.to_be_bytes().as_slice());
+        buf.extend_from_slice(&[0; 8]);
+
+        let atoms = children(&buf);
+        assert_eq!(1, atoms.len());
+        assert_eq!(b"free", &atoms[0].0);
+    }
+}
@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Version {
+    Mpeg1,
+    Mpeg2,
+    Mpeg25,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FrameHeader {
+    version: Version,
+    layer: u8,
+    pub bitrate: u32,
+    pub sample_rate: u32,
+    pub channels: u32,
+    pub frame_len: usize,
+    pub samples: u32,
+}
+
+const BITRATES_V1: [[u32; 16]; 3] = [
+    [
+        0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448, 0,
+    ],
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 0,
+    ],
+    [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 0,
+    ],
+];
+
+const BITRATES_V2: [[u32; 16]; 3] = [
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256, 0,
+    ],
+    [
+        0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160, 0,
+    ],
+    [
+        0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160, 0,
+    ],
+];
+
+pub(crate) fn parse_frame_header(buf: &[u8]) -> Option<FrameHeader> {
+    if buf.len() < 4 || buf[0] != 0xff || buf[1] & 0xe0 != 0xe0 {
+        return None;
+    }
+    let version = match (buf[1] >> 3) & 0x03 {
+        0 => Version::Mpeg25,
+        2 => Version::Mpeg2,
+        3 => Version::Mpeg1,
+        _ => return None,
+    };
+    let layer = match (buf[1] >> 1) & 0x03 {
+        1 => 3,
+        2 => 2,
+        3 => 1,
+        _ => return None,
+    };
+    let bitrate_index = (buf[2] >> 4) as usize;
+    let sample_rate_index = ((buf[2] >> 2) & 0x03) as usize;
+    if sample_rate_index == 3 {
+        return None;
+    }
+    let padding = ((buf[2] >> 1) & 0x01) as usize;
+    let channels = if buf[3] >> 6 == 3 { 1 } else { 2 };
+
+    let bitrate = match version {
+        Version::Mpeg1 => BITRATES_V1[layer as usize - 1][bitrate_index],
+        _ => BITRATES_V2[layer as usize - 1][bitrate_index],
+    };
+    if bitrate == 0 {
+        return None;
+    }
+    let sample_rate = [44100, 48000, 32000][sample_rate_index]
+        / match version {
+            Version::Mpeg1 => 1,
+            Version::Mpeg2 => 2,
+            Version::Mpeg25 => 4,
+        };
+    let samples = match (layer, version) {
+        (1, _) => 384,
+        (3, Version::Mpeg2 | Version::Mpeg25) => 576,
+        _ => 1152,
+    };
+    let frame_len = if layer == 1 {
+        (12 * bitrate as usize * 1000 / sample_rate as usize + padding) * 4
+    } else {
+        samples as usize / 8 * bitrate as usize * 1000 / sample_rate as usize + padding
+    };
+
+    Some(FrameHeader {
+        version,
+        layer,
+        bitrate,
+        sample_rate,
+        channels,
+        frame_len,
+        samples,
+    })
+}
+
+/// Finds the first frame that is followed by another valid frame header,
+/// returning its position in `buf`.
+pub(crate) fn find_first_frame(buf: &[u8]) -> Option<(usize, FrameHeader)> {
+    let mut pos = 0;
+    while pos + 4 <= buf.len() {
+        if let Some(header) = parse_frame_header(&buf[pos..]) {
+            let next = pos + header.frame_len;
+            let confirmed = match buf.get(next..next + 4) {
+                Some(next) => parse_frame_header(next).is_some(),
+                None => next >= buf.len(),
+            };
+            if confirmed {
+                return Some((pos, header));
+            }
+        }
+        pos += 1;
+    }
+    None
+}
+
+/// Reads the frame count from a Xing/Info or VBRI header inside `frame`.
+pub(crate) fn parse_vbr_frames(frame: &[u8], header: &FrameHeader) -> Option<u32> {
+    let side_info = match (header.version, header.channels) {
+        (Version::Mpeg1, 1) => 17,
+        (Version::Mpeg1, _) => 32,
+        (_, 1) => 9,
+        _ => 17,
+    };
+    let xing = 4 + side_info;
+    if header.layer == 3 {
+        if let Some(tag) = frame.get(xing..xing + 12) {
+            if &tag[0..4] == b"Xing" || &tag[0..4] == b"Info" {
+                let flags = u32::from_be_bytes([tag[4], tag[5], tag[6], tag[7]]);
+                if flags & 0x01 != 0 {
+                    return Some(u32::from_be_bytes([tag[8], tag[9], tag[10], tag[11]]));
+                }
+                return None;
+            }
+        }
+    }
+    let vbri = frame.get(36..36 + 18)?;
+    if &vbri[0..4] == b"VBRI" {
+        return Some(u32::from_be_bytes([vbri[14], vbri[15], vbri[16], vbri[17]]));
+    }
+    None
+}
+
+/// Averages the bitrate of the consecutive frames found in `buf`.
+pub(crate) fn scan_average_bitrate(buf: &[u8]) -> Option<u32> {
+    let mut pos = 0;
+    let mut frames = 0u64;
+    let mut total = 0u64;
+    while let Some(header) = buf.get(pos..).and_then(parse_frame_header) {
+        frames += 1;
+        total += header.bitrate as u64;
+        pos += header.frame_len;
+    }
+    if frames == 0 {
+        return None;
+    }
+    Some((total / frames) as u32)
+}
+
+pub(crate) fn frames_duration(frames: u32, header: &FrameHeader) -> Duration {
+    Duration::from_secs_f64(frames as f64 * header.samples as f64 / header.sample_rate as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_first_frame, parse_vbr_frames};
+
+    #[test]
+    fn reads_xing_frame_count() {
+        // MPEG-1 Layer III, 128 kbps, 44.1 kHz, joint stereo
+        let header = [0xff, 0xfb, 0x90, 0x40];
+        let mut buf = Vec::new();
+        for _ in 0..2 {
+            let mut frame = header.to_vec();
+            frame.resize(417, 0);
+            buf.extend_from_slice(&frame);
+        }
+        buf[36..40].copy_from_slice(b"Xing");
+        buf[40..44].copy_from_slice(&1u32.to_be_bytes());
+        buf[44..48].copy_from_slice(&1000u32.to_be_bytes());
+
+        let (pos, header) = find_first_frame(&buf).expect("frame");
+        assert_eq!(pos, 0);
+        assert_eq!(header.frame_len, 417);
+        assert_eq!(parse_vbr_frames(&buf[..417], &header), Some(1000));
+    }
+}
@@ -0,0 +1,159 @@
+pub(crate) const PAGE_HEADER_SIZE: usize = 27;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OggCodec {
+    Vorbis { bitrate_nominal: Option<u32> },
+    Opus { pre_skip: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct OggIdentification {
+    pub codec: OggCodec,
+    pub channels: u32,
+    pub sample_rate: u32,
+}
+
+pub(crate) fn is_ogg(head: &[u8]) -> bool {
+    head.len() >= 4 && &head[0..4] == b"OggS"
+}
+
+/// Returns the number of lacing values of the page whose header is `header`.
+pub(crate) fn page_segment_count(header: &[u8]) -> Option<usize> {
+    if header.len() < PAGE_HEADER_SIZE || !is_ogg(header) {
+        return None;
+    }
+    Some(header[26] as usize)
+}
+
+/// Part of a packet carried by one page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PacketPiece {
+    /// Offset in the page body.
+    pub start: usize,
+    pub len: usize,
+    /// Whether the packet ends in this page.
+    pub complete: bool,
+}
+
+/// Splits the body of a page into packet pieces by its lacing values. A
+/// packet ends at the first lacing value below 255.
+pub(crate) fn split_packets(lacing: &[u8]) -> Vec<PacketPiece> {
+    let mut ret = Vec::new();
+    let mut start = 0;
+    let mut len = 0;
+    for &value in lacing {
+        len += value as usize;
+        if value < 255 {
+            ret.push(PacketPiece {
+                start,
+                len,
+                complete: true,
+            });
+            start += len;
+            len = 0;
+        }
+    }
+    if len > 0 {
+        ret.push(PacketPiece {
+            start,
+            len,
+            complete: false,
+        });
+    }
+    ret
+}
+
+/// Returns the Vorbis comments of a comment header packet of a Vorbis or
+/// Opus stream.
+pub(crate) fn comment_packet_body(packet: &[u8]) -> Option<&[u8]> {
+    packet
+        .strip_prefix(b"\x03vorbis")
+        .or_else(|| packet.strip_prefix(b"OpusTags"))
+}
+
+/// Parses the identification header carried by the first page of the stream.
+pub(crate) fn parse_identification(page: &[u8]) -> Option<OggIdentification> {
+    let segments = *page.get(26)? as usize;
+    let packet = page.get(PAGE_HEADER_SIZE + segments..)?;
+    if packet.len() >= 30 && &packet[0..7] == b"\x01vorbis" {
+        let channels = packet[11] as u32;
+        let sample_rate = u32::from_le_bytes([packet[12], packet[13], packet[14], packet[15]]);
+        let nominal = i32::from_le_bytes([packet[20], packet[21], packet[22], packet[23]]);
+        return Some(OggIdentification {
+            codec: OggCodec::Vorbis {
+                bitrate_nominal: if nominal > 0 {
+                    Some(nominal as u32)
+                } else {
+                    None
+                },
+            },
+            channels,
+            sample_rate,
+        });
+    }
+    if packet.len() >= 19 && &packet[0..8] == b"OpusHead" {
+        let channels = packet[9] as u32;
+        let pre_skip = u16::from_le_bytes([packet[10], packet[11]]) as u32;
+        let sample_rate = u32::from_le_bytes([packet[12], packet[13], packet[14], packet[15]]);
+        return Some(OggIdentification {
+            codec: OggCodec::Opus { pre_skip },
+            channels,
+            sample_rate,
+        });
+    }
+    None
+}
+
+/// Returns the granule position of the last page found in `tail`.
+pub(crate) fn last_granule_position(tail: &[u8]) -> Option<u64> {
+    let mut pos = tail.len().checked_sub(PAGE_HEADER_SIZE)?;
+    loop {
+        if &tail[pos..pos + 4] == b"OggS" {
+            let granule = i64::from_le_bytes(tail[pos + 6..pos + 14].try_into().ok()?);
+            if granule >= 0 {
+                return Some(granule as u64);
+            }
+        }
+        if pos == 0 {
+            return None;
+        }
+        pos -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{comment_packet_body, split_packets, PacketPiece};
+
+    #[test]
+    fn splits_packets_across_pages() {
+        let pieces = split_packets(&[255, 10, 30, 255, 255]);
+        assert_eq!(
+            pieces,
+            vec![
+                PacketPiece {
+                    start: 0,
+                    len: 265,
+                    complete: true,
+                },
+                PacketPiece {
+                    start: 265,
+                    len: 30,
+                    complete: true,
+                },
+                PacketPiece {
+                    start: 295,
+                    len: 510,
+                    complete: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn strips_comment_packet_magic() {
+        assert_eq!(comment_packet_body(b"\x03vorbis\x01"), Some(&[1u8][..]));
+        assert_eq!(comment_packet_body(b"OpusTags\x02"), Some(&[2u8][..]));
+        assert_eq!(comment_packet_body(b"\x01vorbis"), None);
+    }
+}
@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use ease_remote_storage::StorageBackend;
+
+use crate::error::BResult;
+
+const HEAD_SIZE: usize = 64 * 1024;
+
+/// Reads byte ranges of a storage entry without downloading the whole file.
+///
+/// The beginning of the file is cached because most tag layouts start there
+/// and are probed with many small reads.
+pub(crate) struct EntryRangeReader {
+    backend: Arc<dyn StorageBackend + Send + Sync>,
+    path: String,
+    head: Vec<u8>,
+    head_complete: bool,
+    total_len: Option<u64>,
+}
+
+impl EntryRangeReader {
+    pub(crate) fn new(backend: Arc<dyn StorageBackend + Send + Sync>, path: String) -> Self {
+        Self {
+            backend,
+            path,
+            head: Default::default(),
+            head_complete: false,
+            total_len: None,
+        }
+    }
+
+    /// Returns up to `len` bytes starting at `offset`. The result is shorter
+    /// than `len` only when the end of the entry is reached.
+    pub(crate) async fn read(&mut self, offset: u64, len: usize) -> BResult<Vec<u8>> {
+        // No entry is large enough to hold a range past the address space.
+        let Some(end) = usize::try_from(offset)
+            .ok()
+            .and_then(|v| v.checked_add(len))
+        else {
+            return Ok(Default::default());
+        };
+        let start = end - len;
+        if end <= self.head.len() || (self.head_complete && start <= self.head.len()) {
+            let end = end.min(self.head.len());
+            return Ok(self.head[start..end].to_vec());
+        }
+
+        if start < HEAD_SIZE {
+            let want = end.max(HEAD_SIZE);
+            self.head = self.fetch(0, want).await?;
+            self.head_complete = self.head.len() < want;
+            let start = start.min(self.head.len());
+            let end = end.min(self.head.len());
+            return Ok(self.head[start..end].to_vec());
+        }
+
+        self.fetch(offset, len).await
+    }
+
+    /// Returns the size of the entry when the storage reports it.
+    pub(crate) async fn total_len(&mut self) -> BResult<Option<u64>> {
+        if self.total_len.is_none() {
+            let file = self.backend.get(self.path.clone(), 0).await?;
+            self.total_len = file.size().map(|v| v as u64);
+        }
+        Ok(self.total_len)
+    }
+
+    async fn fetch(&mut self, offset: u64, len: usize) -> BResult<Vec<u8>> {
+        let file = self.backend.get(self.path.clone(), offset).await?;
+        if let Some(size) = file.size() {
+            self.total_len = Some(offset + size as u64);
+        }
+        let rx = file.into_rx();
+        let mut buf: Vec<u8> = Vec::with_capacity(len);
+        while buf.len() < len {
+            let Ok(chunk) = rx.recv().await else {
+                break;
+            };
+            let chunk = chunk?;
+            let take = chunk.len().min(len - buf.len());
+            buf.extend_from_slice(&chunk[..take]);
+        }
+        Ok(buf)
+    }
+}
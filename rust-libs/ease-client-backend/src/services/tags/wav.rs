@@ -0,0 +1,37 @@
+pub(crate) const HEADER_SIZE: usize = 12;
+pub(crate) const CHUNK_HEADER_SIZE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct WavFormat {
+    pub channels: u32,
+    pub sample_rate: u32,
+    pub byte_rate: u32,
+    pub bits_per_sample: u32,
+}
+
+pub(crate) fn is_wav(head: &[u8]) -> bool {
+    head.len() >= HEADER_SIZE && &head[0..4] == b"RIFF" && &head[8..12] == b"WAVE"
+}
+
+/// Parses a chunk header into its id and payload size. Payloads are padded to
+/// an even length.
+pub(crate) fn parse_chunk_header(buf: &[u8]) -> Option<([u8; 4], u64)> {
+    if buf.len() < CHUNK_HEADER_SIZE {
+        return None;
+    }
+    let id = [buf[0], buf[1], buf[2], buf[3]];
+    let size = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]) as u64;
+    Some((id, size))
+}
+
+pub(crate) fn parse_format(buf: &[u8]) -> Option<WavFormat> {
+    let buf = buf.get(0..16)?;
+    let u16_at = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]) as u32;
+    let u32_at = |i: usize| u32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
+    Some(WavFormat {
+        channels: u16_at(2),
+        sample_rate: u32_at(4),
+        byte_rate: u32_at(8),
+        bits_per_sample: u16_at(14),
+    })
+}
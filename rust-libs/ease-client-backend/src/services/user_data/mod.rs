@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use ease_client_schema::MusicId;
+
+use crate::{
+    ctx::BackendContext,
+    error::{BError, BResult},
+    services::build_music_abstract,
+    MusicAbstract,
+};
+
+pub(crate) const MAX_RATING: u8 = 5;
+
+#[derive(Debug, Clone, Default, uniffi::Record)]
+pub struct MusicUserData {
+    pub favorite: bool,
+    /// Time the music was favorited since the unix epoch.
+    pub favorite_time: Option<Duration>,
+    /// From 0, unrated, to 5.
+    pub rating: u8,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgSetMusicFavorite {
+    pub id: MusicId,
+    pub favorite: bool,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgSetMusicRating {
+    pub id: MusicId,
+    /// From 0, which clears the rating, to 5.
+    pub rating: u8,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgSetMusicUserTags {
+    pub id: MusicId,
+    /// Replaces the tags of the music. Tags are trimmed and lowercased, and
+    /// empty ones are dropped.
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ArgGetMusicsByRating {
+    pub min: u8,
+    pub max: u8,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct UserTagCount {
+    pub tag: String,
+    pub count: u64,
+}
+
+/// Trims, lowercases and dedups tags, so tags match regardless of case.
+pub(crate) fn normalize_user_tags(tags: Vec<String>) -> Vec<String> {
+    let mut ret: Vec<String> = Default::default();
+    for tag in tags {
+        let tag = tag.split_whitespace().collect::<Vec<_>>().join(" ");
+        let tag = tag.to_lowercase();
+        if !tag.is_empty() && !ret.contains(&tag) {
+            ret.push(tag);
+        }
+    }
+    ret.sort();
+    ret
+}
+
+pub(crate) fn get_music_user_data(cx: &BackendContext, id: MusicId) -> BResult<MusicUserData> {
+    let model = cx.database_server().load_music_user_data(id)?;
+    Ok(MusicUserData {
+        favorite: model.favorite_time.is_some(),
+        favorite_time: model.favorite_time.map(|v| Duration::from_millis(v as u64)),
+        rating: model.rating,
+        tags: model.tags,
+    })
+}
+
+pub(crate) fn set_music_favorite(cx: &BackendContext, arg: ArgSetMusicFavorite) -> BResult<()> {
+    let db = cx.database_server();
+    let current = db.load_music_user_data(arg.id)?.favorite_time;
+    // Favoriting again keeps the time the music was first favorited.
+    let favorite_time = match (arg.favorite, current) {
+        (true, Some(time)) => Some(time),
+        (true, None) => Some(cx.current_time().as_millis() as i64),
+        (false, _) => None,
+    };
+    db.set_music_favorite(arg.id, favorite_time)
+}
+
+pub(crate) fn set_music_rating(cx: &BackendContext, arg: ArgSetMusicRating) -> BResult<()> {
+    if arg.rating > MAX_RATING {
+        return Err(BError::InvalidRating(arg.rating));
+    }
+    cx.database_server().set_music_rating(arg.id, arg.rating)
+}
+
+pub(crate) fn set_music_user_tags(cx: &BackendContext, arg: ArgSetMusicUserTags) -> BResult<()> {
+    cx.database_server()
+        .set_music_user_tags(arg.id, normalize_user_tags(arg.tags))
+}
+
+/// Favorite musics, latest favorited first.
+pub(crate) fn get_favorite_musics(cx: &BackendContext) -> BResult<Vec<MusicAbstract>> {
+    let mut musics = cx.database_server().load_favorite_musics()?;
+    musics.sort_by(|(lhs, lt), (rhs, rt)| rt.cmp(lt).then(lhs.id.cmp(&rhs.id)));
+    Ok(musics
+        .into_iter()
+        .map(|(m, _)| build_music_abstract(cx, m))
+        .collect())
+}
+
+/// Musics rated from `min` to `max`, highest rated first. Unrated musics
+/// are left out.
+pub(crate) fn get_musics_by_rating(
+    cx: &BackendContext,
+    arg: ArgGetMusicsByRating,
+) -> BResult<Vec<MusicAbstract>> {
+    if arg.min > MAX_RATING || arg.max > MAX_RATING {
+        return Err(BError::InvalidRating(arg.min.max(arg.max)));
+    }
+    Ok(cx
+        .database_server()
+        .load_musics_by_rating(arg.min, arg.max)?
+        .into_iter()
+        .map(|m| build_music_abstract(cx, m))
+        .collect())
+}
+
+pub(crate) fn get_musics_by_user_tag(
+    cx: &BackendContext,
+    tag: String,
+) -> BResult<Vec<MusicAbstract>> {
+    let Some(tag) = normalize_user_tags(vec![tag]).pop() else {
+        return Ok(Default::default());
+    };
+    Ok(cx
+        .database_server()
+        .load_musics_by_user_tag(tag)?
+        .into_iter()
+        .map(|m| build_music_abstract(cx, m))
+        .collect())
+}
+
+pub(crate) fn list_user_tags(cx: &BackendContext) -> BResult<Vec<UserTagCount>> {
+    Ok(cx
+        .database_server()
+        .load_user_tags()?
+        .into_iter()
+        .map(|(tag, count)| UserTagCount { tag, count })
+        .collect())
+}
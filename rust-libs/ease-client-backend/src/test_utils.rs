@@ -0,0 +1,58 @@
+//! Fixtures shared by the tests of the backend.
+
+use std::sync::Arc;
+
+use ease_client_schema::{StorageId, StorageType};
+use tempfile::TempDir;
+
+use crate::{
+    controllers::storage::ct_list_storage, create_backend, services::ArgInitializeApp,
+    services::ToAddMusicEntry, Backend, Storage, StorageEntry,
+};
+
+/// Creates a backend on a temporary directory, dropped with the returned
+/// `TempDir`.
+pub(crate) fn setup_backend() -> (TempDir, Arc<Backend>) {
+    let tempdir = tempfile::tempdir().expect("create tempdir");
+    let documents_dir = tempdir.path().join("documents");
+    let cache_dir = tempdir.path().join("cache");
+    std::fs::create_dir_all(&documents_dir).expect("create documents dir");
+    std::fs::create_dir_all(&cache_dir).expect("create cache dir");
+
+    let backend = create_backend(ArgInitializeApp {
+        app_document_dir: format!("{}/", documents_dir.display()),
+        app_cache_dir: format!("{}/", cache_dir.display()),
+        storage_path: "/".to_string(),
+    });
+    backend.init().expect("init backend");
+    (tempdir, backend)
+}
+
+/// The local storage every backend is created with.
+pub(crate) async fn local_storage(backend: &Arc<Backend>) -> Storage {
+    ct_list_storage(backend.clone())
+        .await
+        .expect("list storages")
+        .into_iter()
+        .find(|storage| storage.typ == StorageType::Local)
+        .expect("local storage")
+}
+
+pub(crate) async fn local_storage_id(backend: &Arc<Backend>) -> StorageId {
+    local_storage(backend).await.id
+}
+
+/// A music file to add, named after the last component of its path.
+pub(crate) fn music_entry(storage_id: StorageId, path: &str) -> ToAddMusicEntry {
+    let name = path.rsplit('/').next().unwrap().to_string();
+    ToAddMusicEntry {
+        entry: StorageEntry {
+            storage_id,
+            name: name.clone(),
+            path: path.to_string(),
+            size: None,
+            is_dir: false,
+        },
+        name,
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use pinyin::ToPinyin;
+
+/// Pairs of a traditional character followed by its simplified form, from
+/// the simplified variants of the Unihan database.
+const T2S_PAIRS: &str = include_str!("t2s.txt");
+
+/// Reading terms are generated for at most this many characters of a run,
+/// bounding the terms produced by long lines of text.
+const MAX_READING_RUN: usize = 16;
+
+static T2S: Lazy<HashMap<char, char>> = Lazy::new(|| {
+    T2S_PAIRS
+        .split_whitespace()
+        .filter_map(|pair| {
+            let mut chars = pair.chars();
+            Some((chars.next()?, chars.next()?))
+        })
+        .collect()
+});
+
+pub fn is_han(c: char) -> bool {
+    matches!(c,
+        '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{20000}'..='\u{2FA1F}')
+}
+
+pub fn is_kana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{3096}' | '\u{30A1}'..='\u{30FA}' | 'ー')
+}
+
+/// Folds traditional Chinese characters to their simplified form.
+pub fn to_simplified(c: char) -> char {
+    T2S.get(&c).copied().unwrap_or(c)
+}
+
+/// Folds katakana to hiragana so that both kana scripts match each other.
+pub fn to_hiragana(c: char) -> char {
+    match c {
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+fn kana_romaji(c: char) -> Option<&'static str> {
+    let v = match c {
+        'あ' | 'ぁ' => "a",
+        'い' | 'ぃ' | 'ゐ' => "i",
+        'う' | 'ぅ' => "u",
+        'え' | 'ぇ' | 'ゑ' => "e",
+        'お' | 'ぉ' | 'を' => "o",
+        'か' | 'ゕ' => "ka",
+        'き' => "ki",
+        'く' => "ku",
+        'け' | 'ゖ' => "ke",
+        'こ' => "ko",
+        'さ' => "sa",
+        'し' => "shi",
+        'す' => "su",
+        'せ' => "se",
+        'そ' => "so",
+        'た' => "ta",
+        'ち' => "chi",
+        'つ' => "tsu",
+        'て' => "te",
+        'と' => "to",
+        'な' => "na",
+        'に' => "ni",
+        'ぬ' => "nu",
+        'ね' => "ne",
+        'の' => "no",
+        'は' => "ha",
+        'ひ' => "hi",
+        'ふ' => "fu",
+        'へ' => "he",
+        'ほ' => "ho",
+        'ま' => "ma",
+        'み' => "mi",
+        'む' => "mu",
+        'め' => "me",
+        'も' => "mo",
+        'や' | 'ゃ' => "ya",
+        'ゆ' | 'ゅ' => "yu",
+        'よ' | 'ょ' => "yo",
+        'ら' => "ra",
+        'り' => "ri",
+        'る' => "ru",
+        'れ' => "re",
+        'ろ' => "ro",
+        'わ' | 'ゎ' => "wa",
+        'ん' => "n",
+        'が' => "ga",
+        'ぎ' => "gi",
+        'ぐ' => "gu",
+        'げ' => "ge",
+        'ご' => "go",
+        'ざ' => "za",
+        'じ' | 'ぢ' => "ji",
+        'ず' | 'づ' => "zu",
+        'ぜ' => "ze",
+        'ぞ' => "zo",
+        'だ' => "da",
+        'で' => "de",
+        'ど' => "do",
+        'ば' => "ba",
+        'び' => "bi",
+        'ぶ' => "bu",
+        'べ' => "be",
+        'ぼ' => "bo",
+        'ぱ' => "pa",
+        'ぴ' => "pi",
+        'ぷ' => "pu",
+        'ぺ' => "pe",
+        'ぽ' => "po",
+        'ゔ' => "vu",
+        _ => return None,
+    };
+    Some(v)
+}
+
+/// Converts hiragana to Hepburn romaji. Long vowel marks are dropped, the
+/// way romaji is usually typed.
+pub fn kana_to_romaji(kana: &str) -> String {
+    let chars: Vec<char> = kana.chars().collect();
+    let mut ret = String::new();
+    let mut double_next = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+        if c == 'っ' {
+            double_next = true;
+            continue;
+        }
+        let Some(base) = kana_romaji(c) else {
+            continue;
+        };
+        let mut syllable = base.to_string();
+        match chars.get(i) {
+            Some(small @ ('ゃ' | 'ゅ' | 'ょ')) if base.len() > 1 && base.ends_with('i') => {
+                let stem = &base[..base.len() - 1];
+                let vowel = &kana_romaji(*small).unwrap_or_default()[1..];
+                syllable = if matches!(stem, "sh" | "ch" | "j") {
+                    format!("{stem}{vowel}")
+                } else {
+                    format!("{stem}y{vowel}")
+                };
+                i += 1;
+            }
+            Some(small @ ('ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ')) if base.len() > 1 => {
+                let stem = &base[..base.len() - 1];
+                syllable = format!("{stem}{}", kana_romaji(*small).unwrap_or_default());
+                i += 1;
+            }
+            _ => {}
+        }
+        if double_next {
+            if let Some(first) = syllable.chars().next() {
+                if syllable.starts_with("ch") {
+                    ret.push('t');
+                } else if !"aiueon".contains(first) {
+                    ret.push(first);
+                }
+            }
+            double_next = false;
+        }
+        ret.push_str(&syllable);
+    }
+    ret
+}
+
+/// Returns the pinyin of a run of Han characters, as full syllables and as
+/// initials, starting at each character of the run. The terms let prefix
+/// queries such as "zhoujie", "jielun" or "zjl" find "周杰伦".
+pub fn han_reading_terms(run: &str) -> Vec<String> {
+    let syllables: Vec<&str> = run
+        .chars()
+        .take(MAX_READING_RUN)
+        .filter_map(|c| c.to_pinyin().map(|p| p.plain()))
+        .collect();
+    let mut ret = Vec::new();
+    for start in 0..syllables.len() {
+        let rest = &syllables[start..];
+        ret.push(rest.concat());
+        if rest.len() > 1 {
+            ret.push(rest.iter().filter_map(|s| s.get(..1)).collect());
+        }
+    }
+    ret
+}
+
+/// Returns the romaji of a run of hiragana starting at each kana, so that
+/// romaji queries match from any syllable of the run.
+pub fn kana_reading_terms(run: &str) -> Vec<String> {
+    let chars: Vec<char> = run.chars().take(MAX_READING_RUN).collect();
+    let mut ret = Vec::new();
+    for start in 0..chars.len() {
+        if matches!(
+            chars[start],
+            'ゃ' | 'ゅ' | 'ょ' | 'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' | 'ー'
+        ) {
+            continue;
+        }
+        let romaji = kana_to_romaji(&chars[start..].iter().collect::<String>());
+        if !romaji.is_empty() {
+            ret.push(romaji);
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{han_reading_terms, kana_to_romaji, to_hiragana, to_simplified};
+
+    #[test]
+    fn converts_kana_to_romaji() {
+        let hiragana = |s: &str| s.chars().map(to_hiragana).collect::<String>();
+        assert_eq!(kana_to_romaji("さくら"), "sakura");
+        assert_eq!(kana_to_romaji(&hiragana("キャッチ")), "kyatchi");
+        assert_eq!(kana_to_romaji("しゃしん"), "shashin");
+        assert_eq!(kana_to_romaji(&hiragana("ラーメン")), "ramen");
+        assert_eq!(kana_to_romaji(&hiragana("ファイト")), "faito");
+        assert_eq!(kana_to_romaji("がっこう"), "gakkou");
+    }
+
+    #[test]
+    fn reads_han_runs_as_pinyin() {
+        assert_eq!(
+            han_reading_terms("周杰伦"),
+            vec!["zhoujielun", "zjl", "jielun", "jl", "lun"]
+        );
+        assert_eq!(
+            "周杰倫".chars().map(to_simplified).collect::<String>(),
+            "周杰伦"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+mod cjk;
+
+/// Folds full-width and half-width forms to their canonical width.
+pub fn fold_width(text: &str) -> String {
+    text.nfkc().collect()
+}
+
+/// Folds widths, letter case, traditional Chinese and katakana so that
+/// variants of the same text produce the same terms.
+pub fn normalize(text: &str) -> String {
+    fold_width(text)
+        .to_lowercase()
+        .chars()
+        .map(cjk::to_simplified)
+        .map(cjk::to_hiragana)
+        .collect()
+}
+
+/// Splits `text` into normalized search terms. Words of scripts written
+/// without spaces, such as Han ideographs, are split into single characters.
+pub fn tokenize(text: &str) -> Vec<String> {
+    normalize(text)
+        .unicode_words()
+        .map(|word| word.to_string())
+        .collect()
+}
+
+fn strip_extension(path: &str) -> &str {
+    match path.rsplit_once('.') {
+        Some((stem, ext)) if !ext.contains('/') && !ext.contains('\\') => stem,
+        _ => path,
+    }
+}
+
+/// Splits a storage path into the terms of its file name and folders,
+/// ignoring the file extension.
+pub fn tokenize_path(path: &str) -> Vec<String> {
+    tokenize(strip_extension(path))
+}
+
+/// Returns the latin readings of the Han and kana runs of `text`: pinyin
+/// and pinyin initials for Han, romaji for kana.
+pub fn reading_terms(text: &str) -> Vec<String> {
+    let text = normalize(text);
+    let mut ret = Vec::new();
+    let mut run = String::new();
+    let mut run_is_han = false;
+    let mut flush = |run: &mut String, is_han: bool| {
+        if !run.is_empty() {
+            if is_han {
+                ret.extend(cjk::han_reading_terms(run));
+            } else {
+                ret.extend(cjk::kana_reading_terms(run));
+            }
+            run.clear();
+        }
+    };
+    for c in text.chars() {
+        let is_han = cjk::is_han(c);
+        if !is_han && !cjk::is_kana(c) {
+            flush(&mut run, run_is_han);
+            continue;
+        }
+        if is_han != run_is_han {
+            flush(&mut run, run_is_han);
+            run_is_han = is_han;
+        }
+        run.push(c);
+    }
+    flush(&mut run, run_is_han);
+    ret
+}
+
+/// Returns whether every keyword is found in `text`, either inside its
+/// normalized form or as the start of one of its readings.
+pub fn matches_keywords(text: &str, keywords: &str) -> bool {
+    let normalized = normalize(text);
+    let mut readings: Option<Vec<String>> = None;
+    normalize(keywords).split_whitespace().all(|keyword| {
+        normalized.contains(keyword)
+            || readings
+                .get_or_insert_with(|| reading_terms(text))
+                .iter()
+                .any(|term| term.starts_with(keyword))
+    })
+}
+
+/// Same as [`reading_terms`], ignoring the file extension of a path.
+pub fn path_reading_terms(path: &str) -> Vec<String> {
+    reading_terms(strip_extension(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches_keywords, reading_terms, tokenize, tokenize_path};
+
+    #[test]
+    fn tokenizes_mixed_scripts() {
+        assert_eq!(
+            tokenize("Hello, World! 周杰伦 - 晴天"),
+            vec!["hello", "world", "周", "杰", "伦", "晴", "天"]
+        );
+        assert_eq!(
+            tokenize_path("/Music/Jay Chou/07. Sunny Day.flac"),
+            vec!["music", "jay", "chou", "07", "sunny", "day"]
+        );
+    }
+
+    #[test]
+    fn folds_widths_and_scripts() {
+        assert_eq!(tokenize("ＳＵＮ ｽﾀｰ"), vec!["sun", "す", "た", "ー"]);
+        assert_eq!(tokenize("周杰倫"), tokenize("周杰伦"));
+        assert_eq!(tokenize("サクラ"), tokenize("さくら"));
+    }
+
+    #[test]
+    fn reads_han_and_kana() {
+        assert_eq!(
+            reading_terms("周杰倫 晴天"),
+            vec![
+                "zhoujielun",
+                "zjl",
+                "jielun",
+                "jl",
+                "lun",
+                "qingtian",
+                "qt",
+                "tian"
+            ]
+        );
+        assert_eq!(
+            reading_terms("桜サクラ"),
+            vec!["ying", "sakura", "kura", "ra"]
+        );
+    }
+
+    #[test]
+    fn matches_keywords_by_text_and_reading() {
+        assert!(matches_keywords("周杰倫 - 晴天.mp3", "zjl"));
+        assert!(matches_keywords("周杰倫 - 晴天.mp3", "周杰伦 qingt"));
+        assert!(matches_keywords("ＳＡＫＵＲＡ.flac", "sakura"));
+        assert!(matches_keywords("サクラ.flac", "さくら"));
+        assert!(!matches_keywords("周杰倫 - 晴天.mp3", "zjl yq"));
+    }
+}
@@ -0,0 +1,668 @@
+use std::{fmt::Debug, sync::Arc};
+
+use redb::{
+    Key, MultimapTableDefinition, MultimapTableHandle, ReadTransaction, ReadableMultimapTable,
+    ReadableTable, TableDefinition, TableError, TableHandle, Value, WriteTransaction,
+    backends::InMemoryBackend,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{v2, v3, v4, v5, v6};
+
+/// Schema version of the dumps written by [`dump_database`].
+const CURRENT_SCHEMA_VERSION: u32 = 6;
+
+/// Raw rows of one table, keyed and valued by the bytes redb stores. Rows of
+/// a multimap table repeat their key once per value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TableDump {
+    pub name: String,
+    pub rows: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+/// Logical dump of every table of one schema version. A dump of an older
+/// version is upgraded by loading it into a database of that version and
+/// running the same upgraders as an old install.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DatabaseDump {
+    pub schema_version: u32,
+    pub tables: Vec<TableDump>,
+}
+
+impl DatabaseDump {
+    fn table(&self, name: &str) -> Option<&TableDump> {
+        self.tables.iter().find(|t| t.name == name)
+    }
+
+    /// Number of rows dumped from a table.
+    pub fn count<K: Key + 'static, V: Value + 'static>(&self, def: TableDefinition<K, V>) -> usize {
+        self.table(def.name())
+            .map(|t| t.rows.len())
+            .unwrap_or_default()
+    }
+
+    /// Rewrites every value of a table of the dump in place.
+    pub fn update_values<K, T>(
+        &mut self,
+        def: TableDefinition<K, v6::BinSerde<T>>,
+        mut f: impl FnMut(&mut T) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()>
+    where
+        K: Key + 'static,
+        T: Debug + Serialize + v6::BinSerdeTN + for<'a> Deserialize<'a> + 'static,
+    {
+        let Some(table) = self.tables.iter_mut().find(|t| t.name == def.name()) else {
+            return Ok(());
+        };
+        for (_, value) in table.rows.iter_mut() {
+            let mut v: T = postcard::from_bytes(value)
+                .map_err(|e| anyhow::anyhow!("malformed row of {}: {e}", def.name()))?;
+            f(&mut v)?;
+            *value = <v6::BinSerde<T> as Value>::as_bytes(&v);
+        }
+        Ok(())
+    }
+}
+
+/// Values whose stored bytes can be checked before redb decodes them, as
+/// redb decodes them infallibly and the rows of a dump are not trusted.
+pub(crate) trait CheckedValue: Value {
+    fn check_bytes(data: &[u8]) -> anyhow::Result<()>;
+}
+
+macro_rules! fixed_width_checked_value {
+    ($($ty:ty),* $(,)?) => {
+        $(impl CheckedValue for $ty {
+            fn check_bytes(data: &[u8]) -> anyhow::Result<()> {
+                let width = <$ty as Value>::fixed_width().unwrap_or_default();
+                anyhow::ensure!(
+                    data.len() == width,
+                    "expected {width} bytes, got {}",
+                    data.len()
+                );
+                Ok(())
+            }
+        })*
+    };
+}
+
+fixed_width_checked_value!((), u8, u32, i64);
+
+fn dump_table<K: Key + 'static, V: Value + 'static>(
+    txn: &ReadTransaction,
+    def: TableDefinition<K, V>,
+) -> anyhow::Result<Option<TableDump>> {
+    let table = match txn.open_table(def) {
+        Ok(table) => table,
+        Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut rows = Vec::new();
+    for row in table.iter()? {
+        let (k, v) = row?;
+        rows.push((
+            K::as_bytes(&k.value()).as_ref().to_vec(),
+            V::as_bytes(&v.value()).as_ref().to_vec(),
+        ));
+    }
+    Ok(Some(TableDump {
+        name: def.name().to_string(),
+        rows,
+    }))
+}
+
+fn dump_multimap_table<K: Key + 'static, V: Key + 'static>(
+    txn: &ReadTransaction,
+    def: MultimapTableDefinition<K, V>,
+) -> anyhow::Result<Option<TableDump>> {
+    let table = match txn.open_multimap_table(def) {
+        Ok(table) => table,
+        Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut rows = Vec::new();
+    for row in table.iter()? {
+        let (k, values) = row?;
+        let key = K::as_bytes(&k.value()).as_ref().to_vec();
+        for v in values {
+            rows.push((key.clone(), V::as_bytes(&v?.value()).as_ref().to_vec()));
+        }
+    }
+    Ok(Some(TableDump {
+        name: def.name().to_string(),
+        rows,
+    }))
+}
+
+fn check_rows<K: CheckedValue, V: CheckedValue>(
+    name: &str,
+    dump: &DatabaseDump,
+) -> anyhow::Result<()> {
+    for (k, v) in dump.table(name).into_iter().flat_map(|t| t.rows.iter()) {
+        K::check_bytes(k).map_err(|e| anyhow::anyhow!("malformed key of {name}: {e}"))?;
+        V::check_bytes(v).map_err(|e| anyhow::anyhow!("malformed value of {name}: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Checks that every dumped row of a table can be decoded, so that a
+/// malformed dump fails before any write.
+fn check_table<K: Key + CheckedValue + 'static, V: CheckedValue + 'static>(
+    def: TableDefinition<K, V>,
+    dump: &DatabaseDump,
+) -> anyhow::Result<()> {
+    check_rows::<K, V>(def.name(), dump)
+}
+
+fn check_multimap_table<K: Key + CheckedValue + 'static, V: Key + CheckedValue + 'static>(
+    def: MultimapTableDefinition<K, V>,
+    dump: &DatabaseDump,
+) -> anyhow::Result<()> {
+    check_rows::<K, V>(def.name(), dump)
+}
+
+/// Replaces the rows of a table with the dumped ones, which must have been
+/// checked by [`check_table`]. Tables missing from the dump are left empty.
+fn restore_table<K: Key + 'static, V: Value + 'static>(
+    txn: &WriteTransaction,
+    def: TableDefinition<K, V>,
+    dump: &DatabaseDump,
+) -> anyhow::Result<()> {
+    txn.delete_table(def)?;
+    let mut table = txn.open_table(def)?;
+    for (k, v) in dump
+        .table(def.name())
+        .into_iter()
+        .flat_map(|t| t.rows.iter())
+    {
+        table.insert(K::from_bytes(k), V::from_bytes(v))?;
+    }
+    Ok(())
+}
+
+fn restore_multimap_table<K: Key + 'static, V: Key + 'static>(
+    txn: &WriteTransaction,
+    def: MultimapTableDefinition<K, V>,
+    dump: &DatabaseDump,
+) -> anyhow::Result<()> {
+    txn.delete_multimap_table(def)?;
+    let mut table = txn.open_multimap_table(def)?;
+    for (k, v) in dump
+        .table(def.name())
+        .into_iter()
+        .flat_map(|t| t.rows.iter())
+    {
+        table.insert(K::from_bytes(k), V::from_bytes(v))?;
+    }
+    Ok(())
+}
+
+macro_rules! schema_tables {
+    (
+        $(#[$meta:meta])*
+        $dump:ident, $check:ident, $restore:ident,
+        tables: [$($table:path),* $(,)?],
+        multimap_tables: [$($multimap:path),* $(,)?] $(,)?
+    ) => {
+        $(#[$meta])*
+        fn $dump(txn: &ReadTransaction) -> anyhow::Result<Vec<TableDump>> {
+            let mut ret = Vec::new();
+            $(ret.extend(dump_table(txn, $table)?);)*
+            $(ret.extend(dump_multimap_table(txn, $multimap)?);)*
+            Ok(ret)
+        }
+
+        fn $check(dump: &DatabaseDump) -> anyhow::Result<()> {
+            $(check_table($table, dump)?;)*
+            $(check_multimap_table($multimap, dump)?;)*
+            Ok(())
+        }
+
+        fn $restore(txn: &WriteTransaction, dump: &DatabaseDump) -> anyhow::Result<()> {
+            $(restore_table(txn, $table, dump)?;)*
+            $(restore_multimap_table(txn, $multimap, dump)?;)*
+            Ok(())
+        }
+    };
+}
+
+schema_tables!(
+    // v2 databases are only dumped by tests, to build old dumps.
+    #[cfg_attr(not(test), allow(dead_code))]
+    dump_v2_tables,
+    check_v2_tables,
+    restore_v2_tables,
+    tables: [
+        v2::TABLE_SCHEMA_VERSION,
+        v2::TABLE_ID_ALLOC,
+        v2::TABLE_PLAYLIST,
+        v2::TABLE_MUSIC,
+        v2::TABLE_MUSIC_BY_LOC,
+        v2::TABLE_STORAGE,
+        v2::TABLE_PREFERENCE,
+        v2::TABLE_BLOB,
+    ],
+    multimap_tables: [
+        v2::TABLE_PLAYLIST_MUSIC,
+        v2::TABLE_MUSIC_PLAYLIST,
+        v2::TABLE_STORAGE_MUSIC,
+    ],
+);
+
+schema_tables!(
+    // Dumps of v3 and v4 are only restored, to be upgraded.
+    #[allow(dead_code)]
+    dump_v3_tables,
+    check_v3_tables,
+    restore_v3_tables,
+    tables: [
+        v2::TABLE_SCHEMA_VERSION,
+        v3::TABLE_ID_ALLOC,
+        v3::TABLE_PLAYLIST,
+        v3::TABLE_MUSIC,
+        v3::TABLE_MUSIC_BY_LOC,
+        v3::TABLE_STORAGE,
+        v3::TABLE_PREFERENCE,
+        v3::TABLE_BLOB,
+    ],
+    multimap_tables: [
+        v3::TABLE_PLAYLIST_MUSIC,
+        v3::TABLE_MUSIC_PLAYLIST,
+        v3::TABLE_STORAGE_MUSIC,
+    ],
+);
+
+schema_tables!(
+    #[allow(dead_code)]
+    dump_v4_tables,
+    check_v4_tables,
+    restore_v4_tables,
+    tables: [
+        v2::TABLE_SCHEMA_VERSION,
+        v4::TABLE_ID_ALLOC,
+        v4::TABLE_PLAYLIST,
+        v4::TABLE_MUSIC,
+        v4::TABLE_MUSIC_BY_LOC,
+        v4::TABLE_STORAGE,
+        v4::TABLE_PREFERENCE,
+        v4::TABLE_BLOB,
+    ],
+    multimap_tables: [
+        v4::TABLE_PLAYLIST_MUSIC,
+        v4::TABLE_MUSIC_PLAYLIST,
+        v4::TABLE_STORAGE_MUSIC,
+    ],
+);
+
+schema_tables!(
+    // v5 databases are only dumped by tests, to build old dumps.
+    #[cfg_attr(not(test), allow(dead_code))]
+    dump_v5_tables,
+    check_v5_tables,
+    restore_v5_tables,
+    tables: [
+        v2::TABLE_SCHEMA_VERSION,
+        v5::TABLE_ID_ALLOC,
+        v5::TABLE_PLAYLIST,
+        v5::TABLE_MUSIC,
+        v5::TABLE_MUSIC_BY_LOC,
+        v5::TABLE_STORAGE,
+        v5::TABLE_PREFERENCE,
+        v5::TABLE_BLOB,
+    ],
+    multimap_tables: [
+        v5::TABLE_PLAYLIST_MUSIC,
+        v5::TABLE_MUSIC_PLAYLIST,
+        v5::TABLE_STORAGE_MUSIC,
+    ],
+);
+
+schema_tables!(
+    dump_v6_tables,
+    check_v6_tables,
+    restore_v6_tables,
+    tables: [
+        v2::TABLE_SCHEMA_VERSION,
+        v6::TABLE_ID_ALLOC,
+        v6::TABLE_PLAYLIST,
+        v6::TABLE_MUSIC,
+        v6::TABLE_MUSIC_BY_LOC,
+        v6::TABLE_STORAGE,
+        v6::TABLE_PREFERENCE,
+        v6::TABLE_BLOB,
+        v6::TABLE_ARTIST,
+        v6::TABLE_ARTIST_BY_NAME,
+        v6::TABLE_ALBUM,
+        v6::TABLE_ALBUM_BY_KEY,
+        v6::TABLE_GENRE,
+        v6::TABLE_GENRE_BY_NAME,
+        v6::TABLE_MUSIC_FINGERPRINT,
+        v6::TABLE_SEARCH_DOCUMENT,
+        v6::TABLE_SMART_PLAYLIST,
+        v6::TABLE_PLAYLIST_FOLDER,
+        v6::TABLE_SYNC_CONFIG,
+        v6::TABLE_SYNC_FIELD,
+        v6::TABLE_PLAYLIST_SYNC_ID,
+        v6::TABLE_PLAY_EVENT,
+        v6::TABLE_MUSIC_PLAY_STATS,
+        v6::TABLE_PLAYLIST_PLAY_STATS,
+        v6::TABLE_SCROBBLE_CONFIG,
+        v6::TABLE_SCROBBLE_QUEUE,
+        v6::TABLE_MUSIC_FAVORITE,
+        v6::TABLE_MUSIC_RATING,
+        v6::TABLE_PLAY_QUEUE,
+        v6::TABLE_PLAY_QUEUE_CURSOR,
+        v6::TABLE_BLOB_REF,
+    ],
+    multimap_tables: [
+        v6::TABLE_PLAYLIST_MUSIC,
+        v6::TABLE_MUSIC_PLAYLIST,
+        v6::TABLE_STORAGE_MUSIC,
+        v6::TABLE_ARTIST_MUSIC,
+        v6::TABLE_ALBUM_MUSIC,
+        v6::TABLE_GENRE_MUSIC,
+        v6::TABLE_SEARCH_TERM,
+        v6::TABLE_RATING_MUSIC,
+        v6::TABLE_MUSIC_USER_TAG,
+        v6::TABLE_USER_TAG_MUSIC,
+    ],
+);
+
+fn read_schema_version(txn: &ReadTransaction) -> anyhow::Result<u32> {
+    let version = match txn.open_table(v2::TABLE_SCHEMA_VERSION) {
+        Ok(table) => table.get(())?.map(|v| v.value()).unwrap_or_default(),
+        Err(TableError::TableDoesNotExist(_)) => 0,
+        Err(e) => return Err(e.into()),
+    };
+    Ok(version)
+}
+
+/// Dumps every table of a database at the current schema version.
+pub fn dump_database(db: &redb::Database) -> anyhow::Result<DatabaseDump> {
+    let txn = db.begin_read()?;
+    let schema_version = read_schema_version(&txn)?;
+    anyhow::ensure!(
+        schema_version == CURRENT_SCHEMA_VERSION,
+        "database is at schema v{schema_version}, expected v{CURRENT_SCHEMA_VERSION}"
+    );
+    Ok(DatabaseDump {
+        schema_version,
+        tables: dump_v6_tables(&txn)?,
+    })
+}
+
+/// Checks that every row of a dump can be decoded at the schema version of
+/// the dump. Dumps come from archives, which are not trusted.
+pub fn check_dump(dump: &DatabaseDump) -> anyhow::Result<()> {
+    match dump.schema_version {
+        2 => check_v2_tables(dump),
+        3 => check_v3_tables(dump),
+        4 => check_v4_tables(dump),
+        5 => check_v5_tables(dump),
+        CURRENT_SCHEMA_VERSION => check_v6_tables(dump),
+        v => anyhow::bail!("dumps of schema v{v} cannot be restored"),
+    }
+}
+
+/// Brings a dump to the current schema version. Dumps of v2 and later are
+/// upgraded by the same upgraders as an old install.
+pub fn upgrade_dump(dump: DatabaseDump) -> anyhow::Result<DatabaseDump> {
+    type Restore = fn(&WriteTransaction, &DatabaseDump) -> anyhow::Result<()>;
+    type Upgrade = fn(&Arc<redb::Database>) -> anyhow::Result<()>;
+
+    check_dump(&dump)?;
+    let restore: Restore = match dump.schema_version {
+        CURRENT_SCHEMA_VERSION => return Ok(dump),
+        2 => restore_v2_tables,
+        3 => restore_v3_tables,
+        4 => restore_v4_tables,
+        5 => restore_v5_tables,
+        v => anyhow::bail!("dumps of schema v{v} cannot be restored"),
+    };
+    let db = Arc::new(redb::Database::builder().create_with_backend(InMemoryBackend::new())?);
+    {
+        let txn = db.begin_write()?;
+        restore(&txn, &dump)?;
+        txn.commit()?;
+    }
+    let upgraders: [Upgrade; 4] = [
+        v3::upgrade_v2_to_v3,
+        v4::upgrade_v3_to_v4,
+        v5::upgrade_v4_to_v5,
+        v6::upgrade_v5_to_v6,
+    ];
+    for upgrade in &upgraders[(dump.schema_version - 2) as usize..] {
+        upgrade(&db)?;
+    }
+    dump_database(&db)
+}
+
+/// Replaces the content of a database with a dump at the current schema
+/// version, in one transaction.
+pub fn restore_database(db: &redb::Database, dump: &DatabaseDump) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        dump.schema_version == CURRENT_SCHEMA_VERSION,
+        "dump is at schema v{}, expected v{CURRENT_SCHEMA_VERSION}",
+        dump.schema_version
+    );
+    check_v6_tables(dump)?;
+    let txn = db.begin_write()?;
+    restore_v6_tables(&txn, dump)?;
+    txn.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use redb::ReadableTableMetadata;
+
+    use crate::{v2, v5, v6};
+
+    use super::{
+        DatabaseDump, TableDump, check_dump, dump_database, restore_database, upgrade_dump,
+    };
+
+    fn memory_db() -> redb::Database {
+        redb::Database::builder()
+            .create_with_backend(redb::backends::InMemoryBackend::new())
+            .unwrap()
+    }
+
+    #[test]
+    fn restores_v5_dump_into_current_schema() {
+        let v5_db = memory_db();
+        let loc = crate::StorageEntryLoc {
+            storage_id: crate::StorageId::wrap(1),
+            path: "/Music/a.flac".to_string(),
+        };
+        {
+            let txn = v5_db.begin_write().unwrap();
+            txn.open_table(v5::TABLE_MUSIC)
+                .unwrap()
+                .insert(
+                    &crate::MusicId::wrap(3),
+                    &v5::MusicModel {
+                        id: crate::MusicId::wrap(3),
+                        loc: loc.clone(),
+                        title: "a".to_string(),
+                        duration: None,
+                        cover: None,
+                        lyric: None,
+                        lyric_default: true,
+                        order: vec![1],
+                    },
+                )
+                .unwrap();
+            txn.open_multimap_table(v5::TABLE_STORAGE_MUSIC)
+                .unwrap()
+                .insert(&crate::StorageId::wrap(1), &crate::MusicId::wrap(3))
+                .unwrap();
+            txn.open_table(v2::TABLE_SCHEMA_VERSION)
+                .unwrap()
+                .insert((), 5)
+                .unwrap();
+            txn.commit().unwrap();
+        }
+        let dump = DatabaseDump {
+            schema_version: 5,
+            tables: super::dump_v5_tables(&v5_db.begin_read().unwrap()).unwrap(),
+        };
+
+        let dump = upgrade_dump(dump).unwrap();
+        assert_eq!(6, dump.schema_version);
+        assert_eq!(1, dump.count(v6::TABLE_MUSIC));
+
+        let db = memory_db();
+        {
+            let txn = db.begin_write().unwrap();
+            txn.open_table(v6::TABLE_PLAYLIST)
+                .unwrap()
+                .insert(
+                    &crate::PlaylistId::wrap(9),
+                    &v6::PlaylistModel {
+                        id: crate::PlaylistId::wrap(9),
+                        title: "stale".to_string(),
+                        created_time: 0,
+                        picture: None,
+                        order: vec![1],
+                    },
+                )
+                .unwrap();
+            txn.commit().unwrap();
+        }
+        restore_database(&db, &dump).unwrap();
+
+        let txn = db.begin_read().unwrap();
+        let music = txn
+            .open_table(v6::TABLE_MUSIC)
+            .unwrap()
+            .get(crate::MusicId::wrap(3))
+            .unwrap()
+            .unwrap()
+            .value();
+        assert_eq!(loc, music.loc);
+        assert_eq!(
+            0,
+            txn.open_table(v6::TABLE_PLAYLIST).unwrap().len().unwrap()
+        );
+        let musics: Vec<_> = txn
+            .open_multimap_table(v6::TABLE_STORAGE_MUSIC)
+            .unwrap()
+            .get(crate::StorageId::wrap(1))
+            .unwrap()
+            .map(|v| v.unwrap().value())
+            .collect();
+        assert_eq!(vec![crate::MusicId::wrap(3)], musics);
+        drop(txn);
+
+        let again = dump_database(&db).unwrap();
+        assert_eq!(1, again.count(v6::TABLE_MUSIC));
+    }
+
+    #[test]
+    fn upgrades_v2_dump_through_every_schema() {
+        let v2_db = memory_db();
+        {
+            let txn = v2_db.begin_write().unwrap();
+            txn.open_table(v2::TABLE_MUSIC)
+                .unwrap()
+                .insert(
+                    &v2::MusicId::wrap(3),
+                    &v2::MusicModel {
+                        id: v2::MusicId::wrap(3),
+                        loc: v2::StorageEntryLoc {
+                            storage_id: v2::StorageId::wrap(1),
+                            path: "/Music/a.flac".to_string(),
+                        },
+                        title: "a".to_string(),
+                        duration: None,
+                        cover: None,
+                        lyric: None,
+                        lyric_default: true,
+                    },
+                )
+                .unwrap();
+            txn.open_table(v2::TABLE_SCHEMA_VERSION)
+                .unwrap()
+                .insert((), 2)
+                .unwrap();
+            txn.commit().unwrap();
+        }
+        let dump = DatabaseDump {
+            schema_version: 2,
+            tables: super::dump_v2_tables(&v2_db.begin_read().unwrap()).unwrap(),
+        };
+
+        let dump = upgrade_dump(dump).unwrap();
+        assert_eq!(6, dump.schema_version);
+        assert_eq!(1, dump.count(v6::TABLE_MUSIC));
+
+        let db = memory_db();
+        restore_database(&db, &dump).unwrap();
+        let music = db
+            .begin_read()
+            .unwrap()
+            .open_table(v6::TABLE_MUSIC)
+            .unwrap()
+            .get(crate::MusicId::wrap(3))
+            .unwrap()
+            .unwrap()
+            .value();
+        assert_eq!("/Music/a.flac", music.loc.path);
+        assert_eq!("a", music.title);
+    }
+
+    #[test]
+    fn rejects_malformed_rows_before_writing() {
+        let playlist = v6::PlaylistModel {
+            id: crate::PlaylistId::wrap(9),
+            title: "kept".to_string(),
+            created_time: 0,
+            picture: None,
+            order: vec![1],
+        };
+        let value = postcard::to_allocvec(&playlist).unwrap();
+        let key = postcard::to_allocvec(&playlist.id).unwrap();
+        let dump = |rows: Vec<(Vec<u8>, Vec<u8>)>, name: &str| DatabaseDump {
+            schema_version: 6,
+            tables: vec![TableDump {
+                name: name.to_string(),
+                rows,
+            }],
+        };
+
+        let truncated = dump(
+            vec![(key.clone(), value[..value.len() / 2].to_vec())],
+            "v6_playlist",
+        );
+        assert!(check_dump(&truncated).is_err());
+        assert!(upgrade_dump(truncated.clone()).is_err());
+        let db = memory_db();
+        restore_database(
+            &db,
+            &dump(vec![(key.clone(), value.clone())], "v6_playlist"),
+        )
+        .unwrap();
+        assert!(restore_database(&db, &truncated).is_err());
+        let txn = db.begin_read().unwrap();
+        let kept = txn
+            .open_table(v6::TABLE_PLAYLIST)
+            .unwrap()
+            .get(crate::PlaylistId::wrap(9))
+            .unwrap()
+            .unwrap()
+            .value();
+        assert_eq!("kept", kept.title);
+
+        let wrong_width = dump(vec![(key, vec![1, 2])], "v6_music_favorite");
+        assert!(check_dump(&wrong_width).is_err());
+        let wrong_version = DatabaseDump {
+            schema_version: 5,
+            tables: vec![TableDump {
+                name: "v5_playlist".to_string(),
+                rows: vec![(vec![0xff; 12], value)],
+            }],
+        };
+        assert!(upgrade_dump(wrong_version).is_err());
+    }
+}
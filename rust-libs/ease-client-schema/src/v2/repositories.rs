@@ -49,6 +49,16 @@ where
     }
 }
 
+impl<T> crate::dump::CheckedValue for BinSerde<T>
+where
+    T: Debug + BinSerdeTN + bitcode::Encode + for<'a> bitcode::Decode<'a>,
+{
+    fn check_bytes(data: &[u8]) -> anyhow::Result<()> {
+        bitcode::decode::<T>(data)?;
+        Ok(())
+    }
+}
+
 impl<T> redb::Key for BinSerde<T>
 where
     T: Debug + BinSerdeTN + bitcode::Encode + bitcode::DecodeOwned + Ord,
@@ -52,6 +52,8 @@ macro_rules! define_id {
     };
 }
 
+pub(crate) use define_id;
+
 define_id!(StorageId);
 define_id!(BlobId);
 define_id!(MusicId);
@@ -47,6 +47,16 @@ where
     }
 }
 
+impl<T> crate::dump::CheckedValue for BinSerde<T>
+where
+    T: Debug + Serialize + BinSerdeTN + for<'a> Deserialize<'a>,
+{
+    fn check_bytes(data: &[u8]) -> anyhow::Result<()> {
+        postcard::from_bytes::<T>(data)?;
+        Ok(())
+    }
+}
+
 impl<T> redb::Key for BinSerde<T>
 where
     T: Debug + Serialize + BinSerdeTN + for<'a> Deserialize<'a> + Ord,
@@ -4,6 +4,5 @@ mod repositories;
 mod upgrader;
 
 pub use models::*;
-pub use objects::*;
 pub use repositories::*;
 pub use upgrader::*;
@@ -1,4 +1 @@
-pub use crate::v2::{
-    BlobId, MusicId, PlayMode, PlaylistId, StorageEntryLoc, StorageId, StorageType,
-};
-pub use crate::v3::DataSourceKey;
+pub use crate::v2::{BlobId, MusicId, PlaylistId, StorageEntryLoc, StorageId, StorageType};
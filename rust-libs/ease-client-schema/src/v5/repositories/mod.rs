@@ -1,5 +1,4 @@
 mod bin;
 mod defs;
 
-pub use bin::*;
 pub use defs::*;
@@ -218,7 +218,7 @@ mod tests {
         let storage = txn
             .open_table(v5::TABLE_STORAGE)
             .unwrap()
-            .get(v5::StorageId::wrap(7))
+            .get(crate::StorageId::wrap(7))
             .unwrap()
             .unwrap()
             .value();
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+use super::super::objects::{MusicId, PlaylistId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+pub enum PlayEventKind {
+    Started,
+    /// The music played to its end.
+    Completed,
+    /// The music was left before its end.
+    Skipped,
+}
+
+/// Play events are ordered by time, and by the order they were recorded in
+/// within the same millisecond.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PlayEventKey {
+    pub time: i64,
+    pub id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayEventModel {
+    pub music: MusicId,
+    /// Playlist the music was played from, if any.
+    pub playlist: Option<PlaylistId>,
+    pub kind: PlayEventKind,
+    /// Position in the music, in milliseconds.
+    pub position: u64,
+}
+
+/// Statistics kept up to date as play events are recorded, for a music or a
+/// playlist.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlayStatsModel {
+    pub play_count: u32,
+    pub skip_count: u32,
+    /// Time of the latest play event, in milliseconds since the unix epoch,
+    /// or 0.
+    pub last_played: i64,
+    /// Listening time in milliseconds.
+    pub listened: u64,
+    /// Position the current play started at, until it completes or is
+    /// skipped.
+    pub started_at: Option<u64>,
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DbKeyAlloc {
+    Playlist,
+    Music,
+    Storage,
+    Artist,
+    Album,
+    Genre,
+    PlayEvent,
+}
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+use super::super::objects::{AlbumId, ArtistId, GenreId};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtistModel {
+    pub id: ArtistId,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlbumModel {
+    pub id: AlbumId,
+    pub title: String,
+    pub artist: Option<ArtistId>,
+    pub year: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenreModel {
+    pub id: GenreId,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AlbumKey {
+    pub title: String,
+    pub artist: Option<ArtistId>,
+}
@@ -0,0 +1,23 @@
+mod history;
+mod key;
+mod library;
+mod music;
+mod playlist;
+mod preference;
+mod queue;
+mod scrobble;
+mod search;
+mod storage;
+mod sync;
+
+pub use history::*;
+pub use key::*;
+pub use library::*;
+pub use music::*;
+pub use playlist::*;
+pub use preference::*;
+pub use queue::*;
+pub use scrobble::*;
+pub use search::*;
+pub use storage::*;
+pub use sync::*;
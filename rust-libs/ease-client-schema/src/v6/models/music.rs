@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::super::objects::{AlbumId, ArtistId, BlobId, GenreId, MusicId, StorageEntryLoc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct AudioStreamInfo {
+    pub sample_rate: u32,
+    pub channels: u32,
+    pub bit_depth: Option<u32>,
+    /// Average bitrate in kbps.
+    pub bitrate: Option<u32>,
+}
+
+/// ReplayGain values relative to the -18 LUFS reference level.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ReplayGainModel {
+    pub track_gain: f64,
+    /// Linear sample or true peak, 1.0 being full scale.
+    pub track_peak: Option<f64>,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
+    /// Whether the album values were read from tags rather than derived from
+    /// the tracks of the album.
+    pub album_from_tags: bool,
+}
+
+/// Chromaprint fingerprint of the decoded audio. An empty fingerprint means
+/// the music could not be decoded and should not be fingerprinted again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MusicFingerprintModel {
+    pub fingerprint: Vec<u32>,
+    /// Id of the chromaprint algorithm the fingerprint was computed with.
+    pub algorithm: u8,
+}
+
+/// Size, modification time and etag of the file of a music when it was last
+/// listed. Storages report only some of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct MusicFileStamp {
+    pub size: Option<u64>,
+    /// Milliseconds since the unix epoch.
+    pub modified: Option<i64>,
+    pub etag: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+pub enum MusicFileState {
+    #[default]
+    Present,
+    /// The file was not found by the last rescan.
+    Missing,
+    /// The file differs from the one that was probed.
+    Changed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MusicModel {
+    pub id: MusicId,
+    pub loc: StorageEntryLoc,
+    pub title: String,
+    pub duration: Option<Duration>,
+    pub cover: Option<BlobId>,
+    pub cover_probed: bool,
+    pub lyric: Option<StorageEntryLoc>,
+    pub lyric_default: bool,
+    pub order: Vec<u32>,
+    pub artists: Vec<ArtistId>,
+    pub album: Option<AlbumId>,
+    pub genres: Vec<GenreId>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub stream_info: Option<AudioStreamInfo>,
+    pub stream_probed: bool,
+    pub replay_gain: Option<ReplayGainModel>,
+    pub loudness_analyzed: bool,
+    /// Time the music was added in milliseconds since the unix epoch, or 0
+    /// for musics added before the time was recorded.
+    pub added_time: i64,
+    pub file_stamp: Option<MusicFileStamp>,
+    pub file_state: MusicFileState,
+}
@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::super::objects::{MusicId, PlaylistId, StorageEntryLoc, StorageId};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaylistModel {
+    pub id: PlaylistId,
+    pub title: String,
+    pub created_time: i64,
+    pub picture: Option<StorageEntryLoc>,
+    pub order: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PlaylistMusicModel {
+    pub music_id: MusicId,
+    pub order: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+pub enum SmartRuleCombinator {
+    All,
+    Any,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+pub enum SmartTagField {
+    Artist,
+    Album,
+    Genre,
+}
+
+/// A condition on a music. Ranges are inclusive and an unset bound is open.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, uniffi::Enum)]
+pub enum SmartCondition {
+    Storage {
+        id: StorageId,
+    },
+    PathPrefix {
+        prefix: String,
+    },
+    PathGlob {
+        pattern: String,
+    },
+    TitleContains {
+        text: String,
+    },
+    /// Matches when any tag of `field`, or of any field when unset, contains
+    /// `text`.
+    TagContains {
+        field: Option<SmartTagField>,
+        text: String,
+    },
+    DurationRange {
+        min: Option<Duration>,
+        max: Option<Duration>,
+    },
+    /// Bounds of the time the music was added, since the unix epoch.
+    AddedTimeRange {
+        after: Option<Duration>,
+        before: Option<Duration>,
+    },
+    PlayCountRange {
+        min: Option<u32>,
+        max: Option<u32>,
+    },
+    RatingRange {
+        min: Option<u8>,
+        max: Option<u8>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, uniffi::Record)]
+pub struct SmartConditionGroup {
+    pub combinator: SmartRuleCombinator,
+    pub conditions: Vec<SmartCondition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+pub enum SmartSortField {
+    Title,
+    Path,
+    Duration,
+    AddedTime,
+    PlayCount,
+    Rating,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct SmartSort {
+    pub field: SmartSortField,
+    pub descending: bool,
+}
+
+/// Rule of a smart playlist, stored next to its `PlaylistModel`. The musics
+/// matching the groups, combined with `combinator`, are sorted and then
+/// truncated to `limit`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, uniffi::Record)]
+pub struct SmartPlaylistModel {
+    pub combinator: SmartRuleCombinator,
+    pub groups: Vec<SmartConditionGroup>,
+    pub sort: SmartSort,
+    pub limit: Option<u32>,
+}
+
+/// What a folder refresh does with musics whose file is gone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, uniffi::Enum)]
+pub enum FolderRemovedPolicy {
+    Remove,
+    Mark,
+}
+
+/// Directory a playlist is bound to. Refreshing the playlist adds the music
+/// files found under `dir` and drops or marks the ones that are gone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, uniffi::Record)]
+pub struct PlaylistFolderModel {
+    pub dir: StorageEntryLoc,
+    pub recursive: bool,
+    /// Glob matched against paths relative to `dir`.
+    pub glob: Option<String>,
+    pub on_removed: FolderRemovedPolicy,
+    /// Refreshes in the background once this long has passed since the last
+    /// refresh.
+    pub refresh_interval: Option<Duration>,
+    /// Time of the last refresh in milliseconds since the unix epoch.
+    pub last_refreshed: i64,
+    /// Musics kept in the playlist although their file is gone.
+    pub missing: Vec<MusicId>,
+}
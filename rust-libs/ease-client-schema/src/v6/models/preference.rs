@@ -0,0 +1,8 @@
+use serde::{Deserialize, Serialize};
+
+use crate::PlayMode;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PreferenceModel {
+    pub playmode: PlayMode,
+}
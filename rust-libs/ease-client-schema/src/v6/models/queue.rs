@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+use super::super::objects::{MusicId, PlaylistId};
+
+/// How musics are weighted when the queue is shuffled. Heavier musics tend
+/// to come earlier in the order.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, uniffi::Enum,
+)]
+pub enum ShuffleWeight {
+    #[default]
+    Uniform,
+    Rating,
+    PlayCount,
+}
+
+/// Musics of the playback queue. The cursor is kept apart in
+/// [`PlayQueueCursorModel`], so saving the position does not rewrite the
+/// whole queue.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayQueueModel {
+    /// Playlist the queue was started from.
+    pub playlist: Option<PlaylistId>,
+    pub musics: Vec<MusicId>,
+    /// Set while the queue is shuffled.
+    pub shuffle_seed: Option<u64>,
+    pub shuffle_weight: ShuffleWeight,
+    /// Indexes of `musics` in shuffled play order, empty when not shuffled.
+    pub order: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayQueueCursorModel {
+    /// Index of the current music in `musics`.
+    pub index: u32,
+    /// Position in the current music in milliseconds.
+    pub position: u64,
+}
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+
+use super::super::objects::MusicId;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, uniffi::Enum,
+)]
+pub enum ScrobbleService {
+    ListenBrainz,
+    LastFm,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct ListenBrainzConfig {
+    /// Root of the API, such as `https://api.listenbrainz.org`.
+    pub base_url: String,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct LastFmConfig {
+    /// Endpoint of the API, such as `https://ws.audioscrobbler.com/2.0/`.
+    pub base_url: String,
+    pub api_key: String,
+    pub api_secret: String,
+    /// Session key of the user, from the authentication flow of the client.
+    pub session_key: String,
+}
+
+/// Services listens are submitted to. An unset service is off.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, uniffi::Record)]
+pub struct ScrobbleConfigModel {
+    pub listenbrainz: Option<ListenBrainzConfig>,
+    pub lastfm: Option<LastFmConfig>,
+}
+
+/// A listen waiting for or done with submission. A music is listened at
+/// most once per second, so the same listen recorded twice is kept once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ScrobbleKey {
+    pub service: ScrobbleService,
+    /// Time the listen started, in seconds since the unix epoch.
+    pub listened_at: i64,
+    pub music: MusicId,
+}
+
+/// Tags of the music when it was listened, so the listen can be submitted
+/// after the music is changed or removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScrobbleModel {
+    pub title: String,
+    pub artist: String,
+    pub album: Option<String>,
+    /// Duration in milliseconds.
+    pub duration: Option<u64>,
+    /// Failed submissions so far.
+    pub attempts: u32,
+    /// Submitted listens are kept for a while to drop listens recorded again.
+    pub submitted: bool,
+}
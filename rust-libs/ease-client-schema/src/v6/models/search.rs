@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+use super::super::objects::MusicId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum SearchField {
+    Title,
+    Artist,
+    Album,
+    Genre,
+    Path,
+    Lyric,
+}
+
+/// A music containing an indexed term in one of its fields.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SearchPostingModel {
+    pub music_id: MusicId,
+    pub field: SearchField,
+    /// Occurrences of the term in the field.
+    pub count: u32,
+}
+
+/// The texts a music is indexed with, kept so that its postings can be
+/// removed when the music changes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SearchDocumentModel {
+    pub fields: Vec<(SearchField, String)>,
+}
@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+
+use super::super::objects::StorageEntryLoc;
+
+/// Logical time of a sync op. `time` is in milliseconds since the unix epoch
+/// but never behind an op the device has seen, and the device id breaks
+/// ties, so stamps order the same on every device.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SyncStamp {
+    pub time: i64,
+    pub device: String,
+}
+
+/// A music as known across devices. Storage ids are local to a device, so
+/// storages are identified by their type and address instead.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SyncMusicKey {
+    pub storage: String,
+    pub path: String,
+}
+
+/// A field merged across devices. Playlists are identified by an id given by
+/// the device that created them.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum SyncTarget {
+    Playlist { id: String },
+    PlaylistTitle { id: String },
+    PlaylistOrder { id: String },
+    PlaylistMusic { id: String, music: SyncMusicKey },
+    MusicLyric { music: SyncMusicKey },
+    MusicFavorite { music: SyncMusicKey },
+    MusicRating { music: SyncMusicKey },
+    MusicUserTags { music: SyncMusicKey },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncValue {
+    /// Creation time of the playlist, or unset once it is removed.
+    Playlist {
+        created_time: Option<i64>,
+    },
+    Title {
+        title: String,
+    },
+    Order {
+        order: Vec<u32>,
+    },
+    /// Order of the music in the playlist, or unset once it is removed from
+    /// the playlist.
+    PlaylistMusic {
+        order: Option<Vec<u32>>,
+    },
+    Lyric {
+        lyric: Option<SyncMusicKey>,
+    },
+    /// Time the music was favorited, or unset once it is not a favorite.
+    Favorite {
+        favorite_time: Option<i64>,
+    },
+    /// From 0, unrated, to 5.
+    Rating {
+        rating: u8,
+    },
+    UserTags {
+        tags: Vec<String>,
+    },
+}
+
+/// Latest value of a field and the stamp of the op that wrote it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncFieldModel {
+    pub stamp: SyncStamp,
+    pub value: SyncValue,
+}
+
+/// Sync state of this device. It is kept when sync is turned off, so the
+/// device id and the clock survive until it is turned on again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncConfigModel {
+    pub device_id: String,
+    /// Folder the op logs are published to, unset when sync is off.
+    pub dir: Option<StorageEntryLoc>,
+    /// Time of the latest op issued or seen, in milliseconds since the unix
+    /// epoch.
+    pub clock: i64,
+    /// Time of the last successful sync, or 0.
+    pub last_synced: i64,
+}
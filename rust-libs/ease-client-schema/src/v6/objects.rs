@@ -0,0 +1,10 @@
+use crate::v2::define_id;
+
+pub use crate::v2::{
+    BlobId, MusicId, PlayMode, PlaylistId, StorageEntryLoc, StorageId, StorageType,
+};
+pub use crate::v3::DataSourceKey;
+
+define_id!(ArtistId);
+define_id!(AlbumId);
+define_id!(GenreId);
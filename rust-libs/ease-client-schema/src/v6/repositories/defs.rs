@@ -0,0 +1,251 @@
+use redb::{MultimapTableDefinition, TableDefinition};
+
+use crate::v2;
+
+use super::super::{
+    models::{
+        AlbumKey, AlbumModel, ArtistModel, DbKeyAlloc, GenreModel, MusicFingerprintModel,
+        MusicModel, PlayEventKey, PlayEventModel, PlayQueueCursorModel, PlayQueueModel,
+        PlayStatsModel, PlaylistFolderModel, PlaylistModel, PlaylistMusicModel, PreferenceModel,
+        ScrobbleConfigModel, ScrobbleKey, ScrobbleModel, SearchDocumentModel, SearchPostingModel,
+        SmartPlaylistModel, StorageModel, SyncConfigModel, SyncFieldModel, SyncTarget,
+    },
+    objects::{
+        AlbumId, ArtistId, BlobId, GenreId, MusicId, PlaylistId, StorageEntryLoc, StorageId,
+    },
+};
+
+use super::bin::{BinSerde, BinSerdeTN};
+
+impl BinSerdeTN for DbKeyAlloc {
+    const NAME: &'static str = "DbKeyAlloc";
+}
+
+impl BinSerdeTN for PlaylistId {
+    const NAME: &'static str = "PlaylistId";
+}
+
+impl BinSerdeTN for MusicId {
+    const NAME: &'static str = "MusicId";
+}
+
+impl BinSerdeTN for StorageId {
+    const NAME: &'static str = "StorageId";
+}
+
+impl BinSerdeTN for BlobId {
+    const NAME: &'static str = "BlobId";
+}
+
+impl BinSerdeTN for ArtistId {
+    const NAME: &'static str = "ArtistId";
+}
+
+impl BinSerdeTN for AlbumId {
+    const NAME: &'static str = "AlbumId";
+}
+
+impl BinSerdeTN for GenreId {
+    const NAME: &'static str = "GenreId";
+}
+
+impl BinSerdeTN for String {
+    const NAME: &'static str = "String";
+}
+
+impl BinSerdeTN for StorageEntryLoc {
+    const NAME: &'static str = "StorageEntryLoc";
+}
+
+impl BinSerdeTN for MusicModel {
+    const NAME: &'static str = "MusicModel";
+}
+
+impl BinSerdeTN for MusicFingerprintModel {
+    const NAME: &'static str = "MusicFingerprintModel";
+}
+
+impl BinSerdeTN for SearchPostingModel {
+    const NAME: &'static str = "SearchPostingModel";
+}
+
+impl BinSerdeTN for SearchDocumentModel {
+    const NAME: &'static str = "SearchDocumentModel";
+}
+
+impl BinSerdeTN for PlaylistModel {
+    const NAME: &'static str = "PlaylistModel";
+}
+
+impl BinSerdeTN for SmartPlaylistModel {
+    const NAME: &'static str = "SmartPlaylistModel";
+}
+
+impl BinSerdeTN for PlaylistFolderModel {
+    const NAME: &'static str = "PlaylistFolderModel";
+}
+
+impl BinSerdeTN for PlaylistMusicModel {
+    const NAME: &'static str = "PlaylistMusicModel";
+}
+
+impl BinSerdeTN for PreferenceModel {
+    const NAME: &'static str = "PreferenceModel";
+}
+
+impl BinSerdeTN for StorageModel {
+    const NAME: &'static str = "StorageModel";
+}
+
+impl BinSerdeTN for ArtistModel {
+    const NAME: &'static str = "ArtistModel";
+}
+
+impl BinSerdeTN for AlbumModel {
+    const NAME: &'static str = "AlbumModel";
+}
+
+impl BinSerdeTN for AlbumKey {
+    const NAME: &'static str = "AlbumKey";
+}
+
+impl BinSerdeTN for SyncConfigModel {
+    const NAME: &'static str = "SyncConfigModel";
+}
+
+impl BinSerdeTN for SyncTarget {
+    const NAME: &'static str = "SyncTarget";
+}
+
+impl BinSerdeTN for SyncFieldModel {
+    const NAME: &'static str = "SyncFieldModel";
+}
+
+impl BinSerdeTN for PlayEventKey {
+    const NAME: &'static str = "PlayEventKey";
+}
+
+impl BinSerdeTN for PlayEventModel {
+    const NAME: &'static str = "PlayEventModel";
+}
+
+impl BinSerdeTN for PlayStatsModel {
+    const NAME: &'static str = "PlayStatsModel";
+}
+
+impl BinSerdeTN for ScrobbleConfigModel {
+    const NAME: &'static str = "ScrobbleConfigModel";
+}
+
+impl BinSerdeTN for ScrobbleKey {
+    const NAME: &'static str = "ScrobbleKey";
+}
+
+impl BinSerdeTN for ScrobbleModel {
+    const NAME: &'static str = "ScrobbleModel";
+}
+
+impl BinSerdeTN for PlayQueueModel {
+    const NAME: &'static str = "PlayQueueModel";
+}
+
+impl BinSerdeTN for PlayQueueCursorModel {
+    const NAME: &'static str = "PlayQueueCursorModel";
+}
+
+impl BinSerdeTN for GenreModel {
+    const NAME: &'static str = "GenreModel";
+}
+
+pub const TABLE_ID_ALLOC: TableDefinition<BinSerde<DbKeyAlloc>, i64> =
+    TableDefinition::new("v6_alloc");
+pub const TABLE_PLAYLIST: TableDefinition<BinSerde<PlaylistId>, BinSerde<PlaylistModel>> =
+    TableDefinition::new("v6_playlist");
+pub const TABLE_PLAYLIST_MUSIC: MultimapTableDefinition<
+    BinSerde<PlaylistId>,
+    BinSerde<PlaylistMusicModel>,
+> = MultimapTableDefinition::new("v6_playlist_music");
+pub const TABLE_MUSIC_PLAYLIST: MultimapTableDefinition<BinSerde<MusicId>, BinSerde<PlaylistId>> =
+    MultimapTableDefinition::new("v6_music_playlist");
+pub const TABLE_MUSIC: TableDefinition<BinSerde<MusicId>, BinSerde<MusicModel>> =
+    TableDefinition::new("v6_music");
+pub const TABLE_MUSIC_BY_LOC: TableDefinition<BinSerde<StorageEntryLoc>, BinSerde<MusicId>> =
+    TableDefinition::new("v6_music_by_loc");
+pub const TABLE_STORAGE: TableDefinition<BinSerde<StorageId>, BinSerde<StorageModel>> =
+    TableDefinition::new("v6_storage");
+pub const TABLE_STORAGE_MUSIC: MultimapTableDefinition<BinSerde<StorageId>, BinSerde<MusicId>> =
+    MultimapTableDefinition::new("v6_storage_music");
+pub const TABLE_PREFERENCE: TableDefinition<(), BinSerde<PreferenceModel>> =
+    TableDefinition::new("v6_preference");
+pub use v2::TABLE_SCHEMA_VERSION;
+pub const TABLE_BLOB: TableDefinition<(), BinSerde<BlobId>> = TableDefinition::new("v6_blob");
+pub const TABLE_BLOB_REF: TableDefinition<BinSerde<BlobId>, u32> =
+    TableDefinition::new("v6_blob_ref");
+pub const TABLE_ARTIST: TableDefinition<BinSerde<ArtistId>, BinSerde<ArtistModel>> =
+    TableDefinition::new("v6_artist");
+pub const TABLE_ARTIST_BY_NAME: TableDefinition<BinSerde<String>, BinSerde<ArtistId>> =
+    TableDefinition::new("v6_artist_by_name");
+pub const TABLE_ARTIST_MUSIC: MultimapTableDefinition<BinSerde<ArtistId>, BinSerde<MusicId>> =
+    MultimapTableDefinition::new("v6_artist_music");
+pub const TABLE_ALBUM: TableDefinition<BinSerde<AlbumId>, BinSerde<AlbumModel>> =
+    TableDefinition::new("v6_album");
+pub const TABLE_ALBUM_BY_KEY: TableDefinition<BinSerde<AlbumKey>, BinSerde<AlbumId>> =
+    TableDefinition::new("v6_album_by_key");
+pub const TABLE_ALBUM_MUSIC: MultimapTableDefinition<BinSerde<AlbumId>, BinSerde<MusicId>> =
+    MultimapTableDefinition::new("v6_album_music");
+pub const TABLE_GENRE: TableDefinition<BinSerde<GenreId>, BinSerde<GenreModel>> =
+    TableDefinition::new("v6_genre");
+pub const TABLE_GENRE_BY_NAME: TableDefinition<BinSerde<String>, BinSerde<GenreId>> =
+    TableDefinition::new("v6_genre_by_name");
+pub const TABLE_GENRE_MUSIC: MultimapTableDefinition<BinSerde<GenreId>, BinSerde<MusicId>> =
+    MultimapTableDefinition::new("v6_genre_music");
+pub const TABLE_MUSIC_FINGERPRINT: TableDefinition<
+    BinSerde<MusicId>,
+    BinSerde<MusicFingerprintModel>,
+> = TableDefinition::new("v6_music_fingerprint");
+pub const TABLE_SEARCH_TERM: MultimapTableDefinition<
+    BinSerde<String>,
+    BinSerde<SearchPostingModel>,
+> = MultimapTableDefinition::new("v6_search_term");
+pub const TABLE_SEARCH_DOCUMENT: TableDefinition<BinSerde<MusicId>, BinSerde<SearchDocumentModel>> =
+    TableDefinition::new("v6_search_document");
+pub const TABLE_SMART_PLAYLIST: TableDefinition<
+    BinSerde<PlaylistId>,
+    BinSerde<SmartPlaylistModel>,
+> = TableDefinition::new("v6_smart_playlist");
+pub const TABLE_PLAYLIST_FOLDER: TableDefinition<
+    BinSerde<PlaylistId>,
+    BinSerde<PlaylistFolderModel>,
+> = TableDefinition::new("v6_playlist_folder");
+pub const TABLE_SYNC_CONFIG: TableDefinition<(), BinSerde<SyncConfigModel>> =
+    TableDefinition::new("v6_sync_config");
+pub const TABLE_SYNC_FIELD: TableDefinition<BinSerde<SyncTarget>, BinSerde<SyncFieldModel>> =
+    TableDefinition::new("v6_sync_field");
+pub const TABLE_PLAYLIST_SYNC_ID: TableDefinition<BinSerde<PlaylistId>, BinSerde<String>> =
+    TableDefinition::new("v6_playlist_sync_id");
+pub const TABLE_PLAY_EVENT: TableDefinition<BinSerde<PlayEventKey>, BinSerde<PlayEventModel>> =
+    TableDefinition::new("v6_play_event");
+pub const TABLE_MUSIC_PLAY_STATS: TableDefinition<BinSerde<MusicId>, BinSerde<PlayStatsModel>> =
+    TableDefinition::new("v6_music_play_stats");
+pub const TABLE_PLAYLIST_PLAY_STATS: TableDefinition<
+    BinSerde<PlaylistId>,
+    BinSerde<PlayStatsModel>,
+> = TableDefinition::new("v6_playlist_play_stats");
+pub const TABLE_SCROBBLE_CONFIG: TableDefinition<(), BinSerde<ScrobbleConfigModel>> =
+    TableDefinition::new("v6_scrobble_config");
+pub const TABLE_SCROBBLE_QUEUE: TableDefinition<BinSerde<ScrobbleKey>, BinSerde<ScrobbleModel>> =
+    TableDefinition::new("v6_scrobble_queue");
+pub const TABLE_MUSIC_FAVORITE: TableDefinition<BinSerde<MusicId>, i64> =
+    TableDefinition::new("v6_music_favorite");
+pub const TABLE_MUSIC_RATING: TableDefinition<BinSerde<MusicId>, u8> =
+    TableDefinition::new("v6_music_rating");
+pub const TABLE_RATING_MUSIC: MultimapTableDefinition<u8, BinSerde<MusicId>> =
+    MultimapTableDefinition::new("v6_rating_music");
+pub const TABLE_MUSIC_USER_TAG: MultimapTableDefinition<BinSerde<MusicId>, BinSerde<String>> =
+    MultimapTableDefinition::new("v6_music_user_tag");
+pub const TABLE_USER_TAG_MUSIC: MultimapTableDefinition<BinSerde<String>, BinSerde<MusicId>> =
+    MultimapTableDefinition::new("v6_user_tag_music");
+pub const TABLE_PLAY_QUEUE: TableDefinition<(), BinSerde<PlayQueueModel>> =
+    TableDefinition::new("v6_play_queue");
+pub const TABLE_PLAY_QUEUE_CURSOR: TableDefinition<(), BinSerde<PlayQueueCursorModel>> =
+    TableDefinition::new("v6_play_queue_cursor");
@@ -0,0 +1,5 @@
+mod bin;
+mod defs;
+
+pub use bin::*;
+pub use defs::*;
@@ -0,0 +1,327 @@
+use std::sync::Arc;
+
+use redb::{
+    MultimapTableDefinition, ReadableMultimapTable, ReadableTable, TableDefinition,
+    WriteTransaction,
+};
+
+use crate::{v5, v6};
+
+impl From<v5::DbKeyAlloc> for v6::DbKeyAlloc {
+    fn from(value: v5::DbKeyAlloc) -> Self {
+        match value {
+            v5::DbKeyAlloc::Playlist => v6::DbKeyAlloc::Playlist,
+            v5::DbKeyAlloc::Music => v6::DbKeyAlloc::Music,
+            v5::DbKeyAlloc::Storage => v6::DbKeyAlloc::Storage,
+        }
+    }
+}
+
+impl From<v5::PlaylistModel> for v6::PlaylistModel {
+    fn from(value: v5::PlaylistModel) -> Self {
+        Self {
+            id: value.id,
+            title: value.title,
+            created_time: value.created_time,
+            picture: value.picture,
+            order: value.order,
+        }
+    }
+}
+
+impl From<v5::MusicModel> for v6::MusicModel {
+    fn from(value: v5::MusicModel) -> Self {
+        Self {
+            id: value.id,
+            loc: value.loc,
+            title: value.title,
+            duration: value.duration,
+            cover: value.cover,
+            cover_probed: false,
+            lyric: value.lyric,
+            lyric_default: value.lyric_default,
+            order: value.order,
+            artists: Default::default(),
+            album: None,
+            genres: Default::default(),
+            track_number: None,
+            disc_number: None,
+            stream_info: None,
+            stream_probed: false,
+            replay_gain: None,
+            loudness_analyzed: false,
+            added_time: 0,
+            file_stamp: None,
+            file_state: Default::default(),
+        }
+    }
+}
+
+impl From<v5::StorageModel> for v6::StorageModel {
+    fn from(value: v5::StorageModel) -> Self {
+        Self {
+            id: value.id,
+            addr: value.addr,
+            alias: value.alias,
+            username: value.username,
+            password: value.password,
+            is_anonymous: value.is_anonymous,
+            typ: value.typ,
+            default_path: value.default_path,
+        }
+    }
+}
+
+impl From<v5::PreferenceModel> for v6::PreferenceModel {
+    fn from(value: v5::PreferenceModel) -> Self {
+        Self {
+            playmode: value.playmode,
+        }
+    }
+}
+
+impl From<v5::PlaylistMusicModel> for v6::PlaylistMusicModel {
+    fn from(value: v5::PlaylistMusicModel) -> Self {
+        Self {
+            music_id: value.music_id,
+            order: value.order,
+        }
+    }
+}
+
+fn convert_table<KF, VF, KT, VT>(
+    db: &WriteTransaction,
+    d_from: TableDefinition<KF, VF>,
+    d_to: TableDefinition<KT, VT>,
+) -> anyhow::Result<()>
+where
+    KF: redb::Key + 'static,
+    VF: redb::Value + 'static,
+    KT: redb::Key + 'static,
+    VT: redb::Value + 'static,
+    for<'b> <KT as redb::Value>::SelfType<'b>: From<<KF as redb::Value>::SelfType<'b>>,
+    for<'b> <VT as redb::Value>::SelfType<'b>: From<<VF as redb::Value>::SelfType<'b>>,
+{
+    let ot = db.open_table(d_from)?;
+    let mut nt = db.open_table(d_to)?;
+    for v in ot.iter()? {
+        let (k, v) = v?;
+        let k: <KT as redb::Value>::SelfType<'_> = k.value().into();
+        let v: <VT as redb::Value>::SelfType<'_> = v.value().into();
+        nt.insert(k, v)?;
+    }
+    Ok(())
+}
+
+fn convert_multi_table<KF, VF, KT, VT>(
+    db: &WriteTransaction,
+    d_from: MultimapTableDefinition<KF, VF>,
+    d_to: MultimapTableDefinition<KT, VT>,
+) -> anyhow::Result<()>
+where
+    KF: redb::Key + 'static,
+    VF: redb::Key + 'static,
+    KT: redb::Key + 'static,
+    VT: redb::Key + 'static,
+    for<'b> <KT as redb::Value>::SelfType<'b>: From<<KF as redb::Value>::SelfType<'b>>,
+    for<'b> <VT as redb::Value>::SelfType<'b>: From<<VF as redb::Value>::SelfType<'b>>,
+{
+    let ot = db.open_multimap_table(d_from)?;
+    let mut nt = db.open_multimap_table(d_to)?;
+
+    for v in ot.iter()? {
+        let (k, v) = v?;
+        for v in v.into_iter() {
+            let v = v?;
+            let k: <KT as redb::Value>::SelfType<'_> = k.value().into();
+            let v: <VT as redb::Value>::SelfType<'_> = v.value().into();
+            nt.insert(k, v)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn upgrade_v5_to_v6(database: &Arc<redb::Database>) -> anyhow::Result<()> {
+    let db = database.begin_write()?;
+    {
+        let db = &db;
+        convert_table(db, v5::TABLE_ID_ALLOC, v6::TABLE_ID_ALLOC)?;
+        convert_table(db, v5::TABLE_PLAYLIST, v6::TABLE_PLAYLIST)?;
+        convert_multi_table(db, v5::TABLE_PLAYLIST_MUSIC, v6::TABLE_PLAYLIST_MUSIC)?;
+        convert_multi_table(db, v5::TABLE_MUSIC_PLAYLIST, v6::TABLE_MUSIC_PLAYLIST)?;
+        convert_table(db, v5::TABLE_MUSIC, v6::TABLE_MUSIC)?;
+        convert_table(db, v5::TABLE_MUSIC_BY_LOC, v6::TABLE_MUSIC_BY_LOC)?;
+        convert_table(db, v5::TABLE_STORAGE, v6::TABLE_STORAGE)?;
+        convert_multi_table(db, v5::TABLE_STORAGE_MUSIC, v6::TABLE_STORAGE_MUSIC)?;
+        convert_table(db, v5::TABLE_PREFERENCE, v6::TABLE_PREFERENCE)?;
+        convert_table(db, v5::TABLE_BLOB, v6::TABLE_BLOB)?;
+        db.open_table(v6::TABLE_ARTIST)?;
+        db.open_table(v6::TABLE_ARTIST_BY_NAME)?;
+        db.open_multimap_table(v6::TABLE_ARTIST_MUSIC)?;
+        db.open_table(v6::TABLE_ALBUM)?;
+        db.open_table(v6::TABLE_ALBUM_BY_KEY)?;
+        db.open_multimap_table(v6::TABLE_ALBUM_MUSIC)?;
+        db.open_table(v6::TABLE_GENRE)?;
+        db.open_table(v6::TABLE_GENRE_BY_NAME)?;
+        db.open_multimap_table(v6::TABLE_GENRE_MUSIC)?;
+        db.open_table(v6::TABLE_MUSIC_FINGERPRINT)?;
+        db.open_multimap_table(v6::TABLE_SEARCH_TERM)?;
+        db.open_table(v6::TABLE_SEARCH_DOCUMENT)?;
+        db.open_table(v6::TABLE_SMART_PLAYLIST)?;
+        db.open_table(v6::TABLE_PLAYLIST_FOLDER)?;
+        db.open_table(v6::TABLE_SYNC_CONFIG)?;
+        db.open_table(v6::TABLE_SYNC_FIELD)?;
+        db.open_table(v6::TABLE_PLAYLIST_SYNC_ID)?;
+        db.open_table(v6::TABLE_PLAY_EVENT)?;
+        db.open_table(v6::TABLE_MUSIC_PLAY_STATS)?;
+        db.open_table(v6::TABLE_PLAYLIST_PLAY_STATS)?;
+        db.open_table(v6::TABLE_SCROBBLE_CONFIG)?;
+        db.open_table(v6::TABLE_SCROBBLE_QUEUE)?;
+        db.open_table(v6::TABLE_MUSIC_FAVORITE)?;
+        db.open_table(v6::TABLE_MUSIC_RATING)?;
+        db.open_multimap_table(v6::TABLE_RATING_MUSIC)?;
+        db.open_multimap_table(v6::TABLE_MUSIC_USER_TAG)?;
+        db.open_multimap_table(v6::TABLE_USER_TAG_MUSIC)?;
+        db.open_table(v6::TABLE_PLAY_QUEUE)?;
+        db.open_table(v6::TABLE_PLAY_QUEUE_CURSOR)?;
+        db.open_table(v6::TABLE_BLOB_REF)?;
+        tracing::info!("v5 -> v6: finish library tables migration");
+    }
+    {
+        db.delete_table(v5::TABLE_ID_ALLOC)?;
+        db.delete_table(v5::TABLE_PLAYLIST)?;
+        db.delete_multimap_table(v5::TABLE_PLAYLIST_MUSIC)?;
+        db.delete_multimap_table(v5::TABLE_MUSIC_PLAYLIST)?;
+        db.delete_table(v5::TABLE_MUSIC)?;
+        db.delete_table(v5::TABLE_MUSIC_BY_LOC)?;
+        db.delete_table(v5::TABLE_STORAGE)?;
+        db.delete_multimap_table(v5::TABLE_STORAGE_MUSIC)?;
+        db.delete_table(v5::TABLE_PREFERENCE)?;
+        db.delete_table(v5::TABLE_BLOB)?;
+        tracing::info!("v5 -> v6: finish deleting old tables");
+    }
+    {
+        let mut t = db.open_table(v6::TABLE_SCHEMA_VERSION)?;
+        t.insert((), 6)?;
+    }
+    db.commit()?;
+    tracing::info!("v5 -> v6: finish all");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use redb::ReadableTableMetadata;
+    use tempfile::tempdir;
+
+    use crate::{v5, v6};
+
+    #[test]
+    fn upgrade_v5_to_v6_keeps_musics_and_creates_library_tables() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("data.redb");
+        let db = Arc::new(redb::Database::create(db_path).unwrap());
+
+        let loc = crate::StorageEntryLoc {
+            storage_id: crate::StorageId::wrap(1),
+            path: "/Music/a.flac".to_string(),
+        };
+        {
+            let txn = db.begin_write().unwrap();
+            txn.open_table(v5::TABLE_ID_ALLOC).unwrap();
+            txn.open_table(v5::TABLE_PLAYLIST).unwrap();
+            txn.open_multimap_table(v5::TABLE_PLAYLIST_MUSIC).unwrap();
+            txn.open_multimap_table(v5::TABLE_MUSIC_PLAYLIST).unwrap();
+            txn.open_table(v5::TABLE_MUSIC).unwrap();
+            txn.open_table(v5::TABLE_MUSIC_BY_LOC).unwrap();
+            txn.open_table(v5::TABLE_STORAGE).unwrap();
+            txn.open_multimap_table(v5::TABLE_STORAGE_MUSIC).unwrap();
+            txn.open_table(v5::TABLE_PREFERENCE).unwrap();
+            txn.open_table(v5::TABLE_SCHEMA_VERSION).unwrap();
+            txn.open_table(v5::TABLE_BLOB).unwrap();
+
+            txn.open_table(v5::TABLE_MUSIC)
+                .unwrap()
+                .insert(
+                    &crate::MusicId::wrap(3),
+                    &v5::MusicModel {
+                        id: crate::MusicId::wrap(3),
+                        loc: loc.clone(),
+                        title: "a".to_string(),
+                        duration: None,
+                        cover: None,
+                        lyric: None,
+                        lyric_default: true,
+                        order: vec![1],
+                    },
+                )
+                .unwrap();
+            txn.open_table(v5::TABLE_MUSIC_BY_LOC)
+                .unwrap()
+                .insert(&loc, &crate::MusicId::wrap(3))
+                .unwrap();
+            txn.open_table(v5::TABLE_ID_ALLOC)
+                .unwrap()
+                .insert(&v5::DbKeyAlloc::Music, 3)
+                .unwrap();
+            txn.open_table(v5::TABLE_SCHEMA_VERSION)
+                .unwrap()
+                .insert((), 5)
+                .unwrap();
+            txn.commit().unwrap();
+        }
+
+        super::upgrade_v5_to_v6(&db).unwrap();
+
+        let txn = db.begin_read().unwrap();
+        let music = txn
+            .open_table(v6::TABLE_MUSIC)
+            .unwrap()
+            .get(v6::MusicId::wrap(3))
+            .unwrap()
+            .unwrap()
+            .value();
+        assert_eq!("a", music.title);
+        assert_eq!(loc, music.loc);
+        assert!(music.artists.is_empty());
+        assert!(music.album.is_none());
+
+        let by_loc = txn
+            .open_table(v6::TABLE_MUSIC_BY_LOC)
+            .unwrap()
+            .get(loc)
+            .unwrap()
+            .unwrap()
+            .value();
+        assert_eq!(v6::MusicId::wrap(3), by_loc);
+
+        let next_music_id = txn
+            .open_table(v6::TABLE_ID_ALLOC)
+            .unwrap()
+            .get(v6::DbKeyAlloc::Music)
+            .unwrap()
+            .unwrap()
+            .value();
+        assert_eq!(3, next_music_id);
+
+        assert_eq!(0, txn.open_table(v6::TABLE_ARTIST).unwrap().len().unwrap());
+        assert_eq!(
+            0,
+            txn.open_multimap_table(v6::TABLE_ALBUM_MUSIC)
+                .unwrap()
+                .len()
+                .unwrap()
+        );
+
+        let schema_version = txn
+            .open_table(v6::TABLE_SCHEMA_VERSION)
+            .unwrap()
+            .get(())
+            .unwrap()
+            .unwrap()
+            .value();
+        assert_eq!(6, schema_version);
+    }
+}
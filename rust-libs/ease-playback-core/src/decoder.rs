@@ -0,0 +1,329 @@
+use std::sync::LazyLock;
+
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{CODEC_TYPE_NULL, CodecRegistry, DecoderOptions},
+    errors::Error as SymphoniaError,
+    formats::{FormatOptions, FormatReader},
+    io::MediaSourceStream,
+    meta::{MetadataOptions, Value},
+    probe::Hint,
+};
+
+use crate::{
+    error::{PlaybackError, PlaybackResult},
+    input::MediaInput,
+    opus::OpusDecoder,
+};
+
+/// Codecs of Symphonia, and Opus which it cannot decode.
+static CODECS: LazyLock<CodecRegistry> = LazyLock::new(|| {
+    let mut registry = CodecRegistry::new();
+    symphonia::default::register_enabled_codecs(&mut registry);
+    registry.register_all::<OpusDecoder>();
+    registry
+});
+
+/// Decoded format of a track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackInfo {
+    pub sample_rate: u32,
+    pub channels: usize,
+    /// Frames once the encoder delay and padding are trimmed, when the
+    /// container tells.
+    pub frames: Option<u64>,
+    /// Frames added by the encoder before the audio.
+    pub delay: u32,
+    /// Frames added by the encoder after the audio.
+    pub padding: u32,
+}
+
+/// Drops the encoder delay and padding, so that consecutive tracks of a
+/// gapless album join without silence. Symphonia trims only some codecs
+/// itself, so its gapless mode is off and every codec is trimmed here.
+#[derive(Debug)]
+pub(crate) struct GaplessTrim {
+    skip: u64,
+    remaining: Option<u64>,
+}
+
+impl GaplessTrim {
+    pub(crate) fn new(delay: u32, padding: u32, total_frames: Option<u64>) -> Self {
+        Self {
+            skip: delay as u64,
+            remaining: total_frames.map(|v| v.saturating_sub(delay as u64 + padding as u64)),
+        }
+    }
+
+    /// Frames of `samples` to keep.
+    pub(crate) fn apply<'a>(&mut self, channels: usize, samples: &'a [f32]) -> &'a [f32] {
+        let frames = (samples.len() / channels) as u64;
+        let skip = self.skip.min(frames);
+        self.skip -= skip;
+        let mut keep = frames - skip;
+        if let Some(remaining) = self.remaining.as_mut() {
+            keep = keep.min(*remaining);
+            *remaining -= keep;
+        }
+        let start = skip as usize * channels;
+        &samples[start..start + keep as usize * channels]
+    }
+}
+
+/// Reads the encoder delay, padding and length of the audio from the
+/// `iTunSMPB` comment that iTunes and most AAC encoders write in MP4 files,
+/// whose container tells none of them.
+fn parse_itunsmpb(value: &str) -> Option<(u32, u32, u64)> {
+    let mut fields = value.split_whitespace().skip(1);
+    let delay = u32::from_str_radix(fields.next()?, 16).ok()?;
+    let padding = u32::from_str_radix(fields.next()?, 16).ok()?;
+    let frames = u64::from_str_radix(fields.next()?, 16).ok()?;
+    Some((delay, padding, frames))
+}
+
+fn find_itunsmpb(format: &mut dyn FormatReader) -> Option<(u32, u32, u64)> {
+    let metadata = format.metadata();
+    let tag = metadata
+        .current()?
+        .tags()
+        .iter()
+        .find(|v| v.key.ends_with("iTunSMPB"))?;
+    match &tag.value {
+        Value::String(value) => parse_itunsmpb(value),
+        _ => None,
+    }
+}
+
+/// Decodes the default audio track of an input into interleaved `f32`
+/// samples.
+pub struct Decoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn symphonia::core::codecs::Decoder>,
+    track_id: u32,
+    info: TrackInfo,
+    trim: GaplessTrim,
+    buf: Option<SampleBuffer<f32>>,
+}
+
+impl Decoder {
+    pub fn open(input: MediaInput) -> PlaybackResult<Self> {
+        let source = MediaSourceStream::new(input.source, Default::default());
+        let mut hint = Hint::new();
+        if let Some(extension) = input.extension.as_deref() {
+            hint.with_extension(extension);
+        }
+        let probed = symphonia::default::get_probe().format(
+            &hint,
+            source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )?;
+        let mut format = probed.format;
+        let track = format
+            .tracks()
+            .iter()
+            .find(|v| v.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or(PlaybackError::NoAudioTrack)?;
+        let track_id = track.id;
+        let params = track.codec_params.clone();
+        let decoder = CODECS
+            .make(&params, &DecoderOptions::default())
+            .map_err(|e| match e {
+                SymphoniaError::Unsupported(_) => {
+                    PlaybackError::UnsupportedCodec(params.codec.to_string())
+                }
+                e => e.into(),
+            })?;
+        let (delay, padding, total_frames) = match params.delay {
+            Some(delay) => (delay, params.padding.unwrap_or_default(), params.n_frames),
+            None => match find_itunsmpb(format.as_mut()) {
+                Some((delay, padding, frames)) => {
+                    (delay, padding, Some(delay as u64 + frames + padding as u64))
+                }
+                None => (0, params.padding.unwrap_or_default(), params.n_frames),
+            },
+        };
+        let info = TrackInfo {
+            sample_rate: params.sample_rate.ok_or(PlaybackError::UnsupportedFormat)?,
+            // MP4 tells the channels of AAC only in the codec config.
+            channels: params
+                .channels
+                .unwrap_or_else(|| decoder.last_decoded().spec().channels)
+                .count(),
+            frames: total_frames.map(|v| v.saturating_sub(delay as u64 + padding as u64)),
+            delay,
+            padding,
+        };
+        Ok(Self {
+            track_id,
+            trim: GaplessTrim::new(delay, padding, total_frames),
+            format,
+            decoder,
+            info,
+            buf: None,
+        })
+    }
+
+    pub fn info(&self) -> TrackInfo {
+        self.info
+    }
+
+    /// Next decoded samples, interleaved, or `None` at the end of the track.
+    /// The slice may be empty when a packet is all delay.
+    pub fn next_chunk(&mut self) -> PlaybackResult<Option<&[f32]>> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(e))
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    return Ok(None);
+                }
+                Err(SymphoniaError::ResetRequired) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(SymphoniaError::DecodeError(e)) => {
+                    tracing::warn!("skip undecodable packet: {e}");
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let spec = *decoded.spec();
+            if spec.channels.count() != self.info.channels || spec.rate != self.info.sample_rate {
+                return Err(PlaybackError::Decode(
+                    "format changed within the track".to_string(),
+                ));
+            }
+            let needed = decoded.capacity() * spec.channels.count();
+            if self.buf.as_ref().is_none_or(|v| v.capacity() < needed) {
+                self.buf = Some(SampleBuffer::new(decoded.capacity() as u64, spec));
+            }
+            let buf = self.buf.as_mut().unwrap();
+            buf.copy_interleaved_ref(decoded);
+            let samples = self.trim.apply(self.info.channels, buf.samples());
+            return Ok(Some(samples));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decoder, GaplessTrim, parse_itunsmpb};
+    use crate::input::MediaInput;
+
+    /// Decodes a fixture, encoded from [`chirp`] in every channel, into its
+    /// sample rate and channels.
+    fn decode_fixture(name: &str) -> (u32, Vec<Vec<f32>>) {
+        let path = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+        let mut decoder = Decoder::open(MediaInput::from_file(path).unwrap()).unwrap();
+        let info = decoder.info();
+        let mut samples = Vec::new();
+        while let Some(chunk) = decoder.next_chunk().unwrap() {
+            samples.extend_from_slice(chunk);
+        }
+        let channels: Vec<Vec<f32>> = (0..info.channels)
+            .map(|c| {
+                samples
+                    .iter()
+                    .skip(c)
+                    .step_by(info.channels)
+                    .copied()
+                    .collect()
+            })
+            .collect();
+        assert_eq!(info.frames, Some(channels[0].len() as u64));
+        (info.sample_rate, channels)
+    }
+
+    fn chirp(sample_rate: u32, frames: usize) -> Vec<f32> {
+        let len = frames as f64 / sample_rate as f64;
+        (0..frames)
+            .map(|i| {
+                let t = i as f64 / sample_rate as f64;
+                let phase = 300.0 * t + (3000.0 - 300.0) * t * t / (2.0 * len);
+                (0.5 * (2.0 * std::f64::consts::PI * phase).sin()) as f32
+            })
+            .collect()
+    }
+
+    /// Lag of the decoded samples against the source that correlates best.
+    fn best_lag(decoded: &[f32], source: &[f32]) -> isize {
+        (-64..=64isize)
+            .max_by(|&a, &b| {
+                let correlate = |lag: isize| -> f32 {
+                    (64..source.len() - 64)
+                        .map(|i| decoded[(i as isize + lag) as usize] * source[i])
+                        .sum()
+                };
+                correlate(a).total_cmp(&correlate(b))
+            })
+            .unwrap()
+    }
+
+    /// Checks every channel of a fixture is the chirp, neither delayed nor
+    /// padded, and returns the largest difference.
+    fn assert_gapless(name: &str, channels: usize, frames: usize) -> f32 {
+        let (sample_rate, decoded) = decode_fixture(name);
+        let source = chirp(sample_rate, frames);
+        assert_eq!(channels, decoded.len(), "{name}");
+        let mut error = 0f32;
+        for channel in decoded {
+            assert_eq!(frames, channel.len(), "{name}");
+            assert_eq!(0, best_lag(&channel, &source), "{name}");
+            error = channel
+                .iter()
+                .zip(source.iter())
+                .fold(error, |v, (a, b)| v.max((a - b).abs()));
+        }
+        error
+    }
+
+    #[test]
+    fn trims_mp3_lame_delay() {
+        assert_gapless("gapless.mp3", 1, 22050);
+    }
+
+    #[test]
+    fn trims_aac_itunsmpb_delay() {
+        assert_gapless("gapless.m4a", 1, 22050);
+    }
+
+    #[test]
+    fn trims_opus_pre_skip() {
+        assert_gapless("gapless.opus", 1, 24000);
+    }
+
+    #[test]
+    fn decodes_mono_packets_of_stereo_opus() {
+        // Stereo, with every other 100 ms coded in mono packets, which leave
+        // no seam when decoded by one decoder.
+        let error = assert_gapless("mixed_channels.opus", 2, 24000);
+        assert!(error < 0.05, "{error}");
+    }
+
+    #[test]
+    fn parses_itunsmpb() {
+        let value = " 00000000 00000840 000001DE 0000000000005622 00000000 00000000";
+        assert_eq!(Some((2112, 478, 22050)), parse_itunsmpb(value));
+        assert_eq!(None, parse_itunsmpb(" 00000000 00000840"));
+    }
+
+    #[test]
+    fn trims_encoder_delay_and_padding() {
+        // 2 channels, 10 frames of delay, 5 of padding, 40 frames in all.
+        let mut trim = GaplessTrim::new(10, 5, Some(40));
+        let packet: Vec<f32> = (0..32).map(|v| v as f32).collect();
+        let first = trim.apply(2, &packet[..16]);
+        assert!(first.is_empty());
+        let second = trim.apply(2, &packet);
+        assert_eq!(&packet[4..], second);
+        let third = trim.apply(2, &packet);
+        assert_eq!(&packet[..22], third);
+        assert!(trim.apply(2, &packet).is_empty());
+    }
+}
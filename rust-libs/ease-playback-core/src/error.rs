@@ -0,0 +1,36 @@
+use ease_remote_storage::StorageBackendError;
+use symphonia::core::errors::Error as SymphoniaError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PlaybackError {
+    #[error("unsupported format")]
+    UnsupportedFormat,
+    #[error("unsupported codec: {0}")]
+    UnsupportedCodec(String),
+    #[error("no audio track")]
+    NoAudioTrack,
+    #[error("decode error: {0}")]
+    Decode(String),
+    #[error("resample error: {0}")]
+    Resample(String),
+    #[error("invalid output format: {0}")]
+    InvalidOutputFormat(String),
+    #[error(transparent)]
+    Storage(#[from] StorageBackendError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Wav(#[from] hound::Error),
+}
+
+impl From<SymphoniaError> for PlaybackError {
+    fn from(value: SymphoniaError) -> Self {
+        match value {
+            SymphoniaError::IoError(e) => Self::Io(e),
+            SymphoniaError::Unsupported(_) => Self::UnsupportedFormat,
+            e => Self::Decode(e.to_string()),
+        }
+    }
+}
+
+pub type PlaybackResult<T> = Result<T, PlaybackError>;
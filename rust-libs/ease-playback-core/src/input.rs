@@ -0,0 +1,123 @@
+use std::{
+    fs::File,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use ease_remote_storage::StreamFile;
+use symphonia::core::io::MediaSource;
+
+/// Encoded audio to decode, with the file extension as a hint of its
+/// format.
+pub struct MediaInput {
+    pub(crate) source: Box<dyn MediaSource>,
+    pub(crate) extension: Option<String>,
+}
+
+fn extension_of(name: &str) -> Option<String> {
+    name.rsplit_once('.').map(|(_, ext)| ext.to_lowercase())
+}
+
+impl MediaInput {
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>, extension: Option<&str>) -> Self {
+        Self {
+            source: Box::new(Cursor::new(bytes.into())),
+            extension: extension.map(|v| v.to_lowercase()),
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        Ok(Self {
+            source: Box::new(File::open(path)?),
+            extension: path.to_str().and_then(extension_of),
+        })
+    }
+
+    /// Reads the chunks of a file streamed from a storage. The stream cannot
+    /// seek, so it is decoded from start to end.
+    pub fn from_stream_file(file: StreamFile) -> Self {
+        let extension = extension_of(file.name());
+        let size = file.size().map(|v| v as u64);
+        let rx = file.into_rx();
+        let chunks = std::iter::from_fn(move || {
+            rx.recv_blocking().ok().map(|chunk| {
+                chunk
+                    .map(|v| v.to_vec())
+                    .map_err(|e| io::Error::other(e.to_string()))
+            })
+        });
+        Self::from_chunks(chunks, size, extension.as_deref())
+    }
+
+    /// Reads chunks as they come, such as those of an `AssetStream`:
+    ///
+    /// ```ignore
+    /// let chunks = std::iter::from_fn(move || {
+    ///     tokio_runtime()
+    ///         .block_on(stream.next())
+    ///         .map_err(|e| io::Error::other(e.to_string()))
+    ///         .transpose()
+    /// });
+    /// MediaInput::from_chunks(chunks, stream.size(), Some("mp3"))
+    /// ```
+    pub fn from_chunks(
+        chunks: impl Iterator<Item = io::Result<Vec<u8>>> + Send + Sync + 'static,
+        size: Option<u64>,
+        extension: Option<&str>,
+    ) -> Self {
+        Self {
+            source: Box::new(ChunkReader {
+                chunks: Box::new(chunks),
+                current: Default::default(),
+                offset: 0,
+                size,
+            }),
+            extension: extension.map(|v| v.to_lowercase()),
+        }
+    }
+}
+
+struct ChunkReader {
+    chunks: Box<dyn Iterator<Item = io::Result<Vec<u8>>> + Send + Sync>,
+    current: Vec<u8>,
+    offset: usize,
+    size: Option<u64>,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.offset >= self.current.len() {
+            match self.chunks.next() {
+                Some(chunk) => {
+                    self.current = chunk?;
+                    self.offset = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.current.len() - self.offset);
+        buf[..len].copy_from_slice(&self.current[self.offset..self.offset + len]);
+        self.offset += len;
+        Ok(len)
+    }
+}
+
+impl Seek for ChunkReader {
+    fn seek(&mut self, _pos: SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "streamed input cannot seek",
+        ))
+    }
+}
+
+impl MediaSource for ChunkReader {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.size
+    }
+}
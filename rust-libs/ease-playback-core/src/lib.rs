@@ -0,0 +1,24 @@
+//! Headless playback core. Decodes the musics served by the storages into
+//! PCM, converts them to a single output format and writes them to a
+//! pluggable [`AudioSink`], joining consecutive tracks without gaps.
+//!
+//! Supported codecs are MP3, FLAC, AAC, ALAC, Vorbis, mono and stereo
+//! Opus, and WAV/PCM.
+//!
+//! Decoding blocks on the input, so it runs on its own thread rather than
+//! on the async runtime serving the storages.
+
+mod decoder;
+mod error;
+mod input;
+mod opus;
+mod pipeline;
+mod resample;
+mod sink;
+
+pub use decoder::{Decoder, TrackInfo};
+pub use error::{PlaybackError, PlaybackResult};
+pub use input::MediaInput;
+pub use pipeline::{OutputFormat, Pipeline, TrackReport};
+pub use resample::SampleRateConverter;
+pub use sink::{AudioSink, NullSink, WavFileSink};
@@ -0,0 +1,134 @@
+use opus_decoder::OpusDecoder as PacketDecoder;
+use symphonia::core::{
+    audio::{AsAudioBufferRef, AudioBuffer, AudioBufferRef, Signal, SignalSpec},
+    codecs::{
+        CODEC_TYPE_OPUS, CodecDescriptor, CodecParameters, Decoder, DecoderOptions, FinalizeResult,
+    },
+    errors::{Result, decode_error, unsupported_error},
+    formats::Packet,
+    support_codec,
+};
+
+/// Opus always decodes at 48 kHz.
+const SAMPLE_RATE: u32 = 48_000;
+/// Longest packet allowed, 120 ms.
+const MAX_PACKET_FRAMES: usize = 5760;
+
+/// Channels and output gain of a stream, read from its `OpusHead` header.
+struct OpusHead {
+    channels: usize,
+    /// Output gain in dB, in Q7.8.
+    gain: i16,
+}
+
+/// Reads the `OpusHead` header of a mono or stereo stream. Streams of more
+/// channels are split in several Opus streams, which are not supported.
+fn parse_opus_head(buf: &[u8]) -> Option<OpusHead> {
+    if buf.len() < 19 || &buf[..8] != b"OpusHead" {
+        return None;
+    }
+    let channels = buf[9] as usize;
+    if buf[18] != 0 || !(1..=2).contains(&channels) {
+        return None;
+    }
+    Some(OpusHead {
+        channels,
+        gain: i16::from_le_bytes([buf[16], buf[17]]),
+    })
+}
+
+/// Decodes Opus packets with a pure-Rust decoder. The pre-skip of the stream
+/// is reported by the Ogg demuxer as the encoder delay and trimmed with the
+/// delay of the other codecs.
+pub(crate) struct OpusDecoder {
+    params: CodecParameters,
+    /// Decodes into the channels of the stream, including the mono packets
+    /// encoders may code a stereo stream in at low bitrates.
+    decoder: PacketDecoder,
+    channels: usize,
+    scale: f32,
+    pcm: Vec<f32>,
+    buf: AudioBuffer<f32>,
+}
+
+impl Decoder for OpusDecoder {
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+        let Some(head) = params.extra_data.as_deref().and_then(parse_opus_head) else {
+            return unsupported_error("opus: unsupported identification header");
+        };
+        let Some(layout) = params.channels.filter(|v| v.count() == head.channels) else {
+            return unsupported_error("opus: unsupported channel layout");
+        };
+        let Ok(decoder) = PacketDecoder::new(SAMPLE_RATE, head.channels) else {
+            return unsupported_error("opus: unsupported channel count");
+        };
+        Ok(Self {
+            params: params.clone(),
+            decoder,
+            channels: head.channels,
+            scale: 10f32.powf(head.gain as f32 / (20.0 * 256.0)),
+            pcm: vec![0.0; MAX_PACKET_FRAMES * head.channels],
+            buf: AudioBuffer::new(
+                MAX_PACKET_FRAMES as u64,
+                SignalSpec::new(SAMPLE_RATE, layout),
+            ),
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(CODEC_TYPE_OPUS, "opus", "Opus")]
+    }
+
+    fn reset(&mut self) {
+        self.decoder.reset();
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        self.buf.clear();
+        let frames = match self
+            .decoder
+            .decode_float(&packet.data, &mut self.pcm, false)
+        {
+            Ok(frames) => frames,
+            Err(_) => return decode_error("opus: invalid packet"),
+        };
+        self.buf.render_reserved(Some(frames));
+        for c in 0..self.channels {
+            let samples = self.pcm.iter().skip(c).step_by(self.channels);
+            for (dst, src) in self.buf.chan_mut(c).iter_mut().zip(samples) {
+                *dst = src * self.scale;
+            }
+        }
+        Ok(self.buf.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        Default::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buf.as_audio_buffer_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_opus_head;
+
+    #[test]
+    fn parses_mono_and_stereo_heads() {
+        let mut head = b"OpusHead\x01\x02\x38\x01\x80\xbb\x00\x00\x00\x01\x00".to_vec();
+        let stereo = parse_opus_head(&head).expect("stereo");
+        assert_eq!((2, 256), (stereo.channels, stereo.gain));
+
+        head[9] = 3;
+        assert!(parse_opus_head(&head).is_none());
+        head[18] = 1;
+        head.extend_from_slice(&[2, 1, 0, 2, 1]);
+        assert!(parse_opus_head(&head).is_none());
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{
+    decoder::Decoder,
+    error::{PlaybackError, PlaybackResult},
+    input::MediaInput,
+    resample::SampleRateConverter,
+    sink::AudioSink,
+};
+
+/// Format every track is converted to before reaching the sink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputFormat {
+    pub sample_rate: u32,
+    pub channels: usize,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self {
+            sample_rate: 48_000,
+            channels: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackReport {
+    /// Frames decoded, at the rate of the track.
+    pub frames_in: u64,
+    /// Frames written to the sink, at the output rate.
+    pub frames_out: u64,
+}
+
+/// Plays tracks one after another into a sink. Tracks are trimmed of their
+/// encoder delay and padding and converted to the output format, and
+/// nothing is inserted between them, so gapless albums play gapless.
+pub struct Pipeline<S: AudioSink> {
+    format: OutputFormat,
+    sink: S,
+    opened: bool,
+}
+
+/// Maps interleaved samples to another channel count. Mono is spread to
+/// every channel, and everything is averaged down to mono. Otherwise
+/// channels are kept in order, and missing ones repeat the first ones.
+fn remix(samples: &[f32], from: usize, to: usize, out: &mut Vec<f32>) {
+    if from == to {
+        out.extend_from_slice(samples);
+        return;
+    }
+    for frame in samples.chunks_exact(from) {
+        if to == 1 {
+            out.push(frame.iter().sum::<f32>() / from as f32);
+        } else {
+            out.extend((0..to).map(|c| frame[c % from]));
+        }
+    }
+}
+
+impl<S: AudioSink> Pipeline<S> {
+    pub fn new(format: OutputFormat, sink: S) -> PlaybackResult<Self> {
+        if format.sample_rate == 0 || format.channels == 0 {
+            return Err(PlaybackError::InvalidOutputFormat(format!("{format:?}")));
+        }
+        Ok(Self {
+            format,
+            sink,
+            opened: false,
+        })
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    /// Decodes `input` to its end and writes it to the sink after the
+    /// previous tracks.
+    pub fn play(&mut self, input: MediaInput) -> PlaybackResult<TrackReport> {
+        let mut decoder = Decoder::open(input)?;
+        let info = decoder.info();
+        if !self.opened {
+            self.sink.open(self.format)?;
+            self.opened = true;
+        }
+        let mut converter = SampleRateConverter::new(
+            info.sample_rate,
+            self.format.sample_rate,
+            self.format.channels,
+        )?;
+        let mut report = TrackReport {
+            frames_in: 0,
+            frames_out: 0,
+        };
+        let mut remixed: Vec<f32> = Default::default();
+        let mut converted: Vec<f32> = Default::default();
+        while let Some(samples) = decoder.next_chunk()? {
+            report.frames_in += (samples.len() / info.channels) as u64;
+            remixed.clear();
+            converted.clear();
+            remix(samples, info.channels, self.format.channels, &mut remixed);
+            converter.push(&remixed, &mut converted)?;
+            self.write(&converted, &mut report)?;
+        }
+        converted.clear();
+        converter.finish(&mut converted)?;
+        self.write(&converted, &mut report)?;
+        Ok(report)
+    }
+
+    fn write(&mut self, samples: &[f32], report: &mut TrackReport) -> PlaybackResult<()> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        report.frames_out += (samples.len() / self.format.channels) as u64;
+        self.sink.write(samples)
+    }
+
+    /// Ends playback and gives the sink back.
+    pub fn finish(mut self) -> PlaybackResult<S> {
+        if self.opened {
+            self.sink.finish()?;
+        }
+        Ok(self.sink)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use ease_remote_storage::StreamFile;
+    use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+
+    use crate::{MediaInput, NullSink, PlaybackError, WavFileSink};
+
+    use super::*;
+
+    fn wav(
+        sample_rate: u32,
+        channels: u16,
+        frames: usize,
+        sample: impl Fn(usize) -> f32,
+    ) -> Vec<u8> {
+        let spec = WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let mut buf = Cursor::new(Vec::new());
+        let mut writer = WavWriter::new(&mut buf, spec).expect("create wav");
+        for frame in 0..frames {
+            for _ in 0..channels {
+                writer.write_sample(sample(frame)).expect("write sample");
+            }
+        }
+        writer.finalize().expect("finalize wav");
+        buf.into_inner()
+    }
+
+    #[test]
+    fn joins_resampled_tracks_without_gaps() {
+        let dir = tempfile::tempdir().expect("create tempdir");
+        let path = dir.path().join("out.wav");
+        let sine = |frame: usize| {
+            (frame as f32 * 440.0 * 2.0 * std::f32::consts::PI / 44_100.0).sin() * 0.5
+        };
+        let ramp = |frame: usize| frame as f32 / 4800.0;
+        let mut pipeline =
+            Pipeline::new(OutputFormat::default(), WavFileSink::new(&path)).expect("pipeline");
+
+        let first = pipeline
+            .play(MediaInput::from_bytes(
+                wav(44_100, 2, 4410, sine),
+                Some("wav"),
+            ))
+            .expect("play first");
+        assert_eq!(4410, first.frames_in);
+        assert_eq!(4800, first.frames_out);
+        let second = pipeline
+            .play(MediaInput::from_bytes(
+                wav(48_000, 1, 2400, ramp),
+                Some("WAV"),
+            ))
+            .expect("play second");
+        assert_eq!(2400, second.frames_out);
+        pipeline.finish().expect("finish");
+
+        let mut reader = WavReader::open(&path).expect("open output");
+        assert_eq!(2, reader.spec().channels);
+        assert_eq!(48_000, reader.spec().sample_rate);
+        let samples: Vec<f32> = reader.samples::<f32>().map(|v| v.unwrap()).collect();
+        assert_eq!(7200 * 2, samples.len());
+
+        // The sine keeps its level through the conversion.
+        let middle = &samples[2000..7600];
+        let rms = (middle.iter().map(|v| v * v).sum::<f32>() / middle.len() as f32).sqrt();
+        assert!((rms - 0.5 / 2f32.sqrt()).abs() < 0.02, "rms {rms}");
+        // The mono track starts right after the first one, on both channels.
+        for frame in 0..2400 {
+            let at = (4800 + frame) * 2;
+            assert_eq!(ramp(frame), samples[at]);
+            assert_eq!(ramp(frame), samples[at + 1]);
+        }
+    }
+
+    #[test]
+    fn decodes_streamed_files() {
+        let bytes = wav(22_050, 2, 2205, |frame| (frame % 100) as f32 / 100.0);
+        let file = StreamFile::new_from_bytes(&bytes, "a.wav", 0);
+        let mut pipeline = Pipeline::new(
+            OutputFormat {
+                sample_rate: 44_100,
+                channels: 1,
+            },
+            NullSink::default(),
+        )
+        .expect("pipeline");
+        let report = pipeline
+            .play(MediaInput::from_stream_file(file))
+            .expect("play");
+        assert_eq!(2205, report.frames_in);
+        assert_eq!(4410, pipeline.finish().expect("finish").frames);
+
+        let mut pipeline =
+            Pipeline::new(OutputFormat::default(), NullSink::default()).expect("pipeline");
+        assert!(matches!(
+            pipeline.play(MediaInput::from_bytes(vec![7u8; 4096], Some("mp3"))),
+            Err(PlaybackError::UnsupportedFormat) | Err(PlaybackError::Decode(_))
+        ));
+    }
+}
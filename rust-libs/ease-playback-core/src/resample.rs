@@ -0,0 +1,118 @@
+use rubato::{FftFixedIn, Resampler};
+
+use crate::error::{PlaybackError, PlaybackResult};
+
+const CHUNK_FRAMES: usize = 1024;
+
+/// Converts interleaved samples of one track to another sample rate. The
+/// output has exactly `frames * to / from` frames, rounded, with the delay
+/// of the resampler removed, so tracks still join without gaps.
+pub struct SampleRateConverter {
+    inner: Option<FftFixedIn<f32>>,
+    from: u32,
+    to: u32,
+    channels: usize,
+    /// Planar input waiting for a full chunk.
+    pending: Vec<Vec<f32>>,
+    frames_in: u64,
+    frames_out: u64,
+    /// Output frames still to drop for the resampler delay.
+    skip: usize,
+}
+
+impl SampleRateConverter {
+    pub fn new(from: u32, to: u32, channels: usize) -> PlaybackResult<Self> {
+        let inner = if from == to {
+            None
+        } else {
+            let inner = FftFixedIn::new(from as usize, to as usize, CHUNK_FRAMES, 2, channels)
+                .map_err(|e| PlaybackError::Resample(e.to_string()))?;
+            Some(inner)
+        };
+        let skip = inner.as_ref().map(|v| v.output_delay()).unwrap_or_default();
+        Ok(Self {
+            inner,
+            from,
+            to,
+            channels,
+            pending: vec![Vec::new(); channels],
+            frames_in: 0,
+            frames_out: 0,
+            skip,
+        })
+    }
+
+    fn expected_frames(&self) -> u64 {
+        (self.frames_in * self.to as u64 + self.from as u64 / 2) / self.from as u64
+    }
+
+    fn emit(&mut self, planar: Vec<Vec<f32>>, out: &mut Vec<f32>) {
+        let frames = planar.first().map(|v| v.len()).unwrap_or_default();
+        let skip = self.skip.min(frames);
+        self.skip -= skip;
+        for frame in skip..frames {
+            for channel in planar.iter() {
+                out.push(channel[frame]);
+            }
+        }
+        self.frames_out += (frames - skip) as u64;
+    }
+
+    /// Appends the converted samples of `samples` to `out`. Some of them are
+    /// held back until [`Self::finish`].
+    pub fn push(&mut self, samples: &[f32], out: &mut Vec<f32>) -> PlaybackResult<()> {
+        self.frames_in += (samples.len() / self.channels) as u64;
+        let Some(inner) = self.inner.as_mut() else {
+            out.extend_from_slice(samples);
+            return Ok(());
+        };
+        for frame in samples.chunks_exact(self.channels) {
+            for (channel, sample) in self.pending.iter_mut().zip(frame) {
+                channel.push(*sample);
+            }
+        }
+        let mut converted: Vec<Vec<Vec<f32>>> = Default::default();
+        while self.pending[0].len() >= inner.input_frames_next() {
+            let needed = inner.input_frames_next();
+            let chunk: Vec<Vec<f32>> = self
+                .pending
+                .iter_mut()
+                .map(|v| v.drain(..needed).collect())
+                .collect();
+            converted.push(
+                inner
+                    .process(&chunk, None)
+                    .map_err(|e| PlaybackError::Resample(e.to_string()))?,
+            );
+        }
+        for planar in converted {
+            self.emit(planar, out);
+        }
+        Ok(())
+    }
+
+    /// Appends the samples held back to `out`, ending the track.
+    pub fn finish(&mut self, out: &mut Vec<f32>) -> PlaybackResult<()> {
+        if self.inner.is_none() {
+            return Ok(());
+        }
+        let expected = self.expected_frames();
+        let start = out.len();
+        let mut pending = Some(std::mem::replace(
+            &mut self.pending,
+            vec![Vec::new(); self.channels],
+        ))
+        .filter(|v| !v[0].is_empty());
+        while self.frames_out < expected {
+            let inner = self.inner.as_mut().unwrap();
+            let planar = inner
+                .process_partial(pending.take().as_deref(), None)
+                .map_err(|e| PlaybackError::Resample(e.to_string()))?;
+            self.emit(planar, out);
+        }
+        let extra = (self.frames_out - expected) as usize * self.channels;
+        out.truncate(out.len().max(start + extra) - extra);
+        self.frames_out = expected;
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+use std::{fs::File, io::BufWriter, path::PathBuf};
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+use crate::{error::PlaybackResult, pipeline::OutputFormat};
+
+/// Destination of the decoded audio, such as a sound device.
+pub trait AudioSink {
+    /// Called once before the first samples.
+    fn open(&mut self, format: OutputFormat) -> PlaybackResult<()>;
+    /// Interleaved samples in the opened format.
+    fn write(&mut self, samples: &[f32]) -> PlaybackResult<()>;
+    /// Called once after the last samples.
+    fn finish(&mut self) -> PlaybackResult<()>;
+}
+
+/// Drops the audio, counting the frames written.
+#[derive(Debug, Default)]
+pub struct NullSink {
+    channels: usize,
+    pub frames: u64,
+}
+
+impl AudioSink for NullSink {
+    fn open(&mut self, format: OutputFormat) -> PlaybackResult<()> {
+        self.channels = format.channels;
+        Ok(())
+    }
+
+    fn write(&mut self, samples: &[f32]) -> PlaybackResult<()> {
+        self.frames += (samples.len() / self.channels) as u64;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> PlaybackResult<()> {
+        Ok(())
+    }
+}
+
+/// Writes the audio to a 32-bit float WAV file.
+pub struct WavFileSink {
+    path: PathBuf,
+    writer: Option<WavWriter<BufWriter<File>>>,
+}
+
+impl WavFileSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            writer: None,
+        }
+    }
+}
+
+impl AudioSink for WavFileSink {
+    fn open(&mut self, format: OutputFormat) -> PlaybackResult<()> {
+        let spec = WavSpec {
+            channels: format.channels as u16,
+            sample_rate: format.sample_rate,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        self.writer = Some(WavWriter::create(&self.path, spec)?);
+        Ok(())
+    }
+
+    fn write(&mut self, samples: &[f32]) -> PlaybackResult<()> {
+        if let Some(writer) = self.writer.as_mut() {
+            for sample in samples {
+                writer.write_sample(*sample)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> PlaybackResult<()> {
+        if let Some(writer) = self.writer.take() {
+            writer.finalize()?;
+        }
+        Ok(())
+    }
+}
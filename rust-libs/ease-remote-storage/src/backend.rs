@@ -12,6 +12,9 @@ pub struct Entry {
     pub path: String,
     pub size: Option<usize>,
     pub is_dir: bool,
+    /// Last modification time in milliseconds since the unix epoch.
+    pub modified: Option<i64>,
+    pub etag: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -69,6 +72,8 @@ pub enum StorageBackendError {
     ApiError { code: i64, message: String },
     #[error("Search unavailable")]
     SearchUnavailable,
+    #[error("Write unavailable")]
+    WriteUnavailable,
     #[error("Site blocked request with HTTP {status_code} ({provider})")]
     SiteBlocked { status_code: u16, provider: String },
 }
@@ -140,6 +145,10 @@ impl StorageBackendError {
             )
     }
 
+    pub fn is_write_unavailable(&self) -> bool {
+        matches!(self, StorageBackendError::WriteUnavailable)
+    }
+
     pub fn is_site_blocked(&self) -> bool {
         matches!(self, StorageBackendError::SiteBlocked { .. })
     }
@@ -164,6 +173,16 @@ pub trait StorageBackend {
     ) -> BoxFuture<'_, StorageBackendResult<ResolvedPlaybackSource>> {
         Box::pin(async { Ok(ResolvedPlaybackSource::StreamFallback) })
     }
+    /// Writes `data` to the file at `p`, replacing it when it exists. The
+    /// parent directory must exist.
+    fn put(&self, _p: String, _data: Bytes) -> BoxFuture<'_, StorageBackendResult<()>> {
+        Box::pin(async { Err(StorageBackendError::WriteUnavailable) })
+    }
+    /// Creates the directory at `p` and its missing parents. Succeeds when it
+    /// exists already.
+    fn create_dir(&self, _p: String) -> BoxFuture<'_, StorageBackendResult<()>> {
+        Box::pin(async { Err(StorageBackendError::WriteUnavailable) })
+    }
 }
 
 #[cfg(test)]
@@ -1,3 +1,6 @@
+use std::time::UNIX_EPOCH;
+
+use bytes::Bytes;
 use ease_client_tokio::tokio_runtime;
 use futures_util::future::BoxFuture;
 
@@ -74,6 +77,12 @@ impl LocalBackend {
                         path: path.replace('\\', "/"),
                         size: Some(metadata.len() as usize),
                         is_dir: metadata.is_dir(),
+                        modified: metadata
+                            .modified()
+                            .ok()
+                            .and_then(|v| v.duration_since(UNIX_EPOCH).ok())
+                            .map(|v| v.as_millis() as i64),
+                        etag: None,
                     });
                 }
 
@@ -89,6 +98,22 @@ impl LocalBackend {
         let (path, total) = self.resolve_absolute_path_impl(p).await?;
         Ok(StreamFile::new_from_file(path, total, byte_offset))
     }
+
+    async fn put_impl(&self, p: String, data: Bytes) -> StorageBackendResult<()> {
+        let p = self.normalize_input_path(&p);
+        tokio_runtime()
+            .spawn(async move { tokio::fs::write(p, data).await })
+            .await??;
+        Ok(())
+    }
+
+    async fn create_dir_impl(&self, p: String) -> StorageBackendResult<()> {
+        let p = self.normalize_input_path(&p);
+        tokio_runtime()
+            .spawn(async move { tokio::fs::create_dir_all(p).await })
+            .await??;
+        Ok(())
+    }
 }
 
 impl StorageBackend for LocalBackend {
@@ -107,6 +132,12 @@ impl StorageBackend for LocalBackend {
             Ok(ResolvedPlaybackSource::LocalFile { absolute_path })
         })
     }
+    fn put(&self, p: String, data: Bytes) -> BoxFuture<'_, StorageBackendResult<()>> {
+        Box::pin(self.put_impl(p, data))
+    }
+    fn create_dir(&self, p: String) -> BoxFuture<'_, StorageBackendResult<()>> {
+        Box::pin(self.create_dir_impl(p))
+    }
 }
 
 #[cfg(test)]
@@ -190,4 +221,29 @@ mod test {
             other => panic!("unexpected resolved playback source: {other:?}"),
         }
     }
+
+    #[tokio::test]
+    async fn test_put_creates_and_replaces_file() {
+        let backend = LocalBackend::new();
+
+        let dir = std::env::temp_dir().join(format!(
+            "ease-local-put-{}-{}",
+            std::process::id(),
+            std::time::UNIX_EPOCH.elapsed().unwrap().as_nanos()
+        ));
+        let dir = dir.join("nested").to_string_lossy().to_string();
+        backend.create_dir(dir.clone()).await.unwrap();
+        backend.create_dir(dir.clone()).await.unwrap();
+
+        let p = format!("{dir}/a.json");
+        backend.put(p.clone(), "first".into()).await.unwrap();
+        backend.put(p.clone(), "second".into()).await.unwrap();
+        let bytes = backend.get(p, 0).await.unwrap().bytes().await.unwrap();
+        assert_eq!(String::from_utf8_lossy(bytes.as_ref()), "second");
+
+        let list = backend.list(dir.clone()).await.unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].name, "a.json");
+        std::fs::remove_dir_all(std::path::Path::new(&dir).parent().unwrap()).unwrap();
+    }
 }
@@ -48,6 +48,10 @@ mod onedrive_types {
     #[derive(Debug, Deserialize)]
     pub struct ListItem {
         pub name: String,
+        #[serde(rename = "lastModifiedDateTime", default)]
+        pub last_modified: Option<String>,
+        #[serde(rename = "eTag", default)]
+        pub etag: Option<String>,
         #[serde(flatten)]
         pub kind: ListItemKind,
     }
@@ -280,6 +284,11 @@ impl OneDriveBackend {
             for item in obj.value.into_iter().flatten() {
                 let name = item.name;
                 let path = dir.to_string() + "/" + name.as_str();
+                let modified = item
+                    .last_modified
+                    .as_deref()
+                    .and_then(|v| chrono::DateTime::parse_from_rfc3339(v).ok())
+                    .map(|v| v.timestamp_millis());
                 match item.kind {
                     onedrive_types::ListItemKind::File { size, .. } => {
                         ret.push(Entry {
@@ -287,6 +296,8 @@ impl OneDriveBackend {
                             path,
                             size: Some(size as usize),
                             is_dir: false,
+                            modified,
+                            etag: item.etag,
                         });
                     }
                     onedrive_types::ListItemKind::Folder { .. } => {
@@ -295,6 +306,8 @@ impl OneDriveBackend {
                             path,
                             size: None,
                             is_dir: true,
+                            modified,
+                            etag: item.etag,
                         });
                     }
                 }
@@ -3,6 +3,7 @@ use crate::backend::{
     SearchScope, StorageBackend, StorageBackendError, StorageBackendResult, StreamFile,
 };
 
+use bytes::Bytes;
 use ease_client_tokio::tokio_runtime;
 use futures_util::future::BoxFuture;
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
@@ -87,6 +88,8 @@ struct FsObject {
     r#type: Option<i64>,
     #[serde(default)]
     raw_url: Option<String>,
+    #[serde(default)]
+    modified: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -382,6 +385,19 @@ impl OpenList {
         api_path: &str,
         body: serde_json::Value,
     ) -> StorageBackendResult<T> {
+        let resp: ApiResponse<T> = self.post_api_response(api_path, body).await?;
+        resp.data.ok_or(StorageBackendError::ApiError {
+            code: resp.code,
+            message: resp.message,
+        })
+    }
+
+    /// Like `post_api`, for the endpoints answering without data.
+    async fn post_api_response<T: DeserializeOwned>(
+        &self,
+        api_path: &str,
+        body: serde_json::Value,
+    ) -> StorageBackendResult<ApiResponse<T>> {
         let url = self.build_api_url(api_path)?;
         let body = serde_json::to_vec(&body)?;
         let base_headers = self.browser_api_headers()?;
@@ -485,10 +501,7 @@ impl OpenList {
                 }
                 return Err(err);
             }
-            return resp.data.ok_or(StorageBackendError::ApiError {
-                code: resp.code,
-                message: resp.message,
-            });
+            return Ok(resp);
         }
     }
 
@@ -556,11 +569,18 @@ impl OpenList {
                     item.size.map(|s| s as usize)
                 };
                 let path = join_path(dir.as_str(), name_raw.as_str());
+                let modified = item
+                    .modified
+                    .as_deref()
+                    .and_then(|v| chrono::DateTime::parse_from_rfc3339(v).ok())
+                    .map(|v| v.timestamp_millis());
                 ret.push(Entry {
                     name,
                     path,
                     size,
                     is_dir,
+                    modified,
+                    etag: None,
                 });
             }
 
@@ -629,6 +649,8 @@ impl OpenList {
                     item.size.map(|value| value as usize)
                 },
                 is_dir: item.is_dir,
+                modified: None,
+                etag: None,
             })
             .collect();
 
@@ -801,6 +823,71 @@ impl OpenList {
         Ok(res)
     }
 
+    async fn put_impl(&self, p: &str, data: Bytes) -> StorageBackendResult<()> {
+        let url = self.build_api_url("/api/fs/put")?;
+        let mut headers = self.browser_api_headers()?;
+        headers.insert(
+            HeaderName::from_static("file-path"),
+            build_header_value(urlencoding::encode(normalize_path(p).as_str()).as_ref())?,
+        );
+        headers.insert(
+            reqwest::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/octet-stream"),
+        );
+        if let Some(token) = self.token.read().await.clone() {
+            let mut val = build_header_value(token.as_str())?;
+            val.set_sensitive(true);
+            headers.insert(reqwest::header::AUTHORIZATION, val);
+        }
+
+        let client = self.api_client()?;
+        let resp = tokio_runtime()
+            .spawn(async move { client.put(url).headers(headers).body(data).send().await })
+            .await??;
+        let status = resp.status();
+        let text = resp.text().await?;
+        if !status.is_success() {
+            return Err(StorageBackendError::ApiError {
+                code: status.as_u16() as i64,
+                message: truncate_error_text(text.as_str()),
+            });
+        }
+        let resp: ApiResponse<serde_json::Value> = serde_json::from_str(&text)?;
+        if resp.code != 200 {
+            return Err(StorageBackendError::ApiError {
+                code: resp.code,
+                message: resp.message,
+            });
+        }
+        Ok(())
+    }
+
+    async fn put_with_retry_impl(&self, p: String, data: Bytes) -> StorageBackendResult<()> {
+        self.ensure_token().await?;
+        let r = self.put_impl(p.as_str(), data.clone()).await;
+        if !is_auth_error(&r) {
+            return r;
+        }
+        self.refresh_token().await?;
+        self.put_impl(p.as_str(), data).await
+    }
+
+    /// The server creates the missing parents too.
+    async fn create_dir_with_retry_impl(&self, p: String) -> StorageBackendResult<()> {
+        self.ensure_token().await?;
+        let body = json!({ "path": normalize_path(p.as_str()) });
+        let r = self
+            .post_api_response::<serde_json::Value>("/api/fs/mkdir", body.clone())
+            .await;
+        if !is_auth_error(&r) {
+            return r.map(|_| ());
+        }
+        self.refresh_token().await?;
+        self.post_api_response::<serde_json::Value>("/api/fs/mkdir", body)
+            .await
+            .map(|_| ())
+    }
+
     async fn resolve_direct_http_impl(
         &self,
         p: &str,
@@ -864,6 +951,14 @@ impl StorageBackend for OpenList {
     ) -> BoxFuture<'_, StorageBackendResult<ResolvedPlaybackSource>> {
         Box::pin(self.resolve_playback_source_with_retry_impl(p))
     }
+
+    fn put(&self, p: String, data: Bytes) -> BoxFuture<'_, StorageBackendResult<()>> {
+        Box::pin(self.put_with_retry_impl(p, data))
+    }
+
+    fn create_dir(&self, p: String) -> BoxFuture<'_, StorageBackendResult<()>> {
+        Box::pin(self.create_dir_with_retry_impl(p))
+    }
 }
 
 #[cfg(test)]
@@ -908,6 +1003,7 @@ mod test {
         file_calls: Arc<AtomicUsize>,
         last_search_body: Arc<Mutex<Option<String>>>,
         last_search_headers: Arc<Mutex<Vec<(String, String)>>>,
+        writes: Arc<Mutex<Vec<String>>>,
     }
     impl SetupServerRes {
         pub fn addr(&self) -> String {
@@ -933,6 +1029,10 @@ mod test {
         pub fn last_search_headers(&self) -> Vec<(String, String)> {
             self.last_search_headers.lock().unwrap().clone()
         }
+
+        pub fn writes(&self) -> Vec<String> {
+            self.writes.lock().unwrap().clone()
+        }
     }
     impl Drop for SetupServerRes {
         fn drop(&mut self) {
@@ -960,6 +1060,8 @@ mod test {
         let last_search_headers = Arc::new(Mutex::new(Vec::new()));
         let last_search_body_server = last_search_body.clone();
         let last_search_headers_server = last_search_headers.clone();
+        let writes = Arc::new(Mutex::new(Vec::new()));
+        let writes_server = writes.clone();
         let make_service = hyper::service::make_service_fn(move |_| {
             let port = port;
             let retry = retry;
@@ -969,6 +1071,7 @@ mod test {
             let file_calls = file_calls_server.clone();
             let last_search_body = last_search_body_server.clone();
             let last_search_headers = last_search_headers_server.clone();
+            let writes = writes_server.clone();
             async move {
                 let func = move |req: Request<Body>| {
                     let list_calls = list_calls.clone();
@@ -977,6 +1080,7 @@ mod test {
                     let file_calls = file_calls.clone();
                     let last_search_body = last_search_body.clone();
                     let last_search_headers = last_search_headers.clone();
+                    let writes = writes.clone();
                     async move {
                         let path = req.uri().path().to_string();
                         match (req.method().as_str(), path.as_str()) {
@@ -1059,6 +1163,31 @@ mod test {
                                 let body = r#"{"code":200,"message":"success","data":{"content":[{"parent":"/music","name":"target-song.mp3","is_dir":false,"size":12}],"total":1}}"#;
                                 Ok::<_, Infallible>(Response::new(Body::from(body)))
                             }
+                            ("POST", "/api/fs/mkdir") | ("PUT", "/api/fs/put") => {
+                                let auth = req.headers().get(reqwest::header::AUTHORIZATION);
+                                if auth.map(|v| v.to_str().ok()) != Some(Some(TEST_TOKEN)) {
+                                    let mut resp = Response::new(Body::from(
+                                        r#"{"code":401,"message":"unauthorized","data":null}"#,
+                                    ));
+                                    *resp.status_mut() = StatusCode::UNAUTHORIZED;
+                                    return Ok::<_, Infallible>(resp);
+                                }
+                                let file_path = req
+                                    .headers()
+                                    .get("File-Path")
+                                    .and_then(|v| v.to_str().ok())
+                                    .map(|v| urlencoding::decode(v).unwrap().to_string());
+                                let body_bytes =
+                                    hyper::body::to_bytes(req.into_body()).await.unwrap();
+                                let body = String::from_utf8_lossy(&body_bytes).to_string();
+                                let write = match file_path {
+                                    Some(file_path) => format!("put {file_path} {body}"),
+                                    None => format!("mkdir {body}"),
+                                };
+                                writes.lock().unwrap().push(write);
+                                let body = r#"{"code":200,"message":"success","data":null}"#;
+                                Ok::<_, Infallible>(Response::new(Body::from(body)))
+                            }
                             ("POST", "/api/fs/get") => {
                                 let attempt = get_calls.fetch_add(1, Ordering::SeqCst);
                                 if attempt < retry.get_failures {
@@ -1146,6 +1275,7 @@ mod test {
             file_calls,
             last_search_body,
             last_search_headers,
+            writes,
         }
     }
 
@@ -1426,4 +1556,30 @@ mod test {
             .unwrap_err();
         assert!(err.is_site_blocked());
     }
+
+    #[tokio::test]
+    async fn test_put_and_create_dir_with_login() {
+        let server = setup_server().await;
+
+        let backend = OpenList::new(BuildOpenListArg {
+            addr: server.addr(),
+            username: "user".to_string(),
+            password: "pass".to_string(),
+            is_anonymous: false,
+            connect_timeout: Duration::from_secs(10),
+        });
+        backend.create_dir("/sync/logs".to_string()).await.unwrap();
+        backend
+            .put("/sync/logs/a b.json".to_string(), "{}".into())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            server.writes(),
+            vec![
+                r#"mkdir {"path":"/sync/logs"}"#.to_string(),
+                "put /sync/logs/a b.json {}".to_string(),
+            ]
+        );
+    }
 }
@@ -5,6 +5,7 @@ use crate::backend::{
 use crate::StorageBackendError;
 
 use base64::Engine;
+use bytes::Bytes;
 use ease_client_tokio::tokio_runtime;
 use futures_util::future::BoxFuture;
 use reqwest::header::HeaderValue;
@@ -56,6 +57,8 @@ mod webdav_list_types {
         pub displayname: Option<String>,
         pub resourcetype: ResourceType,
         pub getcontentlength: Option<usize>,
+        pub getlastmodified: Option<String>,
+        pub getetag: Option<String>,
     }
 
     #[derive(Deserialize, Debug)]
@@ -253,6 +256,14 @@ impl Webdav {
             let mut name = item.propstat.prop.displayname.unwrap_or(Default::default());
             let is_dir = item.propstat.prop.resourcetype.collection.is_some();
             let size = item.propstat.prop.getcontentlength;
+            let modified = item
+                .propstat
+                .prop
+                .getlastmodified
+                .as_deref()
+                .and_then(|v| chrono::DateTime::parse_from_rfc2822(v).ok())
+                .map(|v| v.timestamp_millis());
+            let etag = item.propstat.prop.getetag;
             let mut path = self.get_href(path.as_str())?;
 
             if path == "/" {
@@ -279,6 +290,8 @@ impl Webdav {
                 path,
                 size,
                 is_dir,
+                modified,
+                etag,
             });
         }
 
@@ -367,6 +380,66 @@ impl Webdav {
         return self.get_impl(p.as_str(), byte_offset).await;
     }
 
+    async fn put_impl(&self, p: &str, data: Bytes) -> StorageBackendResult<()> {
+        let url = self.get_url::<false>(p)?;
+
+        let mut headers = self.build_base_header_map(reqwest::Method::PUT, &url)?;
+        headers.insert(
+            reqwest::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/octet-stream"),
+        );
+        let resp = {
+            let client = self.build_client()?;
+            tokio_runtime()
+                .spawn(async move { client.put(url).headers(headers).body(data).send().await })
+                .await??
+        };
+        self.post_handle_response(&resp);
+        resp.error_for_status()?;
+        Ok(())
+    }
+
+    async fn put_with_retry_impl(&self, p: String, data: Bytes) -> StorageBackendResult<()> {
+        let r = self.put_impl(p.as_str(), data.clone()).await;
+        if !is_auth_error(&r) {
+            return r;
+        }
+        return self.put_impl(p.as_str(), data).await;
+    }
+
+    /// MKCOL creates a single level, so each missing ancestor is created in
+    /// turn. Servers answer 405 for collections that exist.
+    async fn create_dir_impl(&self, p: &str) -> StorageBackendResult<()> {
+        let method = reqwest::Method::from_bytes(b"MKCOL").unwrap();
+        let mut dir = String::new();
+        for part in p.split('/').filter(|v| !v.is_empty()) {
+            dir = dir + "/" + part;
+            let url = self.get_url::<true>(&dir)?;
+            let headers = self.build_base_header_map(method.clone(), &url)?;
+            let resp = {
+                let client = self.build_client()?;
+                let method = method.clone();
+                tokio_runtime()
+                    .spawn(async move { client.request(method, url).headers(headers).send().await })
+                    .await??
+            };
+            self.post_handle_response(&resp);
+            if resp.status() == StatusCode::METHOD_NOT_ALLOWED {
+                continue;
+            }
+            resp.error_for_status()?;
+        }
+        Ok(())
+    }
+
+    async fn create_dir_with_retry_impl(&self, p: String) -> StorageBackendResult<()> {
+        let r = self.create_dir_impl(p.as_str()).await;
+        if !is_auth_error(&r) {
+            return r;
+        }
+        return self.create_dir_impl(p.as_str()).await;
+    }
+
     fn build_client(&self) -> StorageBackendResult<reqwest::Client> {
         if let Some(client) = self.client.get() {
             return Ok(client.clone());
@@ -438,13 +511,23 @@ impl StorageBackend for Webdav {
                 .unwrap_or(ResolvedPlaybackSource::StreamFallback))
         })
     }
+
+    fn put(&self, p: String, data: Bytes) -> BoxFuture<'_, StorageBackendResult<()>> {
+        Box::pin(self.put_with_retry_impl(p, data))
+    }
+
+    fn create_dir(&self, p: String) -> BoxFuture<'_, StorageBackendResult<()>> {
+        Box::pin(self.create_dir_with_retry_impl(p))
+    }
 }
 
 #[cfg(test)]
 mod test {
     use std::{convert::Infallible, net::SocketAddr, time::Duration};
 
-    use dav_server::{fakels::FakeLs, localfs::LocalFs, DavHandler};
+    use dav_server::{
+        fakels::FakeLs, fs::DavFileSystem, localfs::LocalFs, memfs::MemFs, DavHandler,
+    };
     use tokio::task::JoinHandle;
 
     use crate::backend::StorageBackend;
@@ -468,8 +551,12 @@ mod test {
     }
 
     async fn setup_server(p: &str) -> SetupServerRes {
+        setup_server_with_fs(LocalFs::new(p, false, false, false)).await
+    }
+
+    async fn setup_server_with_fs(fs: Box<dyn DavFileSystem>) -> SetupServerRes {
         let dav_server = DavHandler::builder()
-            .filesystem(LocalFs::new(p, false, false, false))
+            .filesystem(fs)
             .locksystem(FakeLs::new())
             .autoindex(true)
             .build_handler();
@@ -515,6 +602,8 @@ mod test {
         assert_eq!(list.len(), 2);
         assert_eq!(list[0].path, "/a.txt");
         assert_eq!(list[1].path, "/b.log.txt");
+        assert!(list[0].modified.is_some());
+        assert!(list[0].etag.is_some());
     }
 
     #[tokio::test]
@@ -708,4 +797,37 @@ mod test {
             .unwrap();
         assert_eq!(first, second);
     }
+
+    #[tokio::test]
+    async fn test_put_into_created_dir() {
+        let server = setup_server_with_fs(MemFs::new()).await;
+
+        let backend = Webdav::new(BuildWebdavArg {
+            addr: server.addr(),
+            username: Default::default(),
+            password: Default::default(),
+            is_anonymous: true,
+            connect_timeout: Duration::from_secs(10),
+        });
+        backend.create_dir("/sync/logs".to_string()).await.unwrap();
+        backend.create_dir("/sync/logs".to_string()).await.unwrap();
+        backend
+            .put("/sync/logs/a.json".to_string(), "first".into())
+            .await
+            .unwrap();
+        backend
+            .put("/sync/logs/a.json".to_string(), "second".into())
+            .await
+            .unwrap();
+
+        let list = backend.list("/sync/logs".to_string()).await.unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].path, "/sync/logs/a.json");
+        let file = backend
+            .get("/sync/logs/a.json".to_string(), 0)
+            .await
+            .unwrap();
+        let bytes = file.bytes().await.unwrap();
+        assert_eq!(String::from_utf8_lossy(bytes.as_ref()), "second");
+    }
 }